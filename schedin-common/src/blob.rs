@@ -0,0 +1,166 @@
+//! Blob Storage
+//!
+//! Content-addressed storage of run artifacts. Blobs are addressed by the
+//! hex-encoded SHA-256 digest of their contents, so identical files are
+//! stored once. The store is selected by the `BLOB_STORE` environment
+//! variable, e.g. `file:///var/lib/schedin/blobs`.
+
+extern crate hex;
+extern crate sha2;
+extern crate std;
+
+use crate::error::BlobError;
+use sha2::{Digest, Sha256};
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, ErrorKind, Read},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Environment variable holding the URL of the blob store
+pub const BLOB_STORE_VAR: &str = "BLOB_STORE";
+
+/// # Blob Store
+/// Backend storing blobs by digest. Implementations must make blobs
+/// visible atomically, a blob that exists is complete.
+pub trait BlobStore: Send + Sync {
+    /// Store the file at `source` as the blob `digest`, a no-op if it exists
+    fn put(&self, digest: &str, source: &Path) -> Result<(), BlobError>;
+
+    /// Reader of the contents of the blob `digest`, streamed rather than
+    /// read into memory at once
+    fn open(&self, digest: &str) -> Result<Box<dyn Read + Send>, BlobError>;
+
+    /// Delete the blob `digest`, a no-op if it does not exist
+    fn delete(&self, digest: &str) -> Result<(), BlobError>;
+}
+
+/// Blob store of the `BLOB_STORE` environment variable
+pub fn from_env() -> Result<Box<dyn BlobStore>, BlobError> {
+    let url = env::var(BLOB_STORE_VAR).map_err(|_| BlobError::Config)?;
+
+    match url.split_once("://") {
+        Some(("file", root)) if !root.is_empty() => Ok(Box::new(LocalStore::new(root))),
+        _ => Err(BlobError::Config),
+    }
+}
+
+/// Hex-encoded SHA-256 digest of the file at `path`
+pub fn digest(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Whether `digest` is a hex-encoded SHA-256 digest
+pub fn is_digest(digest: &str) -> bool {
+    digest.len() == 64
+        && digest
+            .bytes()
+            .all(|byte| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte))
+}
+
+/// # Local Store
+/// Blobs stored as files under a root directory, fanned out over
+/// subdirectories named by the first two characters of their digest.
+pub struct LocalStore {
+    root: PathBuf,
+}
+
+/// Suffix of the temporary files blobs are written to
+static TEMPORARY: AtomicU64 = AtomicU64::new(0);
+
+impl LocalStore {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, digest: &str) -> Result<PathBuf, BlobError> {
+        // digests end up in paths, never let one escape the root
+        if !is_digest(digest) {
+            return Err(BlobError::NotFound);
+        }
+
+        Ok(self.root.join(&digest[..2]).join(digest))
+    }
+}
+
+impl BlobStore for LocalStore {
+    fn put(&self, digest: &str, source: &Path) -> Result<(), BlobError> {
+        let path = self.path(digest)?;
+        if path.exists() {
+            return Ok(());
+        }
+
+        let directory = path.parent().ok_or(BlobError::Config)?;
+        fs::create_dir_all(directory).map_err(|_| BlobError::Io)?;
+
+        // written aside then renamed, so that readers never see a partial blob
+        let temporary = directory.join(format!(
+            ".{}.{}.{}",
+            digest,
+            process::id(),
+            TEMPORARY.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let stored = fs::copy(source, &temporary).and_then(|_| fs::rename(&temporary, &path));
+        if stored.is_err() {
+            let _ = fs::remove_file(&temporary);
+            return Err(BlobError::Io);
+        }
+
+        Ok(())
+    }
+
+    fn open(&self, digest: &str) -> Result<Box<dyn Read + Send>, BlobError> {
+        match File::open(self.path(digest)?) {
+            Ok(file) => Ok(Box::new(file)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Err(BlobError::NotFound),
+            Err(_) => Err(BlobError::Io),
+        }
+    }
+
+    fn delete(&self, digest: &str) -> Result<(), BlobError> {
+        match fs::remove_file(self.path(digest)?) {
+            Ok(_) => Ok(()),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(()),
+            Err(_) => Err(BlobError::Io),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{digest, BlobStore, LocalStore};
+    use crate::error::BlobError;
+    use std::{env, fs, io::Read, process};
+
+    #[test]
+    fn opens_stored_blobs() {
+        let root = env::temp_dir().join(format!("schedin-blobs-{}", process::id()));
+        let source = root.join("source");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(&source, b"artifact").unwrap();
+
+        let store = LocalStore::new(&root);
+        let digest = digest(&source).unwrap();
+        store.put(&digest, &source).unwrap();
+
+        let mut contents = Vec::new();
+        store
+            .open(&digest)
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        assert_eq!(contents, b"artifact");
+
+        store.delete(&digest).unwrap();
+        assert!(matches!(store.open(&digest), Err(BlobError::NotFound)));
+        assert!(matches!(store.open("../source"), Err(BlobError::NotFound)));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
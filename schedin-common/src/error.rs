@@ -2,8 +2,6 @@
 
 extern crate std;
 
-use std::collections::HashMap;
-
 /// CRUD Errors
 #[derive(Debug)]
 pub enum CrudError {
@@ -21,6 +19,15 @@ pub enum CrudError {
 
     /// Error when reading from database
     Read,
+
+    /// Project quota would be exceeded
+    Quota,
+
+    /// Requested record does not exist
+    NotFound,
+
+    /// Record conflicts with an existing one, e.g. a duplicate name
+    Conflict,
 }
 
 impl CrudError {
@@ -31,27 +38,60 @@ impl CrudError {
             CrudError::Read => "Unable to Read from Database",
             CrudError::Transaction => "Unable to create Transaction",
             CrudError::Validation => "Invalid JSON Parameters",
+            CrudError::Quota => "Project Quota Exceeded",
+            CrudError::NotFound => "Record Not Found",
+            CrudError::Conflict => "Record Already Exists",
         }
     }
+}
 
-    pub fn map(&self) -> HashMap<&str, &str> {
+/// Secret Errors
+#[derive(Debug)]
+pub enum SecretError {
+    /// Master key is not set
+    MissingMasterKey,
+
+    /// Master key is malformed
+    MasterKey,
+
+    /// Error during encryption
+    Encryption,
+
+    /// Error during decryption, the ciphertext was tampered with
+    /// or sealed under a different master key
+    Decryption,
+}
+
+impl SecretError {
+    pub fn reason(self) -> &'static str {
         match self {
-            CrudError::Insertion => error_map(CrudError::Insertion),
-            CrudError::Pooling => error_map(CrudError::Pooling),
-            CrudError::Read => error_map(CrudError::Read),
-            CrudError::Transaction => error_map(CrudError::Transaction),
-            CrudError::Validation => error_map(CrudError::Validation),
+            SecretError::MissingMasterKey => "Secrets Master Key Not Set",
+            SecretError::MasterKey => "Invalid Secrets Master Key",
+            SecretError::Encryption => "Unable to Encrypt Secret",
+            SecretError::Decryption => "Unable to Decrypt Secret",
         }
     }
 }
 
-/// Common error response map
-pub fn error_map<'a>(error: CrudError) -> HashMap<&'a str, &'a str> {
-    eprintln!("Internal Error: {:?}", error);
+/// Blob Store Errors
+#[derive(Debug)]
+pub enum BlobError {
+    /// Blob store is missing or not supported
+    Config,
 
-    let mut map = HashMap::with_capacity(2);
-    map.insert("status", "error");
-    map.insert("reason", error.reason());
+    /// No blob with that digest
+    NotFound,
+
+    /// Error reading or writing a blob
+    Io,
+}
 
-    map
+impl BlobError {
+    pub fn reason(self) -> &'static str {
+        match self {
+            BlobError::Config => "Invalid Blob Store",
+            BlobError::NotFound => "Blob Not Found",
+            BlobError::Io => "Unable to Access Blob Store",
+        }
+    }
 }
@@ -1,5 +1,9 @@
 //! Shared project-wide utilities
 
+pub mod blob;
 pub mod db;
 pub mod error;
+pub mod message;
+pub mod secret;
 pub mod tx;
+pub mod webhook;
@@ -0,0 +1,50 @@
+//! Notification Messages
+//!
+//! Messages are templates with `{{ placeholder }}` substitutions, rendered
+//! with the details of the run they notify about.
+
+extern crate std;
+
+use std::collections::HashMap;
+
+/// Placeholders a message may reference
+pub const PLACEHOLDERS: [&str; 8] = [
+    "event",
+    "job",
+    "project",
+    "run",
+    "status",
+    "error",
+    "failures",
+    "finished_at",
+];
+
+/// # Render
+/// Substitute `values` into the placeholders of `template`. Placeholders
+/// without a value render empty.
+///
+/// ## Errors
+///
+/// The name of an unknown placeholder, or a description of the syntax error.
+pub fn render(template: &str, values: &HashMap<&str, String>) -> Result<String, String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            return Err("Unterminated placeholder".to_string());
+        };
+
+        let name = rest[start + 2..start + 2 + len].trim();
+        if !PLACEHOLDERS.contains(&name) {
+            return Err(format!("Unknown placeholder '{}'", name));
+        }
+
+        output.push_str(&rest[..start]);
+        output.push_str(values.get(name).map(String::as_str).unwrap_or_default());
+        rest = &rest[start + 4 + len..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
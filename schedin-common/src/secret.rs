@@ -0,0 +1,221 @@
+//! Secrets Encryption
+//!
+//! Secrets are sealed with AES-256-GCM under a master key supplied
+//! through the `SECRETS_MASTER_KEY` environment variable (hex-encoded, 32 bytes).
+
+extern crate aes_gcm;
+extern crate hex;
+extern crate std;
+
+use crate::error::SecretError;
+use aes_gcm::{
+    aead::{rand_core::RngCore, Aead, AeadCore, OsRng, Payload},
+    Aes256Gcm, Key, KeyInit, Nonce,
+};
+use std::{env, fmt};
+
+/// Environment variable holding the hex-encoded master key
+pub const MASTER_KEY_VAR: &str = "SECRETS_MASTER_KEY";
+
+/// # Cipher
+/// Authenticated encryption of secrets at rest.
+///
+/// The associated data binds every ciphertext to its owner and name,
+/// so a sealed value copied onto another row fails to decrypt.
+#[derive(Clone)]
+pub struct Cipher(Aes256Gcm);
+
+impl fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Cipher([REDACTED])")
+    }
+}
+
+/// Encrypted secret, as stored in the database
+pub struct Sealed {
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+impl Cipher {
+    /// New instance from a raw 32-byte key
+    pub fn new(key: &[u8]) -> Result<Self, SecretError> {
+        if key.len() != 32 {
+            return Err(SecretError::MasterKey);
+        }
+
+        Ok(Self(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))))
+    }
+
+    /// New instance from the `SECRETS_MASTER_KEY` environment variable
+    pub fn from_env() -> Result<Self, SecretError> {
+        let key = env::var(MASTER_KEY_VAR).map_err(|_| SecretError::MissingMasterKey)?;
+        let key = hex::decode(key.trim()).map_err(|_| SecretError::MasterKey)?;
+        Self::new(&key)
+    }
+
+    /// # Encrypt
+    /// Seals `plaintext` with a fresh random nonce.
+    ///
+    /// ## Arguments
+    ///
+    /// - `plaintext`: Secret value.
+    /// - `aad`: Associated data, which must be supplied again on decryption.
+    pub fn encrypt(&self, plaintext: &[u8], aad: &[u8]) -> Result<Sealed, SecretError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+        let ciphertext = self
+            .0
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .map_err(|_| SecretError::Encryption)?;
+
+        Ok(Sealed {
+            nonce: nonce.to_vec(),
+            ciphertext,
+        })
+    }
+
+    /// # Decrypt
+    /// Opens a sealed secret, the plaintext is wrapped in `Redacted`
+    /// to keep it out of logs.
+    pub fn decrypt(
+        &self,
+        nonce: &[u8],
+        ciphertext: &[u8],
+        aad: &[u8],
+    ) -> Result<Redacted<String>, SecretError> {
+        if nonce.len() != 12 {
+            return Err(SecretError::Decryption);
+        }
+
+        let plaintext = self
+            .0
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| SecretError::Decryption)?;
+
+        String::from_utf8(plaintext)
+            .map(Redacted)
+            .map_err(|_| SecretError::Decryption)
+    }
+}
+
+/// Associated data for a secret owned by `user_id` named `name`
+pub fn aad(user_id: &str, name: &str) -> Vec<u8> {
+    format!("{}/{}", user_id, name).into_bytes()
+}
+
+/// Random hex-encoded token of `len` bytes, e.g. a signing secret
+pub fn token(len: usize) -> Redacted<String> {
+    let mut bytes = vec![0; len];
+    OsRng.fill_bytes(&mut bytes);
+    Redacted(hex::encode(bytes))
+}
+
+/// # Redacted
+/// Wrapper for sensitive values, never printed by `Debug` or `Display`.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Redacted<T>(T);
+
+impl<T> Redacted<T> {
+    pub fn new(inner: T) -> Self {
+        Self(inner)
+    }
+
+    /// Access the sensitive value
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> fmt::Debug for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl<T> fmt::Display for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{aad, Cipher};
+    use crate::error::SecretError;
+
+    const KEY: [u8; 32] = [7; 32];
+
+    #[test]
+    fn round_trips() {
+        let cipher = Cipher::new(&KEY).unwrap();
+        let aad = aad("alice", "api-token");
+        let sealed = cipher.encrypt(b"hunter2", &aad).unwrap();
+
+        assert_ne!(sealed.ciphertext, b"hunter2");
+        let plaintext = cipher
+            .decrypt(&sealed.nonce, &sealed.ciphertext, &aad)
+            .unwrap();
+        assert_eq!(plaintext.expose(), "hunter2");
+    }
+
+    #[test]
+    fn rejects_malformed_keys() {
+        assert!(matches!(Cipher::new(&[7; 16]), Err(SecretError::MasterKey)));
+    }
+
+    #[test]
+    fn fails_under_another_master_key() {
+        let aad = aad("alice", "api-token");
+        let sealed = Cipher::new(&KEY)
+            .unwrap()
+            .encrypt(b"hunter2", &aad)
+            .unwrap();
+
+        let other = Cipher::new(&[8; 32]).unwrap();
+        assert!(matches!(
+            other.decrypt(&sealed.nonce, &sealed.ciphertext, &aad),
+            Err(SecretError::Decryption)
+        ));
+    }
+
+    #[test]
+    fn binds_ciphertexts_to_their_row() {
+        let cipher = Cipher::new(&KEY).unwrap();
+        let sealed = cipher
+            .encrypt(b"hunter2", &aad("alice", "api-token"))
+            .unwrap();
+
+        for (user_id, name) in [("mallory", "api-token"), ("alice", "db-password")] {
+            assert!(matches!(
+                cipher.decrypt(&sealed.nonce, &sealed.ciphertext, &aad(user_id, name)),
+                Err(SecretError::Decryption)
+            ));
+        }
+    }
+
+    #[test]
+    fn fails_on_tampered_ciphertexts() {
+        let cipher = Cipher::new(&KEY).unwrap();
+        let aad = aad("alice", "api-token");
+        let mut sealed = cipher.encrypt(b"hunter2", &aad).unwrap();
+        sealed.ciphertext[0] ^= 1;
+
+        assert!(matches!(
+            cipher.decrypt(&sealed.nonce, &sealed.ciphertext, &aad),
+            Err(SecretError::Decryption)
+        ));
+    }
+}
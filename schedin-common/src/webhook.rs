@@ -0,0 +1,91 @@
+//! Webhook Signatures
+//!
+//! Webhook deliveries are signed with HMAC-SHA256 under the secret of their
+//! subscription. The `Schedin-Signature` header holds the time of the attempt
+//! and one signature per valid secret, e.g. `t=1700000000,v1=5257a8...`.
+//!
+//! Receivers verify a delivery by computing the HMAC-SHA256 of
+//! `{t}.{body}` with their secret, comparing it against every `v1` value in
+//! constant time, and rejecting deliveries whose `t` is more than
+//! `TOLERANCE` seconds away from their clock, so that a captured delivery
+//! cannot be replayed later on.
+
+extern crate hex;
+extern crate hmac;
+extern crate sha2;
+extern crate std;
+
+use crate::secret;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Header holding the timestamp and signatures of a delivery
+pub const SIGNATURE_HEADER: &str = "Schedin-Signature";
+
+/// Header holding the id of the event, identical across redeliveries
+pub const EVENT_ID_HEADER: &str = "Schedin-Event-Id";
+
+/// Header holding the type of the event, e.g. `run.failed`
+pub const EVENT_TYPE_HEADER: &str = "Schedin-Event-Type";
+
+/// Age (in seconds) past which receivers should reject a delivery
+pub const TOLERANCE: i64 = 300;
+
+/// Hex-encoded HMAC-SHA256 of `{timestamp}.{body}` under `secret`
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    // HMAC takes keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// `Schedin-Signature` header of `body` sent at `timestamp`, signed under
+/// each of `secrets`
+pub fn signature(secrets: &[&str], timestamp: i64, body: &[u8]) -> String {
+    let mut header = format!("t={}", timestamp);
+    for secret in secrets {
+        header.push_str(",v1=");
+        header.push_str(&sign(secret, timestamp, body));
+    }
+    header
+}
+
+/// Associated data of the signing secret of the subscription `name` of
+/// `user_id`, apart from the secrets of the user
+pub fn aad(user_id: &str, name: &str) -> Vec<u8> {
+    secret::aad(user_id, &format!("webhook:{}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::signature;
+
+    const BODY: &[u8] = br#"{"type":"run.failed"}"#;
+
+    #[test]
+    fn signs_known_answers() {
+        assert_eq!(
+            signature(&["whsec", "rotated"], 1700000000, BODY),
+            "t=1700000000,\
+             v1=3e763244305bcc54c6e5b7eff71a52db647838242ff4604e799d02c778ad32db,\
+             v1=085ea2559ed1e545b0fa2c3e2b1561c52349621277f9cbf2ad7184dade784add"
+        );
+    }
+
+    #[test]
+    fn signs_the_timestamp_and_body() {
+        let v1 = |timestamp, body| {
+            signature(&["whsec"], timestamp, body)
+                .split_once(",v1=")
+                .unwrap()
+                .1
+                .to_string()
+        };
+
+        let signed = v1(1700000000, BODY);
+        assert_ne!(v1(1700000001, BODY), signed);
+        assert_ne!(v1(1700000000, br#"{"type":"run.succeeded"}"#), signed);
+    }
+}
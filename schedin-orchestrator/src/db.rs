@@ -1,16 +1,53 @@
 //! Database Operations
 
 extern crate schedin_common;
+extern crate serde_json;
 extern crate sqlx;
 extern crate std;
 
-use crate::job::{Job, JobStatus, JobType};
-use schedin_common::error::CrudError;
-use sqlx::{types::time::OffsetDateTime, Pool, Postgres};
-use std::time::Duration;
+use crate::{
+    job::Job,
+    matrix::Matrix,
+    notify::{Attempt, Delivery, Finished, Rule, BACKOFF, MAX_ATTEMPTS},
+    run::{Completed, Placement, Run},
+    webhook,
+    worker::{choose, preempt, Capacity, Pending, Placed, Term, Unplaced},
+};
+use schedin_common::{blob::BlobStore, error::CrudError, tx::Tx};
+use serde_json::Value;
+use sqlx::{
+    types::{time::OffsetDateTime, Json, Uuid},
+    Pool, Postgres, Transaction,
+};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+/// Workers without a heartbeat for this long (in seconds) are considered lost
+const WORKER_TIMEOUT: u64 = 30;
+
+/// Days deleted jobs stay in the trash of projects without a retention
+const DEFAULT_TRASH_RETENTION_DAYS: i32 = 30;
+
+/// Days artifacts are kept in projects without a retention
+const DEFAULT_ARTIFACT_RETENTION_DAYS: i32 = 14;
+
+/// Time (in seconds) a blob stays leased to the worker uploading it
+const BLOB_LEASE: i32 = 3600;
+
+/// Blobs collected per iteration
+const BLOB_BATCH: i64 = 100;
+
+/// Finished runs evaluated against notification rules per iteration
+const NOTIFY_BATCH: i64 = 100;
+
+/// Outbox events fanned out per iteration
+const OUTBOX_BATCH: i64 = 100;
+
+/// Deliveries attempted per iteration
+const DELIVERY_BATCH: i64 = 50;
 
 pub struct DB {
     pub pool: Pool<Postgres>,
+    #[allow(dead_code)]
     pub job: Job,
 }
 
@@ -23,59 +60,249 @@ impl DB {
         }
     }
 
-    /// # Read
-    /// Read Database Records containing scheduled jobs within a specified time range.
+    /// # Dispatch
+    /// Enqueue a run for every scheduled job that is due.
     ///
-    /// This function retrieves a list of scheduled jobs from a database that fall within
-    /// a specified time range starting from the current time. The range is defined by the
-    /// `range` parameter, which represents the maximum time difference, in seconds,
-    /// from the current time for a job to be considered within range.
+    /// This function locks the due jobs, advances their `next_run_at` past the
+    /// current time (or clears it for `@once` jobs) and inserts one `queued` run
+    /// per job, tagged with the revision of the job it executes and reserving
+    /// the resource requests of the job, all within a single statement. Jobs locked by another orchestrator are skipped.
+    /// Runs take the priority of the job's class at dispatch, 0 without a class.
+    /// Jobs in the trash are never due.
     ///
-    /// ## Arguments
+    /// ## Project Quotas
     ///
-    /// `interval` - The time range duration from the current time.
-    /// Jobs falling within this range from the current time will
-    /// be included in the result.
+    /// Due jobs of a project are dispatched, highest priority then earliest first, only while the
+    /// project has fewer queued, unschedulable or running runs than `max_concurrent_runs`.
+    /// Held back jobs stay due and are dispatched once runs complete. The run
+    /// timeout is the job timeout, capped by the project `max_timeout`.
     ///
-    /// ## Returns
+    /// ## Matrix
     ///
-    /// A `Result` containing a vector of `Job` structures representing the scheduled jobs
-    /// found within the specified time range. If the operation is successful, it returns
-    /// `Ok(Vec<Job>)`. If an error occurs during the database read operation, it returns
-    /// `Err(CrudError)` with details about the error.
+    /// The run of a job with a matrix snapshots it, and is expanded right away
+    /// into one queued child per combination, see `expand`. Only the children
+    /// count towards `max_concurrent_runs`, so the job takes one slot per
+    /// combination. A job with more combinations than `max_concurrent_runs`
+    /// is never dispatched, as triggering it is refused.
     ///
-    /// ## Errors
+    /// ## Returns
     ///
-    /// `Err(CrudError)` provides information about the specific error that occurred.
-    pub async fn read(&self, interval: Duration) -> Result<Vec<Job>, CrudError> {
+    /// A `Result` containing the enqueued runs. If an error occurs during the
+    /// database operation, it returns `Err(CrudError)`.
+    pub async fn dispatch(&self) -> Result<Vec<Run>, CrudError> {
+        let tx_manager = Tx::new(Arc::new(self.pool.clone()));
+        let mut tx = tx_manager.init().await?;
+
+        match self.dispatch_inner(&mut tx).await {
+            Ok(runs) => {
+                tx_manager.commit(tx).await?;
+                Ok(runs)
+            }
+            Err(error) => {
+                tx_manager.rollback(tx).await?;
+                Err(error)
+            }
+        }
+    }
+
+    async fn dispatch_inner(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<Run>, CrudError> {
         let current_time = OffsetDateTime::now_utc();
-        let interval = current_time + interval;
 
-        println!("current: {:?}", current_time);
-        println!("interval: {:?}", interval);
+        let runs = match sqlx::query_as!(
+            Run,
+            r#"
+            WITH active AS (
+                SELECT j.project_id, COUNT(*) AS runs FROM runs r 
+                JOIN jobs j ON j.job_id = r.job_id 
+                WHERE r.run_status IN ('queued', 'running', 'unschedulable') AND r.matrix IS NULL 
+                GROUP BY j.project_id
+            ), candidates AS (
+                SELECT j.job_id, p.max_concurrent_runs, COALESCE(a.runs, 0) AS active, 
+                SUM(matrix_runs(j.matrix)) OVER (
+                    PARTITION BY j.project_id ORDER BY COALESCE(pc.value, 0) DESC, j.next_run_at, j.job_id 
+                    ROWS UNBOUNDED PRECEDING
+                ) AS slot 
+                FROM jobs j 
+                JOIN projects p ON p.project_id = j.project_id 
+                LEFT JOIN priority_classes pc ON pc.priority_class_id = j.priority_class_id 
+                LEFT JOIN active a ON a.project_id = j.project_id 
+                WHERE j.job_status = 'scheduled' AND j.next_run_at <= $1 AND j.deleted_at IS NULL 
+                AND (p.max_concurrent_runs IS NULL OR matrix_runs(j.matrix) <= p.max_concurrent_runs)
+            ), due AS (
+                SELECT j.job_id, j.next_run_at FROM jobs j 
+                JOIN candidates c ON c.job_id = j.job_id 
+                WHERE c.max_concurrent_runs IS NULL OR c.active + c.slot <= c.max_concurrent_runs 
+                FOR UPDATE OF j SKIP LOCKED
+            ), advanced AS (
+                UPDATE jobs j SET runs = j.runs + 1, next_run_at = CASE 
+                    WHEN j.job_interval IS NULL OR j.job_interval <= 0 THEN NULL 
+                    ELSE due.next_run_at + make_interval(secs => j.job_interval * 
+                        (FLOOR(EXTRACT(EPOCH FROM ($1 - due.next_run_at)) / j.job_interval) + 1)) 
+                END 
+                FROM due WHERE j.job_id = due.job_id 
+                RETURNING j.job_id, j.project_id, j.priority_class_id, j.revision, j.timeout, 
+                j.matrix, due.next_run_at AS scheduled_at, 
+                COALESCE(j.request_cpu, 0) AS cpu, COALESCE(j.request_memory_mb, 0) AS memory_mb, 
+                COALESCE(j.request_disk_mb, 0) AS disk_mb
+            ) 
+            INSERT INTO runs (job_id, revision, scheduled_at, timeout, cpu, memory_mb, disk_mb, priority, preempt, matrix) 
+            SELECT a.job_id, a.revision, a.scheduled_at, LEAST(a.timeout, p.max_timeout), 
+            a.cpu, a.memory_mb, a.disk_mb, COALESCE(pc.value, 0), COALESCE(pc.preempt, FALSE), a.matrix 
+            FROM advanced a JOIN projects p ON p.project_id = a.project_id 
+            LEFT JOIN priority_classes pc ON pc.priority_class_id = a.priority_class_id 
+            RETURNING run_id, job_id AS "job_id!", revision, scheduled_at, timeout, 
+            matrix AS "matrix: Json<Matrix>"
+            "#,
+            current_time
+        )
+        .fetch_all(&mut **tx)
+        .await
+        {
+            Ok(runs) => runs,
+            Err(error) => {
+                eprintln!("{}", error);
+                return Err(CrudError::Insertion);
+            }
+        };
 
-        match self.read_inner(&current_time, &interval).await {
-            Ok(jobs) => Ok(jobs),
-            Err(error) => Err(error),
+        for run in &runs {
+            if let Some(matrix) = &run.matrix {
+                self.expand(tx, &run.run_id, matrix).await?;
+            }
         }
+
+        Ok(runs)
     }
 
-    pub async fn read_inner(
+    /// # Expand
+    /// Queue one child of the matrix run `run_id` per combination of the
+    /// matrix, in order, with the timeout, requests and priority of the run.
+    /// The matrix run itself is never placed, it is running until its
+    /// children complete, see `aggregate`.
+    async fn expand(
         &self,
-        current_time: &OffsetDateTime,
-        elapsed: &OffsetDateTime,
-    ) -> Result<Vec<Job>, CrudError> {
+        tx: &mut Transaction<'_, Postgres>,
+        run_id: &Uuid,
+        matrix: &Matrix,
+    ) -> Result<(), CrudError> {
+        let combinations = matrix
+            .combinations()
+            .into_iter()
+            .map(Json)
+            .collect::<Vec<_>>();
+
+        for query in [
+            sqlx::query!(
+                r#"
+                INSERT INTO runs (job_id, revision, scheduled_at, timeout, cpu, memory_mb, disk_mb, 
+                priority, preempt, parent_run_id, combination, position) 
+                SELECT r.job_id, r.revision, r.scheduled_at, r.timeout, r.cpu, r.memory_mb, r.disk_mb, 
+                r.priority, r.preempt, r.run_id, c.combination, (c.position - 1)::int 
+                FROM runs r, UNNEST($2::jsonb[]) WITH ORDINALITY AS c(combination, position) 
+                WHERE r.run_id = $1
+                "#,
+                run_id,
+                &combinations as _
+            ),
+            sqlx::query!(
+                "UPDATE runs SET run_status = 'running', started_at = NOW() WHERE run_id = $1",
+                run_id
+            ),
+        ] {
+            if let Err(error) = query.execute(&mut **tx).await {
+                eprintln!("{}", error);
+                return Err(CrudError::Insertion);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// # Cancel
+    /// Cancel the children yet to start of the matrix runs with fail-fast,
+    /// once one of their children failed. Running children complete.
+    pub async fn cancel(&self) -> Result<Vec<Uuid>, CrudError> {
+        match sqlx::query_scalar!(
+            r#"
+            UPDATE runs c SET run_status = 'cancelled', finished_at = NOW(), 
+            reason = 'Cancelled as another run of the matrix failed' 
+            FROM runs p 
+            WHERE c.parent_run_id = p.run_id AND p.run_status = 'running' 
+            AND (p.matrix->>'fail_fast')::boolean 
+            AND c.run_status IN ('queued', 'unschedulable') 
+            AND EXISTS (
+                SELECT 1 FROM runs f WHERE f.parent_run_id = p.run_id AND f.run_status = 'failed'
+            ) 
+            RETURNING c.run_id
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+        {
+            Ok(runs) => Ok(runs),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Aggregate
+    /// Complete the matrix runs whose children all completed. A matrix run
+    /// succeeds if all of its children succeeded, and fails otherwise with
+    /// the number of failed and cancelled children.
+    pub async fn aggregate(&self) -> Result<Vec<Completed>, CrudError> {
         match sqlx::query_as!(
-            Job,
+            Completed,
             r#"
-            SELECT user_id, job_id, job_name, job_description, 
-            job_type as "job_type: JobType", runs, error_count, next_run_at, 
-            created_at, job_status as "job_status: JobStatus" FROM jobs 
-            WHERE next_run_at BETWEEN $1 AND $2 
-            AND job_status = 'scheduled';
+            UPDATE runs p SET finished_at = NOW(), 
+            run_status = CASE WHEN c.failed + c.cancelled = 0 
+                THEN 'succeeded'::run_status ELSE 'failed'::run_status END, 
+            error = CASE WHEN c.failed + c.cancelled > 0 
+                THEN format('%s of %s matrix runs failed', c.failed, c.total) || 
+                CASE WHEN c.cancelled > 0 THEN format(', %s cancelled', c.cancelled) ELSE '' END 
+            END 
+            FROM (
+                SELECT parent_run_id, COUNT(*) AS total, 
+                COUNT(*) FILTER (WHERE run_status = 'failed') AS failed, 
+                COUNT(*) FILTER (WHERE run_status = 'cancelled') AS cancelled, 
+                COUNT(*) FILTER (WHERE run_status IN ('queued', 'running', 'unschedulable')) AS pending 
+                FROM runs 
+                WHERE parent_run_id IN (
+                    SELECT run_id FROM runs WHERE run_status = 'running' AND matrix IS NOT NULL
+                ) 
+                GROUP BY parent_run_id
+            ) c 
+            WHERE p.run_id = c.parent_run_id AND p.run_status = 'running' AND c.pending = 0 
+            RETURNING p.run_id, p.error
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+        {
+            Ok(runs) => Ok(runs),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Purge
+    /// Permanently delete the jobs left in the trash for longer than the
+    /// retention of their project, along with their revisions and runs.
+    pub async fn purge(&self) -> Result<Vec<String>, CrudError> {
+        match sqlx::query_scalar!(
+            r#"
+            DELETE FROM jobs j USING projects p 
+            WHERE p.project_id = j.project_id AND j.deleted_at IS NOT NULL 
+            AND j.deleted_at <= NOW() - make_interval(days => COALESCE(p.trash_retention_days, $1)) 
+            RETURNING j.job_name
             "#,
-            current_time,
-            elapsed
+            DEFAULT_TRASH_RETENTION_DAYS
         )
         .fetch_all(&self.pool)
         .await
@@ -83,8 +310,793 @@ impl DB {
             Ok(jobs) => Ok(jobs),
             Err(error) => {
                 eprintln!("{}", error);
-                Err(CrudError::Read)
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Expire
+    /// Delete the artifacts kept for longer than the retention of their
+    /// project, returns the number of artifacts. Their blobs are collected
+    /// once no other artifact references them, see `collect`.
+    pub async fn expire(&self) -> Result<u64, CrudError> {
+        match sqlx::query!(
+            r#"
+            DELETE FROM run_artifacts a USING runs r, jobs j, projects p 
+            WHERE r.run_id = a.run_id AND j.job_id = r.job_id AND p.project_id = j.project_id 
+            AND a.created_at <= NOW() - make_interval(days => COALESCE(p.artifact_retention_days, $1))
+            "#,
+            DEFAULT_ARTIFACT_RETENTION_DAYS
+        )
+        .execute(&self.pool)
+        .await
+        {
+            Ok(result) => Ok(result.rows_affected()),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Collect
+    /// Delete the blobs no artifact references from `store`, once their
+    /// lease expired, returns their digests.
+    ///
+    /// Blobs stay locked until deleted from the store, so that a worker
+    /// leasing one again waits, and uploads it anew.
+    pub async fn collect(&self, store: &dyn BlobStore) -> Result<Vec<String>, CrudError> {
+        let tx_manager = Tx::new(Arc::new(self.pool.clone()));
+        let mut tx = tx_manager.init().await?;
+
+        let digests = match sqlx::query_scalar!(
+            r#"
+            SELECT b.digest FROM blobs b 
+            WHERE b.leased_at <= NOW() - make_interval(secs => $1) 
+            AND NOT EXISTS (SELECT 1 FROM run_artifacts a WHERE a.digest = b.digest) 
+            LIMIT $2 FOR UPDATE SKIP LOCKED
+            "#,
+            BLOB_LEASE as f64,
+            BLOB_BATCH
+        )
+        .fetch_all(&mut *tx)
+        .await
+        {
+            Ok(digests) => digests,
+            Err(error) => {
+                eprintln!("{}", error);
+                tx_manager.rollback(tx).await?;
+                return Err(CrudError::Read);
+            }
+        };
+
+        let deleted = digests
+            .into_iter()
+            .filter(|digest| match store.delete(digest) {
+                Ok(_) => true,
+                Err(error) => {
+                    eprintln!("blob {}: {}", digest, error.reason());
+                    false
+                }
+            })
+            .collect::<Vec<_>>();
+
+        match sqlx::query!("DELETE FROM blobs WHERE digest = ANY($1)", &deleted)
+            .execute(&mut *tx)
+            .await
+        {
+            Ok(_) => {
+                tx_manager.commit(tx).await?;
+                Ok(deleted)
+            }
+            Err(error) => {
+                eprintln!("{}", error);
+                tx_manager.rollback(tx).await?;
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Notify
+    /// Match the runs that finished since the last call against the
+    /// notification rules of their user, and record a pending delivery per
+    /// match, returns the number of deliveries. Runs are evaluated once,
+    /// in the order they finished, children of matrix runs never are.
+    ///
+    /// `failures` counts the failed runs of the job since the last run that
+    /// succeeded before the run, including the run, so that
+    /// `consecutive_failures` rules fire once per streak, and `recovery`
+    /// rules report the length of the streak.
+    pub async fn notify(&self) -> Result<usize, CrudError> {
+        let tx_manager = Tx::new(Arc::new(self.pool.clone()));
+        let mut tx = tx_manager.init().await?;
+
+        let runs = match sqlx::query_as!(
+            Finished,
+            r#"
+            SELECT r.run_id, j.user_id, j.project_id, j.job_name, 
+            COALESCE(p.project_name, 'default') AS "project_name!", r.run_status::TEXT AS "status!", 
+            r.error, r.timed_out, 
+            to_char(r.finished_at AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS"Z"') AS "finished_at!", 
+            (
+                SELECT COUNT(*) FROM runs f 
+                WHERE f.job_id = r.job_id AND f.parent_run_id IS NULL AND f.run_status = 'failed' 
+                AND f.finished_at <= r.finished_at AND f.finished_at > COALESCE((
+                    SELECT MAX(s.finished_at) FROM runs s 
+                    WHERE s.job_id = r.job_id AND s.parent_run_id IS NULL 
+                    AND s.run_status = 'succeeded' AND s.finished_at < r.finished_at
+                ), '-infinity')
+            ) AS "failures!", 
+            (
+                SELECT q.run_status::TEXT FROM runs q 
+                WHERE q.job_id = r.job_id AND q.parent_run_id IS NULL 
+                AND q.run_status IN ('succeeded', 'failed') AND q.finished_at < r.finished_at 
+                ORDER BY q.finished_at DESC LIMIT 1
+            ) AS previous 
+            FROM runs r JOIN jobs j ON j.job_id = r.job_id 
+            LEFT JOIN projects p ON p.project_id = j.project_id 
+            WHERE r.notified_at IS NULL AND r.parent_run_id IS NULL 
+            AND r.run_status IN ('succeeded', 'failed', 'cancelled') 
+            ORDER BY r.finished_at LIMIT $1 FOR UPDATE OF r SKIP LOCKED
+            "#,
+            NOTIFY_BATCH
+        )
+        .fetch_all(&mut *tx)
+        .await
+        {
+            Ok(runs) => runs,
+            Err(error) => {
+                eprintln!("{}", error);
+                tx_manager.rollback(tx).await?;
+                return Err(CrudError::Read);
+            }
+        };
+
+        if runs.is_empty() {
+            tx_manager.commit(tx).await?;
+            return Ok(0);
+        }
+
+        let users = runs
+            .iter()
+            .filter_map(|run| run.user_id)
+            .collect::<Vec<_>>();
+
+        let rules = match sqlx::query_as!(
+            Rule,
+            r#"
+            SELECT r.rule_id, r.rule_name, r.user_id, r.project_id, r.job_name, 
+            r.event::TEXT AS "event!", r.threshold, r.template, c.channel_id, c.channel_name 
+            FROM notification_rules r 
+            JOIN notification_channels c ON c.channel_id = r.channel_id 
+            WHERE r.user_id = ANY($1)
+            "#,
+            &users
+        )
+        .fetch_all(&mut *tx)
+        .await
+        {
+            Ok(rules) => rules,
+            Err(error) => {
+                eprintln!("{}", error);
+                tx_manager.rollback(tx).await?;
+                return Err(CrudError::Read);
+            }
+        };
+
+        let notifications = runs
+            .iter()
+            .flat_map(|run| {
+                rules
+                    .iter()
+                    .filter(|rule| rule.matches(run))
+                    .map(|rule| rule.notification(run))
+            })
+            .collect::<Vec<_>>();
+
+        for notification in &notifications {
+            if let Err(error) = sqlx::query!(
+                r#"
+                INSERT INTO notification_deliveries (user_id, rule_id, channel_id, run_id, 
+                rule_name, channel_name, job_name, event, message, payload) 
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8::TEXT::notification_event, $9, $10)
+                "#,
+                notification.rule.user_id,
+                notification.rule.rule_id,
+                notification.rule.channel_id,
+                notification.run.run_id,
+                notification.rule.rule_name,
+                notification.rule.channel_name,
+                notification.run.job_name,
+                notification.rule.event,
+                notification.message,
+                notification.payload
+            )
+            .execute(&mut *tx)
+            .await
+            {
+                eprintln!("{}", error);
+                tx_manager.rollback(tx).await?;
+                return Err(CrudError::Insertion);
+            }
+        }
+
+        let run_ids = runs.iter().map(|run| run.run_id).collect::<Vec<_>>();
+
+        match sqlx::query!(
+            "UPDATE runs SET notified_at = NOW() WHERE run_id = ANY($1)",
+            &run_ids
+        )
+        .execute(&mut *tx)
+        .await
+        {
+            Ok(_) => {
+                tx_manager.commit(tx).await?;
+                Ok(notifications.len())
+            }
+            Err(error) => {
+                eprintln!("{}", error);
+                tx_manager.rollback(tx).await?;
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Deliveries
+    /// Claim the pending deliveries that are due for an attempt. Claiming
+    /// counts the attempt and schedules the next one, so that a delivery
+    /// whose attempt is lost is retried after the backoff.
+    pub async fn deliveries(&self) -> Result<Vec<Delivery>, CrudError> {
+        match sqlx::query_as!(
+            Delivery,
+            r#"
+            UPDATE notification_deliveries d SET attempts = d.attempts + 1, 
+            next_attempt_at = NOW() + make_interval(secs => $1 * power(2, d.attempts)) 
+            WHERE d.delivery_id IN (
+                SELECT delivery_id FROM notification_deliveries 
+                WHERE delivery_status = 'pending' AND next_attempt_at <= NOW() 
+                ORDER BY next_attempt_at LIMIT $2 FOR UPDATE SKIP LOCKED
+            ) 
+            RETURNING d.delivery_id, d.attempts, d.event::TEXT AS "event!", d.job_name, d.message, 
+            d.payload AS "payload: Json<Value>", 
+            (SELECT c.kind::TEXT FROM notification_channels c WHERE c.channel_id = d.channel_id) AS kind, 
+            (SELECT c.url FROM notification_channels c WHERE c.channel_id = d.channel_id) AS url, 
+            (SELECT c.recipients FROM notification_channels c WHERE c.channel_id = d.channel_id) AS recipients
+            "#,
+            BACKOFF as f64,
+            DELIVERY_BATCH
+        )
+        .fetch_all(&self.pool)
+        .await
+        {
+            Ok(deliveries) => Ok(deliveries),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Attempted
+    /// Record the outcome of an attempt of `delivery`. Failed deliveries stay
+    /// pending until their last attempt, or a permanent failure.
+    pub async fn attempted(&self, delivery: &Delivery, attempt: &Attempt) -> Result<(), CrudError> {
+        match sqlx::query!(
+            r#"
+            UPDATE notification_deliveries SET delivery_status = $2::TEXT::delivery_status, 
+            response_status = $3, last_error = $4, 
+            delivered_at = CASE WHEN $5 THEN NOW() END 
+            WHERE delivery_id = $1
+            "#,
+            delivery.delivery_id,
+            attempt.status(delivery.attempts, MAX_ATTEMPTS),
+            attempt.response_status,
+            attempt.error,
+            attempt.delivered
+        )
+        .execute(&self.pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Fan Out
+    /// Record a delivery of every event of the outbox not yet dispatched to
+    /// each subscription of its user to the type of the event, returns the
+    /// number of deliveries. Subscriptions only receive the events that
+    /// occurred after they were created.
+    pub async fn fan_out(&self) -> Result<i64, CrudError> {
+        match sqlx::query_scalar!(
+            r#"
+            WITH events AS (
+                SELECT event_id, user_id, event_type, created_at FROM webhook_outbox 
+                WHERE dispatched_at IS NULL 
+                ORDER BY created_at LIMIT $1 FOR UPDATE SKIP LOCKED
+            ), 
+            fanned AS (
+                INSERT INTO webhook_deliveries (event_id, subscription_id) 
+                SELECT e.event_id, s.subscription_id FROM events e 
+                JOIN webhook_subscriptions s ON s.user_id = e.user_id 
+                AND e.event_type = ANY(s.events) AND s.created_at <= e.created_at 
+                ON CONFLICT ON CONSTRAINT unique_delivery_per_subscription DO NOTHING 
+                RETURNING delivery_id
+            ), 
+            dispatched AS (
+                UPDATE webhook_outbox o SET dispatched_at = NOW() 
+                FROM events e WHERE o.event_id = e.event_id
+            ) 
+            SELECT COUNT(*) AS "deliveries!" FROM fanned
+            "#,
+            OUTBOX_BATCH
+        )
+        .fetch_one(&self.pool)
+        .await
+        {
+            Ok(deliveries) => Ok(deliveries),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Prune Outbox
+    /// Delete the events kept in the outbox for longer than the webhook
+    /// retention, along with their deliveries, returns the number of events.
+    pub async fn prune_outbox(&self) -> Result<u64, CrudError> {
+        match sqlx::query!(
+            r#"
+            DELETE FROM webhook_outbox 
+            WHERE created_at <= NOW() - make_interval(days => $1)
+            "#,
+            webhook::RETENTION_DAYS
+        )
+        .execute(&self.pool)
+        .await
+        {
+            Ok(result) => Ok(result.rows_affected()),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Webhook Deliveries
+    /// Claim the pending webhook deliveries that are due for an attempt,
+    /// along with their event and the secrets of their subscription. Like
+    /// notification deliveries, claiming schedules the next attempt.
+    pub async fn webhook_deliveries(&self) -> Result<Vec<webhook::Delivery>, CrudError> {
+        match sqlx::query_as!(
+            webhook::Delivery,
+            r#"
+            UPDATE webhook_deliveries d SET attempts = d.attempts + 1, 
+            next_attempt_at = NOW() + make_interval(secs => LEAST($1 * power(2, d.attempts), $2)) 
+            FROM webhook_subscriptions s, webhook_outbox o 
+            WHERE s.subscription_id = d.subscription_id AND o.event_id = d.event_id 
+            AND d.delivery_id IN (
+                SELECT delivery_id FROM webhook_deliveries 
+                WHERE delivery_status = 'pending' AND next_attempt_at <= NOW() 
+                ORDER BY next_attempt_at LIMIT $3 FOR UPDATE SKIP LOCKED
+            ) 
+            RETURNING d.delivery_id, d.attempts, o.event_id, o.event_type, o.schema_version, 
+            to_char(o.created_at AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS.US"Z"') AS "created_at!", 
+            o.data AS "data: Json<Value>", s.user_id, s.subscription_name, s.url, s.nonce, s.ciphertext, 
+            CASE WHEN s.previous_expires_at > NOW() THEN s.previous_nonce END AS previous_nonce, 
+            CASE WHEN s.previous_expires_at > NOW() THEN s.previous_ciphertext END AS previous_ciphertext
+            "#,
+            BACKOFF as f64,
+            webhook::MAX_BACKOFF as f64,
+            DELIVERY_BATCH
+        )
+        .fetch_all(&self.pool)
+        .await
+        {
+            Ok(deliveries) => Ok(deliveries),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Webhook Attempted
+    /// Record the outcome of an attempt of a webhook `delivery`
+    pub async fn webhook_attempted(
+        &self,
+        delivery: &webhook::Delivery,
+        attempt: &Attempt,
+    ) -> Result<(), CrudError> {
+        match sqlx::query!(
+            r#"
+            UPDATE webhook_deliveries SET delivery_status = $2::TEXT::delivery_status, 
+            response_status = $3, last_error = $4, 
+            delivered_at = CASE WHEN $5 THEN NOW() END 
+            WHERE delivery_id = $1
+            "#,
+            delivery.delivery_id,
+            attempt.status(delivery.attempts, webhook::MAX_ATTEMPTS),
+            attempt.response_status,
+            attempt.error,
+            attempt.delivered
+        )
+        .execute(&self.pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Place
+    /// Assign queued runs to live workers with enough free capacity.
+    ///
+    /// Runs are placed by descending priority, then in the order they were
+    /// scheduled (children of a matrix run in the order of their combinations,
+    /// at most `max_parallel` at a time), each on the worker with the most free memory among those
+    /// matching its node selector and affinity rules, with enough free capacity.
+    /// A run reserves its requests on the worker until it is no longer queued
+    /// or running. Runs no worker can take, e.g. as none matches their node
+    /// selector, are marked `unschedulable` with the reason, runs only lacking
+    /// free capacity stay `queued` with the reason they wait for. Both are
+    /// placed once a worker can take them.
+    ///
+    /// ## Preemption
+    ///
+    /// A run whose class allows preemption, and only lacks free capacity, makes
+    /// room on a worker by preempting runs of lower priority once it waited
+    /// for `LATENESS` past its scheduled time. Queued runs are
+    /// placed again, running runs are terminated gracefully by their worker and
+    /// queued again. The room is held for the run until it is placed.
+    ///
+    /// Queued runs of lost workers are placed again, as are their preempted
+    /// running runs, while their other running runs are failed.
+    pub async fn place(&self) -> Result<Vec<Placement>, CrudError> {
+        let tx_manager = Tx::new(Arc::new(self.pool.clone()));
+        let mut tx = tx_manager.init().await?;
+
+        match self.place_inner(&mut tx).await {
+            Ok(placements) => {
+                tx_manager.commit(tx).await?;
+                Ok(placements)
+            }
+            Err(error) => {
+                tx_manager.rollback(tx).await?;
+                Err(error)
+            }
+        }
+    }
+
+    async fn place_inner(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<Placement>, CrudError> {
+        let alive = OffsetDateTime::now_utc() - Duration::from_secs(WORKER_TIMEOUT);
+
+        for query in [
+            sqlx::query!(
+                r#"
+                UPDATE runs SET worker_id = NULL 
+                WHERE run_status = 'queued' 
+                AND worker_id IN (SELECT worker_id FROM workers WHERE heartbeat_at <= $1)
+                "#,
+                alive
+            ),
+            sqlx::query!(
+                r#"
+                UPDATE runs SET run_status = 'queued', worker_id = NULL, started_at = NULL, 
+                preempted_at = NULL, preemptions = preemptions + 1, 
+                reason = 'Preempted by a run of higher priority' 
+                WHERE run_status = 'running' AND preempted_at IS NOT NULL 
+                AND worker_id IN (SELECT worker_id FROM workers WHERE heartbeat_at <= $1)
+                "#,
+                alive
+            ),
+            sqlx::query!(
+                r#"
+                UPDATE runs SET run_status = 'failed', finished_at = NOW(), error = 'Worker lost' 
+                WHERE run_status = 'running' 
+                AND worker_id IN (SELECT worker_id FROM workers WHERE heartbeat_at <= $1)
+                "#,
+                alive
+            ),
+        ] {
+            if let Err(error) = query.execute(&mut **tx).await {
+                eprintln!("{}", error);
+                return Err(CrudError::Insertion);
+            }
+        }
+
+        // serializes placement across orchestrators
+        if let Err(error) = sqlx::query_scalar!(
+            "SELECT worker_id FROM workers WHERE heartbeat_at > $1 FOR UPDATE",
+            alive
+        )
+        .fetch_all(&mut **tx)
+        .await
+        {
+            eprintln!("{}", error);
+            return Err(CrudError::Read);
+        }
+
+        let mut workers = match sqlx::query_as!(
+            Capacity,
+            r#"
+            SELECT w.worker_id, w.worker_name, 
+            w.labels AS "labels: Json<HashMap<String, String>>", 
+            w.cpu AS total_cpu, w.memory_mb AS total_memory_mb, w.disk_mb AS total_disk_mb, 
+            w.cpu - COALESCE(SUM(r.cpu), 0) AS "cpu!", 
+            w.memory_mb - COALESCE(SUM(r.memory_mb), 0) AS "memory_mb!", 
+            w.disk_mb - COALESCE(SUM(r.disk_mb), 0) AS "disk_mb!" 
+            FROM workers w 
+            LEFT JOIN runs r ON r.worker_id = w.worker_id AND r.run_status IN ('queued', 'running') 
+            WHERE w.heartbeat_at > $1 
+            GROUP BY w.worker_id
+            "#,
+            alive
+        )
+        .fetch_all(&mut **tx)
+        .await
+        {
+            Ok(workers) => workers,
+            Err(error) => {
+                eprintln!("{}", error);
+                return Err(CrudError::Read);
+            }
+        };
+
+        let mut placed = match sqlx::query!(
+            r#"
+            SELECT r.run_id, r.worker_id AS "worker_id!", j.project_id, j.job_name, 
+            j.labels AS "labels: Json<HashMap<String, String>>", 
+            r.priority, r.cpu, r.memory_mb, r.disk_mb, r.started_at, 
+            r.preempted_at IS NOT NULL AS "preempted!" 
+            FROM runs r JOIN jobs j ON j.job_id = r.job_id 
+            WHERE r.worker_id IS NOT NULL AND r.run_status IN ('queued', 'running')
+            "#
+        )
+        .fetch_all(&mut **tx)
+        .await
+        {
+            Ok(rows) => rows
+                .into_iter()
+                .map(|row| Placed {
+                    run_id: row.run_id,
+                    worker_id: row.worker_id,
+                    project_id: row.project_id,
+                    job_name: row.job_name,
+                    labels: row.labels.0,
+                    priority: row.priority,
+                    cpu: row.cpu,
+                    memory_mb: row.memory_mb,
+                    disk_mb: row.disk_mb,
+                    started_at: row.started_at,
+                    preempted: row.preempted,
+                })
+                .collect::<Vec<_>>(),
+            Err(error) => {
+                eprintln!("{}", error);
+                return Err(CrudError::Read);
+            }
+        };
+
+        // children of a matrix run wait for a slot while `max_parallel` of them are placed
+        let pending = match sqlx::query!(
+            r#"
+            WITH active AS (
+                SELECT parent_run_id, COUNT(*) AS runs FROM runs 
+                WHERE parent_run_id IS NOT NULL AND worker_id IS NOT NULL 
+                AND run_status IN ('queued', 'running') 
+                GROUP BY parent_run_id
+            ), waiting AS (
+                SELECT c.run_id, (p.matrix->>'max_parallel')::int AS max_parallel, 
+                COALESCE(a.runs, 0) AS active, 
+                ROW_NUMBER() OVER (PARTITION BY c.parent_run_id ORDER BY c.position) AS slot 
+                FROM runs c JOIN runs p ON p.run_id = c.parent_run_id 
+                LEFT JOIN active a ON a.parent_run_id = c.parent_run_id 
+                WHERE c.run_status IN ('queued', 'unschedulable') AND c.worker_id IS NULL
+            ) 
+            SELECT r.run_id, r.priority, r.preempt, r.scheduled_at, r.cpu, r.memory_mb, r.disk_mb, 
+            j.project_id, j.job_name, 
+            j.labels AS "labels: Json<HashMap<String, String>>", 
+            j.node_selector AS "node_selector: Json<HashMap<String, String>>", 
+            j.affinity AS "affinity: Json<Vec<Term>>", 
+            j.anti_affinity AS "anti_affinity: Json<Vec<Term>>" 
+            FROM runs r JOIN jobs j ON j.job_id = r.job_id 
+            LEFT JOIN waiting c ON c.run_id = r.run_id 
+            WHERE r.run_status IN ('queued', 'unschedulable') AND r.worker_id IS NULL 
+            AND (c.max_parallel IS NULL OR c.active + c.slot <= c.max_parallel) 
+            ORDER BY r.priority DESC, r.scheduled_at, r.position 
+            FOR UPDATE OF r SKIP LOCKED
+            "#
+        )
+        .fetch_all(&mut **tx)
+        .await
+        {
+            Ok(rows) => rows.into_iter().map(|row| Pending {
+                run_id: row.run_id,
+                priority: row.priority,
+                preempt: row.preempt,
+                scheduled_at: row.scheduled_at,
+                project_id: row.project_id,
+                job_name: row.job_name,
+                labels: row.labels.0,
+                cpu: row.cpu,
+                memory_mb: row.memory_mb,
+                disk_mb: row.disk_mb,
+                node_selector: row.node_selector.0,
+                affinity: row.affinity.0,
+                anti_affinity: row.anti_affinity.0,
+            }),
+            Err(error) => {
+                eprintln!("{}", error);
+                return Err(CrudError::Read);
+            }
+        };
+
+        let mut placements = Vec::new();
+
+        for run in pending {
+            let index = match choose(&workers, &placed, &run) {
+                Ok(index) => index,
+                Err(unplaced) => {
+                    let unplaced = match unplaced {
+                        Unplaced::Waiting(reason) if run.preempt => Unplaced::Waiting(
+                            self.preempt(tx, &mut workers, &mut placed, &run)
+                                .await?
+                                .unwrap_or(reason),
+                        ),
+                        unplaced => unplaced,
+                    };
+
+                    // only runs no worker can take are unschedulable, the others keep waiting
+                    let (unschedulable, reason) = match &unplaced {
+                        Unplaced::Unschedulable(reason) => (true, reason),
+                        Unplaced::Waiting(reason) => (false, reason),
+                    };
+
+                    match sqlx::query!(
+                        r#"
+                        UPDATE runs SET reason = $2, run_status = CASE WHEN $3 
+                            THEN 'unschedulable'::run_status ELSE 'queued'::run_status END 
+                        WHERE run_id = $1 
+                        AND (reason IS DISTINCT FROM $2 OR (run_status = 'unschedulable') <> $3)
+                        "#,
+                        run.run_id,
+                        reason,
+                        unschedulable
+                    )
+                    .execute(&mut **tx)
+                    .await
+                    {
+                        Ok(result) if result.rows_affected() == 1 => placements.push(Placement {
+                            run_id: run.run_id,
+                            worker: Err(unplaced),
+                        }),
+                        Ok(_) => {}
+                        Err(error) => {
+                            eprintln!("{}", error);
+                            return Err(CrudError::Insertion);
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            let worker = &mut workers[index];
+
+            if let Err(error) = sqlx::query!(
+                r#"
+                UPDATE runs SET run_status = 'queued', worker_id = $2, reason = NULL 
+                WHERE run_id = $1
+                "#,
+                run.run_id,
+                worker.worker_id
+            )
+            .execute(&mut **tx)
+            .await
+            {
+                eprintln!("{}", error);
+                return Err(CrudError::Insertion);
             }
+
+            worker.reserve(&run);
+            placed.push(Placed {
+                run_id: run.run_id,
+                worker_id: worker.worker_id,
+                project_id: run.project_id,
+                job_name: run.job_name,
+                labels: run.labels,
+                priority: run.priority,
+                cpu: run.cpu,
+                memory_mb: run.memory_mb,
+                disk_mb: run.disk_mb,
+                started_at: None,
+                preempted: false,
+            });
+            placements.push(Placement {
+                run_id: run.run_id,
+                worker: Ok(worker.worker_name.clone()),
+            });
         }
+
+        Ok(placements)
+    }
+
+    /// # Preempt
+    /// Make room for `run` by preempting runs of lower priority, see `preempt`.
+    /// The room is reserved for `run` so that runs placed after it cannot take it.
+    ///
+    /// ## Returns
+    ///
+    /// The reason `run` is still waiting, or `None` if no worker can make room.
+    async fn preempt(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        workers: &mut [Capacity],
+        placed: &mut Vec<Placed>,
+        run: &Pending,
+    ) -> Result<Option<String>, CrudError> {
+        let Some((index, victims)) = preempt(workers, placed, run, OffsetDateTime::now_utc())
+        else {
+            return Ok(None);
+        };
+
+        let worker = &mut workers[index];
+
+        if victims.is_empty() {
+            worker.reserve(run);
+            return Ok(Some(format!(
+                "Waiting for preempted runs to terminate on worker {}",
+                worker.worker_name
+            )));
+        }
+
+        // queued runs are placed again right away, running ones once their worker terminates them
+        let requeued = match sqlx::query_scalar!(
+            r#"
+            UPDATE runs SET 
+            worker_id = CASE WHEN run_status = 'queued' THEN NULL ELSE worker_id END, 
+            preempted_at = CASE WHEN run_status = 'running' THEN NOW() END, 
+            preemptions = CASE WHEN run_status = 'queued' THEN preemptions + 1 ELSE preemptions END, 
+            reason = CASE WHEN run_status = 'queued' 
+                THEN 'Preempted by a run of higher priority' ELSE reason END 
+            WHERE run_id = ANY($1) AND run_status IN ('queued', 'running') 
+            RETURNING CASE WHEN worker_id IS NULL THEN run_id END
+            "#,
+            &victims
+        )
+        .fetch_all(&mut **tx)
+        .await
+        {
+            Ok(rows) => rows.into_iter().flatten().collect::<Vec<_>>(),
+            Err(error) => {
+                eprintln!("{}", error);
+                return Err(CrudError::Insertion);
+            }
+        };
+
+        for p in placed.iter_mut().filter(|p| victims.contains(&p.run_id)) {
+            p.preempted = true;
+        }
+        for p in placed.iter().filter(|p| requeued.contains(&p.run_id)) {
+            worker.release(p);
+        }
+        placed.retain(|p| !requeued.contains(&p.run_id));
+
+        worker.reserve(run);
+        Ok(Some(format!(
+            "Preempting {} run(s) of lower priority on worker {}",
+            victims.len(),
+            worker.worker_name
+        )))
     }
 }
@@ -1,9 +1,12 @@
 //! Job
 
 extern crate sqlx;
+extern crate std;
 
 use sqlx::types::{time::OffsetDateTime, Uuid};
+use std::fmt;
 
+#[allow(dead_code)]
 #[derive(Debug, Default, sqlx::FromRow)]
 pub struct Job {
     pub user_id: Option<Uuid>,
@@ -25,34 +28,29 @@ impl Job {
 }
 
 // Job Type
-#[derive(Debug, sqlx::types::Type)]
+#[derive(Debug, Default, sqlx::types::Type)]
 #[sqlx(type_name = "job_types", rename_all = "lowercase")]
 pub enum JobType {
     Bin,
     Code,
+    #[default]
     Invalid,
     Task,
 }
 
-impl Default for JobType {
-    fn default() -> Self {
-        Self::Invalid
-    }
-}
-
-impl ToString for JobType {
-    fn to_string(&self) -> String {
-        match *self {
-            JobType::Bin => "bin".to_string(),
-            JobType::Code => "code".into(),
-            JobType::Invalid => "invalid".into(),
-            JobType::Task => "task".into(),
-        }
+impl fmt::Display for JobType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            JobType::Bin => "bin",
+            JobType::Code => "code",
+            JobType::Invalid => "invalid",
+            JobType::Task => "task",
+        })
     }
 }
 
 // Job Status
-#[derive(Debug, sqlx::types::Type)]
+#[derive(Debug, Default, sqlx::types::Type)]
 #[sqlx(type_name = "job_status", rename_all = "lowercase")]
 pub enum JobStatus {
     /// Job is running
@@ -62,16 +60,12 @@ pub enum JobStatus {
     Scheduled,
 
     /// Job is disabled
+    #[default]
     Disabled,
 }
 
-impl Default for JobStatus {
-    fn default() -> Self {
-        Self::Disabled
-    }
-}
-
 // Bin
+#[allow(dead_code)]
 #[derive(Debug, Default)]
 pub struct Bin {
     pub path: String,
@@ -79,6 +73,7 @@ pub struct Bin {
 }
 
 // Code
+#[allow(dead_code)]
 #[derive(Debug, Default)]
 pub struct Code {
     pub src: String,
@@ -87,6 +82,7 @@ pub struct Code {
 }
 
 // Task
+#[allow(dead_code)]
 #[derive(Debug, Default, sqlx::FromRow)]
 pub struct Task {
     pub name: String,
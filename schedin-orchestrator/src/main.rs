@@ -7,25 +7,147 @@ extern crate tokio;
 
 mod db;
 mod job;
+mod matrix;
+mod notify;
+mod run;
+mod webhook;
+mod worker;
 
 use db::DB;
-use schedin_common::db::create_pool;
+use schedin_common::{blob, db::create_pool, secret::Cipher};
 use sqlx::Postgres;
-use std::{env, thread, time::Duration};
+use std::{env, time::Duration};
+use worker::Unplaced;
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
+    let pool = create_pool::<Postgres>(&env::var("DATABASE_URL").unwrap())
+        .await
+        .unwrap();
+
+    let db = DB::new(pool.clone());
+
+    // deliveries wait on channels, never hold up scheduling
+    tokio::spawn(notify::deliver(DB::new(pool.clone())));
+
+    // webhooks are signed with secrets sealed under the master key
+    match Cipher::from_env() {
+        Ok(cipher) => {
+            tokio::spawn(webhook::deliver(DB::new(pool), cipher));
+        }
+        Err(error) => eprintln!("webhook: {}", error.reason()),
+    }
+
+    // blobs are only collected with a blob store, expired artifacts regardless
+    let store = match blob::from_env() {
+        Ok(store) => Some(store),
+        Err(error) => {
+            eprintln!("collect: {}", error.reason());
+            None
+        }
+    };
+
     loop {
-        let pool = create_pool::<Postgres>(&env::var("DATABASE_URL").unwrap())
-            .await
-            .unwrap();
+        if let Ok(runs) = db.dispatch().await {
+            for run in runs {
+                println!(
+                    "dispatch: run {} job {} revision {} scheduled at {} timeout {:?}",
+                    run.run_id, run.job_id, run.revision, run.scheduled_at, run.timeout
+                );
+                if let Some(matrix) = &run.matrix {
+                    println!(
+                        "dispatch: run {} expanded into {} runs",
+                        run.run_id,
+                        matrix.combinations().len()
+                    );
+                }
+            }
+        }
+
+        match db.cancel().await {
+            Ok(runs) => {
+                for run_id in runs {
+                    println!("cancel: run {}", run_id);
+                }
+            }
+            Err(error) => eprintln!("cancel: {}", error.reason()),
+        }
+
+        match db.aggregate().await {
+            Ok(runs) => {
+                for run in runs {
+                    match run.error {
+                        Some(error) => println!("aggregate: run {} failed: {}", run.run_id, error),
+                        None => println!("aggregate: run {} succeeded", run.run_id),
+                    }
+                }
+            }
+            Err(error) => eprintln!("aggregate: {}", error.reason()),
+        }
+
+        match db.place().await {
+            Ok(placements) => {
+                for placement in placements {
+                    match placement.worker {
+                        Ok(worker) => {
+                            println!("place: run {} on worker {}", placement.run_id, worker)
+                        }
+                        Err(Unplaced::Unschedulable(reason)) => {
+                            println!("place: run {} unschedulable: {}", placement.run_id, reason)
+                        }
+                        Err(Unplaced::Waiting(reason)) => {
+                            println!("place: run {} waiting: {}", placement.run_id, reason)
+                        }
+                    }
+                }
+            }
+            Err(error) => eprintln!("place: {}", error.reason()),
+        }
+
+        match db.purge().await {
+            Ok(jobs) => {
+                for job in jobs {
+                    println!("purge: job {}", job);
+                }
+            }
+            Err(error) => eprintln!("purge: {}", error.reason()),
+        }
+
+        match db.expire().await {
+            Ok(0) => {}
+            Ok(artifacts) => println!("expire: {} artifacts", artifacts),
+            Err(error) => eprintln!("expire: {}", error.reason()),
+        }
+
+        match db.notify().await {
+            Ok(0) => {}
+            Ok(deliveries) => println!("notify: {} deliveries", deliveries),
+            Err(error) => eprintln!("notify: {}", error.reason()),
+        }
 
-        let db = DB::new(pool);
+        match db.fan_out().await {
+            Ok(0) => {}
+            Ok(deliveries) => println!("fan out: {} webhook deliveries", deliveries),
+            Err(error) => eprintln!("fan out: {}", error.reason()),
+        }
+
+        match db.prune_outbox().await {
+            Ok(0) => {}
+            Ok(events) => println!("prune: {} webhook events", events),
+            Err(error) => eprintln!("prune: {}", error.reason()),
+        }
 
-        if let Ok(jobs) = db.read(Duration::from_secs(600)).await {
-            println!("{:?}", jobs);
+        if let Some(store) = &store {
+            match db.collect(store.as_ref()).await {
+                Ok(digests) => {
+                    for digest in digests {
+                        println!("collect: blob {}", digest);
+                    }
+                }
+                Err(error) => eprintln!("collect: {}", error.reason()),
+            }
         }
 
-        thread::sleep(Duration::from_secs(60))
+        tokio::time::sleep(Duration::from_secs(1)).await;
     }
 }
@@ -0,0 +1,130 @@
+//! Matrix
+
+extern crate serde;
+extern crate serde_json;
+extern crate std;
+
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Matrix of a job, as snapshotted by its runs at dispatch
+#[derive(Debug, Deserialize)]
+pub struct Matrix {
+    pub axes: BTreeMap<String, Axis>,
+}
+
+/// Axis of a matrix, either a list of scalars or a range `start..end`
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Axis {
+    Values(Vec<Value>),
+    Range(String),
+}
+
+impl Axis {
+    /// Values of the axis, as validated by the server
+    fn values(&self) -> Vec<String> {
+        match self {
+            Axis::Values(values) => values
+                .iter()
+                .filter_map(|value| match value {
+                    Value::String(value) => Some(value.clone()),
+                    Value::Number(value) => Some(value.to_string()),
+                    Value::Bool(value) => Some(value.to_string()),
+                    _ => None,
+                })
+                .collect(),
+            Axis::Range(range) => match range
+                .split_once("..")
+                .map(|(start, end)| (start.trim().parse::<i64>(), end.trim().parse::<i64>()))
+            {
+                Some((Ok(start), Ok(end))) => (start..end).map(|value| value.to_string()).collect(),
+                _ => Vec::new(),
+            },
+        }
+    }
+}
+
+impl Matrix {
+    /// Every combination of values, by axis name. Axes are in name order,
+    /// the values of the last one vary first.
+    pub fn combinations(&self) -> Vec<BTreeMap<String, String>> {
+        self.axes
+            .iter()
+            .fold(vec![BTreeMap::new()], |combinations, (name, axis)| {
+                let values = axis.values();
+                combinations
+                    .iter()
+                    .flat_map(|combination| {
+                        values.iter().map(move |value| {
+                            let mut combination = combination.clone();
+                            combination.insert(name.clone(), value.clone());
+                            combination
+                        })
+                    })
+                    .collect()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Matrix;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    fn matrix(axes: serde_json::Value) -> Matrix {
+        serde_json::from_value(json!({ "axes": axes })).unwrap()
+    }
+
+    fn combination(values: &[(&str, &str)]) -> BTreeMap<String, String> {
+        values
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn expands_the_cross_product() {
+        let combinations =
+            matrix(json!({ "shard": "0..2", "region": ["eu", "us"] })).combinations();
+
+        assert_eq!(
+            combinations,
+            vec![
+                combination(&[("region", "eu"), ("shard", "0")]),
+                combination(&[("region", "eu"), ("shard", "1")]),
+                combination(&[("region", "us"), ("shard", "0")]),
+                combination(&[("region", "us"), ("shard", "1")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn formats_scalar_values() {
+        let combinations = matrix(json!({ "flag": [true, 2, "x", null, [1]] })).combinations();
+
+        assert_eq!(
+            combinations,
+            vec![
+                combination(&[("flag", "true")]),
+                combination(&[("flag", "2")]),
+                combination(&[("flag", "x")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn expands_empty_axes_into_nothing() {
+        assert!(matrix(json!({ "region": ["eu"], "shard": [] }))
+            .combinations()
+            .is_empty());
+        assert!(matrix(json!({ "region": ["eu"], "shard": "3..3" }))
+            .combinations()
+            .is_empty());
+        assert!(matrix(json!({ "region": ["eu"], "shard": "a..b" }))
+            .combinations()
+            .is_empty());
+    }
+}
@@ -0,0 +1,35 @@
+//! Run
+
+extern crate sqlx;
+
+use crate::{matrix::Matrix, worker::Unplaced};
+use sqlx::types::{time::OffsetDateTime, Json, Uuid};
+
+/// Run enqueued for a due job
+#[derive(Debug, sqlx::FromRow)]
+pub struct Run {
+    pub run_id: Uuid,
+    pub job_id: Uuid,
+    pub revision: i32,
+    pub scheduled_at: OffsetDateTime,
+    /// Timeout (in seconds), capped by the project quota
+    pub timeout: Option<i32>,
+    /// Matrix of the job, the run is expanded into one child per combination
+    pub matrix: Option<Json<Matrix>>,
+}
+
+/// Matrix run completed along with its children
+#[derive(Debug, sqlx::FromRow)]
+pub struct Completed {
+    pub run_id: Uuid,
+    /// Why the run failed, `None` if it succeeded
+    pub error: Option<String>,
+}
+
+/// Run placed on a worker, or newly unschedulable or waiting
+#[derive(Debug)]
+pub struct Placement {
+    pub run_id: Uuid,
+    /// Name of the worker, or why the run is not placed
+    pub worker: Result<String, Unplaced>,
+}
@@ -0,0 +1,181 @@
+//! Webhooks
+//!
+//! Run and job events are written to the outbox by database triggers, in
+//! the transaction of the change they report. Events are fanned out into
+//! one delivery per matching subscription, and deliveries are attempted
+//! until they succeed, so that every event reaches its subscribers at
+//! least once.
+
+extern crate reqwest;
+extern crate schedin_common;
+extern crate serde_json;
+extern crate sqlx;
+extern crate std;
+extern crate tokio;
+
+use crate::{db::DB, notify::Attempt};
+use reqwest::header::CONTENT_TYPE;
+use schedin_common::{
+    secret::Cipher,
+    webhook::{self, EVENT_ID_HEADER, EVENT_TYPE_HEADER, SIGNATURE_HEADER},
+};
+use serde_json::{json, Value};
+use sqlx::types::{Json, Uuid};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Attempts of a delivery before it is marked `failed`, it may still be
+/// redelivered on request
+pub const MAX_ATTEMPTS: i32 = 12;
+
+/// Maximum delay (in seconds) between attempts, which start out like
+/// notification deliveries and double on every attempt
+pub const MAX_BACKOFF: i32 = 3600;
+
+/// Days events are kept in the outbox, and may be redelivered
+pub const RETENTION_DAYS: i32 = 7;
+
+/// Time (in seconds) a subscriber has to accept a delivery
+const DELIVERY_TIMEOUT: u64 = 10;
+
+/// Delivery claimed for an attempt, along with its event and subscription
+#[derive(Debug, sqlx::FromRow)]
+pub struct Delivery {
+    pub delivery_id: Uuid,
+    pub attempts: i32,
+    pub event_id: Uuid,
+    pub event_type: String,
+    pub schema_version: i32,
+    /// RFC 3339 time the event occurred at
+    pub created_at: String,
+    pub data: Json<Value>,
+    pub user_id: Option<Uuid>,
+    pub subscription_name: String,
+    pub url: String,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+    /// Previous secret, while it is rotated out
+    pub previous_nonce: Option<Vec<u8>>,
+    pub previous_ciphertext: Option<Vec<u8>>,
+}
+
+/// # Deliver
+/// Send due webhook deliveries every second, for as long as the
+/// orchestrator runs.
+pub async fn deliver(db: DB, cipher: Cipher) {
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(DELIVERY_TIMEOUT))
+        .build()
+    {
+        Ok(client) => client,
+        Err(error) => {
+            eprintln!("webhook: {}", error);
+            return;
+        }
+    };
+
+    loop {
+        match db.webhook_deliveries().await {
+            Ok(deliveries) => {
+                for delivery in deliveries {
+                    let attempt = send(&client, &cipher, &delivery).await;
+
+                    match &attempt.error {
+                        None => println!(
+                            "webhook: event {} delivered to {}",
+                            delivery.event_id, delivery.subscription_name
+                        ),
+                        Some(error) => println!(
+                            "webhook: event {} attempt {} to {} failed: {}",
+                            delivery.event_id, delivery.attempts, delivery.subscription_name, error
+                        ),
+                    }
+
+                    if let Err(error) = db.webhook_attempted(&delivery, &attempt).await {
+                        eprintln!("webhook: {}", error.reason());
+                    }
+                }
+            }
+            Err(error) => eprintln!("webhook: {}", error.reason()),
+        }
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Attempt to send `delivery` to its subscriber, signed at the time of
+/// the attempt
+async fn send(client: &reqwest::Client, cipher: &Cipher, delivery: &Delivery) -> Attempt {
+    let secrets = match secrets(cipher, delivery) {
+        Ok(secrets) => secrets,
+        Err(error) => return Attempt::permanent(&error),
+    };
+
+    let body = json!({
+        "id": delivery.event_id.to_string(),
+        "type": delivery.event_type,
+        "version": delivery.schema_version,
+        "created_at": delivery.created_at,
+        "data": delivery.data.0,
+    })
+    .to_string();
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default();
+
+    let secrets = secrets.iter().map(String::as_str).collect::<Vec<_>>();
+    let signature = webhook::signature(&secrets, timestamp, body.as_bytes());
+
+    match client
+        .post(&delivery.url)
+        .header(CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, signature)
+        .header(EVENT_ID_HEADER, delivery.event_id.to_string())
+        .header(EVENT_TYPE_HEADER, &delivery.event_type)
+        .body(body)
+        .send()
+        .await
+    {
+        Ok(response) if response.status().is_success() => Attempt {
+            delivered: true,
+            response_status: Some(response.status().as_u16() as i32),
+            error: None,
+            permanent: false,
+        },
+        Ok(response) => Attempt {
+            delivered: false,
+            response_status: Some(response.status().as_u16() as i32),
+            error: Some(format!("Subscriber responded with {}", response.status())),
+            permanent: false,
+        },
+        Err(error) => Attempt::failed(error.to_string()),
+    }
+}
+
+/// Signing secrets of the subscription of `delivery`, the current one
+/// first
+fn secrets(cipher: &Cipher, delivery: &Delivery) -> Result<Vec<String>, String> {
+    let user_id = delivery
+        .user_id
+        .map(|id| id.to_string())
+        .unwrap_or_default();
+    let aad = webhook::aad(&user_id, &delivery.subscription_name);
+
+    let mut sealed = vec![(&delivery.nonce, &delivery.ciphertext)];
+    if let (Some(nonce), Some(ciphertext)) =
+        (&delivery.previous_nonce, &delivery.previous_ciphertext)
+    {
+        sealed.push((nonce, ciphertext));
+    }
+
+    sealed
+        .into_iter()
+        .map(|(nonce, ciphertext)| {
+            cipher
+                .decrypt(nonce, ciphertext, &aad)
+                .map(|secret| secret.expose().clone())
+                .map_err(|error| error.reason().to_string())
+        })
+        .collect()
+}
@@ -0,0 +1,430 @@
+//! Worker
+
+extern crate serde;
+extern crate sqlx;
+extern crate std;
+
+use serde::Deserialize;
+use sqlx::types::{time::OffsetDateTime, Json, Uuid};
+use std::{collections::HashMap, time::Duration};
+
+/// Time a run may wait for free capacity past its scheduled time, before it
+/// preempts runs of lower priority
+pub const LATENESS: Duration = Duration::from_secs(30);
+
+/// Live worker, with its total capacity and the free capacity left
+/// by the reservations of its queued and running runs
+#[derive(Debug, sqlx::FromRow)]
+pub struct Capacity {
+    pub worker_id: Uuid,
+    pub worker_name: String,
+    pub labels: Json<HashMap<String, String>>,
+    pub total_cpu: i32,
+    pub total_memory_mb: i32,
+    pub total_disk_mb: i32,
+    pub cpu: i64,
+    pub memory_mb: i64,
+    pub disk_mb: i64,
+}
+
+impl Capacity {
+    /// Whether the requests of `run` fit into the free capacity
+    pub fn fits(&self, run: &Pending) -> bool {
+        run.fits((self.cpu, self.memory_mb, self.disk_mb))
+    }
+
+    /// Whether the requests of `run` fit into the total capacity
+    pub fn could_fit(&self, run: &Pending) -> bool {
+        self.total_cpu >= run.cpu
+            && self.total_memory_mb >= run.memory_mb
+            && self.total_disk_mb >= run.disk_mb
+    }
+
+    /// Reserve the requests of `run`
+    pub fn reserve(&mut self, run: &Pending) {
+        self.cpu -= run.cpu as i64;
+        self.memory_mb -= run.memory_mb as i64;
+        self.disk_mb -= run.disk_mb as i64;
+    }
+
+    /// Release the reservation of `placed`
+    pub fn release(&mut self, placed: &Placed) {
+        self.cpu += placed.cpu as i64;
+        self.memory_mb += placed.memory_mb as i64;
+        self.disk_mb += placed.disk_mb as i64;
+    }
+}
+
+/// Run waiting for a worker, along with its resource requests
+/// and placement rules
+#[derive(Debug)]
+pub struct Pending {
+    pub run_id: Uuid,
+    pub priority: i32,
+    /// Whether the run may preempt runs of lower priority
+    pub preempt: bool,
+    pub scheduled_at: OffsetDateTime,
+    pub project_id: Uuid,
+    pub job_name: String,
+    pub labels: HashMap<String, String>,
+    pub cpu: i32,
+    pub memory_mb: i32,
+    pub disk_mb: i32,
+    pub node_selector: HashMap<String, String>,
+    pub affinity: Vec<Term>,
+    pub anti_affinity: Vec<Term>,
+}
+
+impl Pending {
+    /// Whether the requests fit into `free` CPU, memory and disk
+    fn fits(&self, free: (i64, i64, i64)) -> bool {
+        free.0 >= self.cpu as i64
+            && free.1 >= self.memory_mb as i64
+            && free.2 >= self.disk_mb as i64
+    }
+}
+
+/// Queued or running run on a worker
+#[derive(Debug)]
+pub struct Placed {
+    pub run_id: Uuid,
+    pub worker_id: Uuid,
+    pub project_id: Uuid,
+    pub job_name: String,
+    pub labels: HashMap<String, String>,
+    pub priority: i32,
+    pub cpu: i32,
+    pub memory_mb: i32,
+    pub disk_mb: i32,
+    pub started_at: Option<OffsetDateTime>,
+    /// Preempted, its reservation is released once it terminates
+    pub preempted: bool,
+}
+
+/// Affinity term, matches the jobs of the same project by name and/or labels
+#[derive(Debug, Deserialize)]
+pub struct Term {
+    pub job: Option<String>,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+}
+
+impl Term {
+    fn matches(&self, project_id: &Uuid, placed: &Placed) -> bool {
+        placed.project_id == *project_id
+            && self.job.as_ref().is_none_or(|job| *job == placed.job_name)
+            && subset(&self.labels, &placed.labels)
+    }
+}
+
+/// Why a run cannot be placed right now
+#[derive(Debug, PartialEq)]
+pub enum Unplaced {
+    /// No worker can take the run, e.g. none matches its node selector
+    Unschedulable(String),
+
+    /// Workers could take the run, but lack free capacity for now
+    Waiting(String),
+}
+
+/// # Choose
+/// Index of the worker to place `run` on, the one with the most free
+/// memory among the workers satisfying every placement rule.
+///
+/// ## Errors
+///
+/// - `Unplaced::Unschedulable`: The first rule no worker satisfies.
+/// - `Unplaced::Waiting`: Every matching worker lacks free capacity.
+pub fn choose(workers: &[Capacity], placed: &[Placed], run: &Pending) -> Result<usize, Unplaced> {
+    let mut candidates = eligible(workers, placed, run).map_err(Unplaced::Unschedulable)?;
+
+    narrow(
+        workers,
+        &mut candidates,
+        |worker| worker.fits(run),
+        "Insufficient free capacity on every matching worker",
+    )
+    .map_err(Unplaced::Waiting)?;
+
+    Ok(candidates
+        .into_iter()
+        .max_by_key(|&index| (workers[index].memory_mb, workers[index].cpu))
+        .unwrap())
+}
+
+/// # Preempt
+/// Worker to make room for `run` on, along with the runs of lower priority
+/// to preempt there: the fewest runs, lowest priority and most recently
+/// started first, queued runs before running ones.
+///
+/// Runs are only preempted once `run` is late, i.e. it waited for free
+/// capacity for `LATENESS` past its scheduled time as of `now`, as capacity
+/// may free up on its own until then.
+///
+/// No runs are returned when the runs already preempted on a worker free
+/// enough room once they terminate, and `None` when `run` is not late yet
+/// or no eligible worker can make room.
+pub fn preempt(
+    workers: &[Capacity],
+    placed: &[Placed],
+    run: &Pending,
+    now: OffsetDateTime,
+) -> Option<(usize, Vec<Uuid>)> {
+    if run.scheduled_at + LATENESS > now {
+        return None;
+    }
+
+    let mut best: Option<(usize, Vec<Uuid>)> = None;
+
+    for index in eligible(workers, placed, run).ok()? {
+        let worker = &workers[index];
+        let mut free = (worker.cpu, worker.memory_mb, worker.disk_mb);
+
+        let mut releasing = Vec::new();
+        let mut victims = Vec::new();
+        for p in placed.iter().filter(|p| p.worker_id == worker.worker_id) {
+            match p.preempted {
+                true => releasing.push(p),
+                false if p.priority < run.priority => victims.push(p),
+                false => {}
+            }
+        }
+
+        for p in releasing {
+            free = (
+                free.0 + p.cpu as i64,
+                free.1 + p.memory_mb as i64,
+                free.2 + p.disk_mb as i64,
+            );
+        }
+
+        if run.fits(free) {
+            return Some((index, Vec::new()));
+        }
+
+        victims.sort_by_key(|p| (p.priority, p.started_at.map(|at| -at.unix_timestamp())));
+
+        let mut runs = Vec::new();
+        for p in victims {
+            free = (
+                free.0 + p.cpu as i64,
+                free.1 + p.memory_mb as i64,
+                free.2 + p.disk_mb as i64,
+            );
+            runs.push(p.run_id);
+
+            if run.fits(free) {
+                if best
+                    .as_ref()
+                    .is_none_or(|(_, best)| runs.len() < best.len())
+                {
+                    best = Some((index, runs));
+                }
+                break;
+            }
+        }
+    }
+
+    best
+}
+
+/// Candidates satisfying every placement rule but free capacity
+fn eligible(workers: &[Capacity], placed: &[Placed], run: &Pending) -> Result<Vec<usize>, String> {
+    if workers.is_empty() {
+        return Err("No live workers".to_string());
+    }
+
+    let mut candidates: Vec<usize> = (0..workers.len()).collect();
+
+    narrow(
+        workers,
+        &mut candidates,
+        |worker| subset(&run.node_selector, &worker.labels),
+        "No live worker matches the node selector",
+    )?;
+    narrow(
+        workers,
+        &mut candidates,
+        |worker| worker.could_fit(run),
+        "Requests exceed the capacity of every matching worker",
+    )?;
+    narrow(
+        workers,
+        &mut candidates,
+        |worker| {
+            !run.anti_affinity
+                .iter()
+                .any(|term| hosts(worker, placed, &run.project_id, term))
+        },
+        "Anti-affinity rules exclude every matching worker",
+    )?;
+    narrow(
+        workers,
+        &mut candidates,
+        |worker| {
+            run.affinity
+                .iter()
+                .all(|term| hosts(worker, placed, &run.project_id, term))
+        },
+        "No matching worker satisfies the affinity rules",
+    )?;
+
+    Ok(candidates)
+}
+
+/// Keep the candidates satisfying `rule`, fails with `reason` if none does
+fn narrow<F>(
+    workers: &[Capacity],
+    candidates: &mut Vec<usize>,
+    rule: F,
+    reason: &str,
+) -> Result<(), String>
+where
+    F: Fn(&Capacity) -> bool,
+{
+    candidates.retain(|&index| rule(&workers[index]));
+
+    match candidates.is_empty() {
+        true => Err(reason.to_string()),
+        false => Ok(()),
+    }
+}
+
+/// Whether a job of the project matching `term` is queued or running on `worker`
+fn hosts(worker: &Capacity, placed: &[Placed], project_id: &Uuid, term: &Term) -> bool {
+    placed
+        .iter()
+        .any(|p| p.worker_id == worker.worker_id && term.matches(project_id, p))
+}
+
+/// Whether every label of `selector` is in `labels`
+fn subset(selector: &HashMap<String, String>, labels: &HashMap<String, String>) -> bool {
+    selector
+        .iter()
+        .all(|(key, value)| labels.get(key) == Some(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{choose, preempt, Capacity, Pending, Placed, Unplaced, LATENESS};
+    use sqlx::types::{time::OffsetDateTime, Json, Uuid};
+    use std::{collections::HashMap, time::Duration};
+
+    /// Worker with 1 CPU and 1 GiB of memory, all of it reserved
+    fn worker() -> Capacity {
+        Capacity {
+            worker_id: Uuid::from_u128(1),
+            worker_name: "worker-1".to_string(),
+            labels: Json(HashMap::new()),
+            total_cpu: 1000,
+            total_memory_mb: 1024,
+            total_disk_mb: 0,
+            cpu: 0,
+            memory_mb: 0,
+            disk_mb: 0,
+        }
+    }
+
+    fn placed(run_id: u128, priority: i32) -> Placed {
+        Placed {
+            run_id: Uuid::from_u128(run_id),
+            worker_id: Uuid::from_u128(1),
+            project_id: Uuid::nil(),
+            job_name: "batch".to_string(),
+            labels: HashMap::new(),
+            priority,
+            cpu: 500,
+            memory_mb: 512,
+            disk_mb: 0,
+            started_at: None,
+            preempted: false,
+        }
+    }
+
+    fn pending(scheduled_at: OffsetDateTime) -> Pending {
+        Pending {
+            run_id: Uuid::from_u128(10),
+            priority: 100,
+            preempt: true,
+            scheduled_at,
+            project_id: Uuid::nil(),
+            job_name: "urgent".to_string(),
+            labels: HashMap::new(),
+            cpu: 500,
+            memory_mb: 512,
+            disk_mb: 0,
+            node_selector: HashMap::new(),
+            affinity: Vec::new(),
+            anti_affinity: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn waits_for_capacity_until_late() {
+        let now = OffsetDateTime::now_utc();
+        let workers = [worker()];
+        let placed = [placed(1, 0), placed(2, 200)];
+
+        let run = pending(now - LATENESS + Duration::from_secs(1));
+        assert_eq!(preempt(&workers, &placed, &run, now), None);
+
+        let run = pending(now - LATENESS);
+        assert_eq!(
+            preempt(&workers, &placed, &run, now),
+            Some((0, vec![Uuid::from_u128(1)]))
+        );
+    }
+
+    #[test]
+    fn never_preempts_runs_of_higher_priority() {
+        let now = OffsetDateTime::now_utc();
+        let workers = [worker()];
+        let placed = [placed(1, 100), placed(2, 200)];
+
+        let run = pending(now - LATENESS * 2);
+        assert_eq!(preempt(&workers, &placed, &run, now), None);
+    }
+
+    #[test]
+    fn waits_for_free_capacity() {
+        let run = pending(OffsetDateTime::now_utc());
+
+        assert_eq!(
+            choose(&[worker()], &[], &run),
+            Err(Unplaced::Waiting(
+                "Insufficient free capacity on every matching worker".to_string()
+            ))
+        );
+
+        let mut free = worker();
+        free.worker_id = Uuid::from_u128(2);
+        (free.cpu, free.memory_mb) = (1000, 1024);
+        assert_eq!(choose(&[worker(), free], &[], &run), Ok(1));
+    }
+
+    #[test]
+    fn never_places_runs_no_worker_can_take() {
+        let mut run = pending(OffsetDateTime::now_utc());
+        run.memory_mb = 2048;
+        assert_eq!(
+            choose(&[worker()], &[], &run),
+            Err(Unplaced::Unschedulable(
+                "Requests exceed the capacity of every matching worker".to_string()
+            ))
+        );
+
+        let mut run = pending(OffsetDateTime::now_utc());
+        run.node_selector = HashMap::from([("gpu".to_string(), "true".to_string())]);
+        assert_eq!(
+            choose(&[worker()], &[], &run),
+            Err(Unplaced::Unschedulable(
+                "No live worker matches the node selector".to_string()
+            ))
+        );
+
+        assert_eq!(
+            choose(&[], &[], &run),
+            Err(Unplaced::Unschedulable("No live workers".to_string()))
+        );
+    }
+}
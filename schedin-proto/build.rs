@@ -0,0 +1,22 @@
+//! Compiles the `.proto` files with protox, so that building the crate
+//! does not require `protoc`
+
+extern crate protox;
+extern crate tonic_build;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let protos = ["proto/schedin/v1/jobs.proto"];
+
+    for proto in protos {
+        println!("cargo:rerun-if-changed={}", proto);
+    }
+
+    let descriptors = protox::compile(protos, ["proto"])?;
+
+    // consumers pick their own transport
+    tonic_build::configure()
+        .build_transport(false)
+        .compile_fds(descriptors)?;
+
+    Ok(())
+}
@@ -0,0 +1,13 @@
+//! Schedin Protocol Buffers
+//!
+//! Messages and gRPC client and server code of the Schedin API, generated
+//! from the `.proto` files shipped along with the crate.
+
+extern crate prost;
+extern crate prost_types;
+extern crate tonic;
+
+/// Version 1 of the API
+pub mod v1 {
+    tonic::include_proto!("schedin.v1");
+}
@@ -0,0 +1,24 @@
+CREATE TABLE IF NOT EXISTS secrets (
+    secret_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID REFERENCES users(user_id) ON DELETE CASCADE,
+    secret_name VARCHAR(255) NOT NULL,
+    nonce BYTEA NOT NULL,
+    ciphertext BYTEA NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW(),
+    CONSTRAINT unique_secret_name_per_user UNIQUE (user_id, secret_name)
+);
+
+-- job environment, either a plain value or a reference to a secret
+CREATE TABLE IF NOT EXISTS envs (
+    env_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    job_id UUID REFERENCES jobs(job_id) ON DELETE CASCADE,
+    env_name VARCHAR(255) NOT NULL,
+    env_value TEXT,
+    secret_id UUID REFERENCES secrets(secret_id) ON DELETE RESTRICT,
+    CONSTRAINT unique_env_name_per_job UNIQUE (job_id, env_name),
+    CONSTRAINT env_value_or_secret CHECK ((env_value IS NULL) <> (secret_id IS NULL))
+);
+
+-- index on the 'job_id' column for faster environment lookups
+CREATE INDEX idx_envs_job_id ON envs(job_id);
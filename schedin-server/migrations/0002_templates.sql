@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS templates (
+    template_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID REFERENCES users(user_id) ON DELETE CASCADE,
+    template_name VARCHAR(255) NOT NULL,
+    template_description TEXT,
+    parameters JSONB NOT NULL,
+    definition JSONB NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW(),
+    CONSTRAINT unique_template_name_per_user UNIQUE (user_id, template_name)
+);
+
+-- link instances back to their template, along with the values they were created from
+ALTER TABLE jobs ADD COLUMN template_id UUID REFERENCES templates(template_id) ON DELETE SET NULL;
+ALTER TABLE jobs ADD COLUMN template_values JSONB;
+
+-- index on the 'template_id' column for faster instance lookups
+CREATE INDEX idx_jobs_template_id ON jobs(template_id);
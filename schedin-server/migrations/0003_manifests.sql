@@ -0,0 +1,7 @@
+-- keep the schedule as submitted, so that jobs can be read back in their API shape
+ALTER TABLE jobs ADD COLUMN schedule VARCHAR(255);
+
+ALTER TABLE jobs ADD COLUMN labels JSONB NOT NULL DEFAULT '{}';
+
+-- index on the 'labels' column for faster label-based lookups
+CREATE INDEX idx_jobs_labels ON jobs USING GIN (labels);
@@ -0,0 +1,38 @@
+ALTER TABLE jobs ADD COLUMN revision INTEGER NOT NULL DEFAULT 1;
+
+-- immutable snapshots of every job definition
+CREATE TABLE IF NOT EXISTS job_revisions (
+    job_id UUID REFERENCES jobs(job_id) ON DELETE CASCADE,
+    revision INTEGER NOT NULL,
+    changed_by UUID REFERENCES users(user_id) ON DELETE SET NULL,
+    changed_at TIMESTAMPTZ DEFAULT NOW(),
+    definition JSONB NOT NULL,
+    diff JSONB NOT NULL,
+    PRIMARY KEY (job_id, revision)
+);
+
+CREATE TYPE run_status AS ENUM (
+    'queued',
+    'running',
+    'succeeded',
+    'failed'
+);
+
+CREATE TABLE IF NOT EXISTS runs (
+    run_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    job_id UUID REFERENCES jobs(job_id) ON DELETE CASCADE,
+    revision INTEGER NOT NULL,
+    run_status run_status DEFAULT 'queued' NOT NULL,
+    scheduled_at TIMESTAMPTZ NOT NULL,
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ,
+    exit_code INTEGER,
+    error TEXT,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+-- index on the 'job_id' and 'scheduled_at' columns for faster run history lookups
+CREATE INDEX idx_runs_job_id_scheduled_at ON runs(job_id, scheduled_at DESC);
+
+-- index on the 'run_status' column for faster status-based lookups
+CREATE INDEX idx_runs_status ON runs(run_status);
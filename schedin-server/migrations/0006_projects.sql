@@ -0,0 +1,34 @@
+-- projects own jobs and scope their names, quotas are unlimited when NULL
+CREATE TABLE IF NOT EXISTS projects (
+    project_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID REFERENCES users(user_id) ON DELETE CASCADE,
+    project_name VARCHAR(255) NOT NULL,
+    max_jobs INTEGER,
+    min_interval INTEGER,
+    max_concurrent_runs INTEGER,
+    max_timeout INTEGER,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW(),
+    CONSTRAINT unique_project_name_per_user UNIQUE (user_id, project_name)
+);
+
+-- move existing jobs into a 'default' project of their owner
+INSERT INTO projects (user_id, project_name)
+SELECT user_id, 'default' FROM users;
+
+ALTER TABLE jobs ADD COLUMN project_id UUID REFERENCES projects(project_id) ON DELETE CASCADE;
+
+UPDATE jobs j SET project_id = p.project_id
+FROM projects p WHERE p.user_id = j.user_id AND p.project_name = 'default';
+
+ALTER TABLE jobs ALTER COLUMN project_id SET NOT NULL;
+
+ALTER TABLE jobs DROP CONSTRAINT unique_job_name_per_user;
+ALTER TABLE jobs ADD CONSTRAINT unique_job_name_per_project UNIQUE (project_id, job_name);
+
+-- run timeout in seconds
+ALTER TABLE jobs ADD COLUMN timeout INTEGER;
+ALTER TABLE runs ADD COLUMN timeout INTEGER;
+
+-- index on the 'project_id' column for faster project-based lookups
+CREATE INDEX idx_jobs_project_id ON jobs(project_id);
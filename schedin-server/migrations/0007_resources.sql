@@ -0,0 +1,28 @@
+-- resource requests are reserved on a worker, limits are enforced on the run;
+-- cpu is in millicores, memory and disk in MB
+ALTER TABLE jobs ADD COLUMN request_cpu INTEGER;
+ALTER TABLE jobs ADD COLUMN request_memory_mb INTEGER;
+ALTER TABLE jobs ADD COLUMN request_disk_mb INTEGER;
+ALTER TABLE jobs ADD COLUMN limit_cpu INTEGER;
+ALTER TABLE jobs ADD COLUMN limit_memory_mb INTEGER;
+ALTER TABLE jobs ADD COLUMN limit_disk_mb INTEGER;
+
+-- workers advertise their capacity and heartbeat while alive
+CREATE TABLE IF NOT EXISTS workers (
+    worker_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    worker_name VARCHAR(255) UNIQUE NOT NULL,
+    cpu INTEGER NOT NULL,
+    memory_mb INTEGER NOT NULL,
+    disk_mb INTEGER NOT NULL,
+    registered_at TIMESTAMPTZ DEFAULT NOW(),
+    heartbeat_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+-- runs reserve their requests on the worker they are placed on until they end
+ALTER TABLE runs ADD COLUMN worker_id UUID REFERENCES workers(worker_id) ON DELETE SET NULL;
+ALTER TABLE runs ADD COLUMN cpu INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE runs ADD COLUMN memory_mb INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE runs ADD COLUMN disk_mb INTEGER NOT NULL DEFAULT 0;
+
+-- index on the 'worker_id' and 'run_status' columns for faster reservation lookups
+CREATE INDEX idx_runs_worker_id_status ON runs(worker_id, run_status);
@@ -0,0 +1,10 @@
+ALTER TABLE workers ADD COLUMN labels JSONB NOT NULL DEFAULT '{}';
+
+-- worker labels a job must run on, and affinity terms matched against the runs on a worker
+ALTER TABLE jobs ADD COLUMN node_selector JSONB NOT NULL DEFAULT '{}';
+ALTER TABLE jobs ADD COLUMN affinity JSONB NOT NULL DEFAULT '[]';
+ALTER TABLE jobs ADD COLUMN anti_affinity JSONB NOT NULL DEFAULT '[]';
+
+-- runs no worker can take, until one can
+ALTER TYPE run_status ADD VALUE 'unschedulable';
+ALTER TABLE runs ADD COLUMN reason TEXT;
@@ -0,0 +1,25 @@
+-- administrators manage cluster-wide settings such as priority classes,
+-- granted with `UPDATE users SET is_admin = TRUE WHERE username = '...'`
+ALTER TABLE users ADD COLUMN is_admin BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS priority_classes (
+    priority_class_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    class_name VARCHAR(63) UNIQUE NOT NULL,
+    value INTEGER NOT NULL,
+    description TEXT,
+    -- whether runs of the class may preempt running runs of lower priority
+    preempt BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+-- classes referenced by jobs cannot be deleted
+ALTER TABLE jobs ADD COLUMN priority_class_id UUID REFERENCES priority_classes(priority_class_id) ON DELETE RESTRICT;
+
+-- priority of the class at dispatch, and the preemption state of the run
+ALTER TABLE runs ADD COLUMN priority INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE runs ADD COLUMN preempt BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE runs ADD COLUMN preempted_at TIMESTAMPTZ;
+ALTER TABLE runs ADD COLUMN preemptions INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX idx_runs_pending ON runs(priority DESC, scheduled_at) WHERE worker_id IS NULL;
@@ -0,0 +1,34 @@
+ALTER TYPE job_types ADD VALUE 'http';
+
+CREATE TABLE IF NOT EXISTS http_requests (
+    http_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    job_id UUID REFERENCES jobs(job_id) ON DELETE CASCADE,
+    method VARCHAR(16) NOT NULL,
+    url TEXT NOT NULL,
+    body TEXT,
+    -- request timeout (in seconds)
+    timeout INTEGER,
+    -- empty accepts any 2xx status
+    expected_status INTEGER[] NOT NULL DEFAULT '{}',
+    -- JSONPath that must match the response body
+    assertion TEXT
+);
+
+-- request headers, either a plain value or a reference to a secret
+CREATE TABLE IF NOT EXISTS http_headers (
+    header_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    job_id UUID REFERENCES jobs(job_id) ON DELETE CASCADE,
+    header_name VARCHAR(255) NOT NULL,
+    header_value TEXT,
+    secret_id UUID REFERENCES secrets(secret_id) ON DELETE RESTRICT,
+    CONSTRAINT unique_header_name_per_job UNIQUE (job_id, header_name),
+    CONSTRAINT header_value_or_secret CHECK ((header_value IS NULL) <> (secret_id IS NULL))
+);
+
+CREATE INDEX idx_http_requests_job_id ON http_requests(job_id);
+CREATE INDEX idx_http_headers_job_id ON http_headers(job_id);
+
+-- response of http runs, the body is truncated
+ALTER TABLE runs ADD COLUMN response_status INTEGER;
+ALTER TABLE runs ADD COLUMN latency_ms INTEGER;
+ALTER TABLE runs ADD COLUMN response_body TEXT;
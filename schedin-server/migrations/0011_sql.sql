@@ -0,0 +1,17 @@
+ALTER TYPE job_types ADD VALUE 'sql';
+
+-- statements run against the database whose DSN is the referenced secret
+CREATE TABLE IF NOT EXISTS sql_queries (
+    sql_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    job_id UUID REFERENCES jobs(job_id) ON DELETE CASCADE,
+    secret_id UUID NOT NULL REFERENCES secrets(secret_id) ON DELETE RESTRICT,
+    statements TEXT[] NOT NULL,
+    -- statement timeout (in seconds)
+    statement_timeout INTEGER
+);
+
+CREATE INDEX idx_sql_queries_job_id ON sql_queries(job_id);
+
+-- outcome of sql runs, the preview is bounded
+ALTER TABLE runs ADD COLUMN rows_affected BIGINT;
+ALTER TABLE runs ADD COLUMN result_preview JSONB;
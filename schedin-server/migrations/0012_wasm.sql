@@ -0,0 +1,26 @@
+ALTER TYPE job_types ADD VALUE 'wasm';
+
+-- WASI modules, uploaded or inlined by jobs, addressed by their SHA-256 digest
+CREATE TABLE IF NOT EXISTS wasm_modules (
+    module_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID REFERENCES users(user_id) ON DELETE CASCADE,
+    digest CHAR(64) NOT NULL,
+    module BYTEA NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    CONSTRAINT unique_module_digest_per_user UNIQUE (user_id, digest)
+);
+
+CREATE TABLE IF NOT EXISTS wasm_jobs (
+    wasm_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    job_id UUID REFERENCES jobs(job_id) ON DELETE CASCADE,
+    module_id UUID NOT NULL REFERENCES wasm_modules(module_id) ON DELETE RESTRICT,
+    args TEXT[] NOT NULL DEFAULT '{}',
+    -- instructions budget, unbounded if NULL
+    fuel BIGINT
+);
+
+CREATE INDEX idx_wasm_jobs_job_id ON wasm_jobs(job_id);
+
+-- captured output of wasm runs, truncated
+ALTER TABLE runs ADD COLUMN stdout TEXT;
+ALTER TABLE runs ADD COLUMN stderr TEXT;
@@ -0,0 +1,40 @@
+ALTER TYPE job_types ADD VALUE 'pipeline';
+
+-- ordered steps of pipeline jobs, each a task, code, bin or http item
+CREATE TABLE IF NOT EXISTS pipelines (
+    pipeline_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    job_id UUID REFERENCES jobs(job_id) ON DELETE CASCADE,
+    steps JSONB NOT NULL
+);
+
+-- secrets referenced by the headers of http steps
+CREATE TABLE IF NOT EXISTS pipeline_secrets (
+    job_id UUID REFERENCES jobs(job_id) ON DELETE CASCADE,
+    secret_id UUID REFERENCES secrets(secret_id) ON DELETE RESTRICT,
+    PRIMARY KEY (job_id, secret_id)
+);
+
+CREATE INDEX idx_pipelines_job_id ON pipelines(job_id);
+
+CREATE TYPE step_status AS ENUM (
+    'running',
+    'succeeded',
+    'failed',
+    'skipped'
+);
+
+-- steps of pipeline runs, output and response body are truncated
+CREATE TABLE IF NOT EXISTS run_steps (
+    run_id UUID REFERENCES runs(run_id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    step_name VARCHAR(63) NOT NULL,
+    step_status step_status NOT NULL,
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ,
+    exit_code INTEGER,
+    error TEXT,
+    response_status INTEGER,
+    stdout TEXT,
+    stderr TEXT,
+    PRIMARY KEY (run_id, position)
+);
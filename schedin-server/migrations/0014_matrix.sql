@@ -0,0 +1,13 @@
+-- axes the runs of a job fan out over, with their parallelism and fail-fast option
+ALTER TABLE jobs ADD COLUMN matrix JSONB;
+
+-- children of a matrix run left to start once another child failed, with fail-fast
+ALTER TYPE run_status ADD VALUE 'cancelled';
+
+-- a matrix run holds the matrix of its job at dispatch, and has one child per combination
+ALTER TABLE runs ADD COLUMN parent_run_id UUID REFERENCES runs(run_id) ON DELETE CASCADE;
+ALTER TABLE runs ADD COLUMN matrix JSONB;
+ALTER TABLE runs ADD COLUMN combination JSONB;
+ALTER TABLE runs ADD COLUMN position INTEGER;
+
+CREATE INDEX idx_runs_parent_run_id ON runs(parent_run_id, position);
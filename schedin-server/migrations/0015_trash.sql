@@ -0,0 +1,12 @@
+-- deleted jobs stay restorable in the trash until purged, after the retention of their project
+ALTER TABLE jobs ADD COLUMN deleted_at TIMESTAMPTZ;
+
+-- days deleted jobs are kept, 30 when NULL
+ALTER TABLE projects ADD COLUMN trash_retention_days INTEGER;
+
+-- names of deleted jobs can be reused
+ALTER TABLE jobs DROP CONSTRAINT unique_job_name_per_project;
+CREATE UNIQUE INDEX unique_job_name_per_project ON jobs(project_id, job_name) WHERE deleted_at IS NULL;
+
+-- index on the 'deleted_at' column for faster trash lookups
+CREATE INDEX idx_jobs_deleted_at ON jobs(deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,26 @@
+-- globs of the files collected from the work directory of runs
+ALTER TABLE jobs ADD COLUMN artifacts TEXT[];
+
+-- days artifacts are kept after their run, 14 when NULL
+ALTER TABLE projects ADD COLUMN artifact_retention_days INTEGER;
+
+-- blobs of the blob store, addressed by their SHA-256 digest. Workers lease a
+-- blob before uploading it, unreferenced blobs are collected once their lease
+-- expired.
+CREATE TABLE IF NOT EXISTS blobs (
+    digest CHAR(64) PRIMARY KEY,
+    size BIGINT NOT NULL,
+    leased_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS run_artifacts (
+    run_id UUID REFERENCES runs(run_id) ON DELETE CASCADE,
+    path TEXT NOT NULL,
+    digest CHAR(64) NOT NULL REFERENCES blobs(digest) ON DELETE RESTRICT,
+    size BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (run_id, path)
+);
+
+CREATE INDEX idx_run_artifacts_digest ON run_artifacts(digest);
+CREATE INDEX idx_run_artifacts_created_at ON run_artifacts(created_at);
@@ -0,0 +1,80 @@
+-- runs that timed out, as opposed to other failures
+ALTER TABLE runs ADD COLUMN timed_out BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- finished runs are evaluated against the notification rules once,
+-- runs that finished before notifications existed never notify
+ALTER TABLE runs ADD COLUMN notified_at TIMESTAMPTZ;
+UPDATE runs SET notified_at = NOW() WHERE finished_at IS NOT NULL;
+
+CREATE INDEX idx_runs_unnotified ON runs(finished_at) WHERE notified_at IS NULL;
+
+CREATE TYPE channel_kind AS ENUM ('webhook', 'slack', 'email');
+
+-- channels of a user, or of one of their projects if project_id is set
+CREATE TABLE IF NOT EXISTS notification_channels (
+    channel_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID REFERENCES users(user_id) ON DELETE CASCADE,
+    project_id UUID REFERENCES projects(project_id) ON DELETE CASCADE,
+    channel_name VARCHAR(63) NOT NULL,
+    kind channel_kind NOT NULL,
+    url TEXT,
+    recipients TEXT[],
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ,
+    CONSTRAINT unique_channel_name_per_user UNIQUE (user_id, channel_name)
+);
+
+CREATE TYPE notification_event AS ENUM (
+    'failure',
+    'consecutive_failures',
+    'recovery',
+    'timeout'
+);
+
+-- rules notify a channel about the runs of the jobs of a user, optionally
+-- narrowed down to a project and a job name
+CREATE TABLE IF NOT EXISTS notification_rules (
+    rule_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID REFERENCES users(user_id) ON DELETE CASCADE,
+    project_id UUID REFERENCES projects(project_id) ON DELETE CASCADE,
+    rule_name VARCHAR(63) NOT NULL,
+    channel_id UUID NOT NULL REFERENCES notification_channels(channel_id) ON DELETE CASCADE,
+    job_name VARCHAR(255),
+    event notification_event NOT NULL,
+    -- consecutive failures the rule fires at
+    threshold INTEGER,
+    template TEXT,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ,
+    CONSTRAINT unique_rule_name_per_user UNIQUE (user_id, rule_name)
+);
+
+CREATE INDEX idx_notification_rules_user_id ON notification_rules(user_id);
+
+CREATE TYPE delivery_status AS ENUM ('pending', 'delivered', 'failed');
+
+-- delivery log, kept when the rule or channel is deleted
+CREATE TABLE IF NOT EXISTS notification_deliveries (
+    delivery_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID REFERENCES users(user_id) ON DELETE CASCADE,
+    rule_id UUID REFERENCES notification_rules(rule_id) ON DELETE SET NULL,
+    channel_id UUID REFERENCES notification_channels(channel_id) ON DELETE SET NULL,
+    run_id UUID REFERENCES runs(run_id) ON DELETE SET NULL,
+    rule_name VARCHAR(63) NOT NULL,
+    channel_name VARCHAR(63) NOT NULL,
+    job_name VARCHAR(255) NOT NULL,
+    event notification_event NOT NULL,
+    message TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    delivery_status delivery_status NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    response_status INTEGER,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    delivered_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_notification_deliveries_pending ON notification_deliveries(next_attempt_at) 
+    WHERE delivery_status = 'pending';
+CREATE INDEX idx_notification_deliveries_user_id ON notification_deliveries(user_id, created_at);
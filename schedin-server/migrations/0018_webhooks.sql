@@ -0,0 +1,122 @@
+-- subscriptions of a user to run and job events, signed with a secret
+-- sealed like user secrets, the previous secret stays valid while rotating
+CREATE TABLE IF NOT EXISTS webhook_subscriptions (
+    subscription_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID REFERENCES users(user_id) ON DELETE CASCADE,
+    subscription_name VARCHAR(63) NOT NULL,
+    url TEXT NOT NULL,
+    events TEXT[] NOT NULL,
+    nonce BYTEA NOT NULL,
+    ciphertext BYTEA NOT NULL,
+    previous_nonce BYTEA,
+    previous_ciphertext BYTEA,
+    previous_expires_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ,
+    CONSTRAINT unique_subscription_name_per_user UNIQUE (user_id, subscription_name)
+);
+
+-- durable outbox, events are written by triggers in the transaction of the
+-- change they report, then fanned out to the subscriptions of their user
+CREATE TABLE IF NOT EXISTS webhook_outbox (
+    event_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID REFERENCES users(user_id) ON DELETE CASCADE,
+    event_type VARCHAR(63) NOT NULL,
+    -- version of the schema of data, bumped on breaking changes
+    schema_version INTEGER NOT NULL DEFAULT 1,
+    data JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT clock_timestamp(),
+    dispatched_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_webhook_outbox_undispatched ON webhook_outbox(created_at) 
+    WHERE dispatched_at IS NULL;
+CREATE INDEX idx_webhook_outbox_created_at ON webhook_outbox(created_at);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    delivery_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    event_id UUID NOT NULL REFERENCES webhook_outbox(event_id) ON DELETE CASCADE,
+    subscription_id UUID NOT NULL REFERENCES webhook_subscriptions(subscription_id) ON DELETE CASCADE,
+    delivery_status delivery_status NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    response_status INTEGER,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    delivered_at TIMESTAMPTZ,
+    CONSTRAINT unique_delivery_per_subscription UNIQUE (event_id, subscription_id)
+);
+
+CREATE INDEX idx_webhook_deliveries_pending ON webhook_deliveries(next_attempt_at) 
+    WHERE delivery_status = 'pending';
+CREATE INDEX idx_webhook_deliveries_subscription_id ON webhook_deliveries(subscription_id, created_at);
+
+-- run.scheduled on insert, run.started, run.succeeded and run.failed on
+-- transitions, only for users with a subscription
+CREATE FUNCTION webhook_run_event() RETURNS TRIGGER AS $$
+DECLARE
+    event_type TEXT;
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        event_type := CASE NEW.run_status WHEN 'queued' THEN 'run.scheduled' END;
+    ELSIF NEW.run_status IS DISTINCT FROM OLD.run_status THEN
+        event_type := CASE NEW.run_status 
+            WHEN 'running' THEN 'run.started' 
+            WHEN 'succeeded' THEN 'run.succeeded' 
+            WHEN 'failed' THEN 'run.failed' 
+        END;
+    END IF;
+
+    IF event_type IS NULL THEN
+        RETURN NULL;
+    END IF;
+
+    INSERT INTO webhook_outbox (user_id, event_type, data)
+    SELECT j.user_id, event_type, jsonb_build_object(
+        'run_id', NEW.run_id, 
+        'parent_run_id', NEW.parent_run_id, 
+        'job_id', NEW.job_id, 
+        'job', j.job_name, 
+        'project', COALESCE(p.project_name, 'default'), 
+        'revision', NEW.revision, 
+        'status', NEW.run_status, 
+        'scheduled_at', NEW.scheduled_at, 
+        'started_at', NEW.started_at, 
+        'finished_at', NEW.finished_at, 
+        'exit_code', NEW.exit_code, 
+        'error', NEW.error, 
+        'timed_out', NEW.timed_out
+    )
+    FROM jobs j LEFT JOIN projects p ON p.project_id = j.project_id 
+    WHERE j.job_id = NEW.job_id 
+    AND EXISTS (SELECT 1 FROM webhook_subscriptions s WHERE s.user_id = j.user_id);
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER webhook_run_event AFTER INSERT OR UPDATE OF run_status ON runs 
+    FOR EACH ROW EXECUTE FUNCTION webhook_run_event();
+
+-- job.updated on every revision past the first
+CREATE FUNCTION webhook_job_event() RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO webhook_outbox (user_id, event_type, data)
+    SELECT j.user_id, 'job.updated', jsonb_build_object(
+        'job_id', NEW.job_id, 
+        'job', j.job_name, 
+        'project', COALESCE(p.project_name, 'default'), 
+        'revision', NEW.revision, 
+        'changed_at', NEW.changed_at, 
+        'diff', NEW.diff
+    )
+    FROM jobs j LEFT JOIN projects p ON p.project_id = j.project_id 
+    WHERE j.job_id = NEW.job_id 
+    AND EXISTS (SELECT 1 FROM webhook_subscriptions s WHERE s.user_id = j.user_id);
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER webhook_job_event AFTER INSERT ON job_revisions 
+    FOR EACH ROW WHEN (NEW.revision > 1) EXECUTE FUNCTION webhook_job_event();
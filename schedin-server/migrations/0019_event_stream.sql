@@ -0,0 +1,76 @@
+-- the outbox also backs the event streams of the API, events are recorded
+-- for every user and numbered so that streams resume where they left off
+ALTER TABLE webhook_outbox ADD COLUMN seq BIGSERIAL;
+
+CREATE UNIQUE INDEX idx_webhook_outbox_user_id_seq ON webhook_outbox(user_id, seq);
+
+CREATE OR REPLACE FUNCTION webhook_run_event() RETURNS TRIGGER AS $$
+DECLARE
+    event_type TEXT;
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        event_type := CASE NEW.run_status WHEN 'queued' THEN 'run.scheduled' END;
+    ELSIF NEW.run_status IS DISTINCT FROM OLD.run_status THEN
+        event_type := CASE NEW.run_status 
+            WHEN 'running' THEN 'run.started' 
+            WHEN 'succeeded' THEN 'run.succeeded' 
+            WHEN 'failed' THEN 'run.failed' 
+        END;
+    END IF;
+
+    IF event_type IS NULL THEN
+        RETURN NULL;
+    END IF;
+
+    INSERT INTO webhook_outbox (user_id, event_type, data)
+    SELECT j.user_id, event_type, jsonb_build_object(
+        'run_id', NEW.run_id, 
+        'parent_run_id', NEW.parent_run_id, 
+        'job_id', NEW.job_id, 
+        'job', j.job_name, 
+        'project', COALESCE(p.project_name, 'default'), 
+        'revision', NEW.revision, 
+        'status', NEW.run_status, 
+        'scheduled_at', NEW.scheduled_at, 
+        'started_at', NEW.started_at, 
+        'finished_at', NEW.finished_at, 
+        'exit_code', NEW.exit_code, 
+        'error', NEW.error, 
+        'timed_out', NEW.timed_out
+    )
+    FROM jobs j LEFT JOIN projects p ON p.project_id = j.project_id 
+    WHERE j.job_id = NEW.job_id;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION webhook_job_event() RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO webhook_outbox (user_id, event_type, data)
+    SELECT j.user_id, 'job.updated', jsonb_build_object(
+        'job_id', NEW.job_id, 
+        'job', j.job_name, 
+        'project', COALESCE(p.project_name, 'default'), 
+        'revision', NEW.revision, 
+        'changed_at', NEW.changed_at, 
+        'diff', NEW.diff
+    )
+    FROM jobs j LEFT JOIN projects p ON p.project_id = j.project_id 
+    WHERE j.job_id = NEW.job_id;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- wake the streams of the user on every server instance, the payload is
+-- the user id, streams read the events themselves
+CREATE FUNCTION notify_event() RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('schedin_events', NEW.user_id::TEXT);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER notify_event AFTER INSERT ON webhook_outbox 
+    FOR EACH ROW EXECUTE FUNCTION notify_event();
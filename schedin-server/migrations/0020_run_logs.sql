@@ -0,0 +1,31 @@
+-- Output of runs, written by the worker in chunks as it is produced, so
+-- that it can be followed live and read back by byte range once the run
+-- completed. The truncated stdout and stderr columns of runs remain.
+
+CREATE TYPE log_stream AS ENUM (
+    'stdout',
+    'stderr'
+);
+
+CREATE TABLE run_logs (
+    run_id UUID NOT NULL REFERENCES runs(run_id) ON DELETE CASCADE,
+    -- byte offset of the chunk in the output of the run, both streams
+    -- interleaved in the order they were read
+    position BIGINT NOT NULL,
+    stream log_stream NOT NULL,
+    data BYTEA NOT NULL,
+    logged_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (run_id, position)
+);
+
+-- wake the followers of the run on every server instance, notifications
+-- of a transaction are collapsed, so a batch of chunks wakes them once
+CREATE FUNCTION notify_log() RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('schedin_logs', NEW.run_id::TEXT);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER notify_log AFTER INSERT ON run_logs
+    FOR EACH ROW EXECUTE FUNCTION notify_log();
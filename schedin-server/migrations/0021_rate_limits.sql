@@ -0,0 +1,38 @@
+-- token buckets of the rate limiter, shared by the server instances of a
+-- cluster; a bucket left alone refills to full, so idle rows are dropped
+CREATE TABLE rate_limits (
+    bucket TEXT PRIMARY KEY,
+    tokens DOUBLE PRECISION NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX idx_rate_limits_updated_at ON rate_limits(updated_at);
+
+-- refill the bucket for the time elapsed since it was last taken from, at
+-- `rate` tokens per second up to `burst`, then take a token if one is left
+CREATE FUNCTION take_rate_limit(
+    key TEXT,
+    burst DOUBLE PRECISION,
+    rate DOUBLE PRECISION,
+    OUT remaining DOUBLE PRECISION,
+    OUT allowed BOOLEAN
+) AS $$
+DECLARE
+    refilled DOUBLE PRECISION;
+BEGIN
+    INSERT INTO rate_limits (bucket, tokens, updated_at)
+    VALUES (key, burst, clock_timestamp())
+    ON CONFLICT (bucket) DO NOTHING;
+
+    -- the clock is read once the row is locked, concurrent takes queue up
+    SELECT LEAST(burst, r.tokens + GREATEST(EXTRACT(EPOCH FROM clock_timestamp() - r.updated_at), 0) * rate)
+    INTO refilled
+    FROM rate_limits r WHERE r.bucket = key FOR UPDATE;
+
+    allowed := refilled >= 1;
+    remaining := refilled - CASE WHEN allowed THEN 1 ELSE 0 END;
+
+    UPDATE rate_limits SET tokens = remaining, updated_at = clock_timestamp()
+    WHERE bucket = key;
+END;
+$$ LANGUAGE plpgsql;
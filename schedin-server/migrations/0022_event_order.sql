@@ -0,0 +1,7 @@
+-- `seq` is taken when an event is recorded, but transactions commit out of
+-- order, so streams can't just read past the latest `seq` they saw. Events
+-- also record their transaction, streams read them in the order of their
+-- transactions, once no transaction still running could come before them.
+ALTER TABLE webhook_outbox ADD COLUMN txid BIGINT NOT NULL DEFAULT pg_current_xact_id()::TEXT::BIGINT;
+
+CREATE INDEX idx_webhook_outbox_user_id_txid_seq ON webhook_outbox(user_id, txid, seq);
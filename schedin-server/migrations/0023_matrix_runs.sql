@@ -0,0 +1,28 @@
+-- number of children a run of a job with this matrix expands into, as
+-- counted by the server and the orchestrator: the product of the number of
+-- values of each axis, a list of scalars or a range `start..end`
+CREATE FUNCTION matrix_runs(matrix JSONB) RETURNS BIGINT AS $$
+DECLARE
+    axis JSONB;
+    bounds TEXT[];
+    runs BIGINT := 1;
+BEGIN
+    IF matrix IS NULL THEN
+        RETURN 1;
+    END IF;
+
+    FOR axis IN SELECT value FROM jsonb_each(matrix->'axes') LOOP
+        IF jsonb_typeof(axis) = 'array' THEN
+            runs := runs * (
+                SELECT COUNT(*) FROM jsonb_array_elements(axis) v
+                WHERE jsonb_typeof(v) IN ('string', 'number', 'boolean')
+            );
+        ELSE
+            bounds := regexp_match(axis #>> '{}', '^\s*(-?\d+)\s*\.\.\s*(-?\d+)\s*$');
+            runs := runs * COALESCE(GREATEST(bounds[2]::BIGINT - bounds[1]::BIGINT, 0), 0);
+        END IF;
+    END LOOP;
+
+    RETURN runs;
+END;
+$$ LANGUAGE plpgsql IMMUTABLE;
@@ -0,0 +1,3 @@
+-- a key is held by the request that claimed it for a lease only, so a key
+-- left in progress by a crashed server can be claimed again by a retry
+ALTER TABLE idempotency_keys ADD COLUMN claimed_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
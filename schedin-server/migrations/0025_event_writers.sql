@@ -0,0 +1,49 @@
+-- Streams read events once no running transaction could come before them.
+-- Waiting for every transaction of the cluster lets one long transaction,
+-- even one which never records an event, hold back every stream. Instead,
+-- statements which may record events mark their transaction with a shared
+-- advisory lock keyed by its id, and streams only wait for the marked ones.
+--
+-- Transactions are marked from their first statement on the tables events
+-- are recorded from, so a transaction writing elsewhere long before
+-- recording its first event may still come after events already streamed.
+
+CREATE OR REPLACE FUNCTION event_writer() RETURNS TRIGGER AS $$
+BEGIN
+    -- advisory keys are two INTs, the low half of the id is enough to
+    -- tell the running transactions apart
+    PERFORM pg_advisory_xact_lock_shared(
+        x'53434845'::INT,
+        (pg_current_xact_id()::TEXT::BIGINT::BIT(32))::INT
+    );
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER event_writer BEFORE INSERT OR UPDATE OF run_status ON runs
+FOR EACH STATEMENT EXECUTE FUNCTION event_writer();
+
+CREATE TRIGGER event_writer BEFORE INSERT ON job_revisions
+FOR EACH STATEMENT EXECUTE FUNCTION event_writer();
+
+CREATE TRIGGER event_writer BEFORE INSERT ON webhook_outbox
+FOR EACH STATEMENT EXECUTE FUNCTION event_writer();
+
+-- Transaction up to which events can be streamed: the oldest marked one
+-- still running. Transactions over since the snapshot was taken count as
+-- running, their mark is gone but their events are not visible yet.
+CREATE OR REPLACE FUNCTION event_horizon() RETURNS BIGINT AS $$
+    SELECT COALESCE(
+        MIN(x::TEXT::BIGINT),
+        pg_snapshot_xmax(pg_current_snapshot())::TEXT::BIGINT
+    )
+    FROM pg_snapshot_xip(pg_current_snapshot()) x
+    WHERE pg_xact_status(x) <> 'in progress' OR EXISTS (
+        SELECT 1 FROM pg_locks l
+        WHERE l.locktype = 'advisory'
+        AND l.database = (SELECT oid FROM pg_database WHERE datname = current_database())
+        AND l.classid = x'53434845'::INT::OID
+        AND l.objid = (x::TEXT::BIGINT::BIT(32))::INT::OID
+        AND l.objsubid = 2
+    )
+$$ LANGUAGE sql STABLE;
@@ -0,0 +1,10 @@
+-- API keys of a user, for scripts and services signing in without a
+-- password; only a digest of the key is kept, the key is shown once
+CREATE TABLE IF NOT EXISTS api_keys (
+    key_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    key_name VARCHAR(255) NOT NULL,
+    key_hash CHAR(64) NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CONSTRAINT unique_key_name_per_user UNIQUE (user_id, key_name)
+);
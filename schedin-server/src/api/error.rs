@@ -0,0 +1,247 @@
+//! API Errors
+//!
+//! Failures of the API as a status and a stable JSON body,
+//! `{ "code", "message", "details", "request_id" }`. Every request is
+//! identified by its `X-Request-Id` header, which the body of an error
+//! repeats so that it can be matched with the logs of the server.
+
+extern crate actix_web;
+extern crate schedin_common;
+extern crate serde;
+extern crate serde_json;
+extern crate std;
+extern crate utoipa;
+extern crate uuid;
+extern crate validator;
+
+use actix_web::{
+    body::{BoxBody, EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        header::{HeaderName, HeaderValue},
+        StatusCode,
+    },
+    middleware::Next,
+    HttpRequest, HttpResponse, ResponseError,
+};
+use schedin_common::error::CrudError;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::ValidationErrors;
+
+/// Header identifying a request, generated unless the client sets one
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// # API Error
+/// Error of an endpoint, rendered with its status code and a JSON body
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+    details: Option<Value>,
+}
+
+/// # Error
+/// Body of the error responses
+#[derive(Debug, Serialize, ToSchema)]
+#[schema(as = ApiError)]
+pub struct ErrorBody {
+    /// Stable, machine-readable code, e.g. `not_found`
+    code: &'static str,
+
+    /// Human-readable description
+    message: String,
+
+    /// Error specifics, e.g. the fields failing validation
+    #[schema(value_type = Option<Object>)]
+    details: Option<Value>,
+
+    /// `X-Request-Id` of the request
+    request_id: Option<String>,
+}
+
+impl ApiError {
+    /// New error
+    pub fn new<S>(status: StatusCode, code: &'static str, message: S) -> Self
+    where
+        S: ToString,
+    {
+        Self {
+            status,
+            code,
+            message: message.to_string(),
+            details: None,
+        }
+    }
+
+    /// Malformed request, `400`
+    pub fn bad_request<S: ToString>(message: S) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "bad_request", message)
+    }
+
+    /// Missing or invalid credentials, `401`
+    pub fn unauthorized<S: ToString>(message: S) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, "unauthorized", message)
+    }
+
+    /// Request not allowed, `403`
+    pub fn forbidden<S: ToString>(message: S) -> Self {
+        Self::new(StatusCode::FORBIDDEN, "forbidden", message)
+    }
+
+    /// Unknown resource, `404`
+    pub fn not_found<S: ToString>(message: S) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "not_found", message)
+    }
+
+    /// Request conflicting with the state of a resource, `409`
+    pub fn conflict<S: ToString>(message: S) -> Self {
+        Self::new(StatusCode::CONFLICT, "conflict", message)
+    }
+
+    /// Well-formed but invalid request, `422`
+    pub fn invalid<S: ToString>(message: S) -> Self {
+        Self::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid", message)
+    }
+
+    /// Rate limit exceeded, `429`
+    pub fn too_many_requests<S: ToString>(message: S) -> Self {
+        Self::new(StatusCode::TOO_MANY_REQUESTS, "rate_limited", message)
+    }
+
+    /// Failure of the server, `500`
+    pub fn internal<S: ToString>(message: S) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "internal", message)
+    }
+
+    /// Sets a more specific code
+    pub fn code(mut self, code: &'static str) -> Self {
+        self.code = code;
+        self
+    }
+
+    /// Sets the details of the error
+    pub fn details<T>(mut self, details: T) -> Self
+    where
+        T: Serialize,
+    {
+        self.details = serde_json::to_value(details).ok();
+        self
+    }
+
+    /// Body of the error, for the request `request_id`
+    pub fn body(&self, request_id: Option<&str>) -> ErrorBody {
+        ErrorBody {
+            code: self.code,
+            message: self.message.clone(),
+            details: self.details.clone(),
+            request_id: request_id.map(str::to_string),
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status).json(self.body(None))
+    }
+}
+
+impl From<CrudError> for ApiError {
+    fn from(error: CrudError) -> Self {
+        match error {
+            CrudError::NotFound => Self::not_found(error.reason()),
+            CrudError::Conflict => Self::conflict(error.reason()),
+            CrudError::Validation => Self::invalid(error.reason()),
+            CrudError::Quota => Self::forbidden(error.reason()).code("quota_exceeded"),
+            // database failures are logged, not disclosed
+            _ => {
+                eprintln!("Internal Error: {:?}", error);
+                Self::internal("Internal Server Error")
+            }
+        }
+    }
+}
+
+impl From<ValidationErrors> for ApiError {
+    fn from(errors: ValidationErrors) -> Self {
+        Self::invalid("Invalid JSON Parameters").details(errors)
+    }
+}
+
+/// # Rejected
+/// Error handler of the extractors, e.g. of a malformed JSON payload, see
+/// `JsonConfig::error_handler`
+pub fn rejected<E>(error: E, _: &HttpRequest) -> actix_web::Error
+where
+    E: ResponseError,
+{
+    let status = error.status_code();
+    let code = match status {
+        StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+        StatusCode::NOT_FOUND => "not_found",
+        _ => "bad_request",
+    };
+
+    ApiError::new(status, code, error).into()
+}
+
+/// Id of a request, the `X-Request-Id` of the client if it is 1-64 visible
+/// ASCII characters
+fn request_id(req: &ServiceRequest) -> HeaderValue {
+    req.headers()
+        .get(X_REQUEST_ID)
+        .filter(|id| {
+            let id = id.as_bytes();
+            !id.is_empty() && id.len() <= 64 && id.iter().all(u8::is_ascii_graphic)
+        })
+        .cloned()
+        .unwrap_or_else(|| {
+            HeaderValue::from_str(&Uuid::new_v4().to_string())
+                .unwrap_or(HeaderValue::from_static(""))
+        })
+}
+
+/// # Request Id
+/// Middleware setting the `X-Request-Id` header of every response, and the
+/// `request_id` of the body of `ApiError` responses.
+pub async fn identify<B>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, actix_web::Error>
+where
+    B: MessageBody,
+{
+    let id = request_id(&req);
+    let res = next.call(req).await?;
+
+    let body = res
+        .response()
+        .error()
+        .and_then(|error| error.as_error::<ApiError>())
+        .map(|error| serde_json::to_vec(&error.body(id.to_str().ok())).unwrap_or_default());
+
+    // the headers of the response are kept, e.g. `Retry-After`
+    let mut res = match body {
+        Some(body) => res.map_body(|_, _| EitherBody::right(BoxBody::new(body))),
+        None => res.map_into_left_body(),
+    };
+
+    res.headers_mut().insert(X_REQUEST_ID, id);
+
+    Ok(res)
+}
@@ -0,0 +1,211 @@
+//! Event-Related API Endpoints
+
+extern crate actix_web;
+extern crate actix_ws;
+extern crate futures;
+extern crate serde_json;
+extern crate sqlx;
+extern crate std;
+extern crate tokio;
+
+use super::error::{ApiError, ErrorBody};
+use crate::{
+    event::{
+        hub::{Feed, Hub, Item},
+        schema::{Event, Stream},
+    },
+    iam::schema::AuthorizedUser,
+};
+use actix_web::{
+    http::header::{self, HeaderName},
+    rt,
+    web::{Bytes, Data, Payload, Query},
+    HttpRequest, HttpResponse,
+};
+use actix_ws::Message;
+use futures::stream;
+use sqlx::PgPool;
+use std::convert::Infallible;
+
+/// Header holding the id of the last event received by a reconnecting client
+const LAST_EVENT_ID: HeaderName = HeaderName::from_static("last-event-id");
+
+/// # Stream Events
+/// This function streams the job and run events of the user as
+/// Server-Sent Events, from the moment of the request, or after the event
+/// of the `Last-Event-ID` header (or `last_event_id` parameter) when
+/// resuming. Events are kept for 7 days, resuming after an expired or
+/// unknown event streams from the moment of the request.
+///
+/// Every event carries its position as `id`, its type as `event`, and the
+/// webhook payload of schema version 1 as `data`. Idle streams receive a
+/// comment every 15 seconds.
+///
+/// ## Parameters
+///
+/// - `job`: Query parameter, only the events of this job.
+/// - `selector`: Query parameter, only the events of the jobs with all of
+///   these labels, e.g. `team=ops,tier=1`.
+///
+/// ## Errors
+///
+/// - Invalid selector.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```text
+/// id: 42
+/// event: run.failed
+/// data: {"seq":42,"id":"...","type":"run.failed","version":1,"created_at":"...","data":{...}}
+/// ```
+#[utoipa::path(
+    get,
+    path = "/api/events",
+    tag = "event",
+    params(Stream, ("Last-Event-ID" = Option<i64>, Header, description = "Position of the last event received, when resuming")),
+    responses(
+        (status = 200, description = "Server-Sent Events, `data` holding the event", body = Event, content_type = "text/event-stream"),
+        (status = 400, description = "Invalid selector", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn stream_events(
+    account: AuthorizedUser,
+    req: HttpRequest,
+    query: Query<Stream>,
+    hub: Data<Hub>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let feed = match feed(&account, &req, &query, &hub, db).await {
+        Ok(feed) => feed,
+        Err(error) => return Err(error),
+    };
+
+    let events = stream::unfold(feed, |mut feed| async move {
+        let frame = match feed.next().await {
+            Ok(Item::Event(event)) => format!(
+                "id: {}\nevent: {}\ndata: {}\n\n",
+                event.seq,
+                event.event_type,
+                serde_json::to_string(&event).unwrap_or_default()
+            ),
+            Ok(Item::Heartbeat) => ":\n\n".to_string(),
+            // the client reconnects and resumes
+            Err(_) => return None,
+        };
+
+        Some((Ok::<_, Infallible>(Bytes::from(frame)), feed))
+    });
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CONTENT_TYPE, "text/event-stream"))
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(events))
+}
+
+/// # Stream Events over WebSocket
+/// This function streams the same events as `stream_events` over a
+/// WebSocket, as text messages holding the `data` of the Server-Sent
+/// Events. Idle connections are pinged every 15 seconds.
+///
+/// ## Errors
+///
+/// - Not a WebSocket upgrade.
+/// - Invalid selector.
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    get,
+    path = "/api/events/ws",
+    tag = "event",
+    params(Stream, ("Last-Event-ID" = Option<i64>, Header, description = "Position of the last event received, when resuming")),
+    responses(
+        (status = 101, description = "WebSocket of the events, one text message per event", body = Event),
+        (status = 400, description = "Invalid selector, or not a WebSocket upgrade", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn stream_events_ws(
+    account: AuthorizedUser,
+    req: HttpRequest,
+    body: Payload,
+    query: Query<Stream>,
+    hub: Data<Hub>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let mut feed = match feed(&account, &req, &query, &hub, db).await {
+        Ok(feed) => feed,
+        Err(error) => return Err(error),
+    };
+
+    let (response, mut session, mut messages) = match actix_ws::handle(&req, body) {
+        Ok(upgrade) => upgrade,
+        Err(error) => return Err(ApiError::bad_request(error)),
+    };
+
+    rt::spawn(async move {
+        loop {
+            tokio::select! {
+                message = messages.recv() => match message {
+                    Some(Ok(Message::Ping(bytes))) => {
+                        if session.pong(&bytes).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                },
+                item = feed.next() => {
+                    let sent = match item {
+                        Ok(Item::Event(event)) => {
+                            session
+                                .text(serde_json::to_string(&event).unwrap_or_default())
+                                .await
+                        }
+                        Ok(Item::Heartbeat) => session.ping(b"").await,
+                        Err(_) => break,
+                    };
+
+                    if sent.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let _ = session.close(None).await;
+    });
+
+    Ok(response)
+}
+
+/// Feed of the events of the request
+async fn feed(
+    account: &AuthorizedUser,
+    req: &HttpRequest,
+    query: &Stream,
+    hub: &Hub,
+    db: Data<PgPool>,
+) -> Result<Feed, ApiError> {
+    let labels = query.labels().map_err(ApiError::bad_request)?;
+
+    let last = req
+        .headers()
+        .get(LAST_EVENT_ID)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .or(query.last_event_id);
+
+    hub.feed(
+        db.into_inner(),
+        &account.id,
+        last,
+        query.job.clone(),
+        labels,
+    )
+    .await
+    .map_err(ApiError::from)
+}
@@ -0,0 +1,52 @@
+//! Idempotency-Key Handling
+
+extern crate actix_web;
+extern crate hex;
+extern crate serde;
+extern crate serde_json;
+extern crate sha2;
+
+use super::error::ApiError;
+use actix_web::HttpRequest;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Request header carrying the idempotency key
+pub const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+
+/// Response header set on replayed responses
+pub const IDEMPOTENT_REPLAYED: &str = "Idempotent-Replayed";
+
+/// # Idempotency Key
+/// Read the optional `Idempotency-Key` header, which must be 1-255 visible ASCII characters.
+pub fn key(req: &HttpRequest) -> Result<Option<String>, ApiError> {
+    let Some(value) = req.headers().get(IDEMPOTENCY_KEY) else {
+        return Ok(None);
+    };
+
+    match value.to_str() {
+        Ok(key)
+            if !key.is_empty() && key.len() <= 255 && key.chars().all(|c| c.is_ascii_graphic()) =>
+        {
+            Ok(Some(key.to_string()))
+        }
+        _ => Err(ApiError::bad_request(
+            "Idempotency-Key must be 1-255 visible ASCII characters",
+        )),
+    }
+}
+
+/// # Request Hash
+/// Hex-encoded SHA256 of the canonical JSON form of the payload,
+/// so that formatting and key order do not matter.
+pub fn hash<T>(payload: &T) -> String
+where
+    T: Serialize,
+{
+    // `Value` objects are sorted maps, which makes the encoding canonical
+    let canonical = serde_json::to_value(payload)
+        .and_then(|value| serde_json::to_vec(&value))
+        .unwrap_or_default();
+
+    hex::encode(Sha256::digest(canonical))
+}
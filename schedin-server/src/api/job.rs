@@ -1,17 +1,56 @@
 //! Job-Related API Endpoints
 
 extern crate actix_web;
+extern crate futures;
+extern crate schedin_common;
+extern crate serde_json;
+extern crate serde_yaml;
 extern crate sqlx;
 extern crate std;
 extern crate validator;
 
-use crate::{db::DB, iam::schema::AuthorizedUser, job::schema::Job};
+use super::{
+    error::{ApiError, ErrorBody},
+    idempotency,
+    openapi::Status,
+};
+use crate::{
+    db::{
+        idempotency::{Claim, Idempotency},
+        project::Project,
+        revision::Revisions,
+        run::Runs,
+        DB,
+    },
+    iam::schema::AuthorizedUser,
+    job::{
+        manifest::{ApplyOptions, Diff, Manifest, OWNER_LABEL},
+        revision::{Revision, Rollback},
+        schema::{CloneJob, Job, JobPath, JobRecord, JobStatus, JobType, TrashedJob},
+    },
+    project::schema::ProjectScope,
+    run::schema::{Artifact, ArtifactPath, History, Run, RunPath, RunStep},
+};
 use actix_web::{
-    web::{Data, Json},
-    HttpResponse, Responder,
+    http::{
+        header::{self, ContentDisposition},
+        StatusCode,
+    },
+    web::{self, Bytes, Data, Json, Path, Query},
+    HttpRequest, HttpResponse, ResponseError,
+};
+use futures::{stream, Stream};
+use schedin_common::{
+    blob,
+    error::{BlobError, CrudError},
 };
+use serde_json::json;
 use sqlx::PgPool;
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Read},
+    sync::Arc,
+};
 use validator::Validate;
 
 /// # Insert New Job
@@ -23,11 +62,62 @@ use validator::Validate;
 /// - `Binary`: Schedule a binary.
 /// - `Code`: Schedule Function.
 /// - `Task`: Schedule a Task by name.
+/// - `Http`: Schedule an HTTP request, its response is stored with the run.
+/// - `Sql`: Schedule SQL statements against a database whose DSN is a secret.
+/// - `Pipeline`: Schedule ordered `task`, `code`, `bin` or `http` steps, run on one
+///   worker in a shared directory. Steps are listed under the run, see
+///   `/api/job/{id}/runs/{run}/steps`.
+/// - `Wasm`: Schedule a WASI module, run in a sandbox with its output captured.
+///   Modules are inlined base64-encoded, or uploaded to `/api/module/upload`
+///   and referenced by digest, which large modules require.
+///
+/// ## Environment
+/// Optional `env` map of plain values or references to the user's secrets,
+/// e.g. `"API_TOKEN": { "secret": "api-token" }`. Referenced secrets must exist.
 ///
 /// ## Parameters
 ///
 /// - `payload`: A JSON payload containing the information for
-/// the job to be inserted.
+///   the job to be inserted.
+///
+/// ## Resources
+/// Optional `resources` requests and limits of `cpu` (cores), `memory_mb` and `disk_mb`,
+/// e.g. `"resources": { "requests": { "memory_mb": 512 }, "limits": { "memory_mb": 1024 } }`.
+/// Runs are only placed on workers with enough free capacity for the requests.
+///
+/// ## Placement
+/// Optional `node_selector` labels a worker must have, and `affinity` / `anti_affinity`
+/// terms matched against the jobs of the project already on a worker, e.g.
+/// `"anti_affinity": [{ "job": "job-X" }]` never runs two instances of `job-X` together.
+/// Runs no worker can take are `unschedulable`, with the reason in the run history.
+///
+/// ## Matrix
+/// Optional `matrix` of axes, e.g. `"matrix": { "axes": { "region": ["eu", "us"], "shard": "0..8" } }`.
+/// Every fire runs once per combination, with the values as `SCHEDIN_MATRIX_<AXIS>` env
+/// vars, at most `max_parallel` at a time. With `fail_fast`, a failed combination cancels
+/// those yet to start. Combinations are listed under the run, see
+/// `/api/job/{id}/runs/{run}/children`.
+///
+/// ## Artifacts
+/// Optional `artifacts` globs relative to the work directory of `code`, `bin`, `wasm`
+/// and `pipeline` jobs, e.g. `"artifacts": ["reports/*.csv"]`. Matching files are
+/// uploaded to the blob store once the run completes, and listed under the run, see
+/// `/api/job/{id}/runs/{run}/artifacts`.
+///
+/// ## Priority
+/// Optional `priority_class`, the name of a class defined by an administrator
+/// (see `/api/priority/list`). Runs of higher priority are placed first, and may
+/// preempt runs of lower priority if their class allows it.
+///
+/// ## Projects
+/// Jobs belong to the project of the path, `/api/project/{project}/job/new`,
+/// or to the `default` project for `/api/job/new`. The project quotas are
+/// enforced on the job, e.g. its interval and timeout.
+///
+/// ## Idempotency
+/// With an `Idempotency-Key` header, the response is stored along with a hash
+/// of the payload. Repeating the request with the same key replays the original
+/// response, reusing the key with a different payload returns 422.
 ///
 /// ## Errors
 ///
@@ -76,40 +166,184 @@ use validator::Validate;
 ///     }
 /// }
 /// ````
+///
+/// ### HTTP Job
+/// ```json
+/// {
+///     "name": "job-W",
+///     "schedule": "@every 5 min",
+///     "http": {
+///         "method": "POST",
+///         "url": "https://internal.example.com/cache/refresh",
+///         "headers": { "Authorization": { "secret": "cache-token" } },
+///         "timeout": 30,
+///         "expected_status": [200, 204],
+///         "assertion": "$.refreshed"
+///     }
+/// }
+/// ````
+///
+/// ### SQL Job
+/// ```json
+/// {
+///     "name": "job-V",
+///     "schedule": "@every 24 hr",
+///     "sql": {
+///         "connection": "warehouse-dsn",
+///         "statements": ["REFRESH MATERIALIZED VIEW daily_sales"],
+///         "timeout": 300
+///     }
+/// }
+/// ````
+///
+/// ### Pipeline Job
+/// ```json
+/// {
+///     "name": "job-T",
+///     "schedule": "@every 24 hr",
+///     "pipeline": {
+///         "steps": [
+///             { "name": "extract", "bin": { "path": "/opt/etl/extract" }, "timeout": 600 },
+///             { "name": "load", "code": { "src": "base64-encoded-function",
+///               "lang": "python", "cmd": "python load.py" } },
+///             { "name": "notify", "http": { "url": "https://hooks.example.com/etl" },
+///               "continue_on_error": true }
+///         ]
+///     }
+/// }
+/// ````
+///
+/// ### WebAssembly Job
+/// ```json
+/// {
+///     "name": "job-U",
+///     "schedule": "@every 1 hr",
+///     "wasm": {
+///         "module": "base64-encoded-module",
+///         "args": ["--since", "1h"],
+///         "fuel": 10000000000
+///     },
+///     "resources": { "limits": { "memory_mb": 64 } }
+/// }
+/// ````
+#[utoipa::path(
+    post,
+    path = "/api/job/new",
+    tag = "job",
+    params(("Idempotency-Key" = Option<String>, Header, description = "Key of the request, its response is replayed on retries")),
+    request_body = Job,
+    responses(
+        (status = 200, description = "Job inserted, or the replayed response of its Idempotency-Key", body = Status),
+        (status = 400, description = "Invalid Idempotency-Key", body = ErrorBody),
+        (status = 403, description = "Project quota exceeded", body = ErrorBody),
+        (status = 409, description = "A job with this name exists, or a request with this Idempotency-Key is still being processed", body = ErrorBody),
+        (status = 422, description = "Invalid job, or the Idempotency-Key was already used with a different request", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
 pub async fn insert_job(
     account: AuthorizedUser,
+    project: ProjectScope,
+    req: HttpRequest,
     payload: Json<Job>,
     db: Data<PgPool>,
-) -> impl Responder {
-    if let Err(err) = payload.validate() {
-        return HttpResponse::BadRequest().json(err);
-    }
+) -> Result<HttpResponse, ApiError> {
+    let Some(key) = idempotency::key(&req)? else {
+        create_job(&account, &project, payload.0, db.into_inner()).await?;
+        return Ok(HttpResponse::Ok().json(json!({ "status": "ok" })));
+    };
 
-    if let (None, None, None) = (&payload.task, &payload.code, &payload.bin) {
-        return HttpResponse::BadRequest().json("Job must be defined: 'bin', 'task', or 'code'");
-    }
+    let store = Idempotency::new(db.clone().into_inner());
 
-    if let Err(error) = DB::new(db.into_inner())
-        .job(payload.0)
-        .insert(&account.id)
+    match store
+        .claim(
+            &account.id,
+            &key,
+            &idempotency::hash(&(&project.name, &payload.0)),
+        )
         .await
     {
-        return HttpResponse::InternalServerError().json(error.map());
+        Ok(Claim::Started) => {}
+        Ok(Claim::Replay { status, response }) => {
+            return Ok(HttpResponse::build(
+                StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            )
+            .insert_header((idempotency::IDEMPOTENT_REPLAYED, "true"))
+            .json(response));
+        }
+        Ok(Claim::Mismatch) => {
+            return Err(ApiError::invalid(
+                "Idempotency-Key was already used with a different request",
+            )
+            .code("idempotency_mismatch"));
+        }
+        Ok(Claim::InProgress) => {
+            return Err(ApiError::conflict(
+                "A request with this Idempotency-Key is still being processed",
+            )
+            .code("idempotency_in_progress"));
+        }
+        Err(error) => return Err(error.into()),
     }
 
-    let mut map = HashMap::with_capacity(1);
-    map.insert("status", "ok");
-    HttpResponse::Ok().json(map)
+    let created = create_job(&account, &project, payload.0, db.into_inner()).await;
+
+    // errors are stored without a request id, replays are new requests
+    let (status, response) = match &created {
+        Ok(_) => (StatusCode::OK, json!({ "status": "ok" })),
+        Err(error) => (error.status_code(), json!(error.body(None))),
+    };
+
+    // server errors are not replayed, so that the request can be retried
+    let stored = match status.is_server_error() {
+        true => store.release(&account.id, &key).await,
+        false => {
+            store
+                .complete(&account.id, &key, status.as_u16(), &response)
+                .await
+        }
+    };
+
+    if let Err(error) = stored {
+        eprintln!("Idempotency-Key '{}': {}", key, error.reason());
+    }
+
+    created.map(|_| HttpResponse::Ok().json(response))
+}
+
+/// Validate and insert a job
+async fn create_job(
+    account: &AuthorizedUser,
+    project: &ProjectScope,
+    job: Job,
+    pool: Arc<PgPool>,
+) -> Result<(), ApiError> {
+    job.validate()?;
+
+    if let JobType::Invalid = job.kind() {
+        return Err(ApiError::invalid(
+            "Job must be defined: 'bin', 'task', 'code', 'http', 'sql', 'wasm', or 'pipeline'",
+        ));
+    }
+
+    DB::new(pool)
+        .job(job)
+        .project(project.id)
+        .insert(&account.id)
+        .await?;
+
+    Ok(())
 }
 
 /// # Delete Job
-/// This function deletes an existing job from the database based
-/// on the provided JSON payload.
+/// This function moves an existing job to the trash of the project, based
+/// on the provided JSON payload. See `delete_job_by_id`.
 ///
 /// ## Parameters
 ///
 /// - `payload`: A JSON payload containing the information for
-/// the job to be deleted.
+///   the job to be deleted.
 ///
 /// ## Errors
 ///
@@ -126,20 +360,850 @@ pub async fn insert_job(
 ///     "name": "job-X"
 /// }
 /// ```
+#[utoipa::path(
+    post,
+    path = "/api/job/delete",
+    tag = "job",
+    request_body = Job,
+    responses(
+        (status = 200, description = "Job moved to the trash", body = Status),
+        (status = 404, description = "Unknown job", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
 pub async fn delete_job(
-    account: AuthorizedUser,
+    project: ProjectScope,
     payload: Json<Job>,
     db: Data<PgPool>,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
     if let Err(error) = DB::new(db.into_inner())
         .job(payload.0)
-        .delete(account.id)
+        .project(project.id)
+        .delete()
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # Delete Job by ID
+/// This function moves the job of the path to the trash of the project.
+///
+/// Trashed jobs are no longer dispatched nor listed, and their name can be
+/// reused. Their queued runs are cancelled, running runs complete. They can
+/// be restored until they are purged, after the `trash_retention_days` of
+/// the project, 30 by default.
+///
+/// ## Errors
+///
+/// - Unknown job.
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    post,
+    path = "/api/job/{id}/delete",
+    tag = "job",
+    params(JobPath),
+    responses(
+        (status = 200, description = "Job moved to the trash", body = Status),
+        (status = 404, description = "Unknown job", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn delete_job_by_id(
+    project: ProjectScope,
+    path: Path<JobPath>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(error) = DB::new(db.into_inner())
+        .project(project.id)
+        .delete_by_id(&path.id)
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # List Trash
+/// This function lists the deleted jobs of the project, most recently
+/// deleted first, along with the time they are purged at.
+///
+/// ## Errors
+///
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    get,
+    path = "/api/job/trash",
+    tag = "job",
+    responses(
+        (status = 200, description = "Deleted jobs of the project", body = Vec<TrashedJob>),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_trash(project: ProjectScope, db: Data<PgPool>) -> Result<HttpResponse, ApiError> {
+    match DB::new(db.into_inner()).project(project.id).trash().await {
+        Ok(jobs) => Ok(HttpResponse::Ok().json(jobs)),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// # Restore Job
+/// This function moves the job of the path back from the trash. Its
+/// schedule resumes from the next fire time after now.
+///
+/// ## Errors
+///
+/// - Unknown job, or the job is not in the trash.
+/// - Another job took its name since.
+/// - The project reached its maximum of jobs.
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    post,
+    path = "/api/job/{id}/restore",
+    tag = "job",
+    params(JobPath),
+    responses(
+        (status = 200, description = "Job restored", body = Status),
+        (status = 403, description = "Project quota exceeded", body = ErrorBody),
+        (status = 404, description = "Unknown job, or the job is not in the trash", body = ErrorBody),
+        (status = 409, description = "Another job took its name since", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn restore_job(
+    project: ProjectScope,
+    path: Path<JobPath>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(error) = DB::new(db.into_inner())
+        .project(project.id)
+        .restore(&path.id)
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # Purge Job
+/// This function permanently deletes the job of the path from the trash,
+/// along with its revisions and runs, without waiting for the retention.
+///
+/// ## Errors
+///
+/// - Unknown job, or the job is not in the trash.
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    post,
+    path = "/api/job/{id}/purge",
+    tag = "job",
+    params(JobPath),
+    responses(
+        (status = 200, description = "Job purged", body = Status),
+        (status = 404, description = "Unknown job, or the job is not in the trash", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn purge_job(
+    project: ProjectScope,
+    path: Path<JobPath>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(error) = DB::new(db.into_inner())
+        .project(project.id)
+        .purge(&path.id)
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # Clone Job
+/// This function copies the job of the path under a new name, along with its
+/// payload, labels, resources and environment. Secret references are copied
+/// as references, secret values are never read.
+///
+/// ## Parameters
+///
+/// - `schedule`: Schedule of the copy, the schedule of the job if unset.
+/// - `project`: Project of the copy, the project of the job if unset.
+/// - `owner_label`: `schedin/owner` label of the copy, see `/api/job/apply`.
+///   The copy has no owner label if unset, so that pruning the manifest of
+///   the job never deletes it. The copy always belongs to the caller.
+/// - `paused`: Start the copy paused, see `/api/job/{id}/resume`.
+///
+/// ## Errors
+///
+/// - Unknown job or project.
+/// - Invalid copy, e.g. its schedule.
+/// - The project already has a job with that name.
+/// - The copy exceeds the project quotas.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {
+///     "name": "report-eu-next",
+///     "schedule": "@every 2 hr",
+///     "project": "staging",
+///     "paused": true
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/job/{id}/clone",
+    tag = "job",
+    params(JobPath),
+    request_body = CloneJob,
+    responses(
+        (status = 200, description = "Job copied", body = Status),
+        (status = 403, description = "Project quota exceeded", body = ErrorBody),
+        (status = 404, description = "Unknown job or project", body = ErrorBody),
+        (status = 409, description = "The project already has a job with that name", body = ErrorBody),
+        (status = 422, description = "Invalid copy", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn clone_job(
+    account: AuthorizedUser,
+    project: ProjectScope,
+    path: Path<JobPath>,
+    payload: Json<CloneJob>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(err) = payload.validate() {
+        return Err(err.into());
+    }
+
+    let pool = db.into_inner();
+
+    let mut job = match DB::new(pool.clone())
+        .project(project.id)
+        .get(&path.id)
+        .await
+    {
+        Ok(record) => record.job,
+        Err(error) => return Err(error.into()),
+    };
+
+    let target = match &payload.project {
+        Some(name) => match Project::new(pool.clone()).scope(&account.id, name).await {
+            Ok(target) => target,
+            Err(CrudError::NotFound) => {
+                return Err(ApiError::not_found(format!("Unknown project '{}'", name)))
+            }
+            Err(error) => return Err(error.into()),
+        },
+        None => project,
+    };
+
+    let payload = payload.into_inner();
+    job.name = payload.name;
+    if payload.schedule.is_some() {
+        job.schedule = payload.schedule;
+    }
+
+    let mut labels = job.labels.take().unwrap_or_default();
+    match payload.owner_label {
+        Some(owner) => labels.insert(OWNER_LABEL.to_string(), owner),
+        None => labels.remove(OWNER_LABEL),
+    };
+    job.labels = Some(labels).filter(|labels| !labels.is_empty());
+
+    if let Err(err) = job.validate() {
+        return Err(err.into());
+    }
+
+    let status = match payload.paused {
+        true => JobStatus::Paused,
+        false => JobStatus::Scheduled,
+    };
+
+    if let Err(error) = DB::new(pool)
+        .job(job)
+        .project(target.id)
+        .status(status)
+        .insert(&account.id)
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # Pause Job
+/// This function stops dispatching the job of the path, until it is resumed.
+/// Its runs already queued or running are left alone.
+///
+/// ## Errors
+///
+/// - Unknown job, or the job is not scheduled.
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    post,
+    path = "/api/job/{id}/pause",
+    tag = "job",
+    params(JobPath),
+    responses(
+        (status = 200, description = "Job paused", body = Status),
+        (status = 404, description = "Unknown job, or the job is not scheduled", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn pause_job(
+    project: ProjectScope,
+    path: Path<JobPath>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(error) = DB::new(db.into_inner())
+        .project(project.id)
+        .pause(&path.id)
         .await
     {
-        return HttpResponse::InternalServerError().json(error.map());
+        return Err(error.into());
     }
 
     let mut map = HashMap::with_capacity(1);
     map.insert("status", "ok");
-    HttpResponse::Ok().json(map)
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # Resume Job
+/// This function dispatches the paused job of the path again, from the next
+/// fire time of its schedule after now.
+///
+/// ## Errors
+///
+/// - Unknown job, or the job is not paused.
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    post,
+    path = "/api/job/{id}/resume",
+    tag = "job",
+    params(JobPath),
+    responses(
+        (status = 200, description = "Job resumed", body = Status),
+        (status = 404, description = "Unknown job, or the job is not paused", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn resume_job(
+    project: ProjectScope,
+    path: Path<JobPath>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(error) = DB::new(db.into_inner())
+        .project(project.id)
+        .resume(&path.id)
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # List Jobs
+/// This function lists the jobs of the project in the same shape they are submitted in,
+/// so the output can be used as a manifest. Secret values are never included.
+///
+/// ## Errors
+///
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    get,
+    path = "/api/job/list",
+    tag = "job",
+    responses(
+        (status = 200, description = "Jobs of the project", body = Vec<JobRecord>),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_jobs(project: ProjectScope, db: Data<PgPool>) -> Result<HttpResponse, ApiError> {
+    match DB::new(db.into_inner()).project(project.id).list().await {
+        Ok(jobs) => Ok(HttpResponse::Ok().json(jobs)),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// # Apply Manifest
+/// This function computes the create/update/delete diff between a manifest
+/// and the current jobs of the project, and applies it within one transaction.
+///
+/// The manifest is read as YAML for `application/yaml` bodies, JSON otherwise.
+/// Jobs are matched by name. With an `owner`, every job is labelled
+/// `schedin/owner: <owner>`, and `prune` moves the jobs of that owner
+/// which are missing from the manifest to the trash.
+///
+/// ## Parameters
+///
+/// - `dry_run`: Query parameter, return the diff without applying it.
+///
+/// ## Errors
+///
+/// - Invalid manifest.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```yaml
+/// owner: reports-team
+/// prune: true
+/// jobs:
+///   - name: report-eu
+///     schedule: "@every 1 hr"
+///     task:
+///       name: report
+///     labels:
+///       region: eu
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/job/apply",
+    tag = "job",
+    params(ApplyOptions),
+    request_body(content(
+        (Manifest = "application/json"),
+        (Manifest = "application/yaml"),
+    )),
+    responses(
+        (status = 200, description = "Diff of the manifest, applied unless `dry_run`", body = Diff),
+        (status = 400, description = "Malformed manifest", body = ErrorBody),
+        (status = 403, description = "Project quota exceeded", body = ErrorBody),
+        (status = 422, description = "Invalid manifest", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn apply_jobs(
+    account: AuthorizedUser,
+    project: ProjectScope,
+    req: HttpRequest,
+    body: Bytes,
+    options: Query<ApplyOptions>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let yaml = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("yaml"));
+
+    let parsed = match yaml {
+        true => serde_yaml::from_slice::<Manifest>(&body).map_err(|e| e.to_string()),
+        false => serde_json::from_slice::<Manifest>(&body).map_err(|e| e.to_string()),
+    };
+
+    let manifest = match parsed {
+        Ok(manifest) => manifest,
+        Err(err) => return Err(ApiError::bad_request(err)),
+    };
+
+    if let Err(err) = manifest.validate() {
+        return Err(err.into());
+    }
+
+    if manifest.prune && manifest.owner.is_none() {
+        return Err(ApiError::invalid("Pruning requires an 'owner'"));
+    }
+
+    let mut names = HashSet::with_capacity(manifest.jobs.len());
+    for job in &manifest.jobs {
+        if let JobType::Invalid = job.kind() {
+            return Err(ApiError::invalid(format!(
+                "Job '{}' must be defined: 'bin', 'task', 'code', 'http', 'sql', 'wasm', or 'pipeline'",
+                job.name
+            )));
+        }
+
+        if !names.insert(job.name.as_str()) {
+            return Err(ApiError::invalid(format!(
+                "Job '{}' is declared more than once",
+                job.name
+            )));
+        }
+    }
+
+    match DB::new(db.into_inner())
+        .project(project.id)
+        .apply(&account.id, manifest, options.dry_run)
+        .await
+    {
+        Ok(diff) => Ok(HttpResponse::Ok().json(diff)),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// # List Revisions
+/// This function lists the revisions of a job, most recent first. Each revision
+/// records who changed the job, when, the full definition and a diff against
+/// the previous revision.
+///
+/// ## Errors
+///
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    get,
+    path = "/api/job/{id}/revisions",
+    tag = "job",
+    params(JobPath),
+    responses(
+        (status = 200, description = "Revisions of the job", body = Vec<Revision>),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_revisions(
+    project: ProjectScope,
+    path: Path<JobPath>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    match Revisions::new(db.into_inner())
+        .list(&project.id, &path.id)
+        .await
+    {
+        Ok(revisions) => Ok(HttpResponse::Ok().json(revisions)),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// # Rollback Job
+/// This function restores the definition of an earlier revision of a job,
+/// which is recorded as a new revision.
+///
+/// ## Errors
+///
+/// - Unknown job or revision.
+/// - The restored definition is no longer valid, e.g. an elapsed `@once` schedule.
+/// - The restored definition exceeds the project quotas.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {
+///     "revision": 3
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/job/{id}/rollback",
+    tag = "job",
+    params(JobPath),
+    request_body = Rollback,
+    responses(
+        (status = 200, description = "Revision restored", body = Status),
+        (status = 403, description = "Project quota exceeded", body = ErrorBody),
+        (status = 404, description = "Unknown job or revision", body = ErrorBody),
+        (status = 422, description = "The restored definition is no longer valid", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn rollback_job(
+    account: AuthorizedUser,
+    project: ProjectScope,
+    path: Path<JobPath>,
+    payload: Json<Rollback>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let pool = db.into_inner();
+
+    let definition = match Revisions::new(pool.clone())
+        .definition(&project.id, &path.id, payload.revision)
+        .await
+    {
+        Ok(definition) => definition,
+        Err(error) => return Err(error.into()),
+    };
+
+    let job: Job = match serde_json::from_value(definition) {
+        Ok(job) => job,
+        Err(err) => return Err(ApiError::internal(err)),
+    };
+
+    if let Err(err) = job.validate() {
+        return Err(err.into());
+    }
+
+    if let Err(error) = DB::new(pool)
+        .job(job)
+        .project(project.id)
+        .update(&account.id, &path.id)
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # Run History
+/// This function lists the runs of a job, most recent first, along with
+/// the revision each run executed.
+///
+/// ## Parameters
+///
+/// - `limit`: Query parameter, maximum number of runs (default: 50).
+///
+/// ## Errors
+///
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    get,
+    path = "/api/job/{id}/runs",
+    tag = "run",
+    params(JobPath, History),
+    responses(
+        (status = 200, description = "Runs of the job", body = Vec<Run>),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_runs(
+    project: ProjectScope,
+    path: Path<JobPath>,
+    history: Query<History>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    match Runs::new(db.into_inner())
+        .history(&project.id, &path.id, history.limit)
+        .await
+    {
+        Ok(runs) => Ok(HttpResponse::Ok().json(runs)),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// # Run Children
+/// This function lists the children of a matrix run, one per combination
+/// of the matrix, with the values of their combination.
+///
+/// ## Errors
+///
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    get,
+    path = "/api/job/{id}/runs/{run}/children",
+    tag = "run",
+    params(RunPath),
+    responses(
+        (status = 200, description = "Children of the matrix run", body = Vec<Run>),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_children(
+    project: ProjectScope,
+    path: Path<RunPath>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    match Runs::new(db.into_inner())
+        .children(&project.id, &path.id, &path.run)
+        .await
+    {
+        Ok(runs) => Ok(HttpResponse::Ok().json(runs)),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// # Run Steps
+/// This function lists the steps of a pipeline run, in execution order,
+/// with their status, timing and truncated output.
+///
+/// ## Errors
+///
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    get,
+    path = "/api/job/{id}/runs/{run}/steps",
+    tag = "run",
+    params(RunPath),
+    responses(
+        (status = 200, description = "Steps of the pipeline run", body = Vec<RunStep>),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_steps(
+    project: ProjectScope,
+    path: Path<RunPath>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    match Runs::new(db.into_inner())
+        .steps(&project.id, &path.id, &path.run)
+        .await
+    {
+        Ok(steps) => Ok(HttpResponse::Ok().json(steps)),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// # Run Artifacts
+/// This function lists the artifacts of a run, the files matching the
+/// `artifacts` globs of the job once the run completed, along with the time
+/// they expire at. Artifacts are kept for the `artifact_retention_days` of
+/// the project, 14 by default.
+///
+/// ## Errors
+///
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    get,
+    path = "/api/job/{id}/runs/{run}/artifacts",
+    tag = "run",
+    params(RunPath),
+    responses(
+        (status = 200, description = "Artifacts of the run", body = Vec<Artifact>),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_artifacts(
+    project: ProjectScope,
+    path: Path<RunPath>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    match Runs::new(db.into_inner())
+        .artifacts(&project.id, &path.id, &path.run)
+        .await
+    {
+        Ok(artifacts) => Ok(HttpResponse::Ok().json(artifacts)),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// # Download Artifact
+/// This function streams the contents of an artifact of a run, addressed by
+/// its path relative to the work directory, e.g.
+/// `/api/job/{id}/runs/{run}/artifacts/reports/daily.csv`.
+///
+/// ## Errors
+///
+/// - Unknown or expired artifact.
+/// - Blob store is not configured or unavailable.
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    get,
+    path = "/api/job/{id}/runs/{run}/artifacts/{path}",
+    tag = "run",
+    params(ArtifactPath),
+    responses(
+        (status = 200, description = "Contents of the artifact", body = Vec<u8>, content_type = "application/octet-stream"),
+        (status = 404, description = "Unknown or expired artifact", body = ErrorBody),
+        (status = 500, description = "Blob store is not configured or unavailable", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn download_artifact(
+    project: ProjectScope,
+    path: Path<ArtifactPath>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let artifact = match Runs::new(db.into_inner())
+        .artifact(&project.id, &path.id, &path.run, &path.path)
+        .await
+    {
+        Ok(artifact) => artifact,
+        Err(CrudError::NotFound) => {
+            return Err(ApiError::not_found(format!(
+                "Unknown artifact '{}'",
+                path.path
+            )))
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    let digest = artifact.digest.clone();
+    let reader = web::block(move || blob::from_env()?.open(&digest)).await;
+
+    let reader = match reader {
+        Ok(Ok(reader)) => reader,
+        Ok(Err(BlobError::NotFound)) => {
+            return Err(ApiError::not_found(
+                "Artifact is missing from the blob store",
+            ))
+        }
+        Ok(Err(error)) => return Err(ApiError::internal(error.reason())),
+        Err(error) => return Err(ApiError::internal(error)),
+    };
+
+    let name = artifact.path.rsplit('/').next().unwrap_or_default();
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .insert_header(ContentDisposition::attachment(name))
+        .insert_header((header::ETAG, format!("\"{}\"", artifact.digest)))
+        .no_chunking(artifact.size as u64)
+        .streaming(chunks(reader)))
+}
+
+/// Bytes read from a blob at once when streaming it
+const CHUNK: usize = 64 * 1024;
+
+/// Contents of `reader` in chunks, each read on the blocking thread pool
+fn chunks(reader: Box<dyn Read + Send>) -> impl Stream<Item = Result<Bytes, io::Error>> {
+    stream::unfold(Some(reader), |reader| async move {
+        let mut reader = reader?;
+
+        let chunk = web::block(move || {
+            let mut chunk = vec![0; CHUNK];
+            let read = reader.read(&mut chunk)?;
+            chunk.truncate(read);
+            Ok::<_, io::Error>((chunk, reader))
+        })
+        .await;
+
+        match chunk {
+            Ok(Ok((chunk, _))) if chunk.is_empty() => None,
+            Ok(Ok((chunk, reader))) => Some((Ok(Bytes::from(chunk)), Some(reader))),
+            // the response is cut short, the rest of the stream is dropped
+            Ok(Err(error)) => Some((Err(error), None)),
+            Err(error) => Some((Err(io::Error::other(error)), None)),
+        }
+    })
 }
@@ -1,5 +1,18 @@
 //! API
 
+pub mod error;
+pub mod event;
+pub mod idempotency;
 pub mod job;
+pub mod module;
+pub mod notification;
+pub mod openapi;
+pub mod priority;
+pub mod project;
+pub mod routes;
+pub mod run;
+pub mod secret;
+pub mod template;
 pub mod user;
 pub mod validation;
+pub mod webhook;
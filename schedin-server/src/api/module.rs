@@ -0,0 +1,154 @@
+//! WebAssembly Module-Related API Endpoints
+
+extern crate actix_web;
+extern crate sqlx;
+extern crate std;
+
+use super::{
+    error::{ApiError, ErrorBody},
+    openapi::{Status, Uploaded},
+};
+use crate::{
+    db,
+    iam::schema::AuthorizedUser,
+    module::{
+        self,
+        schema::{ModuleDigest, ModuleRow},
+    },
+};
+use actix_web::{
+    web::{Bytes, Data, Json},
+    HttpResponse,
+};
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+/// # Upload Module
+/// This function stores the binary WASI module sent as the request body,
+/// and returns its digest. `wasm` jobs reference it with their `digest`
+/// field, uploading the same module again is a no-op.
+///
+/// ## Errors
+///
+/// - Body is not a WebAssembly module, or exceeds 16 MiB.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```sh
+/// curl -X POST --data-binary @report.wasm https://localhost:8080/api/module/upload
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/module/upload",
+    tag = "module",
+    request_body(content = Vec<u8>, content_type = "application/wasm"),
+    responses(
+        (status = 200, description = "Module stored", body = Uploaded),
+        (status = 422, description = "Not a WebAssembly module", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn upload_module(
+    account: AuthorizedUser,
+    body: Bytes,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if !module::is_wasm(&body) {
+        return Err(ApiError::invalid(
+            "Body must be a WebAssembly module of at most 16 MiB",
+        ));
+    }
+
+    match db::module::Module::new(db.into_inner())
+        .module(body.to_vec())
+        .insert(&account.id)
+        .await
+    {
+        Ok(digest) => {
+            let mut map = HashMap::with_capacity(2);
+            map.insert("status", "ok".to_string());
+            map.insert("digest", digest);
+            Ok(HttpResponse::Ok().json(map))
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// # List Modules
+/// This function lists the modules of the user, newest first,
+/// with their size and number of jobs.
+///
+/// ## Errors
+///
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    get,
+    path = "/api/module/list",
+    tag = "module",
+    responses(
+        (status = 200, description = "Modules of the user", body = Vec<ModuleRow>),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_modules(
+    account: AuthorizedUser,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    match db::module::Module::new(db.into_inner())
+        .list(&account.id)
+        .await
+    {
+        Ok(modules) => Ok(HttpResponse::Ok().json(modules)),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// # Delete Module
+/// This function deletes a module, which fails while any job still references it.
+///
+/// ## Errors
+///
+/// - Unknown module.
+/// - Module is referenced by a job.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {
+///     "digest": "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/module/delete",
+    tag = "module",
+    request_body = ModuleDigest,
+    responses(
+        (status = 200, description = "Module deleted", body = Status),
+        (status = 404, description = "Unknown module", body = ErrorBody),
+        (status = 409, description = "Module is referenced by a job", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn delete_module(
+    account: AuthorizedUser,
+    payload: Json<ModuleDigest>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(error) = db::module::Module::new(db.into_inner())
+        .delete(&account.id, &payload.digest)
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
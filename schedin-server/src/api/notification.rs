@@ -0,0 +1,373 @@
+//! Notification-Related API Endpoints
+
+extern crate actix_web;
+extern crate schedin_common;
+extern crate sqlx;
+extern crate std;
+extern crate uuid;
+extern crate validator;
+
+use super::{
+    error::{ApiError, ErrorBody},
+    openapi::Status,
+};
+use crate::{
+    db::{
+        notification::{Channel, Rule},
+        project::Project,
+    },
+    iam::schema::AuthorizedUser,
+    notification::schema::{self, Deliveries},
+};
+use actix_web::{
+    web::{Data, Json, Query},
+    HttpResponse,
+};
+use schedin_common::error::CrudError;
+use sqlx::PgPool;
+use std::{collections::HashMap, sync::Arc};
+use uuid::Uuid;
+use validator::Validate;
+
+/// # Insert or Update Channel
+/// This function stores a notification channel, replacing any existing
+/// channel of the same name. Channels with a `project` only serve the rules
+/// of that project.
+///
+/// ## Parameters
+///
+/// - `payload`: A JSON payload containing the channel name, kind, and
+///   either the URL or the recipients.
+///
+/// ## Errors
+///
+/// - Invalid payload.
+/// - Unknown project.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {
+///     "name": "ops",
+///     "kind": "slack",
+///     "url": "https://hooks.slack.com/services/T000/B000/XXXX"
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/notification/channel/new",
+    tag = "notification",
+    request_body = schema::Channel,
+    responses(
+        (status = 200, description = "Channel created or updated", body = Status),
+        (status = 404, description = "Unknown project", body = ErrorBody),
+        (status = 422, description = "Invalid channel", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn upsert_channel(
+    account: AuthorizedUser,
+    payload: Json<schema::Channel>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(err) = payload.validate() {
+        return Err(err.into());
+    }
+
+    let pool = db.into_inner();
+
+    let project_id = match resolve(&pool, &account.id, payload.project.as_deref()).await {
+        Ok(project_id) => project_id,
+        Err(error) => return Err(error),
+    };
+
+    if let Err(error) = Channel::new(pool)
+        .channel(payload.0)
+        .project(project_id)
+        .upsert(&account.id)
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # List Channels
+/// This function lists the notification channels of the user.
+///
+/// ## Errors
+///
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    get,
+    path = "/api/notification/channel/list",
+    tag = "notification",
+    responses(
+        (status = 200, description = "Channels of the user", body = Vec<schema::ChannelRow>),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_channels(
+    account: AuthorizedUser,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    match Channel::new(db.into_inner()).list(&account.id).await {
+        Ok(channels) => Ok(HttpResponse::Ok().json(channels)),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// # Delete Channel
+/// This function deletes a notification channel along with its rules.
+/// Its deliveries stay in the delivery log.
+///
+/// ## Errors
+///
+/// - Unknown channel.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {
+///     "name": "ops"
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/notification/channel/delete",
+    tag = "notification",
+    request_body = schema::Channel,
+    responses(
+        (status = 200, description = "Channel deleted", body = Status),
+        (status = 404, description = "Unknown channel", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn delete_channel(
+    account: AuthorizedUser,
+    payload: Json<schema::Channel>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(error) = Channel::new(db.into_inner())
+        .channel(payload.0)
+        .delete(&account.id)
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # Insert or Update Rule
+/// This function stores a notification rule, replacing any existing rule
+/// of the same name. Rules match the runs of the jobs of the user, of
+/// `project` and named `job` if set.
+///
+/// Templates substitute `{{ event }}`, `{{ job }}`, `{{ project }}`,
+/// `{{ run }}`, `{{ status }}`, `{{ error }}`, `{{ failures }}` and
+/// `{{ finished_at }}`.
+///
+/// ## Errors
+///
+/// - Invalid payload.
+/// - Unknown project or channel.
+/// - Channel of another project.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {
+///     "name": "nightly-broken",
+///     "channel": "ops",
+///     "job": "nightly",
+///     "event": "consecutive_failures",
+///     "threshold": 3,
+///     "template": "{{ job }} failed {{ failures }} times: {{ error }}"
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/notification/rule/new",
+    tag = "notification",
+    request_body = schema::Rule,
+    responses(
+        (status = 200, description = "Rule created or updated", body = Status),
+        (status = 404, description = "Unknown project", body = ErrorBody),
+        (status = 422, description = "Invalid rule, unknown channel or channel of another project", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn upsert_rule(
+    account: AuthorizedUser,
+    payload: Json<schema::Rule>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(err) = payload.validate() {
+        return Err(err.into());
+    }
+
+    let pool = db.into_inner();
+
+    let project_id = match resolve(&pool, &account.id, payload.project.as_deref()).await {
+        Ok(project_id) => project_id,
+        Err(error) => return Err(error),
+    };
+
+    let channel = payload.channel.clone();
+
+    match Rule::new(pool)
+        .rule(payload.0)
+        .project(project_id)
+        .upsert(&account.id)
+        .await
+    {
+        Ok(_) => {}
+        Err(CrudError::Validation) => {
+            return Err(ApiError::invalid(format!(
+                "Unknown channel '{}', or channel of another project",
+                channel
+            )))
+        }
+        Err(error) => return Err(error.into()),
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # List Rules
+/// This function lists the notification rules of the user.
+///
+/// ## Errors
+///
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    get,
+    path = "/api/notification/rule/list",
+    tag = "notification",
+    responses(
+        (status = 200, description = "Rules of the user", body = Vec<schema::RuleRow>),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_rules(
+    account: AuthorizedUser,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    match Rule::new(db.into_inner()).list(&account.id).await {
+        Ok(rules) => Ok(HttpResponse::Ok().json(rules)),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// # Delete Rule
+/// This function deletes a notification rule.
+///
+/// ## Errors
+///
+/// - Unknown rule.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {
+///     "name": "nightly-broken"
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/notification/rule/delete",
+    tag = "notification",
+    request_body = schema::Rule,
+    responses(
+        (status = 200, description = "Rule deleted", body = Status),
+        (status = 404, description = "Unknown rule", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn delete_rule(
+    account: AuthorizedUser,
+    payload: Json<schema::Rule>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(error) = Rule::new(db.into_inner())
+        .rule(payload.0)
+        .delete(&account.id)
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # List Deliveries
+/// This function lists the delivery log of the user, most recent first.
+/// Pending deliveries are retried with exponential backoff.
+///
+/// ## Errors
+///
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    get,
+    path = "/api/notification/deliveries",
+    tag = "notification",
+    params(Deliveries),
+    responses(
+        (status = 200, description = "Deliveries of the user, newest first", body = Vec<schema::Delivery>),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_deliveries(
+    account: AuthorizedUser,
+    query: Query<Deliveries>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    match Channel::new(db.into_inner())
+        .deliveries(&account.id, query.limit.clamp(1, 500))
+        .await
+    {
+        Ok(deliveries) => Ok(HttpResponse::Ok().json(deliveries)),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Project of a channel or rule, `None` if it is user-wide
+async fn resolve(
+    pool: &Arc<PgPool>,
+    user_id: &str,
+    name: Option<&str>,
+) -> Result<Option<Uuid>, ApiError> {
+    let Some(name) = name else {
+        return Ok(None);
+    };
+
+    match Project::new(pool.clone()).scope(user_id, name).await {
+        Ok(project) => Ok(Some(project.id)),
+        Err(CrudError::NotFound) => Err(ApiError::not_found(format!("Unknown project '{}'", name))),
+        Err(error) => Err(error.into()),
+    }
+}
@@ -0,0 +1,368 @@
+//! OpenAPI Document
+//!
+//! The document is derived from the handlers and the schema types, and
+//! served along with an interactive docs page. Every route of `api()` must
+//! be described here, see the tests of `main`.
+
+extern crate actix_web;
+extern crate serde;
+extern crate std;
+extern crate utoipa;
+extern crate utoipa_swagger_ui;
+
+use super::webhook as hooks;
+use super::{
+    error::{ApiError, ErrorBody},
+    event, job, module, notification, priority, project, run, secret, template, user,
+};
+use actix_web::{http::header, web, HttpResponse, Responder};
+use serde::Serialize;
+use std::sync::Arc;
+use utoipa::{
+    openapi::{
+        path::{Parameter, ParameterBuilder, ParameterIn},
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+        RefOr, Required, Schema,
+    },
+    Modify, OpenApi, PartialSchema, ToSchema,
+};
+use utoipa_swagger_ui::Config;
+
+/// Prefix of the job endpoints of the default project
+const JOB_PREFIX: &str = "/api/job/";
+
+/// Prefix of the job endpoints of a project, see `ProjectScoped`
+const PROJECT_JOB_PREFIX: &str = "/api/project/{project}/job/";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Schedin",
+        description = "Job scheduling service. Authenticate with the token of `/api/user/signin`, or an API key of `/api/user/key/new`, as `Authorization: Bearer <token>`."
+    ),
+    paths(
+        crate::test_endpoint,
+        openapi_json,
+        docs,
+        docs_asset,
+        user::signup,
+        user::signin,
+        user::create_key,
+        user::list_keys,
+        user::delete_key,
+        event::stream_events,
+        event::stream_events_ws,
+        job::insert_job,
+        job::delete_job,
+        job::list_jobs,
+        job::apply_jobs,
+        job::list_trash,
+        job::delete_job_by_id,
+        job::restore_job,
+        job::purge_job,
+        job::clone_job,
+        job::pause_job,
+        job::resume_job,
+        job::list_revisions,
+        job::rollback_job,
+        job::list_runs,
+        job::list_children,
+        job::list_steps,
+        job::list_artifacts,
+        job::download_artifact,
+        run::run_logs,
+        project::insert_project,
+        project::list_projects,
+        project::update_project,
+        module::upload_module,
+        module::list_modules,
+        module::delete_module,
+        notification::upsert_channel,
+        notification::list_channels,
+        notification::delete_channel,
+        notification::upsert_rule,
+        notification::list_rules,
+        notification::delete_rule,
+        notification::list_deliveries,
+        priority::upsert_priority_class,
+        priority::list_priority_classes,
+        priority::delete_priority_class,
+        secret::upsert_secret,
+        secret::list_secrets,
+        secret::delete_secret,
+        template::insert_template,
+        template::list_templates,
+        template::update_template,
+        template::instantiate_template,
+        hooks::upsert_subscription,
+        hooks::list_subscriptions,
+        hooks::delete_subscription,
+        hooks::rotate_secret,
+        hooks::redeliver_events,
+        hooks::list_webhook_deliveries,
+    ),
+    components(schemas(Status, ErrorBody)),
+    modifiers(&Bearer, &Headings, &ProjectScoped),
+    tags(
+        (name = "user", description = "Sign-up, sign-in and API keys"),
+        (name = "job", description = "Jobs of the default project, and of a project under `/api/project/{project}/job`"),
+        (name = "run", description = "Runs and their logs"),
+        (name = "event", description = "Live job and run events"),
+        (name = "project", description = "Projects and their quotas"),
+        (name = "module", description = "WebAssembly modules of `wasm` jobs"),
+        (name = "notification", description = "Notification channels and routing rules"),
+        (name = "priority", description = "Priority classes of runs"),
+        (name = "secret", description = "Encrypted secrets referenced by jobs"),
+        (name = "template", description = "Job templates and their instances"),
+        (name = "webhook", description = "Signed webhooks of run and job events"),
+        (name = "docs", description = "This document"),
+    )
+)]
+pub struct ApiDoc;
+
+/// # Status
+/// Response of successful writes
+#[derive(Serialize, ToSchema)]
+#[allow(dead_code)]
+pub struct Status {
+    /// Always `ok`
+    pub status: String,
+}
+
+/// # Uploaded Module
+#[derive(Serialize, ToSchema)]
+#[allow(dead_code)]
+pub struct Uploaded {
+    pub status: String,
+    /// Digest of the module, referenced by `wasm` jobs
+    pub digest: String,
+}
+
+/// # Signing Secret
+/// Secret of a webhook subscription, only returned when it is generated
+#[derive(Serialize, ToSchema)]
+#[allow(dead_code)]
+pub struct SigningSecret {
+    pub status: String,
+    pub secret: Option<String>,
+}
+
+/// API key of a user, only returned when it is generated
+#[derive(Serialize, ToSchema)]
+#[allow(dead_code)]
+pub struct NewApiKey {
+    pub status: String,
+    pub key: String,
+}
+
+/// # Redelivered Events
+#[derive(Serialize, ToSchema)]
+#[allow(dead_code)]
+pub struct Redelivered {
+    pub status: String,
+    /// Number of events queued for delivery again
+    pub redelivered: i64,
+}
+
+/// # Updated Template
+#[derive(Serialize, ToSchema)]
+#[allow(dead_code)]
+pub struct RolledOut {
+    pub status: String,
+    /// Number of instances the update was rolled out to
+    pub instances: usize,
+}
+
+/// Bearer token of `/api/user/signin`, or an API key
+struct Bearer;
+
+impl Modify for Bearer {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT or API key")
+                    .build(),
+            ),
+        );
+    }
+}
+
+/// Handler docs open with a `# Title` line, which becomes the summary of
+/// the operation, the rest of the paragraph its description. The same goes
+/// for the title of schemas.
+struct Headings;
+
+impl Modify for Headings {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        for item in openapi.paths.paths.values_mut() {
+            for operation in [&mut item.get, &mut item.post].into_iter().flatten() {
+                let Some(summary) = operation.summary.take() else {
+                    continue;
+                };
+
+                let mut lines = summary.lines();
+                let title = lines.next().unwrap_or_default();
+                let rest = lines.collect::<Vec<_>>().join("\n");
+
+                operation.summary = Some(title.trim_start_matches('#').trim().to_string());
+                operation.description = match (rest.is_empty(), operation.description.take()) {
+                    (true, description) => description,
+                    (false, Some(description)) if !description.is_empty() => {
+                        Some(format!("{}\n\n{}", rest, description))
+                    }
+                    (false, _) => Some(rest),
+                };
+            }
+        }
+
+        let schemas = openapi
+            .components
+            .iter_mut()
+            .flat_map(|components| components.schemas.values_mut());
+
+        for schema in schemas {
+            let RefOr::T(Schema::Object(object)) = schema else {
+                continue;
+            };
+
+            let Some(description) = object.description.take() else {
+                continue;
+            };
+
+            object.description = match description.strip_prefix("# ") {
+                Some(heading) => {
+                    let (title, rest) = heading.split_once('\n').unwrap_or((heading, ""));
+                    object.title = Some(title.trim().to_string());
+                    Some(rest.trim().to_string()).filter(|rest| !rest.is_empty())
+                }
+                None => Some(description),
+            };
+        }
+    }
+}
+
+/// Job endpoints are mounted for the default project and for each project,
+/// the latter are described as copies with a `project` path parameter
+struct ProjectScoped;
+
+impl Modify for ProjectScoped {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let scoped = openapi
+            .paths
+            .paths
+            .iter()
+            .filter_map(|(path, item)| {
+                let rest = path.strip_prefix(JOB_PREFIX)?;
+                let mut item = item.clone();
+
+                for operation in [&mut item.get, &mut item.post].into_iter().flatten() {
+                    operation.operation_id = operation
+                        .operation_id
+                        .as_ref()
+                        .map(|id| format!("{}_in_project", id));
+                    operation
+                        .parameters
+                        .get_or_insert_with(Vec::new)
+                        .insert(0, project_parameter());
+                }
+
+                Some((format!("{}{}", PROJECT_JOB_PREFIX, rest), item))
+            })
+            .collect::<Vec<_>>();
+
+        openapi.paths.paths.extend(scoped);
+    }
+}
+
+fn project_parameter() -> Parameter {
+    ParameterBuilder::new()
+        .name("project")
+        .parameter_in(ParameterIn::Path)
+        .required(Required::True)
+        .description(Some("Name of the project"))
+        .schema(Some(String::schema()))
+        .build()
+}
+
+/// # OpenAPI Document
+/// This function returns the OpenAPI 3.1 document of the API.
+#[utoipa::path(
+    get,
+    path = "/api/openapi.json",
+    tag = "docs",
+    responses((status = 200, description = "OpenAPI document", content_type = "application/json"))
+)]
+pub async fn openapi_json() -> impl Responder {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+/// # API Docs
+/// This function returns an interactive docs page of the OpenAPI document.
+#[utoipa::path(
+    get,
+    path = "/api/docs",
+    tag = "docs",
+    responses((status = 200, description = "Docs page", content_type = "text/html"))
+)]
+pub async fn docs() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(DOCS)
+}
+
+/// # API Docs Asset
+/// This function returns a script or stylesheet of the docs page. The
+/// Swagger UI assets are embedded in the server, the page works offline.
+#[utoipa::path(
+    get,
+    path = "/api/docs/{file}",
+    tag = "docs",
+    params(("file" = String, Path, description = "`swagger-ui.css` or `swagger-ui-bundle.js`")),
+    responses(
+        (status = 200, description = "Asset of the docs page", body = String),
+        (status = 404, description = "Unknown asset", body = ErrorBody),
+    )
+)]
+pub async fn docs_asset(file: web::Path<String>) -> Result<HttpResponse, ApiError> {
+    let unknown = || ApiError::not_found(format!("Unknown asset '{}'", file));
+
+    if !DOCS_ASSETS.contains(&file.as_str()) {
+        return Err(unknown());
+    }
+
+    match utoipa_swagger_ui::serve(&file, Arc::new(Config::from("/api/openapi.json"))) {
+        Ok(Some(asset)) => Ok(HttpResponse::Ok()
+            .content_type(asset.content_type)
+            .insert_header((header::CACHE_CONTROL, "public, max-age=86400"))
+            .body(asset.bytes.into_owned())),
+        Ok(None) => Err(unknown()),
+        Err(error) => Err(ApiError::internal(error.to_string())),
+    }
+}
+
+/// Assets of the Swagger UI distribution the docs page loads
+const DOCS_ASSETS: [&str; 2] = ["swagger-ui.css", "swagger-ui-bundle.js"];
+
+/// Swagger UI, with the assets of `docs_asset`
+const DOCS: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8" />
+  <title>Schedin API</title>
+  <link rel="stylesheet" href="/api/docs/swagger-ui.css" />
+</head>
+<body>
+  <div id="docs"></div>
+  <script src="/api/docs/swagger-ui-bundle.js"></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({ url: "/api/openapi.json", dom_id: "#docs" });
+    };
+  </script>
+</body>
+</html>
+"##;
@@ -0,0 +1,169 @@
+//! Priority Class-Related API Endpoints
+
+extern crate actix_web;
+extern crate sqlx;
+extern crate std;
+extern crate validator;
+
+use super::{
+    error::{ApiError, ErrorBody},
+    openapi::Status,
+};
+use crate::{
+    db,
+    iam::schema::{AdminUser, AuthorizedUser},
+    priority::schema::{PriorityClass, PriorityClassRow},
+};
+use actix_web::{
+    web::{Data, Json},
+    HttpResponse,
+};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use validator::Validate;
+
+/// # Insert or Update Priority Class
+/// This function defines a priority class, jobs reference it by name
+/// with their `priority_class` field. Restricted to administrators.
+///
+/// ## Scheduling
+/// Runs are placed by descending `value`, then by scheduled time. Runs
+/// of a class with `preempt` set, that only lack free capacity, preempt
+/// running runs of lower priority: these are terminated gracefully and
+/// queued again.
+///
+/// ## Errors
+///
+/// - Invalid payload.
+/// - User is not an administrator.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {
+///     "name": "critical",
+///     "value": 1000,
+///     "description": "Billing and alerting",
+///     "preempt": true
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/priority/new",
+    tag = "priority",
+    request_body = PriorityClass,
+    responses(
+        (status = 200, description = "Priority class created or updated", body = Status),
+        (status = 403, description = "Administrator rights required", body = ErrorBody),
+        (status = 422, description = "Invalid priority class", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn upsert_priority_class(
+    admin: AdminUser,
+    payload: Json<PriorityClass>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(err) = payload.validate() {
+        return Err(err.into());
+    }
+
+    println!("priority class '{}' set by {}", payload.name, admin.id);
+
+    if let Err(error) = db::priority::PriorityClass::new(db.into_inner())
+        .class(payload.0)
+        .upsert()
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # List Priority Classes
+/// This function lists the priority classes, highest value first,
+/// with their number of jobs.
+///
+/// ## Errors
+///
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    get,
+    path = "/api/priority/list",
+    tag = "priority",
+    responses(
+        (status = 200, description = "Priority classes", body = Vec<PriorityClassRow>),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_priority_classes(
+    _: AuthorizedUser,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    match db::priority::PriorityClass::new(db.into_inner())
+        .list()
+        .await
+    {
+        Ok(classes) => Ok(HttpResponse::Ok().json(classes)),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// # Delete Priority Class
+/// This function deletes a priority class, which fails while any job
+/// still references it. Restricted to administrators.
+///
+/// ## Errors
+///
+/// - User is not an administrator.
+/// - Unknown priority class.
+/// - Priority class is referenced by a job.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {
+///     "name": "critical"
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/priority/delete",
+    tag = "priority",
+    request_body = PriorityClass,
+    responses(
+        (status = 200, description = "Priority class deleted", body = Status),
+        (status = 403, description = "Administrator rights required", body = ErrorBody),
+        (status = 404, description = "Unknown priority class", body = ErrorBody),
+        (status = 409, description = "Priority class is referenced by a job", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn delete_priority_class(
+    admin: AdminUser,
+    payload: Json<PriorityClass>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    println!("priority class '{}' deleted by {}", payload.name, admin.id);
+
+    if let Err(error) = db::priority::PriorityClass::new(db.into_inner())
+        .class(payload.0)
+        .delete()
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
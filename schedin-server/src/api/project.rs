@@ -0,0 +1,202 @@
+//! Project-Related API Endpoints
+
+extern crate actix_web;
+extern crate futures;
+extern crate schedin_common;
+extern crate sqlx;
+extern crate std;
+extern crate validator;
+
+use super::{
+    error::{ApiError, ErrorBody},
+    openapi::Status,
+};
+use crate::{
+    db,
+    iam::schema::AuthorizedUser,
+    project::schema::{Project, ProjectRow, ProjectScope, DEFAULT_PROJECT},
+};
+use actix_web::{
+    dev::Payload,
+    web::{Data, Json},
+    FromRequest, HttpRequest, HttpResponse,
+};
+use futures::Future;
+use schedin_common::error::CrudError;
+use sqlx::PgPool;
+use std::{collections::HashMap, pin::Pin};
+use validator::Validate;
+
+/// # Insert New Project
+/// This function creates a project, a namespace for jobs with optional quotas.
+///
+/// ## Quotas
+/// - `max_jobs`: Maximum number of jobs.
+/// - `min_interval`: Minimum interval (in seconds) of recurring jobs.
+/// - `max_concurrent_runs`: Maximum number of queued or running runs.
+/// - `max_timeout`: Maximum run timeout (in seconds).
+/// - `trash_retention_days`: Days deleted jobs stay in the trash, 30 by default.
+/// - `artifact_retention_days`: Days the artifacts of runs are kept, 14 by default.
+///
+/// ## Errors
+///
+/// - Invalid payload.
+/// - A project with the same name already exists.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {
+///     "name": "reports",
+///     "quotas": {
+///         "max_jobs": 10,
+///         "min_interval": 60,
+///         "max_concurrent_runs": 2,
+///         "max_timeout": 3600,
+///         "trash_retention_days": 7
+///     }
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/project/new",
+    tag = "project",
+    request_body = Project,
+    responses(
+        (status = 200, description = "Project created", body = Status),
+        (status = 409, description = "A project with this name exists", body = ErrorBody),
+        (status = 422, description = "Invalid project", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn insert_project(
+    account: AuthorizedUser,
+    payload: Json<Project>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(err) = payload.validate() {
+        return Err(err.into());
+    }
+
+    if let Err(error) = db::project::Project::new(db.into_inner())
+        .project(payload.0)
+        .insert(&account.id)
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # List Projects
+/// This function lists the projects of the user, with their quotas
+/// and number of jobs.
+///
+/// ## Errors
+///
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    get,
+    path = "/api/project/list",
+    tag = "project",
+    responses(
+        (status = 200, description = "Projects of the user", body = Vec<ProjectRow>),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_projects(
+    account: AuthorizedUser,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    match db::project::Project::new(db.into_inner())
+        .list(&account.id)
+        .await
+    {
+        Ok(projects) => Ok(HttpResponse::Ok().json(projects)),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// # Update Project
+/// This function replaces the quotas of a project. Lowered quotas apply
+/// to subsequent inserts, updates and dispatches, existing jobs are kept.
+///
+/// ## Errors
+///
+/// - Invalid payload.
+/// - Unknown project.
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    post,
+    path = "/api/project/update",
+    tag = "project",
+    request_body = Project,
+    responses(
+        (status = 200, description = "Project updated", body = Status),
+        (status = 404, description = "Unknown project", body = ErrorBody),
+        (status = 422, description = "Invalid project", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn update_project(
+    account: AuthorizedUser,
+    payload: Json<Project>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(err) = payload.validate() {
+        return Err(err.into());
+    }
+
+    if let Err(error) = db::project::Project::new(db.into_inner())
+        .project(payload.0)
+        .update(&account.id)
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
+
+impl FromRequest for ProjectScope {
+    type Error = ApiError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        // unscoped job endpoints operate on the default project
+        let name = req
+            .match_info()
+            .get("project")
+            .unwrap_or(DEFAULT_PROJECT)
+            .to_string();
+
+        let pool = req.app_data::<Data<PgPool>>().cloned();
+        let account = AuthorizedUser::from_request(req, payload);
+
+        Box::pin(async move {
+            let account = account.await?;
+            let pool = pool.ok_or_else(|| ApiError::internal("Database is unavailable"))?;
+
+            db::project::Project::new(pool.into_inner())
+                .scope(&account.id, &name)
+                .await
+                .map_err(|error| match error {
+                    CrudError::NotFound => {
+                        ApiError::not_found(format!("Unknown project '{}'", name))
+                    }
+                    error => error.into(),
+                })
+        })
+    }
+}
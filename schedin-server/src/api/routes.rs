@@ -0,0 +1,101 @@
+//! Routes
+//!
+//! Scopes of the API record the method and full path of every route they
+//! register, so that the OpenAPI document can be checked against them.
+
+extern crate actix_web;
+extern crate std;
+
+use actix_web::{http::Method, web, FromRequest, Handler, Responder, Scope};
+
+/// # Routes
+/// Scope of the API, along with the routes registered under it
+pub struct Routes {
+    path: String,
+    scope: Scope,
+    routes: Vec<(Method, String)>,
+}
+
+impl Routes {
+    /// New scope mounted at `path`
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            scope: web::scope(path),
+            routes: Vec::new(),
+        }
+    }
+
+    /// Route `GET` requests of `path` to `handler`
+    pub fn get<F, Args>(self, path: &str, handler: F) -> Self
+    where
+        F: Handler<Args>,
+        Args: FromRequest + 'static,
+        F::Output: Responder + 'static,
+    {
+        self.route(Method::GET, path, handler)
+    }
+
+    /// Route `POST` requests of `path` to `handler`
+    pub fn post<F, Args>(self, path: &str, handler: F) -> Self
+    where
+        F: Handler<Args>,
+        Args: FromRequest + 'static,
+        F::Output: Responder + 'static,
+    {
+        self.route(Method::POST, path, handler)
+    }
+
+    fn route<F, Args>(mut self, method: Method, path: &str, handler: F) -> Self
+    where
+        F: Handler<Args>,
+        Args: FromRequest + 'static,
+        F::Output: Responder + 'static,
+    {
+        self.scope = self
+            .scope
+            .route(path, web::method(method.clone()).to(handler));
+        self.routes.push((method, format!("{}{}", self.path, path)));
+        self
+    }
+
+    /// Mount the nested scope `routes`
+    pub fn service(mut self, routes: Routes) -> Self {
+        self.scope = self.scope.service(routes.scope);
+        self.routes.extend(
+            routes
+                .routes
+                .into_iter()
+                .map(|(method, path)| (method, format!("{}{}", self.path, path))),
+        );
+        self
+    }
+
+    /// Data of the requests of the scope, see `Scope::app_data`
+    pub fn app_data<U: 'static>(mut self, data: U) -> Self {
+        self.scope = self.scope.app_data(data);
+        self
+    }
+
+    /// Method and full path of every route, path parameters as `{name}`
+    /// without their pattern
+    #[cfg(test)]
+    pub fn routes(&self) -> impl Iterator<Item = (&Method, String)> {
+        self.routes.iter().map(|(method, path)| {
+            let path = path
+                .split('/')
+                .map(|segment| match segment.split_once(':') {
+                    Some((name, _)) if segment.starts_with('{') => format!("{}}}", name),
+                    _ => segment.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+
+            (method, path)
+        })
+    }
+
+    pub fn into_scope(self) -> Scope {
+        self.scope
+    }
+}
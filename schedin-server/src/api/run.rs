@@ -0,0 +1,166 @@
+//! Run-Related API Endpoints
+
+extern crate actix_web;
+extern crate futures;
+extern crate schedin_common;
+extern crate serde_json;
+extern crate sqlx;
+extern crate std;
+
+use super::error::{ApiError, ErrorBody};
+use crate::{
+    db::run::Runs,
+    event::hub::Hub,
+    iam::schema::AuthorizedUser,
+    run::{
+        schema::{LogEntry, Logs, RunIdPath},
+        tail::Tail,
+    },
+};
+use actix_web::{
+    http::header::{self, ContentRange, ContentRangeSpec, Header, Range},
+    web::{Bytes, Data, Path, Query},
+    HttpRequest, HttpResponse,
+};
+use futures::stream;
+use schedin_common::error::CrudError;
+use sqlx::PgPool;
+use std::convert::Infallible;
+
+/// # Run Logs
+/// This function returns the log of a run, its stdout and stderr
+/// interleaved in the order the worker read them, from byte `offset` on.
+///
+/// The log is returned as text, and single byte ranges may be requested
+/// with the `Range` header, e.g. `Range: bytes=-65536` for its last 64 KiB.
+/// With `follow=true`, the log is streamed instead as newline-delimited
+/// JSON, one line per chunk along with its offset, stream and time, until
+/// the run finished. Logs hold the first 16 MiB of output of a run, and
+/// restart when a preempted run is executed again.
+///
+/// ## Parameters
+///
+/// - `offset`: Query parameter, byte offset to start from, 0 by default.
+/// - `follow`: Query parameter, whether to stream the log as it is written.
+///
+/// ## Errors
+///
+/// - Unknown run.
+/// - Unsatisfiable range.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {"offset":0,"stream":"stdout","time":"2023-10-17T06:45:00.123Z","data":"starting\n"}
+/// {"offset":9,"stream":"stderr","time":"2023-10-17T06:45:01.456Z","data":"retrying\n"}
+/// ```
+#[utoipa::path(
+    get,
+    path = "/api/run/{id}/logs",
+    tag = "run",
+    params(RunIdPath, Logs, ("Range" = Option<String>, Header, description = "Single byte range, e.g. `bytes=-65536`")),
+    responses(
+        (status = 200, description = "Log of the run as text, or as newline-delimited JSON entries with `follow`", content(
+            (String = "text/plain"),
+            (LogEntry = "application/x-ndjson"),
+        )),
+        (status = 206, description = "Byte range of the log", body = String, content_type = "text/plain"),
+        (status = 404, description = "Unknown run", body = ErrorBody),
+        (status = 416, description = "Unsatisfiable range"),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn run_logs(
+    account: AuthorizedUser,
+    req: HttpRequest,
+    path: Path<RunIdPath>,
+    query: Query<Logs>,
+    hub: Data<Hub>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let pool = db.into_inner();
+    let runs = Runs::new(pool.clone());
+
+    match runs.status(&account.id, &path.id).await {
+        Ok(_) => {}
+        Err(CrudError::NotFound) => {
+            return Err(ApiError::not_found(format!("Unknown run '{}'", path.id)))
+        }
+        Err(error) => return Err(error.into()),
+    }
+
+    if query.follow {
+        let tail = Tail::new(&hub, pool, &account.id, path.id, query.offset);
+
+        let chunks = stream::unfold(tail, |mut tail| async move {
+            let line = match tail.next().await {
+                Ok(Some(chunk)) => serde_json::to_string(&chunk.entry()).unwrap_or_default() + "\n",
+                // the client resumes from the offset it read up to
+                Ok(None) | Err(_) => return None,
+            };
+
+            Some((Ok::<_, Infallible>(Bytes::from(line)), tail))
+        });
+
+        return Ok(HttpResponse::Ok()
+            .insert_header((header::CONTENT_TYPE, "application/x-ndjson"))
+            .insert_header((header::CACHE_CONTROL, "no-cache"))
+            .streaming(chunks));
+    }
+
+    let size = match runs.log_size(&path.id).await {
+        Ok(size) => size,
+        Err(error) => return Err(error.into()),
+    };
+
+    // multiple ranges are not supported, the whole log is returned instead
+    let range = match Range::parse(&req) {
+        Ok(Range::Bytes(specs)) if specs.len() == 1 => {
+            match specs[0].to_satisfiable_range(size as u64) {
+                Some((first, last)) => Some((first as i64, last as i64 + 1)),
+                None => {
+                    return Ok(HttpResponse::RangeNotSatisfiable()
+                        .insert_header(ContentRange(ContentRangeSpec::Bytes {
+                            range: None,
+                            instance_length: Some(size as u64),
+                        }))
+                        .finish())
+                }
+            }
+        }
+        _ => None,
+    };
+
+    let (start, end) = range.unwrap_or((query.offset.clamp(0, size), size));
+
+    let chunks = match runs.log(&path.id, start, Some(end), i64::MAX).await {
+        Ok(chunks) => chunks,
+        Err(error) => return Err(error.into()),
+    };
+
+    let mut body = Vec::with_capacity((end - start) as usize);
+    for chunk in chunks {
+        let chunk = chunk.skip_to(start);
+        let len = ((end - chunk.position) as usize).min(chunk.data.len());
+        body.extend_from_slice(&chunk.data[..len]);
+    }
+
+    let mut response = match range {
+        Some(_) => {
+            let mut response = HttpResponse::PartialContent();
+            response.insert_header(ContentRange(ContentRangeSpec::Bytes {
+                range: Some((start as u64, end as u64 - 1)),
+                instance_length: Some(size as u64),
+            }));
+            response
+        }
+        None => HttpResponse::Ok(),
+    };
+
+    Ok(response
+        .content_type("text/plain; charset=utf-8")
+        .insert_header((header::ACCEPT_RANGES, "bytes"))
+        .body(body))
+}
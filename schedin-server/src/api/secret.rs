@@ -0,0 +1,165 @@
+//! Secret-Related API Endpoints
+
+extern crate actix_web;
+extern crate schedin_common;
+extern crate sqlx;
+extern crate std;
+extern crate validator;
+
+use super::{
+    error::{ApiError, ErrorBody},
+    openapi::Status,
+};
+use crate::{
+    db,
+    iam::schema::AuthorizedUser,
+    secret::schema::{Secret, SecretRow},
+};
+use actix_web::{
+    web::{Data, Json},
+    HttpResponse,
+};
+use schedin_common::secret::Cipher;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use validator::Validate;
+
+/// # Insert or Update Secret
+/// This function encrypts the provided secret value and stores it.
+/// Secrets are write-only, their values are never returned by the API.
+///
+/// ## Parameters
+///
+/// - `payload`: A JSON payload containing the secret name and value.
+///
+/// ## Errors
+///
+/// This function may return an HTTP response with an error status code and a corresponding
+/// error message if there are issues with storing the secret.
+/// - Invalid payload.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {
+///     "name": "api-token",
+///     "value": "s3cr3t"
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/secret/new",
+    tag = "secret",
+    request_body = Secret,
+    responses(
+        (status = 200, description = "Secret stored", body = Status),
+        (status = 422, description = "Invalid secret", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn upsert_secret(
+    account: AuthorizedUser,
+    payload: Json<Secret>,
+    cipher: Data<Cipher>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(err) = payload.validate() {
+        return Err(err.into());
+    }
+
+    if payload.value.is_none() {
+        return Err(ApiError::invalid("Secret must have a 'value'"));
+    }
+
+    if let Err(error) = db::secret::Secret::new(db.into_inner())
+        .secret(payload.0)
+        .upsert(&account.id, &cipher)
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # List Secrets
+/// This function lists the names of the secrets owned by the user,
+/// without their values.
+///
+/// ## Errors
+///
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    get,
+    path = "/api/secret/list",
+    tag = "secret",
+    responses(
+        (status = 200, description = "Secrets of the user, without their values", body = Vec<SecretRow>),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_secrets(
+    account: AuthorizedUser,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    match db::secret::Secret::new(db.into_inner())
+        .list(&account.id)
+        .await
+    {
+        Ok(secrets) => Ok(HttpResponse::Ok().json(secrets)),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// # Delete Secret
+/// This function deletes a secret, which fails while any job still references it.
+///
+/// ## Errors
+///
+/// - Unknown secret.
+/// - Secret is referenced by a job.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {
+///     "name": "api-token"
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/secret/delete",
+    tag = "secret",
+    request_body = Secret,
+    responses(
+        (status = 200, description = "Secret deleted", body = Status),
+        (status = 404, description = "Unknown secret", body = ErrorBody),
+        (status = 409, description = "Secret is referenced by a job", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn delete_secret(
+    account: AuthorizedUser,
+    payload: Json<Secret>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(error) = db::secret::Secret::new(db.into_inner())
+        .secret(payload.0)
+        .delete(&account.id)
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
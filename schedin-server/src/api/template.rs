@@ -0,0 +1,321 @@
+//! Template-Related API Endpoints
+
+extern crate actix_web;
+extern crate schedin_common;
+extern crate serde_json;
+extern crate sqlx;
+extern crate std;
+extern crate validator;
+
+use super::{
+    error::{ApiError, ErrorBody},
+    openapi::{RolledOut, Status},
+};
+use crate::{
+    db,
+    iam::schema::AuthorizedUser,
+    job::schema::{Job, JobType},
+    project::schema::DEFAULT_PROJECT,
+    template::schema::{Instance, Rollout, Template, TemplateRow},
+};
+use actix_web::{
+    web::{Data, Json, Query},
+    HttpResponse,
+};
+use schedin_common::error::CrudError;
+use serde_json::Value;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use validator::Validate;
+
+/// # Insert New Template
+/// This function stores a new job template.
+///
+/// ## Parameters
+///
+/// - `payload`: A JSON payload containing the template definition. String fields
+///   of `job` may reference parameters with `{{ name }}` placeholders. A field
+///   made of a single placeholder takes the typed value, e.g. `"timeout": "{{ n }}"`.
+///
+/// ## Parameter Types
+/// - `string`: Any string.
+/// - `int`: Integer, optionally bounded by `min` and `max`.
+/// - `enum`: One of the listed `values`.
+/// - `bool`: `true` or `false`.
+///
+/// ## Errors
+///
+/// - Invalid payload.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {
+///     "name": "report",
+///     "parameters": [
+///         { "name": "region", "type": "enum", "values": ["eu", "us"] },
+///         { "name": "every", "type": "int", "min": 1, "default": 10 }
+///     ],
+///     "job": {
+///         "schedule": "@every {{ every }} min",
+///         "task": { "name": "report-{{ region }}" }
+///     }
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/template/new",
+    tag = "template",
+    request_body = Template,
+    responses(
+        (status = 200, description = "Template created", body = Status),
+        (status = 409, description = "A template with this name exists", body = ErrorBody),
+        (status = 422, description = "Invalid template", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn insert_template(
+    account: AuthorizedUser,
+    payload: Json<Template>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(err) = payload.validate() {
+        return Err(err.into());
+    }
+
+    if let Err(error) = db::template::Template::new(db.into_inner())
+        .template(payload.0)
+        .insert(&account.id)
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # List Templates
+/// This function lists the templates of the user, along with their number of instances.
+///
+/// ## Errors
+///
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    get,
+    path = "/api/template/list",
+    tag = "template",
+    responses(
+        (status = 200, description = "Templates of the user", body = Vec<TemplateRow>),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_templates(
+    account: AuthorizedUser,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    match db::template::Template::new(db.into_inner())
+        .list(&account.id)
+        .await
+    {
+        Ok(templates) => Ok(HttpResponse::Ok().json(templates)),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// # Update Template
+/// This function updates an existing template, identified by name.
+///
+/// With `?rollout=true`, every instance is re-rendered from the updated
+/// template with its original values, all within one transaction. The
+/// update is rejected if any instance no longer renders to a valid job.
+///
+/// ## Errors
+///
+/// - Invalid payload.
+/// - Unknown template.
+/// - An instance fails to render.
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    post,
+    path = "/api/template/update",
+    tag = "template",
+    params(Rollout),
+    request_body = Template,
+    responses(
+        (status = 200, description = "Template updated and rolled out", body = RolledOut),
+        (status = 404, description = "Unknown template", body = ErrorBody),
+        (status = 422, description = "Invalid template, or instances no longer valid", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn update_template(
+    account: AuthorizedUser,
+    payload: Json<Template>,
+    options: Query<Rollout>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(err) = payload.validate() {
+        return Err(err.into());
+    }
+
+    let db = db::template::Template::new(db.into_inner());
+
+    let record = match db.fetch(&account.id, &payload.name).await {
+        Ok(record) => record,
+        Err(error) => return Err(error.into()),
+    };
+
+    let mut rollout = Vec::new();
+
+    if options.rollout {
+        let instances = match db.instances(&record.template_id).await {
+            Ok(instances) => instances,
+            Err(error) => return Err(error.into()),
+        };
+
+        for instance in instances {
+            let values = instance.template_values.map(|v| v.0).unwrap_or_default();
+
+            match render(&payload, &instance.job_name, &values) {
+                Ok(job) => rollout.push((instance.job_id, job)),
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    let instances = rollout.len();
+
+    if let Err(error) = db
+        .template(payload.0)
+        .update(&account.id, &record.template_id, rollout)
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(2);
+    map.insert("status", Value::from("ok"));
+    map.insert("instances", Value::from(instances));
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # Instantiate Template
+/// This function creates a job from a template by supplying parameter values.
+/// Missing values fall back to the parameter defaults. The job is created
+/// in `project`, or in the `default` project if omitted.
+///
+/// ## Errors
+///
+/// - Invalid payload, or values not matching the template parameters.
+/// - Unknown template or project.
+/// - The rendered job exceeds the project quotas.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {
+///     "template": "report",
+///     "name": "report-eu",
+///     "values": { "region": "eu" },
+///     "project": "reports"
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/template/instantiate",
+    tag = "template",
+    request_body = Instance,
+    responses(
+        (status = 200, description = "Job created from the template", body = Status),
+        (status = 403, description = "Project quota exceeded", body = ErrorBody),
+        (status = 404, description = "Unknown template or project", body = ErrorBody),
+        (status = 409, description = "The project already has a job with that name", body = ErrorBody),
+        (status = 422, description = "Invalid values", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn instantiate_template(
+    account: AuthorizedUser,
+    payload: Json<Instance>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(err) = payload.validate() {
+        return Err(err.into());
+    }
+
+    let project_name = payload.project.as_deref().unwrap_or(DEFAULT_PROJECT);
+
+    let project = match db::project::Project::new(db.clone().into_inner())
+        .scope(&account.id, project_name)
+        .await
+    {
+        Ok(project) => project,
+        Err(CrudError::NotFound) => {
+            return Err(ApiError::not_found(format!(
+                "Unknown project '{}'",
+                project_name
+            )))
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    let db = db::template::Template::new(db.into_inner());
+
+    let record = match db.fetch(&account.id, &payload.template).await {
+        Ok(record) => record,
+        Err(error) => return Err(error.into()),
+    };
+
+    let job = match render(&record.template, &payload.name, &payload.values) {
+        Ok(job) => job,
+        Err(error) => return Err(error),
+    };
+
+    if let Err(error) = db
+        .instantiate(
+            &account.id,
+            &project.id,
+            &record.template_id,
+            job,
+            &payload.values,
+        )
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// Render and validate a job from `template`
+fn render(
+    template: &Template,
+    name: &str,
+    values: &HashMap<String, Value>,
+) -> Result<Job, ApiError> {
+    let job = template
+        .render(name, values)
+        .map_err(|err| ApiError::invalid("Invalid template values").details(err))?;
+
+    job.validate()?;
+
+    if let JobType::Invalid = job.kind() {
+        return Err(ApiError::invalid(
+            "Job must be defined: 'bin', 'task', 'code', 'http', 'sql', 'wasm', or 'pipeline'",
+        ));
+    }
+
+    Ok(job)
+}
@@ -2,28 +2,34 @@
 
 extern crate actix_web;
 extern crate futures;
-extern crate jsonwebtoken;
+extern crate schedin_common;
 extern crate sqlx;
 extern crate std;
+extern crate validator;
 
+use super::{
+    error::{ApiError, ErrorBody},
+    openapi::{NewApiKey, Status},
+};
 use crate::{
     db,
     iam::{
-        schema::{AuthorizedUser, SigninResponse, User},
+        key,
+        schema::{AdminUser, ApiKey, ApiKeyRow, AuthorizedUser, SigninResponse, User},
         token::Claims,
     },
 };
 use actix_web::{
     dev::Payload,
-    error::ErrorBadRequest,
     http::header,
     web::{Data, Json},
-    FromRequest, HttpRequest, HttpResponse, Responder,
+    FromRequest, HttpRequest, HttpResponse,
 };
 use futures::Future;
-use jsonwebtoken::{decode, DecodingKey, Validation};
+use schedin_common::error::CrudError;
 use sqlx::PgPool;
-use std::{collections::HashMap, env, pin::Pin};
+use std::{collections::HashMap, pin::Pin};
+use validator::Validate;
 
 /// # Sign-Up
 /// ## Insert New User
@@ -38,10 +44,29 @@ use std::{collections::HashMap, env, pin::Pin};
 ///
 /// This function may return an HTTP response with an error status code and a corresponding
 /// error message if there are issues with the job insertion process.
-/// - Invalid payload.
+/// - Missing username, email or password.
+/// - Username or email already taken.
 /// - Database is down.
 /// - Internal server errors, etc...
-pub async fn signup(payload: Json<User>, db: Data<PgPool>) -> impl Responder {
+#[utoipa::path(
+    post,
+    path = "/api/user/signup",
+    tag = "user",
+    request_body = User,
+    responses(
+        (status = 200, description = "User signed up", body = Status),
+        (status = 409, description = "Username or email already taken", body = ErrorBody),
+        (status = 422, description = "Missing username, email or password", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+)]
+pub async fn signup(payload: Json<User>, db: Data<PgPool>) -> Result<HttpResponse, ApiError> {
+    if payload.username.is_none() || payload.email.is_none() || payload.password.is_none() {
+        return Err(ApiError::invalid(
+            "'username', 'email' and 'password' are required",
+        ));
+    }
+
     // insert new user
     let user = db::user::User::new(db.into_inner())
         .user(payload.0)
@@ -49,12 +74,12 @@ pub async fn signup(payload: Json<User>, db: Data<PgPool>) -> impl Responder {
         .await;
 
     if let Err(error) = user {
-        return HttpResponse::InternalServerError().json(error.map());
+        return Err(error.into());
     }
 
     let mut map = HashMap::with_capacity(1);
     map.insert("status", "ok");
-    HttpResponse::Ok().json(map)
+    Ok(HttpResponse::Ok().json(map))
 }
 
 /// # Sign-In
@@ -63,16 +88,33 @@ pub async fn signup(payload: Json<User>, db: Data<PgPool>) -> impl Responder {
 /// ## Parameters
 ///
 /// - `payload`: A JSON payload containing the user data on the basis of which the
-/// credentials will be validated.
+///   credentials will be validated.
 ///
 /// ## Errors
 ///
 /// This function may return an HTTP response with an error status code and a corresponding
 /// error message if there are issues with the job insertion process.
-/// - Invalid payload.
+/// - Missing username or password.
+/// - Wrong username or password.
 /// - Database is down.
 /// - Internal server errors, etc...
-pub async fn signin(payload: Json<User>, db: Data<PgPool>) -> impl Responder {
+#[utoipa::path(
+    post,
+    path = "/api/user/signin",
+    tag = "user",
+    request_body = User,
+    responses(
+        (status = 200, description = "Signed in, with the bearer token", body = SigninResponse),
+        (status = 401, description = "Wrong username or password", body = ErrorBody),
+        (status = 422, description = "Missing username or password", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+)]
+pub async fn signin(payload: Json<User>, db: Data<PgPool>) -> Result<HttpResponse, ApiError> {
+    if payload.username.is_none() || payload.password.is_none() {
+        return Err(ApiError::invalid("'username' and 'password' are required"));
+    }
+
     // try to retrieve user credentials from the database
     let user = db::user::User::new(db.into_inner())
         .user(payload.0)
@@ -90,51 +132,225 @@ pub async fn signin(payload: Json<User>, db: Data<PgPool>) -> impl Responder {
                 .id(id)
                 .token(token);
 
-            HttpResponse::Ok().json(response)
+            Ok(HttpResponse::Ok().json(response))
         }
-        Err(error) => {
-            return HttpResponse::InternalServerError().json(error.map());
+        Err(CrudError::NotFound) => Err(ApiError::unauthorized("Wrong username or password")),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// # Create API Key
+/// This function generates an API key of the user, sent in place of the
+/// token of sign-in as `Authorization: Bearer <key>`. The key is only
+/// returned once, it is stored hashed.
+///
+/// ## Errors
+///
+/// - Invalid key name.
+/// - Key name already taken.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {
+///     "name": "ci"
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/user/key/new",
+    tag = "user",
+    request_body = ApiKey,
+    responses(
+        (status = 200, description = "New API key, only returned once", body = NewApiKey),
+        (status = 409, description = "Key name already taken", body = ErrorBody),
+        (status = 422, description = "Invalid key name", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn create_key(
+    account: AuthorizedUser,
+    payload: Json<ApiKey>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(err) = payload.validate() {
+        return Err(err.into());
+    }
+
+    let key = match db::key::ApiKeys::new(db.into_inner())
+        .create(&account.id, &payload.name)
+        .await
+    {
+        Ok(key) => key,
+        Err(error) => return Err(error.into()),
+    };
+
+    let mut map = HashMap::with_capacity(2);
+    map.insert("status", "ok");
+    map.insert("key", key.expose());
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # List API Keys
+/// This function lists the names of the API keys of the user, without
+/// the keys.
+///
+/// ## Errors
+///
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    get,
+    path = "/api/user/key/list",
+    tag = "user",
+    responses(
+        (status = 200, description = "API keys of the user, without the keys", body = Vec<ApiKeyRow>),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_keys(
+    account: AuthorizedUser,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    match db::key::ApiKeys::new(db.into_inner())
+        .list(&account.id)
+        .await
+    {
+        Ok(keys) => Ok(HttpResponse::Ok().json(keys)),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// # Revoke API Key
+/// This function deletes an API key of the user, requests sent with it
+/// are rejected from then on.
+///
+/// ## Errors
+///
+/// - Unknown key.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {
+///     "name": "ci"
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/user/key/delete",
+    tag = "user",
+    request_body = ApiKey,
+    responses(
+        (status = 200, description = "API key revoked", body = Status),
+        (status = 404, description = "Unknown key", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn delete_key(
+    account: AuthorizedUser,
+    payload: Json<ApiKey>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    match db::key::ApiKeys::new(db.into_inner())
+        .delete(&account.id, &payload.name)
+        .await
+    {
+        Ok(()) => {}
+        Err(CrudError::NotFound) => {
+            return Err(ApiError::not_found(format!(
+                "Unknown key '{}'",
+                payload.name
+            )))
         }
+        Err(error) => return Err(error.into()),
     }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
 }
 
 impl FromRequest for AuthorizedUser {
-    type Error = actix_web::error::Error;
+    type Error = ApiError;
     type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
 
     fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
         // try to retrieve `authorization` token from headers
-        let token = match req.headers().get(header::AUTHORIZATION) {
-            Some(header) => header.to_str(),
-            None => {
-                let error = actix_web::error::ErrorBadRequest("No Bearer Token found!");
-                return Box::pin(async move { Err(error) });
-            }
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|header| header.to_str().ok())
+            .and_then(|header| header.strip_prefix("Bearer "));
+
+        let Some(token) = token else {
+            let error = ApiError::unauthorized("No Bearer Token found!");
+            return Box::pin(async move { Err(error) });
+        };
+
+        // API keys are looked up, JWT tokens are decoded
+        if key::is_key(token) {
+            let key = token.to_string();
+            let pool = req.app_data::<Data<PgPool>>().cloned();
+
+            return Box::pin(async move {
+                let pool = pool.ok_or_else(|| ApiError::internal("Database is unavailable"))?;
+
+                match db::key::ApiKeys::new(pool.into_inner()).owner(&key).await {
+                    Ok(owner) => Ok(Self {
+                        id: owner.user_id.to_string(),
+                    }),
+                    Err(CrudError::NotFound) => {
+                        Err(ApiError::unauthorized("Unknown or revoked API key!"))
+                    }
+                    Err(error) => Err(error.into()),
+                }
+            });
         }
-        .unwrap();
 
         // try to decode the claims and headers
-        let decoded_token = decode::<Claims>(
-            &token[7..],
-            &DecodingKey::from_secret(env::var("JWT_SECRET").unwrap().as_ref()),
-            &Validation::default(),
-        );
+        let decoded_token = Claims::decode(token);
 
         match decoded_token {
-            Ok(token) => {
-                let authorized_user = Self {
-                    id: token.claims.sub,
-                };
+            Ok(claims) => {
+                let authorized_user = Self { id: claims.sub };
 
                 Box::pin(async move { Ok(authorized_user) }) as _
             }
-            Err(_) => {
-                // todo!(): Robust Error Handling
+            Err(_) => Box::pin(async move {
+                Err(ApiError::unauthorized(
+                    "Token Expired! Please Sign-In Again.",
+                ))
+            }),
+        }
+    }
+}
 
-                Box::pin(
-                    async move { Err(ErrorBadRequest("Token Expired! Please Sign-In Again.")) },
-                )
+impl FromRequest for AdminUser {
+    type Error = ApiError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let pool = req.app_data::<Data<PgPool>>().cloned();
+        let account = AuthorizedUser::from_request(req, payload);
+
+        Box::pin(async move {
+            let account = account.await?;
+            let pool = pool.ok_or_else(|| ApiError::internal("Database is unavailable"))?;
+
+            match db::user::User::new(pool.into_inner())
+                .is_admin(&account.id)
+                .await
+            {
+                Ok(true) => Ok(Self { id: account.id }),
+                Ok(false) => Err(ApiError::forbidden("Administrator rights required")),
+                Err(error) => Err(error.into()),
             }
-        }
+        })
     }
 }
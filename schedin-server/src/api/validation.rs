@@ -1,10 +1,28 @@
 //! Custom Validations for API Fields
 
 extern crate base64;
+extern crate glob;
+extern crate schedin_common;
+extern crate serde_json_path;
+extern crate std;
 extern crate validator;
 
-use crate::job::schedule::Schedule;
+use crate::{
+    job::{
+        schedule::Schedule,
+        schema::{
+            Env, Job, JobType, Matrix, Pipeline, Quantity, Resources, Term, Wasm, MAX_COMBINATIONS,
+        },
+    },
+    module,
+    notification::schema::{Channel, ChannelKind, Event, Rule},
+    template::schema::{ParameterKind, Template},
+};
 use base64::Engine;
+use glob::Pattern;
+use schedin_common::message;
+use serde_json_path::JsonPath;
+use std::collections::{HashMap, HashSet};
 use validator::ValidationError;
 
 /// # Validate Schedule
@@ -41,3 +59,523 @@ pub fn validate_source_format(input: &str) -> Result<(), ValidationError> {
 
     Ok(())
 }
+
+/// # Validate Secret Name
+/// Ensure the secret name is 1-255 characters of `[A-Za-z0-9_.-]`
+pub fn validate_secret_name(input: &str) -> Result<(), ValidationError> {
+    let valid = !input.is_empty()
+        && input.len() <= 255
+        && input
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'));
+
+    if !valid {
+        return Err(ValidationError::new("Invalid secret name"));
+    }
+
+    Ok(())
+}
+
+/// # Validate Key Name
+/// Ensure the API key name is 1-255 characters of `[A-Za-z0-9_.-]`, as
+/// secret names are
+pub fn validate_key_name(input: &str) -> Result<(), ValidationError> {
+    validate_secret_name(input).map_err(|_| ValidationError::new("Invalid key name"))
+}
+
+/// # Validate Project Name
+/// Ensure the project name is 1-63 characters of `[a-z0-9-]` starting
+/// with a letter or digit, so that it can be used as a path segment
+pub fn validate_project_name(input: &str) -> Result<(), ValidationError> {
+    let valid = input.len() <= 63
+        && input
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && input
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+
+    if !valid {
+        return Err(ValidationError::new("Invalid project name"));
+    }
+
+    Ok(())
+}
+
+/// # Validate Priority Class Name
+/// Ensure the priority class name follows the project name rules
+pub fn validate_priority_class_name(input: &str) -> Result<(), ValidationError> {
+    if validate_project_name(input).is_err() {
+        return Err(ValidationError::new("Invalid priority class name"));
+    }
+
+    Ok(())
+}
+
+/// # Validate Environment
+/// Ensure every variable name is a valid POSIX identifier and
+/// every secret reference is a valid secret name
+pub fn validate_env(input: &HashMap<String, Env>) -> Result<(), ValidationError> {
+    for (name, value) in input {
+        if !is_identifier(name) {
+            return Err(ValidationError::new("Invalid environment variable name"));
+        }
+
+        if let Env::Secret { secret } = value {
+            validate_secret_name(secret)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// # Validate HTTP Method
+pub fn validate_http_method(input: &str) -> Result<(), ValidationError> {
+    if !matches!(
+        input,
+        "GET" | "HEAD" | "POST" | "PUT" | "PATCH" | "DELETE" | "OPTIONS"
+    ) {
+        return Err(ValidationError::new("Invalid HTTP method"));
+    }
+
+    Ok(())
+}
+
+/// # Validate HTTP URL
+/// Ensure the URL is absolute, with an `http` or `https` scheme
+pub fn validate_http_url(input: &str) -> Result<(), ValidationError> {
+    let valid = (input.starts_with("http://") || input.starts_with("https://"))
+        && validator::validate_url(input);
+
+    if !valid {
+        return Err(ValidationError::new("Invalid HTTP URL"));
+    }
+
+    Ok(())
+}
+
+/// # Validate HTTP Headers
+/// Ensure every header name is an HTTP token and
+/// every secret reference is a valid secret name
+pub fn validate_http_headers(input: &HashMap<String, Env>) -> Result<(), ValidationError> {
+    for (name, value) in input {
+        let valid = !name.is_empty()
+            && name.len() <= 255
+            && name.chars().all(|c| {
+                c.is_ascii_alphanumeric()
+                    || matches!(
+                        c,
+                        '!' | '#'
+                            | '$'
+                            | '%'
+                            | '&'
+                            | '\''
+                            | '*'
+                            | '+'
+                            | '-'
+                            | '.'
+                            | '^'
+                            | '_'
+                            | '`'
+                            | '|'
+                            | '~'
+                    )
+            });
+
+        if !valid {
+            return Err(ValidationError::new("Invalid HTTP header name"));
+        }
+
+        if let Env::Secret { secret } = value {
+            validate_secret_name(secret)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// # Validate Status Codes
+/// Ensure the list is non-empty and every code is within 100-599
+pub fn validate_status_codes(input: &[i32]) -> Result<(), ValidationError> {
+    if input.is_empty() || input.iter().any(|code| !(100..=599).contains(code)) {
+        return Err(ValidationError::new("Invalid HTTP status code"));
+    }
+
+    Ok(())
+}
+
+/// # Validate Statements
+/// Ensure there is at least one statement and none is blank
+pub fn validate_statements(input: &[String]) -> Result<(), ValidationError> {
+    if input.is_empty() || input.iter().any(|statement| statement.trim().is_empty()) {
+        return Err(ValidationError::new("Invalid SQL statements"));
+    }
+
+    Ok(())
+}
+
+/// # Validate JSONPath
+/// Ensure the query parses as an RFC 9535 JSONPath query
+pub fn validate_json_path(input: &str) -> Result<(), ValidationError> {
+    if JsonPath::parse(input).is_err() {
+        return Err(ValidationError::new("Invalid JSONPath query"));
+    }
+
+    Ok(())
+}
+
+/// # Validate WebAssembly Module
+/// Ensure the module is base64-encoded, starts with the WebAssembly
+/// magic number and is at most 16 MiB
+pub fn validate_wasm_module(input: &str) -> Result<(), ValidationError> {
+    match base64::engine::general_purpose::STANDARD.decode(input) {
+        Ok(module) if module::is_wasm(&module) => Ok(()),
+        _ => Err(ValidationError::new("Invalid WebAssembly module")),
+    }
+}
+
+/// # Validate Module Digest
+/// Ensure the digest is 64 lowercase hexadecimal characters
+pub fn validate_wasm_digest(input: &str) -> Result<(), ValidationError> {
+    let valid = input.len() == 64
+        && input
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c));
+
+    if !valid {
+        return Err(ValidationError::new("Invalid module digest"));
+    }
+
+    Ok(())
+}
+
+/// # Validate WebAssembly Source
+/// Ensure the module is either inlined or referenced by digest, not both
+pub fn validate_wasm_source(wasm: &Wasm) -> Result<(), ValidationError> {
+    if wasm.module.is_some() == wasm.digest.is_some() {
+        return Err(ValidationError::new(
+            "Wasm job must set either 'module' or 'digest'",
+        ));
+    }
+
+    Ok(())
+}
+
+/// # Validate Pipeline
+/// Ensure the pipeline has steps, uniquely named with 1-63 characters of
+/// `[A-Za-z0-9_.-]`, each defining exactly one of `task`, `code`, `bin`
+/// or `http`
+pub fn validate_pipeline(pipeline: &Pipeline) -> Result<(), ValidationError> {
+    if pipeline.steps.is_empty() {
+        return Err(ValidationError::new("Pipeline must have at least one step"));
+    }
+
+    let mut names = HashSet::with_capacity(pipeline.steps.len());
+
+    for step in &pipeline.steps {
+        let valid = !step.name.is_empty()
+            && step.name.len() <= 63
+            && step
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'));
+
+        if !valid || !names.insert(step.name.as_str()) {
+            return Err(ValidationError::new(
+                "Step names must be unique and 1-63 characters of [A-Za-z0-9_.-]",
+            ));
+        }
+
+        let items = [
+            step.task.is_some(),
+            step.code.is_some(),
+            step.bin.is_some(),
+            step.http.is_some(),
+        ];
+
+        if items.into_iter().filter(|&item| item).count() != 1 {
+            return Err(ValidationError::new(
+                "Steps must define exactly one of 'task', 'code', 'bin' or 'http'",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// # Validate Matrix
+/// Ensure the matrix has axes named after env vars, unique regardless of
+/// case, each with at least one value, and at most `MAX_COMBINATIONS`
+/// combinations
+pub fn validate_matrix(matrix: &Matrix) -> Result<(), ValidationError> {
+    if matrix.axes.is_empty() {
+        return Err(ValidationError::new("Matrix must have at least one axis"));
+    }
+
+    let mut names = HashSet::with_capacity(matrix.axes.len());
+
+    for (name, axis) in &matrix.axes {
+        if !is_identifier(name) || name.len() > 63 || !names.insert(name.to_ascii_uppercase()) {
+            return Err(ValidationError::new(
+                "Axis names must be unique and 1-63 characters of [A-Za-z0-9_], not starting with a digit",
+            ));
+        }
+
+        if axis.len().unwrap_or_default() == 0 {
+            return Err(ValidationError::new(
+                "Axes must be a non-empty list of strings, numbers or booleans, or a range 'start..end'",
+            ));
+        }
+    }
+
+    if matrix.combinations().unwrap_or(usize::MAX) > MAX_COMBINATIONS {
+        return Err(ValidationError::new(
+            "Matrix must expand into at most 256 combinations",
+        ));
+    }
+
+    Ok(())
+}
+
+/// # Validate Matrix Job
+/// Ensure jobs with a matrix are given an environment, which `http` and
+/// `sql` jobs are not
+pub fn validate_matrix_job(job: &Job) -> Result<(), ValidationError> {
+    if job.matrix.is_some() && matches!(job.kind(), JobType::Http | JobType::Sql) {
+        return Err(ValidationError::new(
+            "Matrix values are env vars, which 'http' and 'sql' jobs do not have",
+        ));
+    }
+
+    Ok(())
+}
+
+/// # Validate Artifacts Job
+/// Ensure artifacts are only declared by jobs running in a work directory
+pub fn validate_artifacts_job(job: &Job) -> Result<(), ValidationError> {
+    if job.artifacts.is_some()
+        && !matches!(
+            job.kind(),
+            JobType::Code | JobType::Bin | JobType::Wasm | JobType::Pipeline
+        )
+    {
+        return Err(ValidationError::new(
+            "Artifacts are collected from the work directory of 'code', 'bin', 'wasm' and 'pipeline' jobs",
+        ));
+    }
+
+    Ok(())
+}
+
+/// # Validate Artifacts
+/// Ensure there are 1-16 glob patterns of at most 255 characters,
+/// relative to the work directory and never leaving it
+pub fn validate_artifacts(input: &[String]) -> Result<(), ValidationError> {
+    if input.is_empty() || input.len() > 16 {
+        return Err(ValidationError::new("Invalid artifacts"));
+    }
+
+    for pattern in input {
+        let valid = !pattern.is_empty()
+            && pattern.len() <= 255
+            && !pattern.starts_with('/')
+            && !pattern.split('/').any(|component| component == "..")
+            && Pattern::new(pattern).is_ok();
+
+        if !valid {
+            return Err(ValidationError::new("Invalid artifact pattern"));
+        }
+    }
+
+    Ok(())
+}
+
+/// # Validate Labels
+/// Ensure label keys are 1-63 characters of `[A-Za-z0-9_./-]`
+/// and label values are at most 63 characters
+pub fn validate_labels(input: &HashMap<String, String>) -> Result<(), ValidationError> {
+    for (key, value) in input {
+        let valid = !key.is_empty()
+            && key.len() <= 63
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '/' | '-'))
+            && value.len() <= 63;
+
+        if !valid {
+            return Err(ValidationError::new("Invalid label"));
+        }
+    }
+
+    Ok(())
+}
+
+/// # Validate Affinity
+/// Ensure every term matches on a job name and/or valid labels
+pub fn validate_affinity(input: &Vec<Term>) -> Result<(), ValidationError> {
+    for term in input {
+        if term.job.is_none() && term.labels.is_empty() {
+            return Err(ValidationError::new("Empty affinity term"));
+        }
+
+        validate_labels(&term.labels)?;
+    }
+
+    Ok(())
+}
+
+/// # Validate Resources
+/// Ensure every quantity is positive, with at least one millicore of CPU,
+/// and every limit is at least the corresponding request
+pub fn validate_resources(input: &Resources) -> Result<(), ValidationError> {
+    let positive = |quantity: &Quantity| {
+        quantity.cpu_millis().is_none_or(|cpu| cpu >= 1)
+            && quantity.memory_mb.is_none_or(|memory| memory >= 1)
+            && quantity.disk_mb.is_none_or(|disk| disk >= 1)
+    };
+
+    if !positive(&input.requests) || !input.limits.as_ref().is_none_or(positive) {
+        return Err(ValidationError::new("Invalid resource quantity"));
+    }
+
+    if let Some(limits) = &input.limits {
+        let within = |request: Option<i32>, limit: Option<i32>| match (request, limit) {
+            (Some(request), Some(limit)) => request <= limit,
+            _ => true,
+        };
+
+        if !within(input.requests.cpu_millis(), limits.cpu_millis())
+            || !within(input.requests.memory_mb, limits.memory_mb)
+            || !within(input.requests.disk_mb, limits.disk_mb)
+        {
+            return Err(ValidationError::new("Resource request exceeds its limit"));
+        }
+    }
+
+    Ok(())
+}
+
+/// # Validate Template
+/// Ensure the parameters are well-formed, their defaults match their
+/// types, and every placeholder of the job definition is declared
+pub fn validate_template(template: &Template) -> Result<(), ValidationError> {
+    if !template.job.is_object() {
+        return Err(ValidationError::new("Template 'job' must be an object"));
+    }
+
+    let mut names = HashSet::with_capacity(template.parameters.len());
+
+    for parameter in &template.parameters {
+        if !is_identifier(&parameter.name) || !names.insert(parameter.name.as_str()) {
+            return Err(ValidationError::new(
+                "Parameter names must be unique and match [A-Za-z_][A-Za-z0-9_]*",
+            ));
+        }
+
+        if let ParameterKind::Enum { values } = &parameter.kind {
+            if values.is_empty() {
+                return Err(ValidationError::new(
+                    "Enum parameters must list their 'values'",
+                ));
+            }
+        }
+
+        if let Some(default) = &parameter.default {
+            parameter.render(default)?;
+        }
+    }
+
+    if template
+        .placeholders()?
+        .iter()
+        .any(|name| !names.contains(name.as_str()))
+    {
+        return Err(ValidationError::new(
+            "Template uses an undeclared parameter",
+        ));
+    }
+
+    Ok(())
+}
+
+/// # Validate Channel Name
+/// Ensure channel and rule names follow the project name rules
+pub fn validate_channel_name(input: &str) -> Result<(), ValidationError> {
+    if validate_project_name(input).is_err() {
+        return Err(ValidationError::new("Invalid name"));
+    }
+
+    Ok(())
+}
+
+/// # Validate Recipients
+/// Ensure there are 1-16 email addresses
+pub fn validate_recipients(input: &[String]) -> Result<(), ValidationError> {
+    let valid =
+        !input.is_empty() && input.len() <= 16 && input.iter().all(validator::validate_email);
+
+    if !valid {
+        return Err(ValidationError::new("Invalid recipients"));
+    }
+
+    Ok(())
+}
+
+/// # Validate Channel
+/// Ensure `webhook` and `slack` channels have a URL, and `email` channels
+/// have recipients, but not both
+pub fn validate_channel(channel: &Channel) -> Result<(), ValidationError> {
+    let valid = match channel.kind {
+        ChannelKind::Webhook | ChannelKind::Slack => {
+            channel.url.is_some() && channel.recipients.is_none()
+        }
+        ChannelKind::Email => channel.url.is_none() && channel.recipients.is_some(),
+    };
+
+    if !valid {
+        return Err(ValidationError::new(
+            "'webhook' and 'slack' channels take a url, 'email' channels take recipients",
+        ));
+    }
+
+    Ok(())
+}
+
+/// # Validate Message Template
+/// Ensure the template is at most 4096 characters and only references
+/// known placeholders
+pub fn validate_message_template(input: &str) -> Result<(), ValidationError> {
+    if input.len() > 4096 || message::render(input, &HashMap::new()).is_err() {
+        return Err(ValidationError::new("Invalid message template"));
+    }
+
+    Ok(())
+}
+
+/// # Validate Rule
+/// Ensure a rule names its channel, and that `consecutive_failures` rules,
+/// and only those, have a threshold
+pub fn validate_rule(rule: &Rule) -> Result<(), ValidationError> {
+    if rule.channel.is_empty() {
+        return Err(ValidationError::new("Rules need a channel"));
+    }
+
+    if (rule.event == Event::ConsecutiveFailures) != rule.threshold.is_some() {
+        return Err(ValidationError::new(
+            "Only 'consecutive_failures' rules take a threshold, and they require one",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Whether `input` is a valid `[A-Za-z_][A-Za-z0-9_]*` identifier
+fn is_identifier(input: &str) -> bool {
+    let mut chars = input.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && input.len() <= 255
+}
@@ -0,0 +1,326 @@
+//! Webhook-Related API Endpoints
+
+extern crate actix_web;
+extern crate schedin_common;
+extern crate serde_json;
+extern crate sqlx;
+extern crate std;
+extern crate validator;
+
+use super::{
+    error::{ApiError, ErrorBody},
+    openapi::{Redelivered, SigningSecret, Status},
+};
+use crate::{
+    db::webhook::Subscription,
+    iam::schema::AuthorizedUser,
+    webhook::schema::{self, Deliveries, Redeliver, Rotate},
+};
+use actix_web::{
+    web::{Data, Json, Query},
+    HttpResponse,
+};
+use schedin_common::{error::CrudError, secret::Cipher};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use validator::Validate;
+
+/// # Insert or Update Subscription
+/// This function subscribes a URL to run and job events, all of them unless
+/// `events` narrows them down. New subscriptions get a signing secret, which
+/// is only ever returned here and on rotation.
+///
+/// Every delivery is a `POST` of a JSON body of schema version 1:
+/// `{ "id", "type", "version", "created_at", "data" }`. Deliveries are
+/// retried until they succeed, and may arrive more than once or out of
+/// order, `id` (also the `Schedin-Event-Id` header) identifies the event.
+///
+/// The `Schedin-Signature` header reads `t={timestamp},v1={signature}`,
+/// where the signature is the hex-encoded HMAC-SHA256 of `{timestamp}.{body}`
+/// under the secret. Reject deliveries signed more than 5 minutes ago, and
+/// accept any `v1` matching during a rotation.
+///
+/// ## Errors
+///
+/// - Invalid payload.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {
+///     "name": "ci",
+///     "url": "https://ci.example.com/hooks/schedin",
+///     "events": ["run.succeeded", "run.failed"]
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/webhook/new",
+    tag = "webhook",
+    request_body = schema::Subscription,
+    responses(
+        (status = 200, description = "Subscription created or updated, with its signing secret when created", body = SigningSecret),
+        (status = 422, description = "Invalid subscription", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn upsert_subscription(
+    account: AuthorizedUser,
+    payload: Json<schema::Subscription>,
+    cipher: Data<Cipher>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(err) = payload.validate() {
+        return Err(err.into());
+    }
+
+    let secret = match Subscription::new(db.into_inner())
+        .subscription(payload.0)
+        .upsert(&account.id, &cipher)
+        .await
+    {
+        Ok(secret) => secret,
+        Err(error) => return Err(error.into()),
+    };
+
+    let mut map = HashMap::with_capacity(2);
+    map.insert("status", "ok");
+    if let Some(secret) = &secret {
+        map.insert("secret", secret.expose());
+    }
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # List Subscriptions
+/// This function lists the webhook subscriptions of the user, without
+/// their secrets.
+///
+/// ## Errors
+///
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    get,
+    path = "/api/webhook/list",
+    tag = "webhook",
+    responses(
+        (status = 200, description = "Subscriptions of the user", body = Vec<schema::SubscriptionRow>),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_subscriptions(
+    account: AuthorizedUser,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    match Subscription::new(db.into_inner()).list(&account.id).await {
+        Ok(subscriptions) => Ok(HttpResponse::Ok().json(subscriptions)),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// # Delete Subscription
+/// This function deletes a webhook subscription, pending deliveries
+/// are dropped.
+///
+/// ## Errors
+///
+/// - Unknown subscription.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {
+///     "name": "ci"
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/webhook/delete",
+    tag = "webhook",
+    request_body = schema::Subscription,
+    responses(
+        (status = 200, description = "Subscription deleted", body = Status),
+        (status = 404, description = "Unknown subscription", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn delete_subscription(
+    account: AuthorizedUser,
+    payload: Json<schema::Subscription>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(error) = Subscription::new(db.into_inner())
+        .subscription(payload.0)
+        .delete(&account.id)
+        .await
+    {
+        return Err(error.into());
+    }
+
+    let mut map = HashMap::with_capacity(1);
+    map.insert("status", "ok");
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # Rotate Secret
+/// This function replaces the signing secret of a subscription and returns
+/// the new one. Deliveries carry a signature under each secret for `grace`
+/// seconds (default: 1 day, at most 7 days).
+///
+/// ## Errors
+///
+/// - Invalid payload.
+/// - Unknown subscription.
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {
+///     "name": "ci",
+///     "grace": 3600
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/webhook/rotate",
+    tag = "webhook",
+    request_body = Rotate,
+    responses(
+        (status = 200, description = "New signing secret", body = SigningSecret),
+        (status = 404, description = "Unknown subscription", body = ErrorBody),
+        (status = 422, description = "Invalid payload", body = ErrorBody),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn rotate_secret(
+    account: AuthorizedUser,
+    payload: Json<Rotate>,
+    cipher: Data<Cipher>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(err) = payload.validate() {
+        return Err(err.into());
+    }
+
+    let secret = match Subscription::new(db.into_inner())
+        .subscription(schema::Subscription {
+            name: payload.name.clone(),
+            ..Default::default()
+        })
+        .rotate(&account.id, &cipher, payload.grace)
+        .await
+    {
+        Ok(secret) => secret,
+        Err(CrudError::NotFound) => {
+            return Err(ApiError::not_found(format!(
+                "Unknown subscription '{}'",
+                payload.name
+            )))
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    let mut map = HashMap::with_capacity(2);
+    map.insert("status", "ok");
+    map.insert("secret", secret.expose());
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # Redeliver Events
+/// This function delivers the events of a subscription again, e.g. after
+/// the subscriber was down. Only `event` if set, the events since `since`
+/// otherwise, and every retained event (7 days) if neither is set.
+///
+/// ## Errors
+///
+/// - Database is down.
+/// - Internal server errors, etc..
+///
+/// ## Example
+/// ```json
+/// {
+///     "name": "ci",
+///     "since": "2024-01-01T00:00:00Z"
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/api/webhook/redeliver",
+    tag = "webhook",
+    request_body = Redeliver,
+    responses(
+        (status = 200, description = "Events queued for delivery again", body = Redelivered),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn redeliver_events(
+    account: AuthorizedUser,
+    payload: Json<Redeliver>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let payload = payload.into_inner();
+
+    let redelivered = match Subscription::new(db.into_inner())
+        .subscription(schema::Subscription {
+            name: payload.name,
+            ..Default::default()
+        })
+        .redeliver(&account.id, payload.event, payload.since)
+        .await
+    {
+        Ok(redelivered) => redelivered,
+        Err(error) => return Err(error.into()),
+    };
+
+    let mut map = HashMap::with_capacity(2);
+    map.insert("status", Value::from("ok"));
+    map.insert("redelivered", Value::from(redelivered));
+    Ok(HttpResponse::Ok().json(map))
+}
+
+/// # List Deliveries
+/// This function lists the deliveries of a subscription, most recent first.
+///
+/// ## Errors
+///
+/// - Database is down.
+/// - Internal server errors, etc..
+#[utoipa::path(
+    get,
+    path = "/api/webhook/deliveries",
+    tag = "webhook",
+    params(Deliveries),
+    responses(
+        (status = 200, description = "Deliveries of the subscription, newest first", body = Vec<schema::Delivery>),
+        (status = 500, description = "Database is down, or internal server error", body = ErrorBody),
+    ),
+    security(("bearer" = [])),
+)]
+pub async fn list_webhook_deliveries(
+    account: AuthorizedUser,
+    query: Query<Deliveries>,
+    db: Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let query = query.into_inner();
+
+    match Subscription::new(db.into_inner())
+        .subscription(schema::Subscription {
+            name: query.name,
+            ..Default::default()
+        })
+        .deliveries(&account.id, query.limit.clamp(1, 500))
+        .await
+    {
+        Ok(deliveries) => Ok(HttpResponse::Ok().json(deliveries)),
+        Err(error) => Err(error.into()),
+    }
+}
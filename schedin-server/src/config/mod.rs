@@ -0,0 +1,49 @@
+//! Configuration
+//!
+//! Settings of the server, read once from the environment at startup.
+
+extern crate schedin_common;
+extern crate std;
+
+use schedin_common::{error::SecretError, secret::Cipher};
+use std::env;
+
+/// Environment variable holding the address of the REST API
+const HTTP_ADDR_VAR: &str = "HTTP_ADDR";
+
+/// Environment variable holding the address of the gRPC API
+const GRPC_ADDR_VAR: &str = "GRPC_ADDR";
+
+/// Address the REST API listens on by default
+const DEFAULT_HTTP_ADDR: &str = "localhost:8080";
+
+/// Address the gRPC API listens on by default
+const DEFAULT_GRPC_ADDR: &str = "localhost:50051";
+
+#[derive(Debug)]
+pub struct Config {
+    /// Address the REST API listens on
+    pub http_addr: String,
+    /// Address the gRPC API listens on
+    pub grpc_addr: String,
+    /// Cipher of the secrets, sealed under the master key
+    pub cipher: Cipher,
+}
+
+impl Config {
+    /// # Configuration
+    /// Read the configuration from the environment, with defaults
+    /// for unset addresses.
+    ///
+    /// ## Errors
+    ///
+    /// The secrets master key has no default, so a missing or malformed
+    /// `SECRETS_MASTER_KEY` is an error.
+    pub fn from_env() -> Result<Self, SecretError> {
+        Ok(Self {
+            http_addr: env::var(HTTP_ADDR_VAR).unwrap_or_else(|_| DEFAULT_HTTP_ADDR.to_string()),
+            grpc_addr: env::var(GRPC_ADDR_VAR).unwrap_or_else(|_| DEFAULT_GRPC_ADDR.to_string()),
+            cipher: Cipher::from_env()?,
+        })
+    }
+}
@@ -0,0 +1,288 @@
+//! Event-related Crud Ops
+
+extern crate schedin_common;
+extern crate serde_json;
+extern crate sqlx;
+extern crate std;
+extern crate uuid;
+
+use crate::event::schema::Event;
+use schedin_common::error::CrudError;
+use serde_json::Value;
+use sqlx::{query_as, query_scalar, types::Json, PgPool, Pool, Postgres};
+use std::{collections::HashMap, sync::Arc};
+use uuid::Uuid;
+
+/// # Position
+/// Position in the stream of events of a user. Events are streamed in the
+/// order of their transactions, then of their `seq`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    pub txid: i64,
+    pub seq: i64,
+}
+
+pub struct Events {
+    pub pool: Arc<PgPool>,
+}
+
+impl Events {
+    /// New Instance
+    pub fn new(pool: Arc<Pool<Postgres>>) -> Self {
+        Self { pool }
+    }
+
+    /// # Start
+    /// Position of the streams from now on: before the oldest transaction
+    /// still recording events, whose events are yet to come.
+    pub async fn start(&self) -> Result<Position, CrudError> {
+        match query_scalar!(
+            r#"
+            SELECT event_horizon() AS "horizon!"
+            "#
+        )
+        .fetch_one(&*self.pool)
+        .await
+        {
+            Ok(horizon) => Ok(Position {
+                txid: horizon - 1,
+                seq: i64::MAX,
+            }),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// # Position
+    /// Position of the stream of a user right after the event `seq`, or
+    /// from now on if the event is unknown or expired.
+    pub async fn position(&self, user_id: &str, seq: i64) -> Result<Position, CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query_scalar!(
+            r#"
+            SELECT txid FROM webhook_outbox WHERE user_id = $1 AND seq = $2
+            "#,
+            user_id,
+            seq
+        )
+        .fetch_optional(&*self.pool)
+        .await
+        {
+            Ok(Some(txid)) => Ok(Position { txid, seq }),
+            Ok(None) => self.start().await,
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// # Since
+    /// Events of a user after the position `after`, in order, of the job
+    /// named `job` and of the jobs with all of `labels` if set.
+    ///
+    /// Only the events of transactions older than every running one which
+    /// records events are read, no event can come before them anymore.
+    pub async fn since(
+        &self,
+        user_id: &str,
+        after: Position,
+        job: Option<&str>,
+        labels: Option<&HashMap<String, String>>,
+        limit: i64,
+    ) -> Result<Vec<Event>, CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query_as!(
+            Event,
+            r#"
+            SELECT o.seq, o.txid, o.event_id AS id, o.event_type, 
+            o.schema_version AS version, o.created_at, o.data AS "data: Json<Value>" 
+            FROM webhook_outbox o 
+            LEFT JOIN jobs j ON j.job_id = (o.data->>'job_id')::UUID 
+            WHERE o.user_id = $1 AND (o.txid, o.seq) > ($2, $3) 
+            AND o.txid < event_horizon() 
+            AND ($4::TEXT IS NULL OR o.data->>'job' = $4) 
+            AND ($5::JSONB IS NULL OR j.labels @> $5) 
+            ORDER BY o.txid, o.seq LIMIT $6
+            "#,
+            user_id,
+            after.txid,
+            after.seq,
+            job,
+            labels.map(Json) as _,
+            limit
+        )
+        .fetch_all(&*self.pool)
+        .await
+        {
+            Ok(events) => Ok(events),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Events, Position};
+    use crate::db::fixture::user;
+    use sqlx::{query, PgConnection, PgPool};
+    use std::{sync::Arc, time::Duration};
+    use tokio::time;
+
+    async fn record(conn: &mut PgConnection, user_id: &str, event_type: &str) {
+        query("INSERT INTO webhook_outbox (user_id, event_type, data) VALUES ($1::UUID, $2, '{}')")
+            .bind(user_id)
+            .bind(event_type)
+            .execute(conn)
+            .await
+            .unwrap();
+    }
+
+    async fn read(events: &Events, user_id: &str, after: Position) -> Vec<(String, Position)> {
+        events
+            .since(user_id, after, None, None, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|event| {
+                let position = Position {
+                    txid: event.txid,
+                    seq: event.seq,
+                };
+                (event.event_type, position)
+            })
+            .collect()
+    }
+
+    /// `count` events after `after`, once the transactions which ended
+    /// while reading are no longer in its snapshot
+    async fn read_settled(
+        events: &Events,
+        user_id: &str,
+        after: Position,
+        count: usize,
+    ) -> Vec<(String, Position)> {
+        for _ in 0..50 {
+            let read = read(events, user_id, after).await;
+            if read.len() >= count {
+                return read;
+            }
+            time::sleep(Duration::from_millis(100)).await;
+        }
+
+        read(events, user_id, after).await
+    }
+
+    #[sqlx::test]
+    async fn streams_events_committed_out_of_order(pool: PgPool) {
+        let user_id = user(&pool).await;
+        let events = Events::new(Arc::new(pool.clone()));
+
+        let start = events.start().await.unwrap();
+
+        // the first event takes the lower seq, but commits last
+        let mut first = pool.begin().await.unwrap();
+        record(&mut first, &user_id, "run.started").await;
+
+        let mut second = pool.begin().await.unwrap();
+        record(&mut second, &user_id, "run.succeeded").await;
+        second.commit().await.unwrap();
+
+        assert!(read(&events, &user_id, start).await.is_empty());
+
+        first.commit().await.unwrap();
+
+        let both = read_settled(&events, &user_id, start, 2).await;
+        let types = both.iter().map(|(t, _)| t.as_str()).collect::<Vec<_>>();
+        assert_eq!(types, ["run.started", "run.succeeded"]);
+
+        let start = both[1].1;
+
+        // the early transaction starts writing first, and records its
+        // event after the late one
+        let mut early = pool.begin().await.unwrap();
+        query("SELECT pg_current_xact_id()")
+            .execute(&mut *early)
+            .await
+            .unwrap();
+
+        let mut late = pool.begin().await.unwrap();
+        record(&mut late, &user_id, "run.started").await;
+        record(&mut early, &user_id, "job.updated").await;
+        early.commit().await.unwrap();
+
+        let settled = read_settled(&events, &user_id, start, 1).await;
+        assert_eq!(settled.len(), 1);
+        assert_eq!(settled[0].0, "job.updated");
+
+        late.commit().await.unwrap();
+
+        // the cursor is past the higher seq, the lower one still follows
+        let (_, cursor) = settled[0];
+        let rest = read_settled(&events, &user_id, cursor, 1).await;
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].0, "run.started");
+        assert!(rest[0].1.seq < cursor.seq);
+    }
+
+    #[sqlx::test]
+    async fn streams_past_transactions_recording_no_events(pool: PgPool) {
+        let user_id = user(&pool).await;
+        let events = Events::new(Arc::new(pool.clone()));
+
+        let start = events.start().await.unwrap();
+
+        // an older transaction writing elsewhere, e.g. a long migration
+        let mut unrelated = pool.begin().await.unwrap();
+        query("INSERT INTO users (username, passcode, email) VALUES ('bob', 'pw', 'bob@x')")
+            .execute(&mut *unrelated)
+            .await
+            .unwrap();
+
+        let mut conn = pool.acquire().await.unwrap();
+        record(&mut conn, &user_id, "run.started").await;
+
+        let read = read_settled(&events, &user_id, start, 1).await;
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].0, "run.started");
+
+        unrelated.rollback().await.unwrap();
+    }
+
+    #[sqlx::test]
+    async fn resumes_after_an_event(pool: PgPool) {
+        let user_id = user(&pool).await;
+        let events = Events::new(Arc::new(pool.clone()));
+
+        let mut conn = pool.acquire().await.unwrap();
+        for event_type in ["run.scheduled", "run.started", "run.succeeded"] {
+            record(&mut conn, &user_id, event_type).await;
+        }
+
+        let all = read_settled(&events, &user_id, Position::default(), 3).await;
+        assert_eq!(all.len(), 3);
+
+        let resumed = events.position(&user_id, all[1].1.seq).await.unwrap();
+        assert_eq!(resumed, all[1].1);
+
+        let rest = read(&events, &user_id, resumed).await;
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].0, "run.succeeded");
+
+        // an expired event resumes from now on, not from the oldest one
+        let expired = events.position(&user_id, all[2].1.seq + 100).await.unwrap();
+        assert!(read(&events, &user_id, expired).await.is_empty());
+
+        record(&mut conn, &user_id, "run.failed").await;
+        let after = read_settled(&events, &user_id, expired, 1).await;
+        assert_eq!(after.len(), 1);
+        assert_eq!(after[0].0, "run.failed");
+    }
+}
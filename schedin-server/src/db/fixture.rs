@@ -0,0 +1,20 @@
+//! Test Fixtures
+//! Rows shared by the database tests
+
+extern crate sqlx;
+extern crate uuid;
+
+use sqlx::{query_scalar, PgPool};
+use uuid::Uuid;
+
+/// Id of a new user `alice`
+pub async fn user(pool: &PgPool) -> String {
+    let user_id: Uuid = query_scalar(
+        "INSERT INTO users (username, passcode, email) VALUES ('alice', 'pw', 'alice@x') RETURNING user_id",
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap();
+
+    user_id.to_string()
+}
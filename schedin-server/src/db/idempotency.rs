@@ -0,0 +1,264 @@
+//! Idempotency Key Store
+
+extern crate schedin_common;
+extern crate serde_json;
+extern crate sqlx;
+extern crate std;
+extern crate uuid;
+
+use schedin_common::error::CrudError;
+use serde_json::Value;
+use sqlx::{query, PgPool, Pool, Postgres};
+use std::{env, sync::Arc};
+use uuid::Uuid;
+
+/// Default time-to-live of a key, in seconds
+const DEFAULT_TTL: i64 = 24 * 60 * 60;
+
+/// Default time a request holds a key without completing it, in seconds
+const DEFAULT_LEASE: i64 = 60;
+
+/// Outcome of claiming an idempotency key
+pub enum Claim {
+    /// Key is new (or expired), the request must be processed
+    Started,
+
+    /// Key was already used with the same request, replay its response
+    Replay { status: u16, response: Value },
+
+    /// Key was already used with a different request
+    Mismatch,
+
+    /// Key is held by a request still being processed, within its lease
+    InProgress,
+}
+
+pub struct Idempotency {
+    pub pool: Arc<PgPool>,
+}
+
+impl Idempotency {
+    /// New instance
+    pub fn new(pool: Arc<Pool<Postgres>>) -> Self {
+        Self { pool }
+    }
+
+    /// # Claim
+    /// Atomically claim `key` for the request hashed as `request_hash`.
+    ///
+    /// An expired key is reclaimed. Keys live for `IDEMPOTENCY_TTL` seconds
+    /// (24 hours by default).
+    ///
+    /// A key still in progress after `IDEMPOTENCY_LEASE` seconds (60 by
+    /// default) was left behind by a request that never completed, e.g. on
+    /// a crash, and is claimed again.
+    pub async fn claim(
+        &self,
+        user_id: &str,
+        key: &str,
+        request_hash: &str,
+    ) -> Result<Claim, CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+        let ttl = env::var("IDEMPOTENCY_TTL")
+            .ok()
+            .and_then(|ttl| ttl.parse::<i64>().ok())
+            .unwrap_or(DEFAULT_TTL);
+        let lease = env::var("IDEMPOTENCY_LEASE")
+            .ok()
+            .and_then(|lease| lease.parse::<i64>().ok())
+            .unwrap_or(DEFAULT_LEASE);
+
+        let claimed = match query!(
+            r#"
+            INSERT INTO idempotency_keys (user_id, idempotency_key, request_hash, expires_at) 
+            VALUES ($1, $2, $3, NOW() + make_interval(secs => $4)) 
+            ON CONFLICT (user_id, idempotency_key) DO UPDATE SET 
+            request_hash = EXCLUDED.request_hash, status_code = NULL, response = NULL, 
+            created_at = NOW(), claimed_at = NOW(), expires_at = EXCLUDED.expires_at 
+            WHERE idempotency_keys.expires_at < NOW() OR (
+                idempotency_keys.status_code IS NULL 
+                AND idempotency_keys.claimed_at < NOW() - make_interval(secs => $5)
+            )
+            "#,
+            user_id,
+            key,
+            request_hash,
+            ttl as f64,
+            lease as f64
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(result) => result.rows_affected() == 1,
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(CrudError::Insertion);
+            }
+        };
+
+        if claimed {
+            return Ok(Claim::Started);
+        }
+
+        let row = match query!(
+            r#"
+            SELECT request_hash, status_code, response FROM idempotency_keys 
+            WHERE user_id = $1 AND idempotency_key = $2
+            "#,
+            user_id,
+            key
+        )
+        .fetch_one(&*self.pool)
+        .await
+        {
+            Ok(row) => row,
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(CrudError::Read);
+            }
+        };
+
+        Ok(
+            match (
+                row.request_hash == request_hash,
+                row.status_code,
+                row.response,
+            ) {
+                (false, ..) => Claim::Mismatch,
+                (true, Some(status), Some(response)) => Claim::Replay {
+                    status: status as u16,
+                    response,
+                },
+                _ => Claim::InProgress,
+            },
+        )
+    }
+
+    /// Store the response of the request holding `key`
+    pub async fn complete(
+        &self,
+        user_id: &str,
+        key: &str,
+        status: u16,
+        response: &Value,
+    ) -> Result<(), CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query!(
+            r#"
+            UPDATE idempotency_keys SET status_code = $3, response = $4 
+            WHERE user_id = $1 AND idempotency_key = $2
+            "#,
+            user_id,
+            key,
+            status as i32,
+            response
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// Release `key` without a response, so the request can be retried
+    pub async fn release(&self, user_id: &str, key: &str) -> Result<(), CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query!(
+            r#"
+            DELETE FROM idempotency_keys WHERE user_id = $1 AND idempotency_key = $2
+            "#,
+            user_id,
+            key
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Claim, Idempotency};
+    use crate::db::fixture::user;
+    use serde_json::json;
+    use sqlx::{query, PgPool};
+    use std::sync::Arc;
+
+    /// Hashes of two requests, as stored
+    const A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const B: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+
+    /// Move the claim of `key` back past the lease
+    async fn stale(pool: &PgPool, key: &str) {
+        query("UPDATE idempotency_keys SET claimed_at = NOW() - INTERVAL '1 hour' WHERE idempotency_key = $1")
+            .bind(key)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn reclaims_keys_left_in_progress(pool: PgPool) {
+        let user_id = user(&pool).await;
+        let keys = Idempotency::new(Arc::new(pool.clone()));
+
+        assert!(matches!(
+            keys.claim(&user_id, "k", A).await,
+            Ok(Claim::Started)
+        ));
+        assert!(matches!(
+            keys.claim(&user_id, "k", A).await,
+            Ok(Claim::InProgress)
+        ));
+
+        // the request holding the key crashed, its lease ran out
+        stale(&pool, "k").await;
+        assert!(matches!(
+            keys.claim(&user_id, "k", A).await,
+            Ok(Claim::Started)
+        ));
+        assert!(matches!(
+            keys.claim(&user_id, "k", A).await,
+            Ok(Claim::InProgress)
+        ));
+    }
+
+    #[sqlx::test]
+    async fn replays_completed_keys_past_the_lease(pool: PgPool) {
+        let user_id = user(&pool).await;
+        let keys = Idempotency::new(Arc::new(pool.clone()));
+
+        assert!(matches!(
+            keys.claim(&user_id, "k", A).await,
+            Ok(Claim::Started)
+        ));
+        keys.complete(&user_id, "k", 201, &json!({"id": 1}))
+            .await
+            .unwrap();
+
+        stale(&pool, "k").await;
+        match keys.claim(&user_id, "k", A).await {
+            Ok(Claim::Replay { status, response }) => {
+                assert_eq!(status, 201);
+                assert_eq!(response, json!({"id": 1}));
+            }
+            _ => panic!("expected a replay"),
+        }
+        assert!(matches!(
+            keys.claim(&user_id, "k", B).await,
+            Ok(Claim::Mismatch)
+        ));
+    }
+}
@@ -0,0 +1,180 @@
+//! API Key Crud Ops
+
+extern crate schedin_common;
+extern crate sqlx;
+extern crate std;
+extern crate uuid;
+
+use crate::iam::{
+    key,
+    schema::{ApiKeyRow, KeyOwner},
+};
+use schedin_common::{error::CrudError, secret::Redacted};
+use sqlx::{query, query_as, PgPool, Pool, Postgres};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub struct ApiKeys {
+    pub pool: Arc<PgPool>,
+}
+
+impl ApiKeys {
+    /// New Instance
+    pub fn new(pool: Arc<Pool<Postgres>>) -> Self {
+        Self { pool }
+    }
+
+    /// # Create Key
+    /// Generate the API key `name` of the user, and returns it. The key is
+    /// not stored, it cannot be retrieved again.
+    ///
+    /// ## Errors
+    ///
+    /// `CrudError::Conflict` if the user already has a key of this name.
+    pub async fn create(&self, user_id: &str, name: &str) -> Result<Redacted<String>, CrudError> {
+        let key = key::generate();
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query!(
+            r#"
+            INSERT INTO api_keys (user_id, key_name, key_hash) VALUES ($1, $2, $3)
+            "#,
+            user_id,
+            name,
+            key::hash(key.expose())
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(_) => Ok(key),
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(CrudError::Conflict),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// List key names of a user
+    pub async fn list(&self, user_id: &str) -> Result<Vec<ApiKeyRow>, CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query_as!(
+            ApiKeyRow,
+            r#"
+            SELECT key_name AS name, created_at FROM api_keys
+            WHERE user_id=$1 ORDER BY key_name
+            "#,
+            user_id
+        )
+        .fetch_all(&*self.pool)
+        .await
+        {
+            Ok(rows) => Ok(rows),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// Revoke the key `name` of a user
+    pub async fn delete(&self, user_id: &str, name: &str) -> Result<(), CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query!(
+            r#"
+            DELETE FROM api_keys WHERE user_id=$1 AND key_name=$2
+            "#,
+            user_id,
+            name
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 0 => Err(CrudError::NotFound),
+            Ok(_) => Ok(()),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// Owner of the API key `key`,
+    /// `Err(CrudError::NotFound)` if the key is unknown or revoked
+    pub async fn owner(&self, key: &str) -> Result<KeyOwner, CrudError> {
+        match query_as!(
+            KeyOwner,
+            r#"
+            SELECT key_id, user_id FROM api_keys WHERE key_hash=$1
+            "#,
+            key::hash(key)
+        )
+        .fetch_optional(&*self.pool)
+        .await
+        {
+            Ok(Some(owner)) => Ok(owner),
+            Ok(None) => Err(CrudError::NotFound),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ApiKeys;
+    use crate::db::fixture::user;
+    use schedin_common::error::CrudError;
+    use sqlx::PgPool;
+    use std::sync::Arc;
+
+    #[sqlx::test]
+    async fn authenticates_keys_until_revoked(pool: PgPool) {
+        let user_id = user(&pool).await;
+        let keys = ApiKeys::new(Arc::new(pool));
+
+        let key = keys.create(&user_id, "ci").await.unwrap();
+        let owner = keys.owner(key.expose()).await.unwrap();
+        assert_eq!(owner.user_id.to_string(), user_id);
+
+        assert!(matches!(
+            keys.owner(&format!("{}0", key.expose())).await,
+            Err(CrudError::NotFound)
+        ));
+
+        keys.delete(&user_id, "ci").await.unwrap();
+        assert!(matches!(
+            keys.owner(key.expose()).await,
+            Err(CrudError::NotFound)
+        ));
+        assert!(matches!(
+            keys.delete(&user_id, "ci").await,
+            Err(CrudError::NotFound)
+        ));
+    }
+
+    #[sqlx::test]
+    async fn rejects_duplicate_key_names(pool: PgPool) {
+        let user_id = user(&pool).await;
+        let keys = ApiKeys::new(Arc::new(pool));
+
+        keys.create(&user_id, "ci").await.unwrap();
+        assert!(matches!(
+            keys.create(&user_id, "ci").await,
+            Err(CrudError::Conflict)
+        ));
+
+        let names: Vec<String> = keys
+            .list(&user_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|row| row.name)
+            .collect();
+        assert_eq!(names, ["ci"]);
+    }
+}
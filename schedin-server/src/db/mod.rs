@@ -1,24 +1,54 @@
 //! CRUD Ops
 
 extern crate schedin_common;
+extern crate serde_json;
 extern crate sqlx;
 extern crate std;
 extern crate uuid;
 
+pub mod event;
+#[cfg(test)]
+pub mod fixture;
+pub mod idempotency;
+pub mod key;
+pub mod module;
+pub mod notification;
+pub mod priority;
+pub mod project;
+pub mod ratelimit;
+pub mod revision;
+pub mod run;
+pub mod secret;
+pub mod template;
 pub mod user;
+pub mod webhook;
 
-use crate::job::{
-    schedule::{Schedule, Time},
-    schema::{Bin, Code, Job, JobType, Task},
+use crate::{
+    job::{
+        manifest::{Diff, Manifest},
+        revision::diff,
+        schedule::{Routine, Schedule, Time},
+        schema::{
+            Bin, Code, Env, Http, Job, JobRecord, JobStatus, JobType, Matrix, Pipeline, Quantity,
+            Resources, Sql, Step, Task, Term, TrashedJob, Wasm,
+        },
+    },
+    project::schema::DEFAULT_TRASH_RETENTION_DAYS,
 };
 use schedin_common::{error::CrudError, tx::Tx};
-use sqlx::{PgPool, Pool, Postgres};
-use std::sync::Arc;
+use serde_json::Value;
+use sqlx::{
+    types::{time::OffsetDateTime, Json},
+    PgConnection, PgPool, Pool, Postgres, Transaction,
+};
+use std::{collections::HashMap, sync::Arc};
 use uuid::Uuid;
 
 pub struct DB {
     pub pool: Arc<PgPool>,
     pub job: Job,
+    pub project: Uuid,
+    pub status: JobStatus,
 }
 
 impl DB {
@@ -26,6 +56,8 @@ impl DB {
         Self {
             pool,
             job: Job::default(),
+            project: Uuid::nil(),
+            status: JobStatus::default(),
         }
     }
 
@@ -34,53 +66,408 @@ impl DB {
         self
     }
 
-    pub async fn insert(&self, user_id: &str) -> Result<(), CrudError> {
+    /// Sets the project the job belongs to
+    pub fn project(mut self, project: Uuid) -> Self {
+        self.project = project;
+        self
+    }
+
+    /// Sets the status new jobs are inserted with, `Scheduled` by default
+    pub fn status(mut self, status: JobStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Insert the job on behalf of `user_id`, returns the new job id
+    pub async fn insert(&self, user_id: &str) -> Result<Uuid, CrudError> {
         let tx_manager = Tx::new(self.pool.clone());
-        let tx = tx_manager.init().await?;
+        let mut tx = tx_manager.init().await?;
 
-        let job_id = match self.insert_inner(user_id).await {
-            Ok(id) => id,
+        match self.insert_all(&mut tx, user_id).await {
+            Ok(job_id) => {
+                tx_manager.commit(tx).await?;
+                Ok(job_id)
+            }
             Err(error) => {
                 tx_manager.rollback(tx).await?;
-                return Err(error);
+                Err(error)
+            }
+        }
+    }
+
+    /// Insert job and its payload within `tx`, returns the new job id
+    pub(crate) async fn insert_all(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+    ) -> Result<Uuid, CrudError> {
+        self.quota(tx, &self.project, true).await?;
+        let job_id = self.insert_inner(tx, user_id).await?;
+        self.payload(tx, &job_id).await?;
+        self.revision(tx, &job_id, user_id, 1, None).await?;
+        Ok(job_id)
+    }
+
+    /// # Replace
+    /// Replace the definition of the existing job `job_id` within `tx`,
+    /// on behalf of `user_id`.
+    ///
+    /// The job keeps its id, name and run counters, while its description,
+    /// schedule, payload and environment are overwritten. Every change
+    /// produces a new revision, replacing a job with an identical
+    /// definition is a no-op.
+    pub(crate) async fn replace(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        job_id: &Uuid,
+        user_id: &str,
+    ) -> Result<(), CrudError> {
+        let previous = match sqlx::query_scalar!(
+            r#"
+            SELECT definition FROM job_revisions WHERE job_id = $1 
+            ORDER BY revision DESC LIMIT 1
+            "#,
+            job_id
+        )
+        .fetch_optional(&mut **tx)
+        .await
+        {
+            Ok(previous) => previous,
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(CrudError::Read);
             }
         };
 
+        if previous.as_ref() == Some(&self.definition()) {
+            return Ok(());
+        }
+
+        let project_id =
+            match sqlx::query_scalar!("SELECT project_id FROM jobs WHERE job_id = $1", job_id)
+                .fetch_one(&mut **tx)
+                .await
+            {
+                Ok(project_id) => project_id,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return Err(CrudError::Read);
+                }
+            };
+
+        self.quota(tx, &project_id, false).await?;
+        let priority_class_id = self.priority_class(tx).await?;
+
+        let job_type = self.job.kind();
+        let (job_interval, next_run_at) = self.schedule();
+        let (requests, limits) = self.resources();
+
+        let revision = match sqlx::query_scalar!(
+            r#"
+            UPDATE jobs SET job_description = $2, job_type = $3, job_interval = $4, next_run_at = $5, 
+            schedule = $6, labels = $7, timeout = $8, 
+            request_cpu = $9, request_memory_mb = $10, request_disk_mb = $11, 
+            limit_cpu = $12, limit_memory_mb = $13, limit_disk_mb = $14, 
+            node_selector = $15, affinity = $16, anti_affinity = $17, priority_class_id = $18, 
+            matrix = $19, artifacts = $20, revision = revision + 1 
+            WHERE job_id = $1 
+            RETURNING revision
+            "#,
+            job_id,
+            self.job.description,
+            job_type as JobType,
+            job_interval,
+            next_run_at,
+            self.job.schedule,
+            Json(self.job.labels.clone().unwrap_or_default()) as _,
+            self.job.timeout,
+            requests.cpu_millis(),
+            requests.memory_mb,
+            requests.disk_mb,
+            limits.cpu_millis(),
+            limits.memory_mb,
+            limits.disk_mb,
+            Json(self.job.node_selector.clone().unwrap_or_default()) as _,
+            Json(self.job.affinity.clone().unwrap_or_default()) as _,
+            Json(self.job.anti_affinity.clone().unwrap_or_default()) as _,
+            priority_class_id,
+            self.job.matrix.clone().map(Json) as _,
+            self.job.artifacts.as_deref()
+        )
+        .fetch_one(&mut **tx)
+        .await
+        {
+            Ok(revision) => revision,
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(CrudError::Insertion);
+            }
+        };
+
+        for query in [
+            sqlx::query!("DELETE FROM tasks WHERE job_id = $1", job_id),
+            sqlx::query!("DELETE FROM codes WHERE job_id = $1", job_id),
+            sqlx::query!("DELETE FROM bins WHERE job_id = $1", job_id),
+            sqlx::query!("DELETE FROM http_requests WHERE job_id = $1", job_id),
+            sqlx::query!("DELETE FROM http_headers WHERE job_id = $1", job_id),
+            sqlx::query!("DELETE FROM sql_queries WHERE job_id = $1", job_id),
+            sqlx::query!("DELETE FROM wasm_jobs WHERE job_id = $1", job_id),
+            sqlx::query!("DELETE FROM pipelines WHERE job_id = $1", job_id),
+            sqlx::query!("DELETE FROM pipeline_secrets WHERE job_id = $1", job_id),
+            sqlx::query!("DELETE FROM envs WHERE job_id = $1", job_id),
+        ] {
+            if let Err(e) = query.execute(&mut **tx).await {
+                eprintln!("{}", e);
+                return Err(CrudError::Insertion);
+            }
+        }
+
+        self.payload(tx, job_id).await?;
+        self.revision(tx, job_id, user_id, revision, previous.as_ref())
+            .await
+    }
+
+    /// # Update
+    /// Replace the definition of the job `job_id` of the project,
+    /// on behalf of `user_id`.
+    pub async fn update(&self, user_id: &str, job_id: &Uuid) -> Result<(), CrudError> {
+        let tx_manager = Tx::new(self.pool.clone());
+        let mut tx = tx_manager.init().await?;
+
+        let owned = sqlx::query_scalar!(
+            r#"
+            SELECT job_id FROM jobs 
+            WHERE job_id = $1 AND project_id = $2 AND deleted_at IS NULL FOR UPDATE
+            "#,
+            job_id,
+            self.project
+        )
+        .fetch_optional(&mut *tx)
+        .await;
+
+        let result = match owned {
+            Ok(Some(_)) => self.replace(&mut tx, job_id, user_id).await,
+            Ok(None) => Err(CrudError::NotFound),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        };
+
+        match result {
+            Ok(_) => tx_manager.commit(tx).await,
+            Err(error) => {
+                tx_manager.rollback(tx).await?;
+                Err(error)
+            }
+        }
+    }
+
+    /// Record revision `revision` of the job definition
+    async fn revision(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        job_id: &Uuid,
+        user_id: &str,
+        revision: i32,
+        previous: Option<&Value>,
+    ) -> Result<(), CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+        let definition = self.definition();
+        let diff = diff(previous, &definition);
+
+        match sqlx::query!(
+            r#"
+            INSERT INTO job_revisions (job_id, revision, changed_by, definition, diff) 
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            job_id,
+            revision,
+            user_id,
+            definition,
+            diff
+        )
+        .execute(&mut **tx)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Quota
+    /// Lock the project `project_id` and enforce its quotas on the job,
+    /// `new` jobs also count towards the maximum number of jobs.
+    async fn quota(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        project_id: &Uuid,
+        new: bool,
+    ) -> Result<(), CrudError> {
+        let quota = match sqlx::query!(
+            r#"
+            SELECT p.max_jobs, p.min_interval, p.max_timeout, 
+            (SELECT COUNT(*) FROM jobs j 
+                WHERE j.project_id = p.project_id AND j.deleted_at IS NULL) AS "jobs!" 
+            FROM projects p WHERE p.project_id = $1 FOR UPDATE
+            "#,
+            project_id
+        )
+        .fetch_one(&mut **tx)
+        .await
+        {
+            Ok(quota) => quota,
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(CrudError::Read);
+            }
+        };
+
+        if new && quota.max_jobs.is_some_and(|max| quota.jobs >= max as i64) {
+            eprintln!("Project {} reached its maximum of jobs", project_id);
+            return Err(CrudError::Quota);
+        }
+
+        let (job_interval, _) = self.schedule();
+        if let (Some(interval), Some(min)) = (job_interval, quota.min_interval) {
+            if interval < min {
+                eprintln!(
+                    "Job '{}' runs more often than every {}s",
+                    self.job.name, min
+                );
+                return Err(CrudError::Quota);
+            }
+        }
+
+        if let (Some(timeout), Some(max)) = (self.job.timeout, quota.max_timeout) {
+            if timeout > max {
+                eprintln!(
+                    "Job '{}' exceeds the maximum timeout of {}s",
+                    self.job.name, max
+                );
+                return Err(CrudError::Quota);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Job definition, as snapshotted in revisions, inlined modules are
+    /// snapshotted by digest
+    fn definition(&self) -> Value {
+        let mut job = self.job.clone();
+        job.wasm = job.wasm.as_ref().map(Wasm::normalize);
+        serde_json::to_value(&job).unwrap_or_default()
+    }
+
+    /// Insert job payload and environment
+    async fn payload(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        job_id: &Uuid,
+    ) -> Result<(), CrudError> {
         if let Some(task) = &self.job.task {
-            self.task(&job_id, task).await?;
+            self.task(tx, job_id, task).await?;
         };
 
         if let Some(code) = &self.job.code {
-            self.code(&job_id, code).await?;
+            self.code(tx, job_id, code).await?;
         };
 
         if let Some(bin) = &self.job.bin {
-            self.bin(&job_id, bin).await?;
+            self.bin(tx, job_id, bin).await?;
+        };
+
+        if let Some(http) = &self.job.http {
+            self.http(tx, job_id, http).await?;
+        };
+
+        if let Some(sql) = &self.job.sql {
+            self.sql(tx, job_id, sql).await?;
+        };
+
+        if let Some(wasm) = &self.job.wasm {
+            self.wasm(tx, job_id, wasm).await?;
         };
 
-        tx_manager.commit(tx).await?;
+        if let Some(pipeline) = &self.job.pipeline {
+            self.pipeline(tx, job_id, pipeline).await?;
+        };
+
+        if let Some(env) = &self.job.env {
+            self.env(tx, job_id, env).await?;
+        };
 
         Ok(())
     }
 
-    async fn insert_inner(&self, user_id: &str) -> Result<Uuid, CrudError> {
-        let user_id = Uuid::parse_str(user_id).unwrap();
-        let job_id = self.job.gen_uuid();
-        let job_type = self.job.kind();
-
+    /// Interval (in seconds) and next run of the job schedule
+    fn schedule(&self) -> (Option<i32>, Option<OffsetDateTime>) {
         let schedule_str = self.job.schedule.as_ref().unwrap();
         let schedule = Schedule::new(schedule_str).parse().unwrap();
         let next_run = schedule.next_run();
 
-        let (job_interval, next_run_at) = match schedule.timestamp.time {
-            Time::Integer(int) => (Some(int as i32), Some(next_run)),
-            Time::Timestamp(_) => (None, Some(next_run)),
+        match (&schedule.routine, &schedule.timestamp.time) {
+            (Routine::Every, Time::Integer(int)) => (Some(*int as i32), Some(next_run)),
+            _ => (None, Some(next_run)),
+        }
+    }
+
+    /// Resource requests and limits, unset ones are stored as NULL
+    fn resources(&self) -> (Quantity, Quantity) {
+        let resources = self.job.resources.clone().unwrap_or_default();
+        (resources.requests, resources.limits.unwrap_or_default())
+    }
+
+    /// Resolve the priority class of the job, unknown classes are rejected
+    async fn priority_class(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Uuid>, CrudError> {
+        let Some(name) = &self.job.priority_class else {
+            return Ok(None);
         };
 
+        match sqlx::query_scalar!(
+            "SELECT priority_class_id FROM priority_classes WHERE class_name = $1",
+            name
+        )
+        .fetch_optional(&mut **tx)
+        .await
+        {
+            Ok(Some(priority_class_id)) => Ok(Some(priority_class_id)),
+            Ok(None) => {
+                eprintln!("Unknown priority class '{}'", name);
+                Err(CrudError::Validation)
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    async fn insert_inner(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+    ) -> Result<Uuid, CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+        let job_id = self.job.gen_uuid();
+        let job_type = self.job.kind();
+        let (job_interval, next_run_at) = self.schedule();
+        let (requests, limits) = self.resources();
+        let priority_class_id = self.priority_class(tx).await?;
+
         match sqlx::query!(
             r#"
-            INSERT INTO jobs (user_id, job_id, job_name, job_description, job_type, job_interval, next_run_at) 
-            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            INSERT INTO jobs (user_id, job_id, job_name, job_description, job_type, job_interval, next_run_at, schedule, labels, project_id, timeout, 
+            request_cpu, request_memory_mb, request_disk_mb, limit_cpu, limit_memory_mb, limit_disk_mb, 
+            node_selector, affinity, anti_affinity, priority_class_id, matrix, job_status, artifacts) 
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24)
             "#,
             user_id,
             job_id,
@@ -88,11 +475,32 @@ impl DB {
             self.job.description,
             job_type as JobType,
             job_interval,
-            next_run_at
+            next_run_at,
+            self.job.schedule,
+            Json(self.job.labels.clone().unwrap_or_default()) as _,
+            self.project,
+            self.job.timeout,
+            requests.cpu_millis(),
+            requests.memory_mb,
+            requests.disk_mb,
+            limits.cpu_millis(),
+            limits.memory_mb,
+            limits.disk_mb,
+            Json(self.job.node_selector.clone().unwrap_or_default()) as _,
+            Json(self.job.affinity.clone().unwrap_or_default()) as _,
+            Json(self.job.anti_affinity.clone().unwrap_or_default()) as _,
+            priority_class_id,
+            self.job.matrix.clone().map(Json) as _,
+            self.status as JobStatus,
+            self.job.artifacts.as_deref()
         )
-        .execute(&*self.pool)
+        .execute(&mut **tx)
         .await {
             Ok(_) => Ok(job_id),
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                eprintln!("Job '{}' already exists", self.job.name);
+                Err(CrudError::Conflict)
+            }
             Err(e) => {
                 eprintln!("{}", e);
                 Err(CrudError::Insertion)
@@ -100,7 +508,12 @@ impl DB {
         }
     }
 
-    async fn task(&self, job_id: &Uuid, task: &Task) -> Result<(), CrudError> {
+    async fn task(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        job_id: &Uuid,
+        task: &Task,
+    ) -> Result<(), CrudError> {
         match sqlx::query!(
             r#"
             INSERT INTO tasks (job_id, task_name) 
@@ -109,7 +522,7 @@ impl DB {
             job_id,
             task.name
         )
-        .execute(&*self.pool)
+        .execute(&mut **tx)
         .await
         {
             Ok(_) => Ok(()),
@@ -120,7 +533,12 @@ impl DB {
         }
     }
 
-    async fn code(&self, job_id: &Uuid, code: &Code) -> Result<(), CrudError> {
+    async fn code(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        job_id: &Uuid,
+        code: &Code,
+    ) -> Result<(), CrudError> {
         match sqlx::query!(
             r#"
             INSERT INTO codes (job_id, src, lang, cmd) 
@@ -131,7 +549,7 @@ impl DB {
             code.lang,
             code.cmd
         )
-        .execute(&*self.pool)
+        .execute(&mut **tx)
         .await
         {
             Ok(_) => Ok(()),
@@ -142,7 +560,12 @@ impl DB {
         }
     }
 
-    async fn bin(&self, job_id: &Uuid, bin: &Bin) -> Result<(), CrudError> {
+    async fn bin(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        job_id: &Uuid,
+        bin: &Bin,
+    ) -> Result<(), CrudError> {
         match sqlx::query!(
             r#"
         INSERT INTO bins (job_id, path, cmd) 
@@ -152,7 +575,7 @@ impl DB {
             bin.path,
             bin.cmd
         )
-        .execute(&*self.pool)
+        .execute(&mut **tx)
         .await
         {
             Ok(_) => Ok(()),
@@ -163,25 +586,906 @@ impl DB {
         }
     }
 
-    pub async fn delete(&self, user_id: String) -> Result<(), CrudError> {
-        let user_id = Uuid::parse_str(&user_id).unwrap();
+    /// Insert the request of an http job, along with its headers
+    async fn http(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        job_id: &Uuid,
+        http: &Http,
+    ) -> Result<(), CrudError> {
+        if let Err(e) = sqlx::query!(
+            r#"
+            INSERT INTO http_requests (job_id, method, url, body, timeout, expected_status, assertion) 
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            job_id,
+            http.method,
+            http.url,
+            http.body,
+            http.timeout,
+            http.expected_status.as_deref().unwrap_or_default(),
+            http.assertion
+        )
+        .execute(&mut **tx)
+        .await
+        {
+            eprintln!("{}", e);
+            return Err(CrudError::Insertion);
+        }
 
+        for (name, value) in http.headers.iter().flatten() {
+            let result = match value {
+                Env::Value(value) => {
+                    sqlx::query!(
+                        r#"
+                        INSERT INTO http_headers (job_id, header_name, header_value) 
+                        VALUES ($1, $2, $3)
+                        "#,
+                        job_id,
+                        name,
+                        value
+                    )
+                    .execute(&mut **tx)
+                    .await
+                }
+                Env::Secret { secret } => {
+                    sqlx::query!(
+                        r#"
+                        INSERT INTO http_headers (job_id, header_name, secret_id) 
+                        SELECT $1, $2, secret_id FROM secrets 
+                        WHERE user_id = (SELECT user_id FROM jobs WHERE job_id = $1) 
+                        AND secret_name = $3
+                        "#,
+                        job_id,
+                        name,
+                        secret
+                    )
+                    .execute(&mut **tx)
+                    .await
+                }
+            };
+
+            match result {
+                Ok(result) if result.rows_affected() == 1 => {}
+                Ok(_) => {
+                    eprintln!("Unknown secret referenced by header '{}'", name);
+                    return Err(CrudError::Validation);
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return Err(CrudError::Insertion);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Insert the statements of a sql job, the connection is resolved against
+    /// the secrets of the job owner and fails if no such secret exists.
+    async fn sql(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        job_id: &Uuid,
+        sql: &Sql,
+    ) -> Result<(), CrudError> {
+        match sqlx::query!(
+            r#"
+            INSERT INTO sql_queries (job_id, secret_id, statements, statement_timeout) 
+            SELECT $1, secret_id, $3, $4 FROM secrets 
+            WHERE user_id = (SELECT user_id FROM jobs WHERE job_id = $1) 
+            AND secret_name = $2
+            "#,
+            job_id,
+            sql.connection,
+            &sql.statements,
+            sql.timeout
+        )
+        .execute(&mut **tx)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 1 => Ok(()),
+            Ok(_) => {
+                eprintln!(
+                    "Unknown secret referenced by connection '{}'",
+                    sql.connection
+                );
+                Err(CrudError::Validation)
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// Insert the module reference of a wasm job. Inlined modules are stored
+    /// first, uploaded ones are resolved against the modules of the job owner.
+    async fn wasm(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        job_id: &Uuid,
+        wasm: &Wasm,
+    ) -> Result<(), CrudError> {
+        if let Some(module) = wasm.module() {
+            if let Err(e) = sqlx::query!(
+                r#"
+                INSERT INTO wasm_modules (user_id, digest, module) 
+                SELECT user_id, $2, $3 FROM jobs WHERE job_id = $1 
+                ON CONFLICT ON CONSTRAINT unique_module_digest_per_user DO NOTHING
+                "#,
+                job_id,
+                crate::module::digest(&module),
+                module
+            )
+            .execute(&mut **tx)
+            .await
+            {
+                eprintln!("{}", e);
+                return Err(CrudError::Insertion);
+            }
+        }
+
+        let digest = wasm.normalize().digest.unwrap_or_default();
+
+        match sqlx::query!(
+            r#"
+            INSERT INTO wasm_jobs (job_id, module_id, args, fuel) 
+            SELECT $1, module_id, $3, $4 FROM wasm_modules 
+            WHERE user_id = (SELECT user_id FROM jobs WHERE job_id = $1) 
+            AND digest = $2
+            "#,
+            job_id,
+            digest,
+            &wasm.args,
+            wasm.fuel
+        )
+        .execute(&mut **tx)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 1 => Ok(()),
+            Ok(_) => {
+                eprintln!("Unknown module '{}'", digest);
+                Err(CrudError::Validation)
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// Insert the steps of a pipeline job. Secrets referenced by the headers of
+    /// http steps are resolved against the secrets of the job owner, and fail
+    /// if no such secret exists.
+    async fn pipeline(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        job_id: &Uuid,
+        pipeline: &Pipeline,
+    ) -> Result<(), CrudError> {
+        if let Err(e) = sqlx::query!(
+            r#"
+            INSERT INTO pipelines (job_id, steps) 
+            VALUES ($1, $2)
+            "#,
+            job_id,
+            Json(pipeline.clone().normalize().steps) as _
+        )
+        .execute(&mut **tx)
+        .await
+        {
+            eprintln!("{}", e);
+            return Err(CrudError::Insertion);
+        }
+
+        for secret in pipeline.secrets() {
+            match sqlx::query!(
+                r#"
+                INSERT INTO pipeline_secrets (job_id, secret_id) 
+                SELECT $1, secret_id FROM secrets 
+                WHERE user_id = (SELECT user_id FROM jobs WHERE job_id = $1) 
+                AND secret_name = $2
+                "#,
+                job_id,
+                secret
+            )
+            .execute(&mut **tx)
+            .await
+            {
+                Ok(result) if result.rows_affected() == 1 => {}
+                Ok(_) => {
+                    eprintln!("Unknown secret '{}' referenced by a step", secret);
+                    return Err(CrudError::Validation);
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return Err(CrudError::Insertion);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Insert job environment, secret references are resolved against
+    /// the secrets of the job owner and fail if no such secret exists.
+    async fn env(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        job_id: &Uuid,
+        env: &HashMap<String, Env>,
+    ) -> Result<(), CrudError> {
+        for (name, value) in env {
+            let result = match value {
+                Env::Value(value) => {
+                    sqlx::query!(
+                        r#"
+                        INSERT INTO envs (job_id, env_name, env_value) 
+                        VALUES ($1, $2, $3)
+                        "#,
+                        job_id,
+                        name,
+                        value
+                    )
+                    .execute(&mut **tx)
+                    .await
+                }
+                Env::Secret { secret } => {
+                    sqlx::query!(
+                        r#"
+                        INSERT INTO envs (job_id, env_name, secret_id) 
+                        SELECT $1, $2, secret_id FROM secrets 
+                        WHERE user_id = (SELECT user_id FROM jobs WHERE job_id = $1) 
+                        AND secret_name = $3
+                        "#,
+                        job_id,
+                        name,
+                        secret
+                    )
+                    .execute(&mut **tx)
+                    .await
+                }
+            };
+
+            match result {
+                Ok(result) if result.rows_affected() == 1 => {}
+                Ok(_) => {
+                    eprintln!("Unknown secret referenced by env '{}'", name);
+                    return Err(CrudError::Validation);
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return Err(CrudError::Insertion);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// # Delete
+    /// Move the job named `self.job.name` to the trash of the project, see
+    /// `discard`.
+    pub async fn delete(&self) -> Result<(), CrudError> {
         let tx_manager = Tx::new(self.pool.clone());
-        let tx = tx_manager.init().await?;
+        let mut tx = tx_manager.init().await?;
 
-        sqlx::query!(
+        let job_id = sqlx::query_scalar!(
             r#"
-            DELETE FROM jobs WHERE user_id=$1 AND job_name=$2
+            SELECT job_id FROM jobs 
+            WHERE project_id = $1 AND job_name = $2 AND deleted_at IS NULL FOR UPDATE
             "#,
-            user_id,
+            self.project,
             self.job.name
         )
+        .fetch_optional(&mut *tx)
+        .await;
+
+        let result = match job_id {
+            Ok(Some(job_id)) => self.discard(&mut tx, &job_id).await,
+            Ok(None) => Err(CrudError::NotFound),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        };
+
+        match result {
+            Ok(_) => tx_manager.commit(tx).await,
+            Err(error) => {
+                tx_manager.rollback(tx).await?;
+                Err(error)
+            }
+        }
+    }
+
+    /// # Delete by ID
+    /// Move the job `job_id` of the project to the trash, see `discard`.
+    pub async fn delete_by_id(&self, job_id: &Uuid) -> Result<(), CrudError> {
+        let tx_manager = Tx::new(self.pool.clone());
+        let mut tx = tx_manager.init().await?;
+
+        match self.discard(&mut tx, job_id).await {
+            Ok(_) => tx_manager.commit(tx).await,
+            Err(error) => {
+                tx_manager.rollback(tx).await?;
+                Err(error)
+            }
+        }
+    }
+
+    /// # Discard
+    /// Move the job `job_id` of the project to the trash, where it is no
+    /// longer dispatched nor listed, and its name can be reused. Its runs yet
+    /// to start are cancelled, running runs complete.
+    ///
+    /// ## Errors
+    ///
+    /// `Err(CrudError::NotFound)` if the project has no such job.
+    async fn discard(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        job_id: &Uuid,
+    ) -> Result<(), CrudError> {
+        match sqlx::query!(
+            r#"
+            UPDATE jobs SET deleted_at = NOW() 
+            WHERE project_id = $1 AND job_id = $2 AND deleted_at IS NULL
+            "#,
+            self.project,
+            job_id
+        )
+        .execute(&mut **tx)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 1 => {}
+            Ok(_) => return Err(CrudError::NotFound),
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(CrudError::Insertion);
+            }
+        }
+
+        match sqlx::query!(
+            r#"
+            UPDATE runs SET run_status = 'cancelled', finished_at = NOW(), reason = 'Job deleted' 
+            WHERE job_id = $1 AND run_status IN ('queued', 'unschedulable')
+            "#,
+            job_id
+        )
+        .execute(&mut **tx)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Pause
+    /// Stop dispatching the scheduled job `job_id` of the project, its runs
+    /// already queued or running are left alone.
+    ///
+    /// ## Errors
+    ///
+    /// `Err(CrudError::NotFound)` if the project has no such scheduled job.
+    pub async fn pause(&self, job_id: &Uuid) -> Result<(), CrudError> {
+        match sqlx::query!(
+            r#"
+            UPDATE jobs SET job_status = 'paused' 
+            WHERE project_id = $1 AND job_id = $2 AND job_status = 'scheduled' AND deleted_at IS NULL
+            "#,
+            self.project,
+            job_id
+        )
         .execute(&*self.pool)
         .await
-        .unwrap();
+        {
+            Ok(result) if result.rows_affected() == 1 => Ok(()),
+            Ok(_) => Err(CrudError::NotFound),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Resume
+    /// Dispatch the paused job `job_id` of the project again, from the next
+    /// fire time of its schedule after now. Fire times missed while paused
+    /// are skipped.
+    ///
+    /// ## Errors
+    ///
+    /// `Err(CrudError::NotFound)` if the project has no such paused job.
+    pub async fn resume(&self, job_id: &Uuid) -> Result<(), CrudError> {
+        let schedule = match sqlx::query_scalar!(
+            r#"
+            SELECT schedule FROM jobs 
+            WHERE project_id = $1 AND job_id = $2 AND job_status = 'paused' AND deleted_at IS NULL
+            "#,
+            self.project,
+            job_id
+        )
+        .fetch_optional(&*self.pool)
+        .await
+        {
+            Ok(Some(schedule)) => schedule,
+            Ok(None) => return Err(CrudError::NotFound),
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(CrudError::Read);
+            }
+        };
 
-        tx_manager.commit(tx).await?;
+        let next_run_at = schedule
+            .as_deref()
+            .and_then(|schedule| Schedule::new(schedule).parse().ok())
+            .map(|schedule| schedule.next_run());
 
-        Ok(())
+        match sqlx::query!(
+            r#"
+            UPDATE jobs SET job_status = 'scheduled', next_run_at = COALESCE($3, next_run_at) 
+            WHERE project_id = $1 AND job_id = $2 AND job_status = 'paused' AND deleted_at IS NULL
+            "#,
+            self.project,
+            job_id,
+            next_run_at
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 1 => Ok(()),
+            Ok(_) => Err(CrudError::NotFound),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Trash
+    /// Deleted jobs of the project, most recently deleted first, along with
+    /// the time they are purged at.
+    pub async fn trash(&self) -> Result<Vec<TrashedJob>, CrudError> {
+        match sqlx::query_as!(
+            TrashedJob,
+            r#"
+            SELECT j.job_id AS id, j.job_name AS name, j.deleted_at AS "deleted_at!", 
+            j.deleted_at + make_interval(days => COALESCE(p.trash_retention_days, $2)) AS "purge_at!" 
+            FROM jobs j JOIN projects p ON p.project_id = j.project_id 
+            WHERE j.project_id = $1 AND j.deleted_at IS NOT NULL 
+            ORDER BY j.deleted_at DESC
+            "#,
+            self.project,
+            DEFAULT_TRASH_RETENTION_DAYS
+        )
+        .fetch_all(&*self.pool)
+        .await
+        {
+            Ok(jobs) => Ok(jobs),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// # Restore
+    /// Move the job `job_id` back from the trash of the project. Its
+    /// schedule resumes from the next fire time after now.
+    ///
+    /// ## Errors
+    ///
+    /// - `Err(CrudError::NotFound)` if the trash has no such job.
+    /// - `Err(CrudError::Conflict)` if another job took its name since.
+    /// - `Err(CrudError::Quota)` if the project reached its maximum of jobs.
+    pub async fn restore(&self, job_id: &Uuid) -> Result<(), CrudError> {
+        let tx_manager = Tx::new(self.pool.clone());
+        let mut tx = tx_manager.init().await?;
+
+        match self.restore_inner(&mut tx, job_id).await {
+            Ok(_) => tx_manager.commit(tx).await,
+            Err(error) => {
+                tx_manager.rollback(tx).await?;
+                Err(error)
+            }
+        }
+    }
+
+    async fn restore_inner(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        job_id: &Uuid,
+    ) -> Result<(), CrudError> {
+        // jobs are inserted with the project locked, see `quota`
+        let quota = match sqlx::query!(
+            r#"
+            SELECT p.max_jobs, 
+            (SELECT COUNT(*) FROM jobs j 
+                WHERE j.project_id = p.project_id AND j.deleted_at IS NULL) AS "jobs!", 
+            EXISTS (
+                SELECT 1 FROM jobs j JOIN jobs t ON t.job_name = j.job_name 
+                WHERE j.project_id = p.project_id AND t.job_id = $2 AND j.deleted_at IS NULL
+            ) AS "taken!" 
+            FROM projects p WHERE p.project_id = $1 FOR UPDATE
+            "#,
+            self.project,
+            job_id
+        )
+        .fetch_one(&mut **tx)
+        .await
+        {
+            Ok(quota) => quota,
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(CrudError::Read);
+            }
+        };
+
+        if quota.taken {
+            eprintln!("Job {} cannot be restored, its name was taken", job_id);
+            return Err(CrudError::Conflict);
+        }
+
+        if quota.max_jobs.is_some_and(|max| quota.jobs >= max as i64) {
+            eprintln!("Project {} reached its maximum of jobs", self.project);
+            return Err(CrudError::Quota);
+        }
+
+        match sqlx::query!(
+            r#"
+            UPDATE jobs SET deleted_at = NULL 
+            WHERE project_id = $1 AND job_id = $2 AND deleted_at IS NOT NULL
+            "#,
+            self.project,
+            job_id
+        )
+        .execute(&mut **tx)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 1 => Ok(()),
+            Ok(_) => Err(CrudError::NotFound),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Purge
+    /// Permanently delete the job `job_id` from the trash of the project,
+    /// along with its revisions and runs.
+    pub async fn purge(&self, job_id: &Uuid) -> Result<(), CrudError> {
+        match sqlx::query!(
+            r#"
+            DELETE FROM jobs WHERE project_id = $1 AND job_id = $2 AND deleted_at IS NOT NULL
+            "#,
+            self.project,
+            job_id
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 1 => Ok(()),
+            Ok(_) => Err(CrudError::NotFound),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Apply
+    /// Plan a manifest against the current jobs of the project and apply it
+    /// within one transaction. The jobs are locked before they are read, so
+    /// that the plan is not computed from jobs changed concurrently. Deletions
+    /// go first, so that pruned jobs free up the project quota.
+    ///
+    /// ## Returns
+    ///
+    /// The diff of the manifest, which is not applied on a dry run.
+    pub async fn apply(
+        &self,
+        user_id: &str,
+        manifest: Manifest,
+        dry_run: bool,
+    ) -> Result<Diff, CrudError> {
+        let tx_manager = Tx::new(self.pool.clone());
+        let mut tx = tx_manager.init().await?;
+
+        match self.apply_inner(&mut tx, user_id, manifest, dry_run).await {
+            Ok(diff) => {
+                tx_manager.commit(tx).await?;
+                Ok(diff)
+            }
+            Err(error) => {
+                tx_manager.rollback(tx).await?;
+                Err(error)
+            }
+        }
+    }
+
+    async fn apply_inner(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+        manifest: Manifest,
+        dry_run: bool,
+    ) -> Result<Diff, CrudError> {
+        if let Err(e) = sqlx::query!(
+            r#"
+            SELECT job_id FROM jobs WHERE project_id = $1 AND deleted_at IS NULL FOR UPDATE
+            "#,
+            self.project
+        )
+        .fetch_all(&mut **tx)
+        .await
+        {
+            eprintln!("{}", e);
+            return Err(CrudError::Read);
+        }
+
+        let current = self.select(tx, None).await?;
+        let (plan, mut diff) = manifest.plan(current);
+        diff.dry_run = dry_run;
+
+        if dry_run {
+            return Ok(diff);
+        }
+
+        for job_id in plan.delete {
+            self.discard(tx, &job_id).await?;
+        }
+
+        for (job_id, job) in plan.update {
+            DB::new(self.pool.clone())
+                .job(job)
+                .project(self.project)
+                .replace(tx, &job_id, user_id)
+                .await?;
+        }
+
+        for job in plan.create {
+            DB::new(self.pool.clone())
+                .job(job)
+                .project(self.project)
+                .insert_all(tx, user_id)
+                .await?;
+        }
+
+        Ok(diff)
+    }
+
+    /// # List Jobs
+    /// Read back every job of the project in its API shape.
+    pub async fn list(&self) -> Result<Vec<JobRecord>, CrudError> {
+        let mut conn = match self.pool.acquire().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(CrudError::Read);
+            }
+        };
+
+        self.select(&mut conn, None).await
+    }
+
+    /// # Get Job
+    /// Read back the job `job_id` of the project in its API shape.
+    ///
+    /// ## Errors
+    ///
+    /// `Err(CrudError::NotFound)` if the project has no such job.
+    pub async fn get(&self, job_id: &Uuid) -> Result<JobRecord, CrudError> {
+        let mut conn = match self.pool.acquire().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(CrudError::Read);
+            }
+        };
+
+        self.select(&mut conn, Some(job_id))
+            .await?
+            .pop()
+            .ok_or(CrudError::NotFound)
+    }
+
+    async fn select(
+        &self,
+        conn: &mut PgConnection,
+        job_id: Option<&Uuid>,
+    ) -> Result<Vec<JobRecord>, CrudError> {
+        let rows = match sqlx::query!(
+            r#"
+            SELECT j.job_id, j.job_name, j.job_description, j.schedule, j.timeout, 
+            j.request_cpu, j.request_memory_mb, j.request_disk_mb, 
+            j.limit_cpu, j.limit_memory_mb, j.limit_disk_mb, 
+            j.node_selector AS "node_selector: Json<HashMap<String, String>>", 
+            j.affinity AS "affinity: Json<Vec<Term>>", 
+            j.anti_affinity AS "anti_affinity: Json<Vec<Term>>", 
+            j.matrix AS "matrix: Json<Matrix>", j.artifacts, 
+            j.labels AS "labels: Json<HashMap<String, String>>", 
+            pc.class_name AS "priority_class?", 
+            t.task_name AS "task_name?", 
+            c.src AS "src?", c.lang AS "lang?", c.cmd AS "code_cmd?", 
+            b.path AS "path?", b.cmd AS "bin_cmd?", 
+            h.method AS "method?", h.url AS "url?", h.body AS "http_body?", 
+            h.timeout AS "http_timeout?", h.expected_status AS "expected_status?", 
+            h.assertion AS "assertion?", 
+            cs.secret_name AS "connection?", q.statements AS "statements?", 
+            q.statement_timeout AS "statement_timeout?", 
+            wm.digest AS "digest?", w.args AS "args?", w.fuel AS "fuel?", 
+            p.steps AS "steps?: Json<Vec<Step>>" 
+            FROM jobs j 
+            LEFT JOIN tasks t ON t.job_id = j.job_id 
+            LEFT JOIN codes c ON c.job_id = j.job_id 
+            LEFT JOIN bins b ON b.job_id = j.job_id 
+            LEFT JOIN http_requests h ON h.job_id = j.job_id 
+            LEFT JOIN sql_queries q ON q.job_id = j.job_id 
+            LEFT JOIN secrets cs ON cs.secret_id = q.secret_id 
+            LEFT JOIN wasm_jobs w ON w.job_id = j.job_id 
+            LEFT JOIN wasm_modules wm ON wm.module_id = w.module_id 
+            LEFT JOIN pipelines p ON p.job_id = j.job_id 
+            LEFT JOIN priority_classes pc ON pc.priority_class_id = j.priority_class_id 
+            WHERE j.project_id = $1 AND j.deleted_at IS NULL AND ($2::uuid IS NULL OR j.job_id = $2) 
+            ORDER BY j.job_name
+            "#,
+            self.project,
+            job_id
+        )
+        .fetch_all(&mut *conn)
+        .await
+        {
+            Ok(rows) => rows,
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(CrudError::Read);
+            }
+        };
+
+        let envs = match sqlx::query!(
+            r#"
+            SELECT e.job_id AS "job_id!", e.env_name, e.env_value, s.secret_name AS "secret_name?" 
+            FROM envs e 
+            JOIN jobs j ON j.job_id = e.job_id 
+            LEFT JOIN secrets s ON s.secret_id = e.secret_id 
+            WHERE j.project_id = $1 AND ($2::uuid IS NULL OR j.job_id = $2)
+            "#,
+            self.project,
+            job_id
+        )
+        .fetch_all(&mut *conn)
+        .await
+        {
+            Ok(rows) => rows,
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(CrudError::Read);
+            }
+        };
+
+        let headers = match sqlx::query!(
+            r#"
+            SELECT h.job_id AS "job_id!", h.header_name, h.header_value, s.secret_name AS "secret_name?" 
+            FROM http_headers h 
+            JOIN jobs j ON j.job_id = h.job_id 
+            LEFT JOIN secrets s ON s.secret_id = h.secret_id 
+            WHERE j.project_id = $1 AND ($2::uuid IS NULL OR j.job_id = $2)
+            "#,
+            self.project,
+            job_id
+        )
+        .fetch_all(&mut *conn)
+        .await
+        {
+            Ok(rows) => rows,
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(CrudError::Read);
+            }
+        };
+
+        let mut header: HashMap<Uuid, HashMap<String, Env>> = HashMap::new();
+        for row in headers {
+            let value = match (row.header_value, row.secret_name) {
+                (Some(value), _) => Env::Value(value),
+                (None, Some(secret)) => Env::Secret { secret },
+                (None, None) => continue,
+            };
+            header
+                .entry(row.job_id)
+                .or_default()
+                .insert(row.header_name, value);
+        }
+
+        let mut env: HashMap<Uuid, HashMap<String, Env>> = HashMap::new();
+        for row in envs {
+            let value = match (row.env_value, row.secret_name) {
+                (Some(value), _) => Env::Value(value),
+                (None, Some(secret)) => Env::Secret { secret },
+                (None, None) => continue,
+            };
+            env.entry(row.job_id)
+                .or_default()
+                .insert(row.env_name, value);
+        }
+
+        Ok(rows
+            .into_iter()
+            .map(|row| JobRecord {
+                id: row.job_id,
+                job: Job {
+                    name: row.job_name,
+                    description: row.job_description,
+                    schedule: row.schedule,
+                    task: row.task_name.map(|name| Task { name }),
+                    code: match (row.src, row.lang, row.code_cmd) {
+                        (Some(src), Some(lang), Some(cmd)) => Some(Code { src, lang, cmd }),
+                        _ => None,
+                    },
+                    bin: row.path.map(|path| Bin {
+                        path,
+                        cmd: row.bin_cmd,
+                    }),
+                    http: match (row.method, row.url) {
+                        (Some(method), Some(url)) => Some(Http {
+                            method,
+                            url,
+                            headers: header.remove(&row.job_id),
+                            body: row.http_body,
+                            timeout: row.http_timeout,
+                            expected_status: row.expected_status.filter(|codes| !codes.is_empty()),
+                            assertion: row.assertion,
+                        }),
+                        _ => None,
+                    },
+                    sql: match (row.connection, row.statements) {
+                        (Some(connection), Some(statements)) => Some(Sql {
+                            connection,
+                            statements,
+                            timeout: row.statement_timeout,
+                        }),
+                        _ => None,
+                    },
+                    wasm: match (row.digest, row.args) {
+                        (Some(digest), Some(args)) => Some(Wasm {
+                            module: None,
+                            digest: Some(digest),
+                            args,
+                            fuel: row.fuel,
+                        }),
+                        _ => None,
+                    },
+                    pipeline: row.steps.map(|steps| Pipeline { steps: steps.0 }),
+                    env: env.remove(&row.job_id),
+                    labels: Some(row.labels.0).filter(|labels| !labels.is_empty()),
+                    timeout: row.timeout,
+                    matrix: row.matrix.map(|matrix| matrix.0),
+                    artifacts: row.artifacts,
+                    resources: Resources {
+                        requests: Quantity::from_millis(
+                            row.request_cpu,
+                            row.request_memory_mb,
+                            row.request_disk_mb,
+                        ),
+                        limits: Some(Quantity::from_millis(
+                            row.limit_cpu,
+                            row.limit_memory_mb,
+                            row.limit_disk_mb,
+                        )),
+                    }
+                    .normalize(),
+                    node_selector: Some(row.node_selector.0).filter(|labels| !labels.is_empty()),
+                    affinity: Some(row.affinity.0).filter(|terms| !terms.is_empty()),
+                    anti_affinity: Some(row.anti_affinity.0).filter(|terms| !terms.is_empty()),
+                    priority_class: row.priority_class,
+                },
+            })
+            .collect())
     }
 }
@@ -0,0 +1,109 @@
+//! WebAssembly Module-related Crud Ops
+
+extern crate schedin_common;
+extern crate sqlx;
+extern crate std;
+extern crate uuid;
+
+use crate::module::{self, schema::ModuleRow};
+use schedin_common::error::CrudError;
+use sqlx::{query, query_as, PgPool, Pool, Postgres};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub struct Module {
+    pub pool: Arc<PgPool>,
+    pub module: Vec<u8>,
+}
+
+impl Module {
+    /// New Module
+    pub fn new(pool: Arc<Pool<Postgres>>) -> Self {
+        Self {
+            pool,
+            module: Vec::new(),
+        }
+    }
+
+    /// Sets and returns modified module
+    pub fn module(mut self, module: Vec<u8>) -> Self {
+        self.module = module;
+        self
+    }
+
+    /// # Insert Module
+    /// Stores the module once per user, returns its digest.
+    pub async fn insert(&self, user_id: &str) -> Result<String, CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+        let digest = module::digest(&self.module);
+
+        match query!(
+            r#"
+            INSERT INTO wasm_modules (user_id, digest, module) 
+            VALUES ($1, $2, $3) 
+            ON CONFLICT ON CONSTRAINT unique_module_digest_per_user DO NOTHING
+            "#,
+            user_id,
+            digest,
+            self.module
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(_) => Ok(digest),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// List modules of a user, along with their number of jobs
+    pub async fn list(&self, user_id: &str) -> Result<Vec<ModuleRow>, CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query_as!(
+            ModuleRow,
+            r#"
+            SELECT m.digest, LENGTH(m.module) AS "size!", 
+            (SELECT COUNT(*) FROM wasm_jobs w WHERE w.module_id = m.module_id) AS "jobs!", 
+            m.created_at 
+            FROM wasm_modules m WHERE m.user_id = $1 ORDER BY m.created_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&*self.pool)
+        .await
+        {
+            Ok(rows) => Ok(rows),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// Delete Module `digest`, fails while any job still references it
+    pub async fn delete(&self, user_id: &str, digest: &str) -> Result<(), CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query!(
+            "DELETE FROM wasm_modules WHERE user_id = $1 AND digest = $2",
+            user_id,
+            digest
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 0 => Err(CrudError::NotFound),
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => {
+                Err(CrudError::Conflict)
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! Notification-related Crud Ops
+
+extern crate schedin_common;
+extern crate sqlx;
+extern crate std;
+extern crate uuid;
+
+use crate::notification::schema::{self, ChannelKind, ChannelRow, Delivery, Event, RuleRow};
+use schedin_common::error::CrudError;
+use sqlx::{query, query_as, PgPool, Pool, Postgres};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub struct Channel {
+    pub pool: Arc<PgPool>,
+    pub channel: schema::Channel,
+    pub project_id: Option<Uuid>,
+}
+
+impl Channel {
+    /// New Channel
+    pub fn new(pool: Arc<Pool<Postgres>>) -> Self {
+        Self {
+            pool,
+            channel: schema::Channel::default(),
+            project_id: None,
+        }
+    }
+
+    /// Sets and returns modified channel
+    pub fn channel(mut self, channel: schema::Channel) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    /// Sets and returns modified project of the channel
+    pub fn project(mut self, project_id: Option<Uuid>) -> Self {
+        self.project_id = project_id;
+        self
+    }
+
+    /// # Upsert Channel
+    /// Stores the channel, replacing any existing channel of the same name.
+    /// Rules of the channel keep notifying it.
+    pub async fn upsert(&self, user_id: &str) -> Result<(), CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query!(
+            r#"
+            INSERT INTO notification_channels (user_id, project_id, channel_name, kind, url, recipients)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT ON CONSTRAINT unique_channel_name_per_user
+            DO UPDATE SET project_id = EXCLUDED.project_id, kind = EXCLUDED.kind,
+            url = EXCLUDED.url, recipients = EXCLUDED.recipients, updated_at = NOW()
+            "#,
+            user_id,
+            self.project_id,
+            self.channel.name,
+            self.channel.kind as ChannelKind,
+            self.channel.url,
+            self.channel.recipients.as_deref()
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// List channels of a user
+    pub async fn list(&self, user_id: &str) -> Result<Vec<ChannelRow>, CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query_as!(
+            ChannelRow,
+            r#"
+            SELECT c.channel_name AS name, p.project_name AS "project?", c.kind AS "kind: _",
+            c.url, c.recipients, c.created_at, c.updated_at
+            FROM notification_channels c LEFT JOIN projects p ON p.project_id = c.project_id
+            WHERE c.user_id = $1 ORDER BY c.channel_name
+            "#,
+            user_id
+        )
+        .fetch_all(&*self.pool)
+        .await
+        {
+            Ok(rows) => Ok(rows),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// Delete Channel, along with its rules
+    pub async fn delete(&self, user_id: &str) -> Result<(), CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query!(
+            r#"
+            DELETE FROM notification_channels WHERE user_id = $1 AND channel_name = $2
+            "#,
+            user_id,
+            self.channel.name
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 0 => Err(CrudError::NotFound),
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => {
+                Err(CrudError::Conflict)
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Deliveries
+    /// Delivery log of a user, most recent first
+    pub async fn deliveries(&self, user_id: &str, limit: i64) -> Result<Vec<Delivery>, CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query_as!(
+            Delivery,
+            r#"
+            SELECT delivery_id AS id, rule_name AS rule, channel_name AS channel,
+            job_name AS job, run_id, event AS "event: _", delivery_status AS "status: _",
+            attempts, message, response_status, last_error, created_at, next_attempt_at,
+            delivered_at
+            FROM notification_deliveries WHERE user_id = $1
+            ORDER BY created_at DESC LIMIT $2
+            "#,
+            user_id,
+            limit
+        )
+        .fetch_all(&*self.pool)
+        .await
+        {
+            Ok(rows) => Ok(rows),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+}
+
+pub struct Rule {
+    pub pool: Arc<PgPool>,
+    pub rule: schema::Rule,
+    pub project_id: Option<Uuid>,
+}
+
+impl Rule {
+    /// New Rule
+    pub fn new(pool: Arc<Pool<Postgres>>) -> Self {
+        Self {
+            pool,
+            rule: schema::Rule::default(),
+            project_id: None,
+        }
+    }
+
+    /// Sets and returns modified rule
+    pub fn rule(mut self, rule: schema::Rule) -> Self {
+        self.rule = rule;
+        self
+    }
+
+    /// Sets and returns modified project of the rule
+    pub fn project(mut self, project_id: Option<Uuid>) -> Self {
+        self.project_id = project_id;
+        self
+    }
+
+    /// # Upsert Rule
+    /// Stores the rule, replacing any existing rule of the same name.
+    ///
+    /// ## Errors
+    ///
+    /// `CrudError::Validation` if the channel is not one of the user, or
+    /// belongs to another project than the rule.
+    pub async fn upsert(&self, user_id: &str) -> Result<(), CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        let channel = match query!(
+            r#"
+            SELECT channel_id, project_id FROM notification_channels
+            WHERE user_id = $1 AND channel_name = $2
+            "#,
+            user_id,
+            self.rule.channel
+        )
+        .fetch_optional(&*self.pool)
+        .await
+        {
+            Ok(Some(channel)) => channel,
+            Ok(None) => return Err(CrudError::Validation),
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(CrudError::Read);
+            }
+        };
+
+        // project channels only serve the rules of their project
+        if channel.project_id.is_some() && channel.project_id != self.project_id {
+            return Err(CrudError::Validation);
+        }
+
+        match query!(
+            r#"
+            INSERT INTO notification_rules (user_id, project_id, rule_name, channel_id, job_name,
+            event, threshold, template)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT ON CONSTRAINT unique_rule_name_per_user
+            DO UPDATE SET project_id = EXCLUDED.project_id, channel_id = EXCLUDED.channel_id,
+            job_name = EXCLUDED.job_name, event = EXCLUDED.event, threshold = EXCLUDED.threshold,
+            template = EXCLUDED.template, updated_at = NOW()
+            "#,
+            user_id,
+            self.project_id,
+            self.rule.name,
+            channel.channel_id,
+            self.rule.job,
+            self.rule.event as Event,
+            self.rule.threshold,
+            self.rule.template
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// List rules of a user
+    pub async fn list(&self, user_id: &str) -> Result<Vec<RuleRow>, CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query_as!(
+            RuleRow,
+            r#"
+            SELECT r.rule_name AS name, c.channel_name AS channel, p.project_name AS "project?",
+            r.job_name AS job, r.event AS "event: _", r.threshold, r.template,
+            r.created_at, r.updated_at
+            FROM notification_rules r
+            JOIN notification_channels c ON c.channel_id = r.channel_id
+            LEFT JOIN projects p ON p.project_id = r.project_id
+            WHERE r.user_id = $1 ORDER BY r.rule_name
+            "#,
+            user_id
+        )
+        .fetch_all(&*self.pool)
+        .await
+        {
+            Ok(rows) => Ok(rows),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// Delete Rule
+    pub async fn delete(&self, user_id: &str) -> Result<(), CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query!(
+            r#"
+            DELETE FROM notification_rules WHERE user_id = $1 AND rule_name = $2
+            "#,
+            user_id,
+            self.rule.name
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 0 => Err(CrudError::NotFound),
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => {
+                Err(CrudError::Conflict)
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Priority Class-related Crud Ops
+
+extern crate schedin_common;
+extern crate sqlx;
+extern crate std;
+
+use crate::priority::schema::{self, PriorityClassRow};
+use schedin_common::error::CrudError;
+use sqlx::{query, query_as, PgPool, Pool, Postgres};
+use std::sync::Arc;
+
+pub struct PriorityClass {
+    pub pool: Arc<PgPool>,
+    pub class: schema::PriorityClass,
+}
+
+impl PriorityClass {
+    /// New Priority Class
+    pub fn new(pool: Arc<Pool<Postgres>>) -> Self {
+        Self {
+            pool,
+            class: schema::PriorityClass::default(),
+        }
+    }
+
+    /// Sets and returns modified priority class
+    pub fn class(mut self, class: schema::PriorityClass) -> Self {
+        self.class = class;
+        self
+    }
+
+    /// # Upsert Priority Class
+    /// Stores the class, replacing any existing class of the same name.
+    /// New values apply to runs dispatched afterwards.
+    pub async fn upsert(&self) -> Result<(), CrudError> {
+        let Some(value) = self.class.value else {
+            return Err(CrudError::Validation);
+        };
+
+        match query!(
+            r#"
+            INSERT INTO priority_classes (class_name, value, description, preempt)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (class_name)
+            DO UPDATE SET value = EXCLUDED.value, description = EXCLUDED.description,
+            preempt = EXCLUDED.preempt, updated_at = NOW()
+            "#,
+            self.class.name,
+            value,
+            self.class.description,
+            self.class.preempt
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// List priority classes, highest value first, along with their number of jobs
+    pub async fn list(&self) -> Result<Vec<PriorityClassRow>, CrudError> {
+        match query_as!(
+            PriorityClassRow,
+            r#"
+            SELECT c.class_name AS name, c.value, c.description, c.preempt,
+            (SELECT COUNT(*) FROM jobs j WHERE j.priority_class_id = c.priority_class_id) AS "jobs!",
+            c.created_at, c.updated_at
+            FROM priority_classes c ORDER BY c.value DESC, c.class_name
+            "#
+        )
+        .fetch_all(&*self.pool)
+        .await
+        {
+            Ok(rows) => Ok(rows),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// Delete Priority Class, fails while any job still references it
+    pub async fn delete(&self) -> Result<(), CrudError> {
+        match query!(
+            "DELETE FROM priority_classes WHERE class_name = $1",
+            self.class.name
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 0 => Err(CrudError::NotFound),
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => {
+                Err(CrudError::Conflict)
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+}
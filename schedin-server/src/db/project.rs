@@ -0,0 +1,187 @@
+//! Project-related Crud Ops
+
+extern crate schedin_common;
+extern crate sqlx;
+extern crate std;
+extern crate uuid;
+
+use crate::project::schema::{self, ProjectRow, ProjectScope, Quotas, DEFAULT_PROJECT};
+use schedin_common::error::CrudError;
+use sqlx::{query, query_as, PgPool, Pool, Postgres};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub struct Project {
+    pub pool: Arc<PgPool>,
+    pub project: schema::Project,
+}
+
+impl Project {
+    /// New Project
+    pub fn new(pool: Arc<Pool<Postgres>>) -> Self {
+        Self {
+            pool,
+            project: schema::Project::default(),
+        }
+    }
+
+    /// Sets and returns modified project
+    pub fn project(mut self, project: schema::Project) -> Self {
+        self.project = project;
+        self
+    }
+
+    /// Insert New Project
+    pub async fn insert(&self, user_id: &str) -> Result<(), CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+        let quotas = &self.project.quotas;
+
+        match query!(
+            r#"
+            INSERT INTO projects (user_id, project_name, max_jobs, min_interval, max_concurrent_runs, max_timeout,
+            trash_retention_days, artifact_retention_days)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            user_id,
+            self.project.name,
+            quotas.max_jobs,
+            quotas.min_interval,
+            quotas.max_concurrent_runs,
+            quotas.max_timeout,
+            quotas.trash_retention_days,
+            quotas.artifact_retention_days
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(CrudError::Conflict),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// Replace the quotas of an existing project
+    pub async fn update(&self, user_id: &str) -> Result<(), CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+        let quotas = &self.project.quotas;
+
+        match query!(
+            r#"
+            UPDATE projects SET max_jobs = $3, min_interval = $4, max_concurrent_runs = $5,
+            max_timeout = $6, trash_retention_days = $7, artifact_retention_days = $8,
+            updated_at = NOW()
+            WHERE user_id = $1 AND project_name = $2
+            "#,
+            user_id,
+            self.project.name,
+            quotas.max_jobs,
+            quotas.min_interval,
+            quotas.max_concurrent_runs,
+            quotas.max_timeout,
+            quotas.trash_retention_days,
+            quotas.artifact_retention_days
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 1 => Ok(()),
+            Ok(_) => Err(CrudError::NotFound),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// List projects of a user, along with their number of jobs
+    pub async fn list(&self, user_id: &str) -> Result<Vec<ProjectRow>, CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        let rows = match query!(
+            r#"
+            SELECT p.project_name, p.max_jobs, p.min_interval, p.max_concurrent_runs, p.max_timeout,
+            p.trash_retention_days, p.artifact_retention_days,
+            (SELECT COUNT(*) FROM jobs j WHERE j.project_id = p.project_id AND j.deleted_at IS NULL) AS "jobs!",
+            p.created_at, p.updated_at
+            FROM projects p WHERE p.user_id = $1 ORDER BY p.project_name
+            "#,
+            user_id
+        )
+        .fetch_all(&*self.pool)
+        .await
+        {
+            Ok(rows) => rows,
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(CrudError::Read);
+            }
+        };
+
+        Ok(rows
+            .into_iter()
+            .map(|row| ProjectRow {
+                name: row.project_name,
+                quotas: Quotas {
+                    max_jobs: row.max_jobs,
+                    min_interval: row.min_interval,
+                    max_concurrent_runs: row.max_concurrent_runs,
+                    max_timeout: row.max_timeout,
+                    trash_retention_days: row.trash_retention_days,
+                    artifact_retention_days: row.artifact_retention_days,
+                },
+                jobs: row.jobs,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            })
+            .collect())
+    }
+
+    /// # Scope
+    /// Resolve the project `name` of a user, the default project
+    /// is created on first use.
+    pub async fn scope(&self, user_id: &str, name: &str) -> Result<ProjectScope, CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        let scope = match name {
+            DEFAULT_PROJECT => {
+                query_as!(
+                    ProjectScope,
+                    r#"
+                    INSERT INTO projects (user_id, project_name) VALUES ($1, $2)
+                    ON CONFLICT (user_id, project_name) DO UPDATE SET project_name = EXCLUDED.project_name
+                    RETURNING project_id AS id, project_name AS name
+                    "#,
+                    user_id,
+                    name
+                )
+                .fetch_one(&*self.pool)
+                .await
+            }
+            _ => {
+                query_as!(
+                    ProjectScope,
+                    r#"
+                    SELECT project_id AS id, project_name AS name FROM projects
+                    WHERE user_id = $1 AND project_name = $2
+                    "#,
+                    user_id,
+                    name
+                )
+                .fetch_one(&*self.pool)
+                .await
+            }
+        };
+
+        match scope {
+            Ok(scope) => Ok(scope),
+            Err(sqlx::Error::RowNotFound) => Err(CrudError::NotFound),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+}
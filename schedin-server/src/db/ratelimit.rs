@@ -0,0 +1,73 @@
+//! Rate Limit Buckets
+
+extern crate schedin_common;
+extern crate sqlx;
+extern crate std;
+
+use schedin_common::error::CrudError;
+use sqlx::{query, query_as, PgPool, Pool, Postgres};
+use std::sync::Arc;
+
+/// State of a bucket after a take
+pub struct Take {
+    /// Tokens left in the bucket
+    pub remaining: f64,
+
+    /// Whether a token was taken
+    pub allowed: bool,
+}
+
+pub struct RateLimits {
+    pub pool: Arc<PgPool>,
+}
+
+impl RateLimits {
+    /// New Instance
+    pub fn new(pool: Arc<Pool<Postgres>>) -> Self {
+        Self { pool }
+    }
+
+    /// # Take
+    /// Take a token from the bucket `key`, holding up to `burst` tokens and
+    /// refilled at `rate` tokens per second.
+    pub async fn take(&self, key: &str, burst: f64, rate: f64) -> Result<Take, CrudError> {
+        match query_as!(
+            Take,
+            r#"
+            SELECT remaining AS "remaining!", allowed AS "allowed!"
+            FROM take_rate_limit($1, $2, $3)
+            "#,
+            key,
+            burst,
+            rate
+        )
+        .fetch_one(&*self.pool)
+        .await
+        {
+            Ok(take) => Ok(take),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// Drop the buckets untouched for `idle` seconds, full again by then
+    pub async fn purge(&self, idle: f64) -> Result<(), CrudError> {
+        match query!(
+            r#"
+            DELETE FROM rate_limits WHERE updated_at < NOW() - make_interval(secs => $1)
+            "#,
+            idle
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+}
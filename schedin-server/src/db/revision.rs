@@ -0,0 +1,78 @@
+//! Revision-related Crud Ops
+
+extern crate schedin_common;
+extern crate serde_json;
+extern crate sqlx;
+extern crate std;
+extern crate uuid;
+
+use crate::job::revision::Revision;
+use schedin_common::error::CrudError;
+use serde_json::Value;
+use sqlx::{query_as, query_scalar, PgPool, Pool, Postgres};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub struct Revisions {
+    pub pool: Arc<PgPool>,
+}
+
+impl Revisions {
+    /// New instance
+    pub fn new(pool: Arc<Pool<Postgres>>) -> Self {
+        Self { pool }
+    }
+
+    /// List the revisions of a job of the project `project_id`, most recent first
+    pub async fn list(&self, project_id: &Uuid, job_id: &Uuid) -> Result<Vec<Revision>, CrudError> {
+        match query_as!(
+            Revision,
+            r#"
+            SELECT r.revision, r.changed_by, r.changed_at, r.definition, r.diff 
+            FROM job_revisions r JOIN jobs j ON j.job_id = r.job_id 
+            WHERE r.job_id = $1 AND j.project_id = $2 
+            ORDER BY r.revision DESC
+            "#,
+            job_id,
+            project_id
+        )
+        .fetch_all(&*self.pool)
+        .await
+        {
+            Ok(revisions) => Ok(revisions),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// Definition of revision `revision` of a job of the project `project_id`,
+    /// `Err(CrudError::NotFound)` if there is no such revision
+    pub async fn definition(
+        &self,
+        project_id: &Uuid,
+        job_id: &Uuid,
+        revision: i32,
+    ) -> Result<Value, CrudError> {
+        match query_scalar!(
+            r#"
+            SELECT r.definition FROM job_revisions r JOIN jobs j ON j.job_id = r.job_id 
+            WHERE r.job_id = $1 AND j.project_id = $2 AND r.revision = $3
+            "#,
+            job_id,
+            project_id,
+            revision
+        )
+        .fetch_one(&*self.pool)
+        .await
+        {
+            Ok(definition) => Ok(definition),
+            Err(sqlx::Error::RowNotFound) => Err(CrudError::NotFound),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+}
@@ -0,0 +1,432 @@
+//! Run-related Crud Ops
+
+extern crate schedin_common;
+extern crate sqlx;
+extern crate std;
+extern crate uuid;
+
+use crate::{
+    job::schema::Matrix,
+    project::schema::DEFAULT_ARTIFACT_RETENTION_DAYS,
+    run::schema::{Artifact, LogChunk, LogStream, Run, RunStatus, RunStep, StepStatus},
+};
+use schedin_common::{error::CrudError, tx::Tx};
+use sqlx::{query, query_as, query_scalar, types::Json, PgPool, Pool, Postgres, Transaction};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub struct Runs {
+    pub pool: Arc<PgPool>,
+}
+
+impl Runs {
+    /// New instance
+    pub fn new(pool: Arc<Pool<Postgres>>) -> Self {
+        Self { pool }
+    }
+
+    /// Run history of a job of the project `project_id`, most recent first,
+    /// without the children of matrix runs
+    pub async fn history(
+        &self,
+        project_id: &Uuid,
+        job_id: &Uuid,
+        limit: i64,
+    ) -> Result<Vec<Run>, CrudError> {
+        match query_as!(
+            Run,
+            r#"
+            SELECT r.run_id AS id, r.job_id AS "job_id!", r.revision, 
+            r.run_status AS "status: RunStatus", r.scheduled_at, r.started_at, r.finished_at, 
+            r.exit_code, r.error, r.timeout, w.worker_name AS "worker?", r.reason, 
+            r.priority, r.preemptions, r.response_status, r.latency_ms, r.response_body, 
+            r.rows_affected, r.result_preview, r.stdout, r.stderr, 
+            r.parent_run_id AS parent_id, r.combination 
+            FROM runs r JOIN jobs j ON j.job_id = r.job_id 
+            LEFT JOIN workers w ON w.worker_id = r.worker_id 
+            WHERE r.job_id = $1 AND j.project_id = $2 AND r.parent_run_id IS NULL 
+            ORDER BY r.scheduled_at DESC LIMIT $3
+            "#,
+            job_id,
+            project_id,
+            limit.clamp(1, 1000)
+        )
+        .fetch_all(&*self.pool)
+        .await
+        {
+            Ok(runs) => Ok(runs),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// Run `run_id` of a job of the project `project_id`,
+    /// `Err(CrudError::NotFound)` if there is no such run
+    pub async fn get(&self, project_id: &Uuid, run_id: &Uuid) -> Result<Run, CrudError> {
+        match query_as!(
+            Run,
+            r#"
+            SELECT r.run_id AS id, r.job_id AS "job_id!", r.revision, 
+            r.run_status AS "status: RunStatus", r.scheduled_at, r.started_at, r.finished_at, 
+            r.exit_code, r.error, r.timeout, w.worker_name AS "worker?", r.reason, 
+            r.priority, r.preemptions, r.response_status, r.latency_ms, r.response_body, 
+            r.rows_affected, r.result_preview, r.stdout, r.stderr, 
+            r.parent_run_id AS parent_id, r.combination 
+            FROM runs r JOIN jobs j ON j.job_id = r.job_id 
+            LEFT JOIN workers w ON w.worker_id = r.worker_id 
+            WHERE r.run_id = $1 AND j.project_id = $2
+            "#,
+            run_id,
+            project_id
+        )
+        .fetch_one(&*self.pool)
+        .await
+        {
+            Ok(run) => Ok(run),
+            Err(sqlx::Error::RowNotFound) => Err(CrudError::NotFound),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// # Trigger
+    /// Queue a run of the job `job_id` of the project `project_id` right
+    /// away, as the orchestrator does at dispatch, without advancing its
+    /// schedule. Paused jobs can be triggered as well. The run of a job with
+    /// a matrix is expanded right away into one queued child per combination.
+    ///
+    /// ## Errors
+    ///
+    /// - `Err(CrudError::NotFound)` if the project has no such job.
+    /// - `Err(CrudError::Quota)` if the project already has
+    ///   `max_concurrent_runs` runs queued, unschedulable or running.
+    pub async fn trigger(&self, project_id: &Uuid, job_id: &Uuid) -> Result<Uuid, CrudError> {
+        let tx_manager = Tx::new(self.pool.clone());
+        let mut tx = tx_manager.init().await?;
+
+        match self.trigger_inner(&mut tx, project_id, job_id).await {
+            Ok(run_id) => {
+                tx_manager.commit(tx).await?;
+                Ok(run_id)
+            }
+            Err(error) => {
+                tx_manager.rollback(tx).await?;
+                Err(error)
+            }
+        }
+    }
+
+    async fn trigger_inner(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        project_id: &Uuid,
+        job_id: &Uuid,
+    ) -> Result<Uuid, CrudError> {
+        // the lock on the job serializes triggers against the quota
+        let job = match query!(
+            r#"
+            SELECT j.matrix AS "matrix: Json<Matrix>", p.max_concurrent_runs, 
+            (
+                SELECT COUNT(*) FROM runs r JOIN jobs a ON a.job_id = r.job_id 
+                WHERE a.project_id = p.project_id AND r.matrix IS NULL 
+                AND r.run_status IN ('queued', 'running', 'unschedulable')
+            ) AS "active!" 
+            FROM jobs j JOIN projects p ON p.project_id = j.project_id 
+            WHERE j.job_id = $1 AND j.project_id = $2 AND j.deleted_at IS NULL 
+            FOR UPDATE OF j
+            "#,
+            job_id,
+            project_id
+        )
+        .fetch_optional(&mut **tx)
+        .await
+        {
+            Ok(Some(job)) => job,
+            Ok(None) => return Err(CrudError::NotFound),
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(CrudError::Read);
+            }
+        };
+
+        let combinations = job.matrix.map(|matrix| matrix.expand()).unwrap_or_default();
+
+        // only the children of a matrix run count towards the quota
+        let runs = combinations.len().max(1) as i64;
+        if let Some(max) = job.max_concurrent_runs {
+            if job.active + runs > max as i64 {
+                return Err(CrudError::Quota);
+            }
+        }
+
+        let run_id = match query_scalar!(
+            r#"
+            INSERT INTO runs (job_id, revision, scheduled_at, timeout, cpu, memory_mb, disk_mb, priority, preempt, matrix) 
+            SELECT j.job_id, j.revision, NOW(), LEAST(j.timeout, p.max_timeout), 
+            COALESCE(j.request_cpu, 0), COALESCE(j.request_memory_mb, 0), COALESCE(j.request_disk_mb, 0), 
+            COALESCE(pc.value, 0), COALESCE(pc.preempt, FALSE), j.matrix 
+            FROM jobs j JOIN projects p ON p.project_id = j.project_id 
+            LEFT JOIN priority_classes pc ON pc.priority_class_id = j.priority_class_id 
+            WHERE j.job_id = $1 
+            RETURNING run_id
+            "#,
+            job_id
+        )
+        .fetch_one(&mut **tx)
+        .await
+        {
+            Ok(run_id) => run_id,
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(CrudError::Insertion);
+            }
+        };
+
+        if let Err(e) = query!("UPDATE jobs SET runs = runs + 1 WHERE job_id = $1", job_id)
+            .execute(&mut **tx)
+            .await
+        {
+            eprintln!("{}", e);
+            return Err(CrudError::Insertion);
+        }
+
+        if combinations.is_empty() {
+            return Ok(run_id);
+        }
+
+        let combinations = combinations.into_iter().map(Json).collect::<Vec<_>>();
+
+        for query in [
+            query!(
+                r#"
+                INSERT INTO runs (job_id, revision, scheduled_at, timeout, cpu, memory_mb, disk_mb, 
+                priority, preempt, parent_run_id, combination, position) 
+                SELECT r.job_id, r.revision, r.scheduled_at, r.timeout, r.cpu, r.memory_mb, r.disk_mb, 
+                r.priority, r.preempt, r.run_id, c.combination, (c.position - 1)::int 
+                FROM runs r, UNNEST($2::jsonb[]) WITH ORDINALITY AS c(combination, position) 
+                WHERE r.run_id = $1
+                "#,
+                run_id,
+                &combinations as _
+            ),
+            query!(
+                "UPDATE runs SET run_status = 'running', started_at = NOW() WHERE run_id = $1",
+                run_id
+            ),
+        ] {
+            if let Err(e) = query.execute(&mut **tx).await {
+                eprintln!("{}", e);
+                return Err(CrudError::Insertion);
+            }
+        }
+
+        Ok(run_id)
+    }
+
+    /// Children of the matrix run `run_id` of a job of the project
+    /// `project_id`, in the order of their combinations
+    pub async fn children(
+        &self,
+        project_id: &Uuid,
+        job_id: &Uuid,
+        run_id: &Uuid,
+    ) -> Result<Vec<Run>, CrudError> {
+        match query_as!(
+            Run,
+            r#"
+            SELECT r.run_id AS id, r.job_id AS "job_id!", r.revision, 
+            r.run_status AS "status: RunStatus", r.scheduled_at, r.started_at, r.finished_at, 
+            r.exit_code, r.error, r.timeout, w.worker_name AS "worker?", r.reason, 
+            r.priority, r.preemptions, r.response_status, r.latency_ms, r.response_body, 
+            r.rows_affected, r.result_preview, r.stdout, r.stderr, 
+            r.parent_run_id AS parent_id, r.combination 
+            FROM runs r JOIN jobs j ON j.job_id = r.job_id 
+            LEFT JOIN workers w ON w.worker_id = r.worker_id 
+            WHERE r.parent_run_id = $1 AND r.job_id = $2 AND j.project_id = $3 
+            ORDER BY r.position
+            "#,
+            run_id,
+            job_id,
+            project_id
+        )
+        .fetch_all(&*self.pool)
+        .await
+        {
+            Ok(runs) => Ok(runs),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// Steps of the pipeline run `run_id` of a job of the project `project_id`
+    pub async fn steps(
+        &self,
+        project_id: &Uuid,
+        job_id: &Uuid,
+        run_id: &Uuid,
+    ) -> Result<Vec<RunStep>, CrudError> {
+        match query_as!(
+            RunStep,
+            r#"
+            SELECT s.position, s.step_name AS name, s.step_status AS "status: StepStatus", 
+            s.started_at, s.finished_at, s.exit_code, s.error, s.response_status, 
+            s.stdout, s.stderr 
+            FROM run_steps s JOIN runs r ON r.run_id = s.run_id 
+            JOIN jobs j ON j.job_id = r.job_id 
+            WHERE s.run_id = $1 AND r.job_id = $2 AND j.project_id = $3 
+            ORDER BY s.position
+            "#,
+            run_id,
+            job_id,
+            project_id
+        )
+        .fetch_all(&*self.pool)
+        .await
+        {
+            Ok(steps) => Ok(steps),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// Artifacts of a run of a job of the project `project_id` that have
+    /// not expired yet, by path
+    pub async fn artifacts(
+        &self,
+        project_id: &Uuid,
+        job_id: &Uuid,
+        run_id: &Uuid,
+    ) -> Result<Vec<Artifact>, CrudError> {
+        match query_as!(
+            Artifact,
+            r#"
+            SELECT a.path, a.digest, a.size, a.created_at, 
+            a.created_at + make_interval(days => COALESCE(p.artifact_retention_days, $4)) AS "expires_at!" 
+            FROM run_artifacts a JOIN runs r ON r.run_id = a.run_id 
+            JOIN jobs j ON j.job_id = r.job_id 
+            JOIN projects p ON p.project_id = j.project_id 
+            WHERE a.run_id = $1 AND r.job_id = $2 AND j.project_id = $3 
+            AND a.created_at + make_interval(days => COALESCE(p.artifact_retention_days, $4)) > NOW() 
+            ORDER BY a.path
+            "#,
+            run_id,
+            job_id,
+            project_id,
+            DEFAULT_ARTIFACT_RETENTION_DAYS
+        )
+        .fetch_all(&*self.pool)
+        .await
+        {
+            Ok(artifacts) => Ok(artifacts),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// Artifact `path` of a run of a job of the project `project_id`,
+    /// `Err(CrudError::NotFound)` if there is no such artifact or it expired
+    pub async fn artifact(
+        &self,
+        project_id: &Uuid,
+        job_id: &Uuid,
+        run_id: &Uuid,
+        path: &str,
+    ) -> Result<Artifact, CrudError> {
+        self.artifacts(project_id, job_id, run_id)
+            .await?
+            .into_iter()
+            .find(|artifact| artifact.path == path)
+            .ok_or(CrudError::NotFound)
+    }
+
+    /// Status of the run `run_id` of a job of the user,
+    /// `Err(CrudError::Read)` if there is no such run
+    pub async fn status(&self, user_id: &str, run_id: &Uuid) -> Result<RunStatus, CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query_scalar!(
+            r#"
+            SELECT r.run_status AS "status: RunStatus" 
+            FROM runs r JOIN jobs j ON j.job_id = r.job_id 
+            WHERE r.run_id = $1 AND j.user_id = $2
+            "#,
+            run_id,
+            user_id
+        )
+        .fetch_one(&*self.pool)
+        .await
+        {
+            Ok(status) => Ok(status),
+            Err(sqlx::Error::RowNotFound) => Err(CrudError::NotFound),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// # Log
+    /// Chunks of the log of a run holding bytes from `from` on, before
+    /// `to` if set, in order.
+    pub async fn log(
+        &self,
+        run_id: &Uuid,
+        from: i64,
+        to: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<LogChunk>, CrudError> {
+        match query_as!(
+            LogChunk,
+            r#"
+            SELECT position, stream AS "stream: LogStream", data, logged_at 
+            FROM run_logs 
+            WHERE run_id = $1 AND position + length(data) > $2 
+            AND ($3::BIGINT IS NULL OR position < $3) 
+            ORDER BY position LIMIT $4
+            "#,
+            run_id,
+            from,
+            to,
+            limit
+        )
+        .fetch_all(&*self.pool)
+        .await
+        {
+            Ok(chunks) => Ok(chunks),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// Size in bytes of the log of a run
+    pub async fn log_size(&self, run_id: &Uuid) -> Result<i64, CrudError> {
+        match query_scalar!(
+            r#"
+            SELECT COALESCE(MAX(position + length(data)), 0)::BIGINT AS "size!" 
+            FROM run_logs WHERE run_id = $1
+            "#,
+            run_id
+        )
+        .fetch_one(&*self.pool)
+        .await
+        {
+            Ok(size) => Ok(size),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Secret-related Crud Ops
+
+extern crate schedin_common;
+extern crate sqlx;
+extern crate std;
+extern crate uuid;
+
+use crate::secret::schema::{self, SecretRow};
+use schedin_common::{
+    error::CrudError,
+    secret::{self as crypto, Cipher},
+};
+use sqlx::{query, query_as, PgPool, Pool, Postgres};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub struct Secret {
+    pub pool: Arc<PgPool>,
+    pub secret: schema::Secret,
+}
+
+impl Secret {
+    /// New Secret
+    pub fn new(pool: Arc<Pool<Postgres>>) -> Self {
+        Self {
+            pool,
+            secret: schema::Secret::default(),
+        }
+    }
+
+    /// Sets and returns modified secret
+    pub fn secret(mut self, secret: schema::Secret) -> Self {
+        self.secret = secret;
+        self
+    }
+
+    /// # Upsert Secret
+    /// Encrypts the value and stores it, replacing any existing secret
+    /// of the same name so that referencing jobs pick up the new value.
+    pub async fn upsert(&self, user_id: &str, cipher: &Cipher) -> Result<(), CrudError> {
+        let Some(value) = self.secret.value.as_ref() else {
+            return Err(CrudError::Validation);
+        };
+
+        let sealed =
+            match cipher.encrypt(value.as_bytes(), &crypto::aad(user_id, &self.secret.name)) {
+                Ok(sealed) => sealed,
+                Err(err) => {
+                    eprintln!("{}", err.reason());
+                    return Err(CrudError::Insertion);
+                }
+            };
+
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query!(
+            r#"
+            INSERT INTO secrets (user_id, secret_name, nonce, ciphertext) 
+            VALUES ($1, $2, $3, $4) 
+            ON CONFLICT ON CONSTRAINT unique_secret_name_per_user 
+            DO UPDATE SET nonce = EXCLUDED.nonce, ciphertext = EXCLUDED.ciphertext, 
+            updated_at = NOW()
+            "#,
+            user_id,
+            self.secret.name,
+            sealed.nonce,
+            sealed.ciphertext,
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// List secret names of a user
+    pub async fn list(&self, user_id: &str) -> Result<Vec<SecretRow>, CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query_as!(
+            SecretRow,
+            r#"
+            SELECT secret_name AS name, created_at, updated_at FROM secrets 
+            WHERE user_id=$1 ORDER BY secret_name
+            "#,
+            user_id
+        )
+        .fetch_all(&*self.pool)
+        .await
+        {
+            Ok(rows) => Ok(rows),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// Delete Secret, fails while any job still references it
+    pub async fn delete(&self, user_id: &str) -> Result<(), CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query!(
+            r#"
+            DELETE FROM secrets WHERE user_id=$1 AND secret_name=$2
+            "#,
+            user_id,
+            self.secret.name
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 0 => Err(CrudError::NotFound),
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => {
+                Err(CrudError::Conflict)
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+}
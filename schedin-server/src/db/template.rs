@@ -0,0 +1,240 @@
+//! Template-related Crud Ops
+
+extern crate schedin_common;
+extern crate serde_json;
+extern crate sqlx;
+extern crate std;
+extern crate uuid;
+
+use super::DB;
+use crate::{
+    job::schema::Job,
+    template::schema::{self, InstanceRow, TemplateRecord, TemplateRow},
+};
+use schedin_common::{error::CrudError, tx::Tx};
+use serde_json::Value;
+use sqlx::{query, query_as, types::Json, PgPool, Pool, Postgres};
+use std::{collections::HashMap, sync::Arc};
+use uuid::Uuid;
+
+pub struct Template {
+    pub pool: Arc<PgPool>,
+    pub template: schema::Template,
+}
+
+impl Template {
+    /// New Template
+    pub fn new(pool: Arc<Pool<Postgres>>) -> Self {
+        Self {
+            pool,
+            template: schema::Template::default(),
+        }
+    }
+
+    /// Sets and returns modified template
+    pub fn template(mut self, template: schema::Template) -> Self {
+        self.template = template;
+        self
+    }
+
+    /// Insert New Template
+    pub async fn insert(&self, user_id: &str) -> Result<(), CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query!(
+            r#"
+            INSERT INTO templates (user_id, template_name, template_description, parameters, definition) 
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            user_id,
+            self.template.name,
+            self.template.description,
+            Json(&self.template.parameters) as _,
+            self.template.job,
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(CrudError::Conflict),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// List templates of a user, along with their number of instances
+    pub async fn list(&self, user_id: &str) -> Result<Vec<TemplateRow>, CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query_as!(
+            TemplateRow,
+            r#"
+            SELECT t.template_name AS name, t.template_description AS description, 
+            t.parameters AS "parameters: Json<Value>", t.definition AS "job: Json<Value>", 
+            (SELECT COUNT(*) FROM jobs j WHERE j.template_id = t.template_id AND j.deleted_at IS NULL) AS instances, 
+            t.created_at, t.updated_at 
+            FROM templates t WHERE t.user_id = $1 ORDER BY t.template_name
+            "#,
+            user_id
+        )
+        .fetch_all(&*self.pool)
+        .await
+        {
+            Ok(rows) => Ok(rows),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// Get a template of a user by name
+    pub async fn fetch(&self, user_id: &str, name: &str) -> Result<TemplateRecord, CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        let row = match query!(
+            r#"
+            SELECT template_id, template_name, template_description, 
+            parameters AS "parameters: Json<Vec<schema::Parameter>>", definition 
+            FROM templates WHERE user_id = $1 AND template_name = $2
+            "#,
+            user_id,
+            name
+        )
+        .fetch_one(&*self.pool)
+        .await
+        {
+            Ok(row) => row,
+            Err(sqlx::Error::RowNotFound) => return Err(CrudError::NotFound),
+            Err(e) => {
+                eprintln!("{}", e);
+                return Err(CrudError::Read);
+            }
+        };
+
+        Ok(TemplateRecord {
+            template_id: row.template_id,
+            template: schema::Template {
+                name: row.template_name,
+                description: row.template_description,
+                parameters: row.parameters.0,
+                job: row.definition,
+            },
+        })
+    }
+
+    /// Instances of a template
+    pub async fn instances(&self, template_id: &Uuid) -> Result<Vec<InstanceRow>, CrudError> {
+        match query_as!(
+            InstanceRow,
+            r#"
+            SELECT job_id, job_name, 
+            template_values AS "template_values: Json<HashMap<String, Value>>" 
+            FROM jobs WHERE template_id = $1 AND deleted_at IS NULL
+            "#,
+            template_id
+        )
+        .fetch_all(&*self.pool)
+        .await
+        {
+            Ok(rows) => Ok(rows),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// # Instantiate
+    /// Insert a job rendered from the template `template_id` into the
+    /// project `project_id` and link it back.
+    pub async fn instantiate(
+        &self,
+        user_id: &str,
+        project_id: &Uuid,
+        template_id: &Uuid,
+        job: Job,
+        values: &HashMap<String, Value>,
+    ) -> Result<(), CrudError> {
+        let db = DB::new(self.pool.clone()).job(job).project(*project_id);
+
+        let tx_manager = Tx::new(self.pool.clone());
+        let mut tx = tx_manager.init().await?;
+
+        let result = match db.insert_all(&mut tx, user_id).await {
+            Ok(job_id) => query!(
+                r#"
+                UPDATE jobs SET template_id = $2, template_values = $3 WHERE job_id = $1
+                "#,
+                job_id,
+                template_id,
+                Json(values) as _
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                eprintln!("{}", e);
+                CrudError::Insertion
+            }),
+            Err(error) => Err(error),
+        };
+
+        match result {
+            Ok(_) => tx_manager.commit(tx).await,
+            Err(error) => {
+                tx_manager.rollback(tx).await?;
+                Err(error)
+            }
+        }
+    }
+
+    /// # Update Template
+    /// Update the template `template_id` and replace the definition
+    /// of every instance in `rollout` within one transaction.
+    pub async fn update(
+        &self,
+        user_id: &str,
+        template_id: &Uuid,
+        rollout: Vec<(Uuid, Job)>,
+    ) -> Result<(), CrudError> {
+        let tx_manager = Tx::new(self.pool.clone());
+        let mut tx = tx_manager.init().await?;
+
+        let result = query!(
+            r#"
+            UPDATE templates SET template_description = $2, parameters = $3, definition = $4, 
+            updated_at = NOW() WHERE template_id = $1
+            "#,
+            template_id,
+            self.template.description,
+            Json(&self.template.parameters) as _,
+            self.template.job,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            eprintln!("{}", e);
+            CrudError::Insertion
+        });
+
+        if let Err(error) = result {
+            tx_manager.rollback(tx).await?;
+            return Err(error);
+        }
+
+        for (job_id, job) in rollout {
+            if let Err(error) = DB::new(self.pool.clone())
+                .job(job)
+                .replace(&mut tx, &job_id, user_id)
+                .await
+            {
+                tx_manager.rollback(tx).await?;
+                return Err(error);
+            }
+        }
+
+        tx_manager.commit(tx).await
+    }
+}
@@ -3,11 +3,13 @@
 extern crate schedin_common;
 extern crate sqlx;
 extern crate std;
+extern crate uuid;
 
 use crate::iam::schema::{self};
 use schedin_common::{error::CrudError, tx::Tx};
-use sqlx::{query, query_as, PgPool, Pool, Postgres};
+use sqlx::{query, query_as, query_scalar, PgPool, Pool, Postgres};
 use std::sync::Arc;
+use uuid::Uuid;
 
 pub struct User {
     pub pool: Arc<PgPool>,
@@ -37,7 +39,7 @@ impl User {
         let tx_manager = Tx::new(self.pool.clone());
         let tx = tx_manager.init().await?;
 
-        query!(
+        let query = query!(
             r#"
             INSERT INTO users (user_id, username, passcode, email) 
             VALUES ($1, $2, $3, $4)
@@ -48,25 +50,37 @@ impl User {
             self.user.email,
         )
         .execute(&*self.pool)
-        .await
-        .unwrap();
+        .await;
+
+        if let Err(e) = query {
+            tx_manager.rollback(tx).await?;
+
+            return match e {
+                sqlx::Error::Database(e) if e.is_unique_violation() => Err(CrudError::Conflict),
+                e => {
+                    eprintln!("{}", e);
+                    Err(CrudError::Insertion)
+                }
+            };
+        }
 
         tx_manager.commit(tx).await?;
 
         Ok(())
     }
 
-    /// Get User Credentials
+    /// Get User Credentials,
+    /// `Err(CrudError::NotFound)` if the username or password is wrong
     pub async fn credentials(self) -> Result<schema::SigninRow, CrudError> {
         let password = self.user.hash();
 
         let query = query_as!(
             schema::SigninRow,
             r#"
-            SELECT user_id, username, passcode FROM users 
+            SELECT user_id, username FROM users 
             WHERE username=$1 AND passcode=$2
             "#,
-            self.user.username.unwrap(),
+            self.user.username,
             password
         )
         .fetch_one(&*self.pool)
@@ -74,6 +88,23 @@ impl User {
 
         match query {
             Ok(row) => Ok(row),
+            Err(sqlx::Error::RowNotFound) => Err(CrudError::NotFound),
+            Err(err) => {
+                eprintln!("{}", err);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// Whether the user `user_id` is an administrator
+    pub async fn is_admin(&self, user_id: &str) -> Result<bool, CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query_scalar!("SELECT is_admin FROM users WHERE user_id = $1", user_id)
+            .fetch_optional(&*self.pool)
+            .await
+        {
+            Ok(admin) => Ok(admin.unwrap_or(false)),
             Err(err) => {
                 eprintln!("{}", err);
                 Err(CrudError::Read)
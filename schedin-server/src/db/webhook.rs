@@ -0,0 +1,274 @@
+//! Webhook-related Crud Ops
+
+extern crate schedin_common;
+extern crate sqlx;
+extern crate std;
+extern crate time;
+extern crate uuid;
+
+use crate::webhook::schema::{self, Delivery, EventType, SubscriptionRow};
+use schedin_common::{
+    error::CrudError,
+    secret::{self, Cipher, Redacted},
+    webhook,
+};
+use sqlx::{query, query_as, query_scalar, PgPool, Pool, Postgres};
+use std::sync::Arc;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// Random bytes of a signing secret
+const SECRET_BYTES: usize = 32;
+
+pub struct Subscription {
+    pub pool: Arc<PgPool>,
+    pub subscription: schema::Subscription,
+}
+
+impl Subscription {
+    /// New Subscription
+    pub fn new(pool: Arc<Pool<Postgres>>) -> Self {
+        Self {
+            pool,
+            subscription: schema::Subscription::default(),
+        }
+    }
+
+    /// Sets and returns modified subscription
+    pub fn subscription(mut self, subscription: schema::Subscription) -> Self {
+        self.subscription = subscription;
+        self
+    }
+
+    /// # Upsert Subscription
+    /// Stores the subscription, replacing the URL and events of any existing
+    /// subscription of the same name. Returns the signing secret of new
+    /// subscriptions, existing ones keep theirs.
+    pub async fn upsert(
+        &self,
+        user_id: &str,
+        cipher: &Cipher,
+    ) -> Result<Option<Redacted<String>>, CrudError> {
+        let secret = secret::token(SECRET_BYTES);
+        let sealed = seal(cipher, user_id, &self.subscription.name, &secret)?;
+
+        let events = self
+            .subscription
+            .events
+            .as_deref()
+            .unwrap_or(&EventType::ALL)
+            .iter()
+            .map(|event| event.name().to_string())
+            .collect::<Vec<_>>();
+
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query_scalar!(
+            r#"
+            INSERT INTO webhook_subscriptions (user_id, subscription_name, url, events, nonce, ciphertext) 
+            VALUES ($1, $2, $3, $4, $5, $6) 
+            ON CONFLICT ON CONSTRAINT unique_subscription_name_per_user 
+            DO UPDATE SET url = EXCLUDED.url, events = EXCLUDED.events, updated_at = NOW() 
+            RETURNING (xmax = 0) AS "inserted!"
+            "#,
+            user_id,
+            self.subscription.name,
+            self.subscription.url,
+            &events,
+            sealed.nonce,
+            sealed.ciphertext
+        )
+        .fetch_one(&*self.pool)
+        .await
+        {
+            Ok(true) => Ok(Some(secret)),
+            Ok(false) => Ok(None),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// List subscriptions of a user, without their secrets
+    pub async fn list(&self, user_id: &str) -> Result<Vec<SubscriptionRow>, CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query_as!(
+            SubscriptionRow,
+            r#"
+            SELECT subscription_name AS name, url, events, 
+            CASE WHEN previous_expires_at > NOW() THEN previous_expires_at END AS rotating_until, 
+            created_at, updated_at 
+            FROM webhook_subscriptions WHERE user_id = $1 ORDER BY subscription_name
+            "#,
+            user_id
+        )
+        .fetch_all(&*self.pool)
+        .await
+        {
+            Ok(rows) => Ok(rows),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// Delete Subscription, along with its pending deliveries
+    pub async fn delete(&self, user_id: &str) -> Result<(), CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query!(
+            r#"
+            DELETE FROM webhook_subscriptions WHERE user_id = $1 AND subscription_name = $2
+            "#,
+            user_id,
+            self.subscription.name
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 0 => Err(CrudError::NotFound),
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => {
+                Err(CrudError::Conflict)
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Rotate Secret
+    /// Replace the signing secret of the subscription, and returns the new
+    /// one. Deliveries are signed with both secrets for `grace` seconds, so
+    /// that subscribers can switch over without rejecting any.
+    ///
+    /// ## Errors
+    ///
+    /// `CrudError::NotFound` if the subscription does not exist.
+    pub async fn rotate(
+        &self,
+        user_id: &str,
+        cipher: &Cipher,
+        grace: i32,
+    ) -> Result<Redacted<String>, CrudError> {
+        let secret = secret::token(SECRET_BYTES);
+        let sealed = seal(cipher, user_id, &self.subscription.name, &secret)?;
+
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query!(
+            r#"
+            UPDATE webhook_subscriptions SET previous_nonce = nonce, 
+            previous_ciphertext = ciphertext, 
+            previous_expires_at = NOW() + make_interval(secs => $3), 
+            nonce = $4, ciphertext = $5, updated_at = NOW() 
+            WHERE user_id = $1 AND subscription_name = $2
+            "#,
+            user_id,
+            self.subscription.name,
+            grace as f64,
+            sealed.nonce,
+            sealed.ciphertext
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(result) if result.rows_affected() == 1 => Ok(secret),
+            Ok(_) => Err(CrudError::NotFound),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Redeliver
+    /// Schedule the deliveries of the subscription for another round of
+    /// attempts, whatever their outcome, returns their number. Only the
+    /// delivery of `event` if set, those of the events since `since`
+    /// otherwise, as far as the outbox retention goes.
+    pub async fn redeliver(
+        &self,
+        user_id: &str,
+        event: Option<Uuid>,
+        since: Option<OffsetDateTime>,
+    ) -> Result<u64, CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query!(
+            r#"
+            UPDATE webhook_deliveries d SET delivery_status = 'pending', attempts = 0, 
+            next_attempt_at = NOW(), last_error = NULL 
+            FROM webhook_subscriptions s, webhook_outbox o 
+            WHERE s.subscription_id = d.subscription_id AND o.event_id = d.event_id 
+            AND s.user_id = $1 AND s.subscription_name = $2 
+            AND ($3::UUID IS NULL OR o.event_id = $3) 
+            AND ($4::TIMESTAMPTZ IS NULL OR o.created_at >= $4)
+            "#,
+            user_id,
+            self.subscription.name,
+            event,
+            since
+        )
+        .execute(&*self.pool)
+        .await
+        {
+            Ok(result) => Ok(result.rows_affected()),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Deliveries
+    /// Delivery log of the subscription, most recent first
+    pub async fn deliveries(&self, user_id: &str, limit: i64) -> Result<Vec<Delivery>, CrudError> {
+        let user_id = Uuid::parse_str(user_id).unwrap();
+
+        match query_as!(
+            Delivery,
+            r#"
+            SELECT d.delivery_id AS id, d.event_id, o.event_type AS event, 
+            d.delivery_status AS "status: _", d.attempts, d.response_status, d.last_error, 
+            d.created_at, d.next_attempt_at, d.delivered_at 
+            FROM webhook_deliveries d 
+            JOIN webhook_subscriptions s ON s.subscription_id = d.subscription_id 
+            JOIN webhook_outbox o ON o.event_id = d.event_id 
+            WHERE s.user_id = $1 AND s.subscription_name = $2 
+            ORDER BY d.created_at DESC LIMIT $3
+            "#,
+            user_id,
+            self.subscription.name,
+            limit
+        )
+        .fetch_all(&*self.pool)
+        .await
+        {
+            Ok(rows) => Ok(rows),
+            Err(e) => {
+                eprintln!("{}", e);
+                Err(CrudError::Read)
+            }
+        }
+    }
+}
+
+/// Seal the signing `secret` of the subscription `name` of a user
+fn seal(
+    cipher: &Cipher,
+    user_id: &str,
+    name: &str,
+    secret: &Redacted<String>,
+) -> Result<secret::Sealed, CrudError> {
+    cipher
+        .encrypt(secret.expose().as_bytes(), &webhook::aad(user_id, name))
+        .map_err(|err| {
+            eprintln!("{}", err.reason());
+            CrudError::Insertion
+        })
+}
@@ -0,0 +1,212 @@
+//! Event Hub
+//!
+//! Events are recorded in the outbox, and announced by Postgres with a
+//! `schedin_events` notification carrying the id of their user. Each server
+//! instance listens once, and wakes the streams of that user, which then
+//! read the new events from the outbox. Streams also read the outbox every
+//! `HEARTBEAT` seconds, so that a missed notification only delays events.
+//!
+//! Streams only read past the events of transactions which are over, as
+//! those still running may have recorded events before, see `Events::since`.
+//! Their events follow on the next read once they are.
+//!
+//! Chunks of run logs are announced the same way, on `schedin_logs` with
+//! the id of their run, to wake the followers of the run.
+
+extern crate actix_web;
+extern crate schedin_common;
+extern crate sqlx;
+extern crate std;
+extern crate tokio;
+extern crate uuid;
+
+use crate::{
+    db::event::{Events, Position},
+    event::schema::Event,
+};
+use actix_web::rt;
+use schedin_common::error::CrudError;
+use sqlx::{postgres::PgListener, PgPool};
+use std::{collections::HashMap, collections::VecDeque, future, sync::Arc, time::Duration};
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    time,
+};
+use uuid::Uuid;
+
+/// Channel events are announced on
+const EVENTS_CHANNEL: &str = "schedin_events";
+
+/// Channel chunks of run logs are announced on
+const LOGS_CHANNEL: &str = "schedin_logs";
+
+/// Time (in seconds) between heartbeats of idle streams
+pub const HEARTBEAT: u64 = 15;
+
+/// Events read from the outbox at once
+const BATCH: i64 = 100;
+
+/// Announcements buffered per stream, slower streams read the outbox anyway
+const CAPACITY: usize = 1024;
+
+/// # Hub
+/// Relays the announcements of events to the streams of this instance
+pub struct Hub {
+    sender: broadcast::Sender<Signal>,
+}
+
+/// Announcement relayed by the hub
+#[derive(Debug, Clone, Copy)]
+pub enum Signal {
+    /// Event of the user with this id
+    Event(Uuid),
+
+    /// Chunk of the log of the run with this id
+    Log(Uuid),
+}
+
+impl Hub {
+    /// # Listen
+    /// Listen to announcements on a dedicated connection, reconnecting
+    /// whenever it is lost.
+    pub async fn listen(pool: &PgPool) -> Result<Self, sqlx::Error> {
+        let mut listener = PgListener::connect_with(pool).await?;
+        listener.listen_all([EVENTS_CHANNEL, LOGS_CHANNEL]).await?;
+
+        let (sender, _) = broadcast::channel(CAPACITY);
+        let relay = sender.clone();
+
+        rt::spawn(async move {
+            loop {
+                match listener.recv().await {
+                    Ok(notification) => {
+                        let Ok(id) = Uuid::parse_str(notification.payload()) else {
+                            continue;
+                        };
+
+                        let signal = match notification.channel() {
+                            LOGS_CHANNEL => Signal::Log(id),
+                            _ => Signal::Event(id),
+                        };
+
+                        // no stream listening is fine
+                        let _ = relay.send(signal);
+                    }
+                    Err(error) => {
+                        eprintln!("events: {}", error);
+                        time::sleep(Duration::from_secs(1)).await;
+                    }
+                }
+            }
+        });
+
+        Ok(Self { sender })
+    }
+
+    /// Announcements relayed from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<Signal> {
+        self.sender.subscribe()
+    }
+
+    /// Feed of the events of `user_id` after the event `last`, or from now
+    /// on if unset
+    pub async fn feed(
+        &self,
+        pool: Arc<PgPool>,
+        user_id: &str,
+        last: Option<i64>,
+        job: Option<String>,
+        labels: Option<HashMap<String, String>>,
+    ) -> Result<Feed, CrudError> {
+        // subscribed first, so that no event slips in between
+        let receiver = self.sender.subscribe();
+
+        let events = Events::new(pool.clone());
+        let last = match last {
+            Some(seq) => events.position(user_id, seq).await?,
+            None => events.start().await?,
+        };
+
+        Ok(Feed {
+            pool,
+            user_id: user_id.to_string(),
+            job,
+            labels,
+            last,
+            buffer: VecDeque::new(),
+            receiver,
+        })
+    }
+}
+
+/// Item of a feed
+pub enum Item {
+    Event(Event),
+
+    /// Nothing happened for `HEARTBEAT` seconds
+    Heartbeat,
+}
+
+/// # Feed
+/// Events of a user, in the order of their transactions
+pub struct Feed {
+    pool: Arc<PgPool>,
+    user_id: String,
+    job: Option<String>,
+    labels: Option<HashMap<String, String>>,
+    last: Position,
+    buffer: VecDeque<Event>,
+    receiver: broadcast::Receiver<Signal>,
+}
+
+impl Feed {
+    /// # Next
+    /// Next event, or a heartbeat if there is none for a while
+    pub async fn next(&mut self) -> Result<Item, CrudError> {
+        loop {
+            if let Some(event) = self.buffer.pop_front() {
+                self.last = Position {
+                    txid: event.txid,
+                    seq: event.seq,
+                };
+                return Ok(Item::Event(event));
+            }
+
+            let events = Events::new(self.pool.clone())
+                .since(
+                    &self.user_id,
+                    self.last,
+                    self.job.as_deref(),
+                    self.labels.as_ref(),
+                    BATCH,
+                )
+                .await?;
+
+            if !events.is_empty() {
+                self.buffer.extend(events);
+                continue;
+            }
+
+            if time::timeout(Duration::from_secs(HEARTBEAT), self.wake())
+                .await
+                .is_err()
+            {
+                return Ok(Item::Heartbeat);
+            }
+        }
+    }
+
+    /// Wait for an announcement of an event of the user
+    async fn wake(&mut self) {
+        loop {
+            match self.receiver.recv().await {
+                Ok(Signal::Event(user_id)) if user_id.to_string() == self.user_id => return,
+                Ok(_) => continue,
+                // announcements were dropped, read the outbox
+                Err(RecvError::Lagged(_)) => return,
+                // the listener is gone, heartbeats keep reading the outbox
+                Err(RecvError::Closed) => future::pending().await,
+            }
+        }
+    }
+}
@@ -0,0 +1,4 @@
+//! Event Streams
+
+pub mod hub;
+pub mod schema;
@@ -0,0 +1,76 @@
+//! Event Schema
+
+extern crate serde;
+extern crate serde_json;
+extern crate sqlx;
+extern crate time;
+extern crate utoipa;
+extern crate uuid;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::types::Json;
+use std::collections::HashMap;
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// # Event
+/// Change of the state of a job or run, as delivered to webhooks
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Event {
+    /// Id of the event in the stream of the user, to resume after it.
+    /// Events of concurrent transactions may arrive out of `seq` order.
+    pub seq: i64,
+    /// Transaction of the event, see `Position`
+    #[serde(skip)]
+    pub txid: i64,
+    pub id: Uuid,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub version: i32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[schema(value_type = Object)]
+    pub data: Json<Value>,
+}
+
+/// Event stream query
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Stream {
+    /// Only the events of the job of this name
+    pub job: Option<String>,
+    /// Only the events of the jobs with these labels, e.g. `team=ops,tier=1`
+    pub selector: Option<String>,
+    /// Resume after this event, for clients unable to send `Last-Event-ID`
+    pub last_event_id: Option<i64>,
+}
+
+impl Stream {
+    /// # Labels
+    /// Labels of the selector, every one of them must match
+    ///
+    /// ## Errors
+    ///
+    /// A term which is not a `key=value` pair.
+    pub fn labels(&self) -> Result<Option<HashMap<String, String>>, String> {
+        let Some(selector) = &self.selector else {
+            return Ok(None);
+        };
+
+        selector
+            .split(',')
+            .map(|term| match term.split_once('=') {
+                Some((key, value)) if !key.trim().is_empty() => {
+                    Ok((key.trim().to_string(), value.trim().to_string()))
+                }
+                _ => Err(format!(
+                    "Invalid selector term '{}', expected key=value",
+                    term
+                )),
+            })
+            .collect::<Result<HashMap<_, _>, _>>()
+            .map(Some)
+    }
+}
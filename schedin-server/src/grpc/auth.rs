@@ -0,0 +1,70 @@
+//! gRPC Authentication
+
+extern crate schedin_common;
+extern crate sqlx;
+extern crate std;
+extern crate tonic;
+
+use crate::{
+    db::key::ApiKeys,
+    iam::{key, schema::AuthorizedUser, token::Claims},
+};
+use schedin_common::error::CrudError;
+use sqlx::PgPool;
+use std::sync::Arc;
+use tonic::{Request, Status};
+
+/// API key of a request, looked up by `user`
+#[derive(Clone)]
+struct Key(String);
+
+/// # Authenticate
+/// Interceptor checking the JWT token of the `authorization: Bearer <token>`
+/// metadata of a request, as the REST API does. The authorized user is
+/// stored in the extensions of the request, see `user`. API keys are only
+/// stored, the interceptor cannot reach the database.
+#[allow(clippy::result_large_err)]
+pub fn authenticate(mut request: Request<()>) -> Result<Request<()>, Status> {
+    let token = request
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| Status::unauthenticated("No Bearer Token found!"))?;
+
+    if key::is_key(token) {
+        let key = Key(token.to_string());
+        request.extensions_mut().insert(key);
+        return Ok(request);
+    }
+
+    let claims = Claims::decode(token)
+        .map_err(|_| Status::unauthenticated("Token Expired! Please Sign-In Again."))?;
+
+    request
+        .extensions_mut()
+        .insert(AuthorizedUser { id: claims.sub });
+
+    Ok(request)
+}
+
+/// User of a request authorized by `authenticate`, the owner of its API
+/// key if it was sent one
+#[allow(clippy::result_large_err)]
+pub async fn user<T>(pool: &Arc<PgPool>, request: &Request<T>) -> Result<AuthorizedUser, Status> {
+    if let Some(user) = request.extensions().get::<AuthorizedUser>() {
+        return Ok(user.clone());
+    }
+
+    let Some(Key(key)) = request.extensions().get::<Key>() else {
+        return Err(Status::unauthenticated("No Bearer Token found!"));
+    };
+
+    match ApiKeys::new(pool.clone()).owner(key).await {
+        Ok(owner) => Ok(AuthorizedUser {
+            id: owner.user_id.to_string(),
+        }),
+        Err(CrudError::NotFound) => Err(Status::unauthenticated("Unknown or revoked API key!")),
+        Err(error) => Err(Status::internal(error.reason())),
+    }
+}
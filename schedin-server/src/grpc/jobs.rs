@@ -0,0 +1,347 @@
+//! gRPC Jobs Service
+
+extern crate futures;
+extern crate prost_types;
+extern crate schedin_common;
+extern crate schedin_proto;
+extern crate serde_json;
+extern crate sqlx;
+extern crate std;
+extern crate time;
+extern crate tonic;
+extern crate uuid;
+extern crate validator;
+
+use super::auth;
+use crate::{
+    db::{project::Project, run::Runs, DB},
+    event::{
+        hub::{Hub, Item},
+        schema::Event,
+    },
+    iam::schema::AuthorizedUser,
+    job::schema::{Job, JobRecord, JobType},
+    project::schema::{ProjectScope, DEFAULT_PROJECT},
+    run::schema::{Run, RunStatus},
+};
+use futures::{stream, Stream};
+use prost_types::Timestamp;
+use schedin_common::error::CrudError;
+use schedin_proto::v1::{self, jobs_server::Jobs};
+use sqlx::PgPool;
+use std::{pin::Pin, sync::Arc};
+use time::OffsetDateTime;
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+use validator::Validate;
+
+/// Runs listed by default
+const DEFAULT_HISTORY: i64 = 50;
+
+/// # Job Service
+/// Job CRUD, run history, run-now and run events of the user, over the
+/// same database layer as the REST endpoints
+pub struct JobService {
+    pool: Arc<PgPool>,
+    hub: Arc<Hub>,
+}
+
+impl JobService {
+    /// New instance
+    pub fn new(pool: Arc<PgPool>, hub: Arc<Hub>) -> Self {
+        Self { pool, hub }
+    }
+
+    /// Project `name` of the user, the `default` project if empty
+    async fn scope(&self, user: &AuthorizedUser, name: &str) -> Result<ProjectScope, Status> {
+        let name = match name.is_empty() {
+            true => DEFAULT_PROJECT,
+            false => name,
+        };
+
+        Project::new(self.pool.clone())
+            .scope(&user.id, name)
+            .await
+            .map_err(|error| match error {
+                CrudError::NotFound => Status::not_found(format!("Unknown project '{}'", name)),
+                error => status(error),
+            })
+    }
+}
+
+#[tonic::async_trait]
+impl Jobs for JobService {
+    async fn create_job(
+        &self,
+        request: Request<v1::CreateJobRequest>,
+    ) -> Result<Response<v1::Job>, Status> {
+        let user = auth::user(&self.pool, &request).await?;
+        let request = request.into_inner();
+        let project = self.scope(&user, &request.project).await?;
+
+        let job = spec(request.spec)?;
+
+        let db = DB::new(self.pool.clone()).job(job).project(project.id);
+        let job_id = db.insert(&user.id).await.map_err(status)?;
+
+        let record = db.get(&job_id).await.map_err(status)?;
+
+        Ok(Response::new(message(record)))
+    }
+
+    async fn get_job(
+        &self,
+        request: Request<v1::GetJobRequest>,
+    ) -> Result<Response<v1::Job>, Status> {
+        let user = auth::user(&self.pool, &request).await?;
+        let request = request.into_inner();
+        let project = self.scope(&user, &request.project).await?;
+
+        let record = DB::new(self.pool.clone())
+            .project(project.id)
+            .get(&id(&request.id)?)
+            .await
+            .map_err(status)?;
+
+        Ok(Response::new(message(record)))
+    }
+
+    async fn list_jobs(
+        &self,
+        request: Request<v1::ListJobsRequest>,
+    ) -> Result<Response<v1::ListJobsResponse>, Status> {
+        let user = auth::user(&self.pool, &request).await?;
+        let project = self.scope(&user, &request.get_ref().project).await?;
+
+        let jobs = DB::new(self.pool.clone())
+            .project(project.id)
+            .list()
+            .await
+            .map_err(status)?
+            .into_iter()
+            .map(message)
+            .collect();
+
+        Ok(Response::new(v1::ListJobsResponse { jobs }))
+    }
+
+    async fn update_job(
+        &self,
+        request: Request<v1::UpdateJobRequest>,
+    ) -> Result<Response<v1::Job>, Status> {
+        let user = auth::user(&self.pool, &request).await?;
+        let request = request.into_inner();
+        let project = self.scope(&user, &request.project).await?;
+
+        let job_id = id(&request.id)?;
+        let job = spec(request.spec)?;
+
+        let db = DB::new(self.pool.clone()).job(job).project(project.id);
+        db.update(&user.id, &job_id).await.map_err(status)?;
+
+        let record = db.get(&job_id).await.map_err(status)?;
+
+        Ok(Response::new(message(record)))
+    }
+
+    async fn delete_job(
+        &self,
+        request: Request<v1::DeleteJobRequest>,
+    ) -> Result<Response<()>, Status> {
+        let user = auth::user(&self.pool, &request).await?;
+        let request = request.into_inner();
+        let project = self.scope(&user, &request.project).await?;
+
+        DB::new(self.pool.clone())
+            .project(project.id)
+            .delete_by_id(&id(&request.id)?)
+            .await
+            .map_err(status)?;
+
+        Ok(Response::new(()))
+    }
+
+    async fn list_runs(
+        &self,
+        request: Request<v1::ListRunsRequest>,
+    ) -> Result<Response<v1::ListRunsResponse>, Status> {
+        let user = auth::user(&self.pool, &request).await?;
+        let request = request.into_inner();
+        let project = self.scope(&user, &request.project).await?;
+
+        let runs = Runs::new(self.pool.clone())
+            .history(
+                &project.id,
+                &id(&request.job_id)?,
+                request.limit.unwrap_or(DEFAULT_HISTORY),
+            )
+            .await
+            .map_err(status)?;
+
+        Ok(Response::new(v1::ListRunsResponse {
+            runs: runs.into_iter().map(v1::Run::from).collect(),
+        }))
+    }
+
+    async fn run_now(
+        &self,
+        request: Request<v1::RunNowRequest>,
+    ) -> Result<Response<v1::Run>, Status> {
+        let user = auth::user(&self.pool, &request).await?;
+        let request = request.into_inner();
+        let project = self.scope(&user, &request.project).await?;
+
+        let runs = Runs::new(self.pool.clone());
+        let run_id = runs
+            .trigger(&project.id, &id(&request.job_id)?)
+            .await
+            .map_err(status)?;
+
+        let run = runs.get(&project.id, &run_id).await.map_err(status)?;
+
+        Ok(Response::new(run.into()))
+    }
+
+    type WatchRunsStream = Pin<Box<dyn Stream<Item = Result<v1::RunEvent, Status>> + Send>>;
+
+    async fn watch_runs(
+        &self,
+        request: Request<v1::WatchRunsRequest>,
+    ) -> Result<Response<Self::WatchRunsStream>, Status> {
+        let user = auth::user(&self.pool, &request).await?;
+        let request = request.into_inner();
+
+        let labels = Some(request.labels).filter(|labels| !labels.is_empty());
+
+        let feed = self
+            .hub
+            .feed(
+                self.pool.clone(),
+                &user.id,
+                request.last_event_id,
+                request.job,
+                labels,
+            )
+            .await
+            .map_err(status)?;
+
+        // the stream ends after an error, clients resume from the last event
+        let events = stream::unfold(Some(feed), |feed| async move {
+            let mut feed = feed?;
+
+            loop {
+                match feed.next().await {
+                    Ok(Item::Event(event)) if event.event_type.starts_with("run.") => {
+                        return Some((Ok(run_event(event)), Some(feed)))
+                    }
+                    Ok(_) => continue,
+                    Err(error) => return Some((Err(status(error)), None)),
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(events)))
+    }
+}
+
+/// Job of a spec, validated as the jobs of the REST API
+#[allow(clippy::result_large_err)]
+fn spec(spec: Option<v1::JobSpec>) -> Result<Job, Status> {
+    let job = Job::from(spec.ok_or_else(|| Status::invalid_argument("Job spec is required"))?);
+
+    job.validate()
+        .map_err(|error| Status::invalid_argument(error.to_string()))?;
+
+    if let JobType::Invalid = job.kind() {
+        return Err(Status::invalid_argument(
+            "Job must be defined: 'bin', 'task', 'code', 'http', 'sql', 'wasm', or 'pipeline'",
+        ));
+    }
+
+    Ok(job)
+}
+
+fn message(record: JobRecord) -> v1::Job {
+    v1::Job {
+        id: record.id.to_string(),
+        name: record.job.name.clone(),
+        spec: Some(record.job.into()),
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn id(id: &str) -> Result<Uuid, Status> {
+    Uuid::parse_str(id).map_err(|_| Status::invalid_argument(format!("Invalid id '{}'", id)))
+}
+
+fn status(error: CrudError) -> Status {
+    match error {
+        CrudError::NotFound => Status::not_found(error.reason()),
+        CrudError::Conflict => Status::already_exists(error.reason()),
+        CrudError::Validation => Status::invalid_argument(error.reason()),
+        CrudError::Quota => Status::resource_exhausted(error.reason()),
+        _ => Status::internal(error.reason()),
+    }
+}
+
+fn timestamp(time: OffsetDateTime) -> Timestamp {
+    Timestamp {
+        seconds: time.unix_timestamp(),
+        nanos: time.nanosecond() as i32,
+    }
+}
+
+impl From<&RunStatus> for v1::RunStatus {
+    fn from(status: &RunStatus) -> Self {
+        match status {
+            RunStatus::Queued => Self::Queued,
+            RunStatus::Running => Self::Running,
+            RunStatus::Succeeded => Self::Succeeded,
+            RunStatus::Failed => Self::Failed,
+            RunStatus::Unschedulable => Self::Unschedulable,
+            RunStatus::Cancelled => Self::Cancelled,
+        }
+    }
+}
+
+impl From<Run> for v1::Run {
+    fn from(run: Run) -> Self {
+        Self {
+            id: run.id.to_string(),
+            job_id: run.job_id.to_string(),
+            revision: run.revision,
+            status: v1::RunStatus::from(&run.status).into(),
+            scheduled_at: Some(timestamp(run.scheduled_at)),
+            started_at: run.started_at.map(timestamp),
+            finished_at: run.finished_at.map(timestamp),
+            exit_code: run.exit_code,
+            error: run.error,
+            worker: run.worker,
+            reason: run.reason,
+            parent_id: run.parent_id.map(|id| id.to_string()),
+        }
+    }
+}
+
+/// Run event of the payload of a `run.*` event
+fn run_event(event: Event) -> v1::RunEvent {
+    let data = &event.data.0;
+    let field = |name: &str| data[name].as_str().unwrap_or_default().to_string();
+
+    let status = serde_json::from_value::<RunStatus>(data["status"].clone())
+        .map(|status| v1::RunStatus::from(&status))
+        .unwrap_or(v1::RunStatus::Unspecified);
+
+    v1::RunEvent {
+        seq: event.seq,
+        id: event.id.to_string(),
+        r#type: event.event_type.clone(),
+        created_at: Some(timestamp(event.created_at)),
+        run_id: field("run_id"),
+        job_id: field("job_id"),
+        job: field("job"),
+        project: field("project"),
+        status: status.into(),
+        data: data.to_string(),
+    }
+}
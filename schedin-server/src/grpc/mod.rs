@@ -0,0 +1,145 @@
+//! gRPC API
+//!
+//! The `Jobs` service of `schedin-proto`, served on a separate port with the
+//! TLS configuration of the REST API. It shares the database layer and the
+//! event hub with the REST endpoints, and accepts the same bearer tokens.
+
+extern crate futures;
+extern crate rustls;
+extern crate schedin_proto;
+extern crate sqlx;
+extern crate std;
+extern crate tokio;
+extern crate tokio_rustls;
+extern crate tonic;
+
+pub mod auth;
+pub mod jobs;
+pub mod spec;
+
+use crate::event::hub::Hub;
+use futures::{stream, StreamExt};
+use jobs::JobService;
+use rustls::ServerConfig;
+use schedin_proto::v1::jobs_server::JobsServer;
+use sqlx::PgPool;
+use std::{
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{TcpListener, TcpStream},
+    time,
+};
+use tokio_rustls::{server::TlsStream, TlsAcceptor};
+use tonic::transport::{server::Connected, Server};
+
+/// TLS handshakes in progress at once
+const HANDSHAKES: usize = 64;
+
+/// Time (in seconds) a client has to complete the TLS handshake
+const HANDSHAKE_TIMEOUT: u64 = 10;
+
+/// # Serve
+/// Serve the gRPC API on the connections of `listener`, over TLS with
+/// `config` and HTTP/2.
+pub async fn serve(
+    listener: TcpListener,
+    mut config: ServerConfig,
+    pool: Arc<PgPool>,
+    hub: Arc<Hub>,
+) -> Result<(), tonic::transport::Error> {
+    // gRPC clients only speak HTTP/2
+    config.alpn_protocols = vec![b"h2".to_vec()];
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+
+    let connections = stream::unfold(listener, |listener| async move {
+        loop {
+            match listener.accept().await {
+                Ok((tcp, _)) => return Some((tcp, listener)),
+                Err(error) => {
+                    eprintln!("grpc: {}", error);
+                    // e.g. out of file descriptors, give connections time to close
+                    time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        }
+    })
+    .map(move |tcp| time::timeout(Duration::from_secs(HANDSHAKE_TIMEOUT), acceptor.accept(tcp)))
+    .buffer_unordered(HANDSHAKES)
+    .filter_map(|handshake| async move {
+        match handshake {
+            Ok(Ok(tls)) => Some(Ok::<_, io::Error>(Connection(tls))),
+            Ok(Err(error)) => {
+                eprintln!("grpc: {}", error);
+                None
+            }
+            Err(_) => None,
+        }
+    });
+
+    Server::builder()
+        .add_service(JobsServer::with_interceptor(
+            JobService::new(pool, hub),
+            auth::authenticate,
+        ))
+        .serve_with_incoming(connections)
+        .await
+}
+
+/// TLS connection of a client, tonic only accepts the TLS streams of its
+/// own rustls version as is
+pub struct Connection(TlsStream<TcpStream>);
+
+impl Connected for Connection {
+    type ConnectInfo = Option<SocketAddr>;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.0.get_ref().0.peer_addr().ok()
+    }
+}
+
+impl AsyncRead for Connection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Connection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.0.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
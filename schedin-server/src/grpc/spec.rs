@@ -0,0 +1,480 @@
+//! gRPC Job Spec
+//!
+//! Conversions between the job of the REST API and its `JobSpec` message.
+//! Unset messages, empty maps and empty lists of the message are the unset
+//! fields of the job, and binary fields are base64-encoded as in the REST API.
+
+extern crate base64;
+extern crate schedin_proto;
+extern crate serde_json;
+extern crate std;
+
+use crate::job::schema::{
+    Axis, Bin, Code, Env, Http, Job, Matrix, Pipeline, Quantity, Resources, Sql, Step, Task, Term,
+    Wasm,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use schedin_proto::v1::{self, axis, env_value, job_spec, step};
+use serde_json::Value;
+use std::collections::HashMap;
+
+impl From<v1::JobSpec> for Job {
+    fn from(spec: v1::JobSpec) -> Self {
+        let mut job = Job {
+            name: spec.name,
+            description: spec.description,
+            schedule: Some(spec.schedule).filter(|schedule| !schedule.is_empty()),
+            env: some(spec.env.into_iter().map(|(k, v)| (k, v.into())).collect()),
+            labels: some(spec.labels),
+            timeout: spec.timeout,
+            matrix: spec.matrix.map(Matrix::from),
+            artifacts: Some(spec.artifacts).filter(|artifacts| !artifacts.is_empty()),
+            resources: spec.resources.map(Resources::from),
+            node_selector: some(spec.node_selector),
+            affinity: terms(spec.affinity),
+            anti_affinity: terms(spec.anti_affinity),
+            priority_class: spec.priority_class,
+            ..Default::default()
+        };
+
+        match spec.kind {
+            Some(job_spec::Kind::Task(task)) => job.task = Some(task.into()),
+            Some(job_spec::Kind::Code(code)) => job.code = Some(code.into()),
+            Some(job_spec::Kind::Bin(bin)) => job.bin = Some(bin.into()),
+            Some(job_spec::Kind::Http(http)) => job.http = Some(http.into()),
+            Some(job_spec::Kind::Sql(sql)) => job.sql = Some(sql.into()),
+            Some(job_spec::Kind::Wasm(wasm)) => job.wasm = Some(wasm.into()),
+            Some(job_spec::Kind::Pipeline(pipeline)) => job.pipeline = Some(pipeline.into()),
+            None => {}
+        }
+
+        job
+    }
+}
+
+impl From<Job> for v1::JobSpec {
+    fn from(job: Job) -> Self {
+        let kind = if let Some(task) = job.task {
+            Some(job_spec::Kind::Task(task.into()))
+        } else if let Some(code) = job.code {
+            Some(job_spec::Kind::Code(code.into()))
+        } else if let Some(bin) = job.bin {
+            Some(job_spec::Kind::Bin(bin.into()))
+        } else if let Some(http) = job.http {
+            Some(job_spec::Kind::Http(http.into()))
+        } else if let Some(sql) = job.sql {
+            Some(job_spec::Kind::Sql(sql.into()))
+        } else if let Some(wasm) = job.wasm {
+            Some(job_spec::Kind::Wasm(wasm.into()))
+        } else {
+            job.pipeline
+                .map(|pipeline| job_spec::Kind::Pipeline(pipeline.into()))
+        };
+
+        Self {
+            name: job.name,
+            description: job.description,
+            schedule: job.schedule.unwrap_or_default(),
+            kind,
+            env: job
+                .env
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(k, v)| (k, v.into()))
+                .collect(),
+            labels: job.labels.unwrap_or_default(),
+            timeout: job.timeout,
+            matrix: job.matrix.map(v1::Matrix::from),
+            artifacts: job.artifacts.unwrap_or_default(),
+            resources: job.resources.map(v1::Resources::from),
+            node_selector: job.node_selector.unwrap_or_default(),
+            affinity: job
+                .affinity
+                .unwrap_or_default()
+                .into_iter()
+                .map(v1::Term::from)
+                .collect(),
+            anti_affinity: job
+                .anti_affinity
+                .unwrap_or_default()
+                .into_iter()
+                .map(v1::Term::from)
+                .collect(),
+            priority_class: job.priority_class,
+        }
+    }
+}
+
+/// `None` for an empty map
+fn some<V>(map: HashMap<String, V>) -> Option<HashMap<String, V>> {
+    Some(map).filter(|map| !map.is_empty())
+}
+
+/// Terms of an affinity, `None` if there are none
+fn terms(terms: Vec<v1::Term>) -> Option<Vec<Term>> {
+    Some(terms)
+        .filter(|terms| !terms.is_empty())
+        .map(|terms| terms.into_iter().map(Term::from).collect())
+}
+
+impl From<v1::EnvValue> for Env {
+    fn from(value: v1::EnvValue) -> Self {
+        match value.source {
+            Some(env_value::Source::Secret(secret)) => Env::Secret { secret },
+            Some(env_value::Source::Value(value)) => Env::Value(value),
+            None => Env::Value(String::new()),
+        }
+    }
+}
+
+impl From<Env> for v1::EnvValue {
+    fn from(env: Env) -> Self {
+        let source = match env {
+            Env::Value(value) => env_value::Source::Value(value),
+            Env::Secret { secret } => env_value::Source::Secret(secret),
+        };
+
+        Self {
+            source: Some(source),
+        }
+    }
+}
+
+impl From<v1::Task> for Task {
+    fn from(task: v1::Task) -> Self {
+        Self { name: task.name }
+    }
+}
+
+impl From<Task> for v1::Task {
+    fn from(task: Task) -> Self {
+        Self { name: task.name }
+    }
+}
+
+impl From<v1::Code> for Code {
+    fn from(code: v1::Code) -> Self {
+        Self {
+            src: STANDARD.encode(code.src),
+            lang: code.lang,
+            cmd: code.cmd,
+        }
+    }
+}
+
+impl From<Code> for v1::Code {
+    fn from(code: Code) -> Self {
+        Self {
+            // sources are validated as base64 on insert
+            src: STANDARD.decode(code.src).unwrap_or_default(),
+            lang: code.lang,
+            cmd: code.cmd,
+        }
+    }
+}
+
+impl From<v1::Bin> for Bin {
+    fn from(bin: v1::Bin) -> Self {
+        Self {
+            path: bin.path,
+            cmd: bin.cmd,
+        }
+    }
+}
+
+impl From<Bin> for v1::Bin {
+    fn from(bin: Bin) -> Self {
+        Self {
+            path: bin.path,
+            cmd: bin.cmd,
+        }
+    }
+}
+
+impl From<v1::Http> for Http {
+    fn from(http: v1::Http) -> Self {
+        Self {
+            method: match http.method.is_empty() {
+                true => "GET".to_string(),
+                false => http.method,
+            },
+            url: http.url,
+            headers: some(
+                http.headers
+                    .into_iter()
+                    .map(|(k, v)| (k, v.into()))
+                    .collect(),
+            ),
+            body: http.body,
+            timeout: http.timeout,
+            expected_status: Some(http.expected_status).filter(|status| !status.is_empty()),
+            assertion: http.assertion,
+        }
+    }
+}
+
+impl From<Http> for v1::Http {
+    fn from(http: Http) -> Self {
+        Self {
+            method: http.method,
+            url: http.url,
+            headers: http
+                .headers
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(k, v)| (k, v.into()))
+                .collect(),
+            body: http.body,
+            timeout: http.timeout,
+            expected_status: http.expected_status.unwrap_or_default(),
+            assertion: http.assertion,
+        }
+    }
+}
+
+impl From<v1::Sql> for Sql {
+    fn from(sql: v1::Sql) -> Self {
+        Self {
+            connection: sql.connection,
+            statements: sql.statements,
+            timeout: sql.timeout,
+        }
+    }
+}
+
+impl From<Sql> for v1::Sql {
+    fn from(sql: Sql) -> Self {
+        Self {
+            connection: sql.connection,
+            statements: sql.statements,
+            timeout: sql.timeout,
+        }
+    }
+}
+
+impl From<v1::Wasm> for Wasm {
+    fn from(wasm: v1::Wasm) -> Self {
+        Self {
+            module: wasm.module.map(|module| STANDARD.encode(module)),
+            digest: wasm.digest,
+            args: wasm.args,
+            fuel: wasm.fuel,
+        }
+    }
+}
+
+impl From<Wasm> for v1::Wasm {
+    fn from(wasm: Wasm) -> Self {
+        Self {
+            module: wasm.module(),
+            digest: wasm.digest,
+            args: wasm.args,
+            fuel: wasm.fuel,
+        }
+    }
+}
+
+impl From<v1::Pipeline> for Pipeline {
+    fn from(pipeline: v1::Pipeline) -> Self {
+        Self {
+            steps: pipeline.steps.into_iter().map(Step::from).collect(),
+        }
+    }
+}
+
+impl From<Pipeline> for v1::Pipeline {
+    fn from(pipeline: Pipeline) -> Self {
+        Self {
+            steps: pipeline.steps.into_iter().map(v1::Step::from).collect(),
+        }
+    }
+}
+
+impl From<v1::Step> for Step {
+    fn from(step: v1::Step) -> Self {
+        let mut converted = Step {
+            name: step.name,
+            timeout: step.timeout,
+            continue_on_error: step.continue_on_error,
+            ..Default::default()
+        };
+
+        match step.kind {
+            Some(step::Kind::Task(task)) => converted.task = Some(task.into()),
+            Some(step::Kind::Code(code)) => converted.code = Some(code.into()),
+            Some(step::Kind::Bin(bin)) => converted.bin = Some(bin.into()),
+            Some(step::Kind::Http(http)) => converted.http = Some(http.into()),
+            None => {}
+        }
+
+        converted
+    }
+}
+
+impl From<Step> for v1::Step {
+    fn from(step: Step) -> Self {
+        let kind = if let Some(task) = step.task {
+            Some(step::Kind::Task(task.into()))
+        } else if let Some(code) = step.code {
+            Some(step::Kind::Code(code.into()))
+        } else if let Some(bin) = step.bin {
+            Some(step::Kind::Bin(bin.into()))
+        } else {
+            step.http.map(|http| step::Kind::Http(http.into()))
+        };
+
+        Self {
+            name: step.name,
+            kind,
+            timeout: step.timeout,
+            continue_on_error: step.continue_on_error,
+        }
+    }
+}
+
+impl From<v1::Matrix> for Matrix {
+    fn from(matrix: v1::Matrix) -> Self {
+        Self {
+            axes: matrix
+                .axes
+                .into_iter()
+                .map(|(name, axis)| (name, axis.into()))
+                .collect(),
+            max_parallel: matrix.max_parallel,
+            fail_fast: matrix.fail_fast,
+        }
+    }
+}
+
+impl From<Matrix> for v1::Matrix {
+    fn from(matrix: Matrix) -> Self {
+        Self {
+            axes: matrix
+                .axes
+                .into_iter()
+                .map(|(name, axis)| (name, axis.into()))
+                .collect(),
+            max_parallel: matrix.max_parallel,
+            fail_fast: matrix.fail_fast,
+        }
+    }
+}
+
+impl From<v1::Axis> for Axis {
+    fn from(axis: v1::Axis) -> Self {
+        match axis.values {
+            Some(axis::Values::Range(range)) => Axis::Range(range),
+            Some(axis::Values::List(list)) => {
+                Axis::Values(list.values.into_iter().map(Value::String).collect())
+            }
+            None => Axis::Values(Vec::new()),
+        }
+    }
+}
+
+impl From<Axis> for v1::Axis {
+    fn from(axis: Axis) -> Self {
+        let values = match axis {
+            Axis::Range(range) => axis::Values::Range(range),
+            axis => axis::Values::List(v1::AxisValues {
+                values: axis.values(),
+            }),
+        };
+
+        Self {
+            values: Some(values),
+        }
+    }
+}
+
+impl From<v1::Resources> for Resources {
+    fn from(resources: v1::Resources) -> Self {
+        Self {
+            requests: resources.requests.map(Quantity::from).unwrap_or_default(),
+            limits: resources.limits.map(Quantity::from),
+        }
+    }
+}
+
+impl From<Resources> for v1::Resources {
+    fn from(resources: Resources) -> Self {
+        Self {
+            requests: Some(resources.requests.into()),
+            limits: resources.limits.map(v1::Quantity::from),
+        }
+    }
+}
+
+impl From<v1::Quantity> for Quantity {
+    fn from(quantity: v1::Quantity) -> Self {
+        Self {
+            cpu: quantity.cpu,
+            memory_mb: quantity.memory_mb,
+            disk_mb: quantity.disk_mb,
+        }
+    }
+}
+
+impl From<Quantity> for v1::Quantity {
+    fn from(quantity: Quantity) -> Self {
+        Self {
+            cpu: quantity.cpu,
+            memory_mb: quantity.memory_mb,
+            disk_mb: quantity.disk_mb,
+        }
+    }
+}
+
+impl From<v1::Term> for Term {
+    fn from(term: v1::Term) -> Self {
+        Self {
+            job: term.job,
+            labels: term.labels,
+        }
+    }
+}
+
+impl From<Term> for v1::Term {
+    fn from(term: Term) -> Self {
+        Self {
+            job: term.job,
+            labels: term.labels,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::job::schema::Job;
+    use schedin_proto::v1;
+    use serde_json::json;
+
+    #[test]
+    fn round_trips_jobs() {
+        let job = serde_json::from_value::<Job>(json!({
+            "name": "etl",
+            "description": "Nightly load",
+            "schedule": "@every 1 hr",
+            "pipeline": { "steps": [
+                { "name": "extract", "code": { "src": "ZWNobyBoaQ==", "lang": "sh", "cmd": "sh" } },
+                { "name": "notify", "http": {
+                    "method": "POST",
+                    "url": "https://hooks.example.com/etl",
+                    "headers": { "Authorization": { "secret": "hook-token" } },
+                    "expected_status": [200, 202]
+                }, "continue_on_error": true }
+            ] },
+            "env": { "MODE": "production", "TOKEN": { "secret": "api-token" } },
+            "labels": { "team": "data" },
+            "timeout": 600,
+            "matrix": { "axes": { "region": ["eu", "us"], "shard": "0..4" }, "max_parallel": 2 },
+            "artifacts": ["out/*.csv"],
+            "resources": { "requests": { "cpu": 0.5, "memory_mb": 512 } },
+            "anti_affinity": [{ "job": "etl" }],
+            "priority_class": "batch"
+        }))
+        .unwrap();
+
+        assert_eq!(Job::from(v1::JobSpec::from(job.clone())), job);
+    }
+}
@@ -0,0 +1,67 @@
+//! API Keys
+//!
+//! Long-lived credentials of a user, sent like the JWT token of sign-in as
+//! `Authorization: Bearer <key>`. Keys are told apart from tokens by their
+//! prefix, and only their SHA-256 digest is stored.
+
+extern crate hex;
+extern crate schedin_common;
+extern crate sha2;
+
+use hex::encode;
+use schedin_common::secret::{self, Redacted};
+use sha2::{Digest, Sha256};
+
+/// Prefix of the API keys
+pub const KEY_PREFIX: &str = "sk_";
+
+/// Random bytes of an API key
+const KEY_BYTES: usize = 32;
+
+/// New random API key
+pub fn generate() -> Redacted<String> {
+    Redacted::new(format!(
+        "{}{}",
+        KEY_PREFIX,
+        secret::token(KEY_BYTES).expose()
+    ))
+}
+
+/// Whether the bearer `token` is an API key rather than a JWT token
+pub fn is_key(token: &str) -> bool {
+    token.starts_with(KEY_PREFIX)
+}
+
+/// Hex-encoded SHA-256 digest of `key`, as stored
+pub fn hash(key: &str) -> String {
+    encode(Sha256::digest(key.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate, hash, is_key};
+
+    #[test]
+    fn generates_distinct_keys() {
+        let key = generate();
+        let other = generate();
+
+        assert!(is_key(key.expose()));
+        assert_eq!(key.expose().len(), 3 + 64);
+        assert_ne!(key.expose(), other.expose());
+    }
+
+    #[test]
+    fn tells_keys_from_tokens() {
+        assert!(is_key("sk_test"));
+        assert!(!is_key("eyJhbGciOiJIUzI1NiJ9.e30.signature"));
+    }
+
+    #[test]
+    fn hashes_keys_with_sha256() {
+        assert_eq!(
+            hash("sk_test"),
+            "12b2820cf1639904311da5771de1e5bb65c77073fdc7c555df395942df42896b"
+        );
+    }
+}
@@ -1,4 +1,5 @@
 //! Identity and Access Management
 
+pub mod key;
 pub mod schema;
 pub mod token;
@@ -4,14 +4,21 @@
 extern crate hex;
 extern crate serde;
 extern crate sha2;
+extern crate time;
+extern crate utoipa;
 extern crate uuid;
+extern crate validator;
 
+use crate::api::validation::validate_key_name;
 use hex::encode;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
 use uuid::Uuid;
+use validator::Validate;
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default, Deserialize, ToSchema)]
 pub struct User {
     pub username: Option<String>,
     pub email: Option<String>,
@@ -35,14 +42,14 @@ impl User {
     /// Hex Encoded String
     pub fn hash(&self) -> String {
         let mut hasher = Sha256::new();
-        hasher.update(self.password.as_ref().unwrap().as_bytes());
+        hasher.update(self.password.as_deref().unwrap_or_default().as_bytes());
         encode(hasher.finalize())
     }
 }
 
 /// # Sign-in Response
 /// Success response after sign-in
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct SigninResponse {
     pub success: bool,
     pub message: String,
@@ -97,11 +104,21 @@ impl Default for SigninResponse {
 
 /// Authorized User
 ///
-/// Represents an authorized user after decoding and validating a JWT token.
+/// Represents an authorized user after decoding and validating a JWT token,
+/// or looking up an API key.
+#[derive(Debug, Clone)]
 pub struct AuthorizedUser {
     pub id: String,
 }
 
+/// Administrator
+///
+/// Represents an authorized user allowed to manage cluster-wide settings,
+/// such as priority classes.
+pub struct AdminUser {
+    pub id: String,
+}
+
 /// Represents user sign-in information retrieved from the database.
 ///
 /// This data is typically retrieved from a database when a user
@@ -110,5 +127,31 @@ pub struct AuthorizedUser {
 pub struct SigninRow {
     pub user_id: Uuid,
     pub username: String,
-    pub passcode: String,
+}
+
+/// # API Key
+/// Name of an API key of the user, the key itself is generated.
+#[derive(Debug, Default, Deserialize, Validate, ToSchema)]
+pub struct ApiKey {
+    #[validate(custom(
+        function = "validate_key_name",
+        message = "Key name must be 1-255 characters of [A-Za-z0-9_.-]"
+    ))]
+    pub name: String,
+}
+
+/// # API Key Metadata
+/// Listing entry for an API key, without the key.
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct ApiKeyRow {
+    pub name: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+/// Owner of an API key, retrieved from the database when authenticating
+#[derive(sqlx::FromRow)]
+pub struct KeyOwner {
+    pub key_id: Uuid,
+    pub user_id: Uuid,
 }
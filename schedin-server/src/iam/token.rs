@@ -6,7 +6,7 @@ extern crate std;
 extern crate time;
 
 use super::schema::SigninRow;
-use jsonwebtoken::{encode, EncodingKey, Header};
+use jsonwebtoken::{decode, encode, errors::Error, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 use std::{env, time::Duration};
 use time::OffsetDateTime;
@@ -21,6 +21,18 @@ pub struct Claims {
     pub sub: String,
 }
 
+impl Claims {
+    /// Decode and validate a JWT Token
+    pub fn decode(token: &str) -> Result<Self, Error> {
+        decode::<Claims>(
+            token,
+            &DecodingKey::from_secret(env::var("JWT_SECRET").unwrap().as_ref()),
+            &Validation::default(),
+        )
+        .map(|token| token.claims)
+    }
+}
+
 impl SigninRow {
     /// Generate JWT Token
     pub fn gen_token(&self) -> String {
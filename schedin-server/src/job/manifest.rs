@@ -0,0 +1,332 @@
+//! Job Manifests
+//! Declarative set of jobs, applied the way `kubectl apply` works
+
+extern crate serde;
+extern crate std;
+extern crate utoipa;
+extern crate uuid;
+extern crate validator;
+
+use super::schema::{Job, JobRecord, Pipeline, Resources, Wasm};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+/// Label identifying the owner of the jobs of a manifest
+pub const OWNER_LABEL: &str = "schedin/owner";
+
+/// # Manifest
+/// Desired state of a set of jobs.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct Manifest {
+    /// Owner, set as the `schedin/owner` label of every job and used to scope pruning
+    #[validate(length(min = 1, max = 63))]
+    pub owner: Option<String>,
+
+    /// Delete the jobs of the owner that are missing from the manifest
+    #[serde(default)]
+    pub prune: bool,
+
+    #[validate]
+    pub jobs: Vec<Job>,
+}
+
+/// Apply options
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ApplyOptions {
+    /// Compute the diff without applying it
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// # Diff
+/// Changes between the manifest and the current jobs, by job name.
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct Diff {
+    pub dry_run: bool,
+    pub create: Vec<String>,
+    pub update: Vec<Change>,
+    pub delete: Vec<String>,
+    pub unchanged: Vec<String>,
+}
+
+/// Updated job along with its changed fields
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Change {
+    pub name: String,
+    pub fields: Vec<&'static str>,
+}
+
+/// Operations to apply
+#[derive(Debug, Default)]
+pub struct Plan {
+    pub create: Vec<Job>,
+    pub update: Vec<(Uuid, Job)>,
+    pub delete: Vec<Uuid>,
+}
+
+impl Manifest {
+    /// # Plan
+    /// Compute the operations turning `current` into the manifest.
+    ///
+    /// Jobs are matched by name. Current jobs missing from the manifest are
+    /// deleted only when pruning, and only if they carry the owner label.
+    pub fn plan(self, current: Vec<JobRecord>) -> (Plan, Diff) {
+        let mut plan = Plan::default();
+        let mut diff = Diff::default();
+
+        let desired: HashSet<String> = self.jobs.iter().map(|job| job.name.clone()).collect();
+        let mut current: HashMap<String, JobRecord> = current
+            .into_iter()
+            .map(|record| (record.job.name.clone(), record))
+            .collect();
+
+        for mut job in self.jobs {
+            normalize(&mut job, self.owner.as_deref());
+
+            match current.remove(&job.name) {
+                None => {
+                    diff.create.push(job.name.clone());
+                    plan.create.push(job);
+                }
+                Some(record) => {
+                    let fields = changes(&record.job, &job);
+
+                    if fields.is_empty() {
+                        diff.unchanged.push(job.name);
+                    } else {
+                        diff.update.push(Change {
+                            name: job.name.clone(),
+                            fields,
+                        });
+                        plan.update.push((record.id, job));
+                    }
+                }
+            }
+        }
+
+        if let (true, Some(owner)) = (self.prune, &self.owner) {
+            for (name, record) in current {
+                let owned = record
+                    .job
+                    .labels
+                    .as_ref()
+                    .and_then(|labels| labels.get(OWNER_LABEL))
+                    .is_some_and(|value| value == owner);
+
+                if owned && !desired.contains(&name) {
+                    diff.delete.push(name);
+                    plan.delete.push(record.id);
+                }
+            }
+        }
+
+        diff.create.sort();
+        diff.delete.sort();
+        diff.unchanged.sort();
+
+        (plan, diff)
+    }
+}
+
+/// Add the owner label, drop empty maps and normalize resources,
+/// so that jobs compare equal to stored jobs
+fn normalize(job: &mut Job, owner: Option<&str>) {
+    if let Some(owner) = owner {
+        job.labels
+            .get_or_insert_with(HashMap::new)
+            .insert(OWNER_LABEL.to_string(), owner.to_string());
+    }
+
+    job.env = job.env.take().filter(|env| !env.is_empty());
+    if let Some(http) = job.http.as_mut() {
+        http.headers = http.headers.take().filter(|headers| !headers.is_empty());
+    }
+    job.pipeline = job.pipeline.take().map(Pipeline::normalize);
+    job.labels = job.labels.take().filter(|labels| !labels.is_empty());
+    job.resources = job.resources.take().and_then(Resources::normalize);
+    job.node_selector = job.node_selector.take().filter(|labels| !labels.is_empty());
+    job.affinity = job.affinity.take().filter(|terms| !terms.is_empty());
+    job.anti_affinity = job.anti_affinity.take().filter(|terms| !terms.is_empty());
+}
+
+/// Names of the fields that differ between two jobs
+fn changes(current: &Job, desired: &Job) -> Vec<&'static str> {
+    let mut fields = Vec::new();
+
+    if current.description != desired.description {
+        fields.push("description");
+    }
+    if current.schedule != desired.schedule {
+        fields.push("schedule");
+    }
+    if current.task != desired.task {
+        fields.push("task");
+    }
+    if current.code != desired.code {
+        fields.push("code");
+    }
+    if current.bin != desired.bin {
+        fields.push("bin");
+    }
+    if current.http != desired.http {
+        fields.push("http");
+    }
+    if current.sql != desired.sql {
+        fields.push("sql");
+    }
+    if current.wasm.as_ref().map(Wasm::normalize) != desired.wasm.as_ref().map(Wasm::normalize) {
+        fields.push("wasm");
+    }
+    if current.pipeline != desired.pipeline {
+        fields.push("pipeline");
+    }
+    if current.env != desired.env {
+        fields.push("env");
+    }
+    if current.labels != desired.labels {
+        fields.push("labels");
+    }
+    if current.timeout != desired.timeout {
+        fields.push("timeout");
+    }
+    if current.matrix != desired.matrix {
+        fields.push("matrix");
+    }
+    if current.artifacts != desired.artifacts {
+        fields.push("artifacts");
+    }
+    if current.resources != desired.resources {
+        fields.push("resources");
+    }
+    if current.node_selector != desired.node_selector {
+        fields.push("node_selector");
+    }
+    if current.affinity != desired.affinity {
+        fields.push("affinity");
+    }
+    if current.anti_affinity != desired.anti_affinity {
+        fields.push("anti_affinity");
+    }
+    if current.priority_class != desired.priority_class {
+        fields.push("priority_class");
+    }
+
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{changes, Manifest};
+    use crate::job::schema::{Job, JobRecord};
+    use serde_json::{json, Value};
+    use uuid::Uuid;
+
+    fn job(job: Value) -> Job {
+        serde_json::from_value(job).unwrap()
+    }
+
+    /// Stored job `id` named `name`, as read back
+    fn record(id: u128, name: &str, owner: Option<&str>) -> JobRecord {
+        JobRecord {
+            id: Uuid::from_u128(id),
+            job: job(json!({
+                "name": name,
+                "schedule": "@every 1 hr",
+                "task": { "name": name },
+                "labels": owner.map(|owner| json!({ "schedin/owner": owner })),
+            })),
+        }
+    }
+
+    fn manifest(manifest: Value) -> Manifest {
+        serde_json::from_value(manifest).unwrap()
+    }
+
+    #[test]
+    fn detects_unchanged_and_updated_jobs() {
+        let (plan, diff) = manifest(json!({
+            "owner": "etl",
+            "jobs": [
+                { "name": "load", "schedule": "@every 1 hr", "task": { "name": "load" } },
+                { "name": "report", "schedule": "@every 2 hr", "task": { "name": "report" },
+                  "env": {}, "node_selector": {} }
+            ]
+        }))
+        .plan(vec![
+            record(1, "load", Some("etl")),
+            record(2, "report", Some("etl")),
+        ]);
+
+        assert_eq!(diff.unchanged, ["load"]);
+        assert_eq!(diff.update.len(), 1);
+        assert_eq!(diff.update[0].name, "report");
+        assert_eq!(diff.update[0].fields, ["schedule"]);
+        assert_eq!(plan.update.len(), 1);
+        assert_eq!(plan.update[0].0, Uuid::from_u128(2));
+        assert!(plan.create.is_empty() && plan.delete.is_empty());
+    }
+
+    #[test]
+    fn prunes_only_jobs_of_the_owner() {
+        let current = || {
+            vec![
+                record(1, "load", Some("etl")),
+                record(2, "stale", Some("etl")),
+                record(3, "adhoc", None),
+                record(4, "other", Some("ops")),
+            ]
+        };
+        let jobs =
+            json!([{ "name": "load", "schedule": "@every 1 hr", "task": { "name": "load" } }]);
+
+        let (plan, diff) =
+            manifest(json!({ "owner": "etl", "prune": true, "jobs": jobs })).plan(current());
+        assert_eq!(diff.delete, ["stale"]);
+        assert_eq!(plan.delete, [Uuid::from_u128(2)]);
+
+        let (plan, diff) = manifest(json!({ "owner": "etl", "jobs": jobs })).plan(current());
+        assert!(diff.delete.is_empty() && plan.delete.is_empty());
+    }
+
+    #[test]
+    fn renames_as_a_delete_and_a_create() {
+        let (plan, diff) = manifest(json!({
+            "owner": "etl",
+            "prune": true,
+            "jobs": [{ "name": "ingest", "schedule": "@every 1 hr", "task": { "name": "load" } }]
+        }))
+        .plan(vec![record(1, "load", Some("etl"))]);
+
+        assert_eq!(diff.create, ["ingest"]);
+        assert_eq!(diff.delete, ["load"]);
+        assert_eq!(plan.create[0].name, "ingest");
+        assert_eq!(
+            plan.create[0].labels.as_ref().unwrap()["schedin/owner"],
+            "etl"
+        );
+        assert_eq!(plan.delete, [Uuid::from_u128(1)]);
+        assert!(diff.update.is_empty() && diff.unchanged.is_empty());
+    }
+
+    #[test]
+    fn lists_changed_fields() {
+        let current = record(1, "load", None).job;
+        let desired = job(json!({
+            "name": "load",
+            "description": "Nightly load",
+            "schedule": "@every 1 hr",
+            "code": { "src": "ZWNobyBoaQ==", "lang": "sh", "cmd": "sh" },
+            "timeout": 60
+        }));
+
+        assert_eq!(
+            changes(&current, &desired),
+            ["description", "task", "code", "timeout"]
+        );
+        assert!(changes(&current, &current).is_empty());
+    }
+}
@@ -1,4 +1,6 @@
 //! Job
 
+pub mod manifest;
+pub mod revision;
 pub mod schedule;
 pub mod schema;
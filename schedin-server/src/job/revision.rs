@@ -0,0 +1,58 @@
+//! Job Revisions
+
+extern crate serde;
+extern crate serde_json;
+extern crate sqlx;
+extern crate time;
+extern crate utoipa;
+extern crate uuid;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// # Revision
+/// Immutable snapshot of a job definition.
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct Revision {
+    pub revision: i32,
+    pub changed_by: Option<Uuid>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub changed_at: Option<OffsetDateTime>,
+    pub definition: Value,
+    pub diff: Value,
+}
+
+/// Rollback request
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct Rollback {
+    /// Revision to restore
+    pub revision: i32,
+}
+
+/// # Diff
+/// Changed top-level fields between two job definitions,
+/// as `{ "field": { "from": ..., "to": ... } }`.
+pub fn diff(previous: Option<&Value>, next: &Value) -> Value {
+    let empty = Map::new();
+    let previous = previous.and_then(Value::as_object).unwrap_or(&empty);
+    let next = next.as_object().unwrap_or(&empty);
+
+    let mut changes = Map::new();
+
+    for key in previous.keys().chain(next.keys()) {
+        let from = previous.get(key).unwrap_or(&Value::Null);
+        let to = next.get(key).unwrap_or(&Value::Null);
+
+        if from != to && !changes.contains_key(key) {
+            let mut change = Map::with_capacity(2);
+            change.insert("from".to_string(), from.clone());
+            change.insert("to".to_string(), to.clone());
+            changes.insert(key.clone(), Value::Object(change));
+        }
+    }
+
+    Value::Object(changes)
+}
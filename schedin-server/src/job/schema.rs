@@ -1,28 +1,174 @@
 //! Job Schema
 //! Unified Schema for API and Database
 
+extern crate base64;
 extern crate serde;
+extern crate serde_json;
+extern crate std;
+extern crate time;
+extern crate utoipa;
 extern crate uuid;
 extern crate validator;
 
-use crate::api::validation::{validate_schedule, validate_source_format};
-use serde::Deserialize;
+use crate::{
+    api::validation::{
+        validate_affinity, validate_artifacts, validate_artifacts_job, validate_env,
+        validate_http_headers, validate_http_method, validate_http_url, validate_json_path,
+        validate_labels, validate_matrix, validate_matrix_job, validate_pipeline,
+        validate_priority_class_name, validate_resources, validate_schedule, validate_secret_name,
+        validate_source_format, validate_statements, validate_status_codes, validate_wasm_digest,
+        validate_wasm_module, validate_wasm_source,
+    },
+    module,
+};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+};
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 use validator::Validate;
 
-#[derive(Debug, Default, Deserialize, Validate)]
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, Validate, ToSchema)]
+#[validate(schema(function = "validate_matrix_job"))]
+#[validate(schema(function = "validate_artifacts_job"))]
 pub struct Job {
     pub name: String,
     pub description: Option<String>,
-    #[validate(custom(
-        function = "validate_schedule",
-        message = "Valid Examples: @every 10 min or @once 2023-10-17 06:45:00>"
-    ))]
+    #[validate(
+        required(message = "Job must have a schedule"),
+        custom(
+            function = "validate_schedule",
+            message = "Valid Examples: @every 10 min or @once 2023-10-17 06:45:00>"
+        )
+    )]
     pub schedule: Option<String>,
     pub task: Option<Task>,
     #[validate]
     pub code: Option<Code>,
     pub bin: Option<Bin>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate]
+    pub http: Option<Http>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate]
+    pub sql: Option<Sql>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate]
+    pub wasm: Option<Wasm>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate]
+    pub pipeline: Option<Pipeline>,
+    #[validate(custom(
+        function = "validate_env",
+        message = "Environment variable names must match [A-Za-z_][A-Za-z0-9_]*"
+    ))]
+    pub env: Option<HashMap<String, Env>>,
+    #[validate(custom(
+        function = "validate_labels",
+        message = "Label keys must be 1-63 characters of [A-Za-z0-9_./-], values at most 63"
+    ))]
+    pub labels: Option<HashMap<String, String>>,
+    /// Run timeout (in seconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 1))]
+    pub timeout: Option<i32>,
+    /// Axes the runs fan out over, one child run per combination
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate]
+    pub matrix: Option<Matrix>,
+    /// Globs of the files collected from the work directory once the run completes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(custom(
+        function = "validate_artifacts",
+        message = "Artifacts must be 1-16 globs relative to the work directory"
+    ))]
+    pub artifacts: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(custom(
+        function = "validate_resources",
+        message = "Resources must be positive, and limits at least the requests"
+    ))]
+    pub resources: Option<Resources>,
+    /// Labels a worker must have to run the job
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(custom(
+        function = "validate_labels",
+        message = "Label keys must be 1-63 characters of [A-Za-z0-9_./-], values at most 63"
+    ))]
+    pub node_selector: Option<HashMap<String, String>>,
+    /// Run only on workers running a job matching every term
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(custom(
+        function = "validate_affinity",
+        message = "Affinity terms must set a 'job' or 'labels'"
+    ))]
+    pub affinity: Option<Vec<Term>>,
+    /// Never run on workers running a job matching any term
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(custom(
+        function = "validate_affinity",
+        message = "Affinity terms must set a 'job' or 'labels'"
+    ))]
+    pub anti_affinity: Option<Vec<Term>>,
+    /// Name of the priority class of the runs, unset is priority 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(custom(
+        function = "validate_priority_class_name",
+        message = "Priority class name must be 1-63 characters of [a-z0-9-]"
+    ))]
+    pub priority_class: Option<String>,
+}
+
+/// # Job Record
+/// Stored job, as read back from the database
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobRecord {
+    pub id: Uuid,
+    #[serde(flatten)]
+    pub job: Job,
+}
+
+/// # Trashed Job
+/// Deleted job, restorable until it is purged
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TrashedJob {
+    pub id: Uuid,
+    pub name: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub deleted_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub purge_at: OffsetDateTime,
+}
+
+/// Path of job endpoints addressing a single job
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct JobPath {
+    pub id: Uuid,
+}
+
+/// # Clone Request
+/// Copy of an existing job under a new name
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CloneJob {
+    pub name: String,
+    /// Schedule of the copy, the schedule of the job if unset
+    pub schedule: Option<String>,
+    /// Project of the copy, the project of the job if unset
+    pub project: Option<String>,
+    /// `schedin/owner` label of the copy, as set by manifests. Only the label
+    /// is set, the copy always belongs to the caller. The copy has no owner
+    /// label if unset.
+    #[validate(length(min = 1, max = 63))]
+    pub owner_label: Option<String>,
+    /// Start the copy paused
+    #[serde(default)]
+    pub paused: bool,
 }
 
 impl Job {
@@ -39,6 +185,14 @@ impl Job {
             return JobType::Code;
         } else if self.bin.is_some() {
             return JobType::Bin;
+        } else if self.http.is_some() {
+            return JobType::Http;
+        } else if self.sql.is_some() {
+            return JobType::Sql;
+        } else if self.wasm.is_some() {
+            return JobType::Wasm;
+        } else if self.pipeline.is_some() {
+            return JobType::Pipeline;
         }
         JobType::Invalid
     }
@@ -49,28 +203,41 @@ impl Job {
 pub enum JobType {
     Bin,
     Code,
+    Http,
     Invalid,
+    Pipeline,
+    Sql,
     Task,
+    Wasm,
 }
 
-impl ToString for JobType {
-    fn to_string(&self) -> String {
-        match *self {
-            JobType::Bin => "bin".to_string(),
-            JobType::Code => "code".into(),
-            JobType::Invalid => "invalid".into(),
-            JobType::Task => "task".into(),
-        }
+impl fmt::Display for JobType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            JobType::Bin => "bin",
+            JobType::Code => "code",
+            JobType::Http => "http",
+            JobType::Invalid => "invalid",
+            JobType::Pipeline => "pipeline",
+            JobType::Sql => "sql",
+            JobType::Task => "task",
+            JobType::Wasm => "wasm",
+        })
     }
 }
 
-#[derive(Debug, Deserialize, sqlx::types::Type)]
+#[allow(dead_code)]
+#[derive(Debug, Default, Clone, Copy, Deserialize, sqlx::types::Type)]
 #[sqlx(type_name = "job_status", rename_all = "lowercase")]
 pub enum JobStatus {
     /// Job is running
     Running,
 
+    /// Job is paused, it is not dispatched until resumed
+    Paused,
+
     /// Job is scheduled
+    #[default]
     Scheduled,
 
     /// Job is disabled
@@ -79,7 +246,7 @@ pub enum JobStatus {
 
 // Bin
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
 pub struct Bin {
     pub path: String,
     pub cmd: Option<String>,
@@ -87,7 +254,7 @@ pub struct Bin {
 
 // Code
 
-#[derive(Debug, Default, Deserialize, Validate)]
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, Validate, ToSchema)]
 pub struct Code {
     #[validate(custom(
         function = "validate_source_format",
@@ -98,9 +265,455 @@ pub struct Code {
     pub cmd: String,
 }
 
+// Http
+
+/// # HTTP Request
+/// Request sent by the worker, the run succeeds if the response status is
+/// expected and the JSON body matches the assertion. Header values are
+/// either plain or references to secrets of the job owner.
+///
+/// ```json
+/// {
+///     "method": "POST",
+///     "url": "https://internal.example.com/reports/refresh",
+///     "headers": { "Authorization": { "secret": "reports-token" } },
+///     "body": "{\"full\": true}",
+///     "timeout": 30,
+///     "expected_status": [200, 202],
+///     "assertion": "$.jobs[?@.state == 'queued']"
+/// }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, Validate, ToSchema)]
+pub struct Http {
+    #[serde(default = "default_method")]
+    #[validate(custom(
+        function = "validate_http_method",
+        message = "Method must be one of GET, HEAD, POST, PUT, PATCH, DELETE, OPTIONS"
+    ))]
+    pub method: String,
+    #[validate(custom(
+        function = "validate_http_url",
+        message = "URL must be an absolute http(s) URL"
+    ))]
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(custom(
+        function = "validate_http_headers",
+        message = "Header names must be valid HTTP tokens"
+    ))]
+    pub headers: Option<HashMap<String, Env>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// Request timeout (in seconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 1))]
+    pub timeout: Option<i32>,
+    /// Accepted response statuses, any 2xx if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(custom(
+        function = "validate_status_codes",
+        message = "Expected statuses must be a non-empty list of 100-599"
+    ))]
+    pub expected_status: Option<Vec<i32>>,
+    /// JSONPath query that must select at least one node of the response body
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(custom(
+        function = "validate_json_path",
+        message = "Assertion must be a valid JSONPath query"
+    ))]
+    pub assertion: Option<String>,
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+// Sql
+
+/// # SQL Statements
+/// Statements run in order within a single transaction, against the
+/// PostgreSQL database whose DSN is stored in the secret `connection`.
+/// The run records the rows affected and a preview of the returned rows.
+///
+/// ```json
+/// {
+///     "connection": "warehouse-dsn",
+///     "statements": [
+///         "REFRESH MATERIALIZED VIEW daily_sales",
+///         "DELETE FROM events WHERE created_at < NOW() - INTERVAL '90 days'"
+///     ],
+///     "timeout": 300
+/// }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, Validate, ToSchema)]
+pub struct Sql {
+    /// Name of the secret holding the DSN
+    #[validate(custom(
+        function = "validate_secret_name",
+        message = "Connection must be a secret name of 1-255 characters of [A-Za-z0-9_.-]"
+    ))]
+    pub connection: String,
+    #[validate(custom(
+        function = "validate_statements",
+        message = "Statements must be a non-empty list of non-blank statements"
+    ))]
+    pub statements: Vec<String>,
+    /// Statement timeout (in seconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 1))]
+    pub timeout: Option<i32>,
+}
+
+// Wasm
+
+/// # WebAssembly Module
+/// WASI module run by the worker in a sandboxed runtime, either inlined
+/// base64-encoded in `module`, or uploaded beforehand and referenced by its
+/// SHA-256 `digest`. Stored jobs are read back with the digest only.
+///
+/// ```json
+/// {
+///     "digest": "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+///     "args": ["--since", "1d"],
+///     "fuel": 10000000000
+/// }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, Validate, ToSchema)]
+#[validate(schema(function = "validate_wasm_source", skip_on_field_errors = false))]
+pub struct Wasm {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(custom(
+        function = "validate_wasm_module",
+        message = "Module must be a base64-encoded WebAssembly module of at most 16 MiB"
+    ))]
+    pub module: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(custom(
+        function = "validate_wasm_digest",
+        message = "Digest must be a hex-encoded SHA-256 digest"
+    ))]
+    pub digest: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Instructions budget, unbounded if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 1))]
+    pub fuel: Option<i64>,
+}
+
+impl Wasm {
+    /// Decoded module, if inlined
+    pub fn module(&self) -> Option<Vec<u8>> {
+        self.module.as_ref().and_then(|module| {
+            base64::engine::general_purpose::STANDARD
+                .decode(module)
+                .ok()
+        })
+    }
+
+    /// Wasm as read back once stored, the inlined module replaced by its digest
+    pub fn normalize(&self) -> Self {
+        Self {
+            module: None,
+            digest: self
+                .module()
+                .map(|module| module::digest(&module))
+                .or_else(|| self.digest.clone()),
+            args: self.args.clone(),
+            fuel: self.fuel,
+        }
+    }
+}
+
+// Pipeline
+
+/// # Pipeline
+/// Ordered steps run on one worker, in a shared working directory. A failed
+/// step fails the run and skips the next steps, unless it continues on error.
+/// Each step is recorded under the run, with its status, timing and output.
+///
+/// ```json
+/// {
+///     "steps": [
+///         { "name": "extract", "bin": { "path": "/opt/etl/extract" }, "timeout": 600 },
+///         { "name": "notify", "http": { "url": "https://hooks.example.com/etl" },
+///           "continue_on_error": true }
+///     ]
+/// }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, Validate, ToSchema)]
+#[validate(schema(function = "validate_pipeline", skip_on_field_errors = false))]
+pub struct Pipeline {
+    #[validate]
+    pub steps: Vec<Step>,
+}
+
+impl Pipeline {
+    /// Names of the secrets referenced by the headers of http steps
+    pub fn secrets(&self) -> HashSet<&str> {
+        self.steps
+            .iter()
+            .filter_map(|step| step.http.as_ref()?.headers.as_ref())
+            .flat_map(|headers| headers.values())
+            .filter_map(|value| match value {
+                Env::Secret { secret } => Some(secret.as_str()),
+                Env::Value(_) => None,
+            })
+            .collect()
+    }
+
+    /// Pipeline as read back once stored, with empty headers dropped
+    pub fn normalize(mut self) -> Self {
+        for step in &mut self.steps {
+            if let Some(http) = step.http.as_mut() {
+                http.headers = http.headers.take().filter(|headers| !headers.is_empty());
+            }
+        }
+        self
+    }
+}
+
+/// # Pipeline Step
+/// Exactly one of `task`, `code`, `bin` or `http`, with its own timeout.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, Validate, ToSchema)]
+pub struct Step {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task: Option<Task>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate]
+    pub code: Option<Code>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bin: Option<Bin>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate]
+    pub http: Option<Http>,
+    /// Step timeout (in seconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 1))]
+    pub timeout: Option<i32>,
+    /// Whether the next steps run even if this one fails
+    #[serde(default)]
+    pub continue_on_error: bool,
+}
+
+// Matrix
+
+/// Combinations a matrix may expand into
+pub const MAX_COMBINATIONS: usize = 256;
+
+/// # Matrix
+/// Axes of values the runs of a job fan out over. Every fire of the schedule
+/// expands into one child run per combination of values, given its values as
+/// `SCHEDIN_MATRIX_<AXIS>` env vars. An axis is a list of scalars or an
+/// integer range `start..end`, the end excluded.
+///
+/// At most `max_parallel` children run at a time. With `fail_fast`, the first
+/// failed child cancels the children yet to start. The parent run succeeds
+/// once every child succeeded, and fails otherwise.
+///
+/// ```json
+/// {
+///     "axes": { "region": ["eu", "us", "ap"], "shard": "0..8" },
+///     "max_parallel": 4,
+///     "fail_fast": true
+/// }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, Validate, ToSchema)]
+#[validate(schema(function = "validate_matrix", skip_on_field_errors = false))]
+pub struct Matrix {
+    pub axes: BTreeMap<String, Axis>,
+    /// Children running at a time, unbounded if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 1))]
+    pub max_parallel: Option<i32>,
+    /// Whether a failed child cancels the children yet to start
+    #[serde(default)]
+    pub fail_fast: bool,
+}
+
+impl Matrix {
+    /// Number of combinations, `None` if an axis is invalid
+    pub fn combinations(&self) -> Option<usize> {
+        self.axes
+            .values()
+            .try_fold(1usize, |count, axis| count.checked_mul(axis.len()?))
+    }
+
+    /// Every combination of values, by axis name, in the order the
+    /// orchestrator expands them: axes in name order, the last varying first
+    pub fn expand(&self) -> Vec<BTreeMap<String, String>> {
+        self.axes
+            .iter()
+            .fold(vec![BTreeMap::new()], |combinations, (name, axis)| {
+                let values = axis.values();
+                combinations
+                    .iter()
+                    .flat_map(|combination| {
+                        values.iter().map(move |value| {
+                            let mut combination = combination.clone();
+                            combination.insert(name.clone(), value.clone());
+                            combination
+                        })
+                    })
+                    .collect()
+            })
+    }
+}
+
+/// # Matrix Axis
+/// Either a list of strings, numbers or booleans, or a range such as `"0..8"`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum Axis {
+    Values(Vec<Value>),
+    Range(String),
+}
+
+impl Axis {
+    /// Number of values of the axis, `None` if a value is not a scalar
+    /// or the range is invalid
+    pub fn len(&self) -> Option<usize> {
+        match self {
+            Axis::Values(values) => values
+                .iter()
+                .all(|value| matches!(value, Value::String(_) | Value::Number(_) | Value::Bool(_)))
+                .then_some(values.len()),
+            Axis::Range(range) => {
+                let (start, end) = range.split_once("..")?;
+                let start = start.trim().parse::<i64>().ok()?;
+                let end = end.trim().parse::<i64>().ok()?;
+                usize::try_from(end.checked_sub(start)?).ok()
+            }
+        }
+    }
+
+    /// Values of the axis, as given to the children of a matrix run
+    pub fn values(&self) -> Vec<String> {
+        match self {
+            Axis::Values(values) => values
+                .iter()
+                .filter_map(|value| match value {
+                    Value::String(value) => Some(value.clone()),
+                    Value::Number(value) => Some(value.to_string()),
+                    Value::Bool(value) => Some(value.to_string()),
+                    _ => None,
+                })
+                .collect(),
+            Axis::Range(range) => match range
+                .split_once("..")
+                .map(|(start, end)| (start.trim().parse::<i64>(), end.trim().parse::<i64>()))
+            {
+                Some((Ok(start), Ok(end))) => (start..end).map(|value| value.to_string()).collect(),
+                _ => Vec::new(),
+            },
+        }
+    }
+}
+
 // Task
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
 pub struct Task {
     pub name: String,
 }
+
+// Env
+
+/// # Environment Variable
+/// Either a plain value or a reference to a secret of the job owner,
+/// secrets are resolved only when the job is dispatched.
+///
+/// ```json
+/// {
+///     "MODE": "production",
+///     "API_TOKEN": { "secret": "api-token" }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum Env {
+    Value(String),
+    Secret { secret: String },
+}
+
+// Affinity
+
+/// # Affinity Term
+/// Matches the queued or running jobs of the same project on a worker, by
+/// name and/or labels. For instance, a job `etl` with the anti-affinity term
+/// `{ "job": "etl" }` never runs twice on the same worker.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Term {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, String>,
+}
+
+// Resources
+
+/// # Resources
+/// Requests are reserved on the worker a run is placed on, limits are
+/// enforced on the run. Unset requests are zero, unset limits unbounded.
+///
+/// ## Example
+/// ```json
+/// {
+///     "requests": { "cpu": 0.5, "memory_mb": 512 },
+///     "limits": { "memory_mb": 1024 }
+/// }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Resources {
+    #[serde(default)]
+    pub requests: Quantity,
+    pub limits: Option<Quantity>,
+}
+
+impl Resources {
+    /// Resources as read back once stored: CPU rounded to millicores,
+    /// empty limits dropped, and `None` if nothing is requested or limited
+    pub fn normalize(self) -> Option<Self> {
+        let requests = self.requests.normalize();
+        let limits = self.limits.map(Quantity::normalize);
+        let limits = limits.filter(|limits| !limits.is_empty());
+
+        match (requests.is_empty(), &limits) {
+            (true, None) => None,
+            _ => Some(Self { requests, limits }),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Quantity {
+    /// CPU cores, e.g. `0.5`
+    pub cpu: Option<f64>,
+    pub memory_mb: Option<i32>,
+    pub disk_mb: Option<i32>,
+}
+
+impl Quantity {
+    /// CPU in millicores, as stored
+    pub fn cpu_millis(&self) -> Option<i32> {
+        self.cpu.map(|cpu| (cpu * 1000.0).round() as i32)
+    }
+
+    /// Quantity from CPU millicores, memory and disk
+    pub fn from_millis(cpu: Option<i32>, memory_mb: Option<i32>, disk_mb: Option<i32>) -> Self {
+        Self {
+            cpu: cpu.map(|cpu| cpu as f64 / 1000.0),
+            memory_mb,
+            disk_mb,
+        }
+    }
+
+    fn normalize(self) -> Self {
+        Self::from_millis(self.cpu_millis(), self.memory_mb, self.disk_mb)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cpu.is_none() && self.memory_mb.is_none() && self.disk_mb.is_none()
+    }
+}
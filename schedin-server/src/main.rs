@@ -4,6 +4,8 @@ extern crate actix_web;
 extern crate schedin_common;
 extern crate sqlx;
 extern crate std;
+extern crate tokio;
+extern crate utoipa;
 
 use actix_web::{
     middleware,
@@ -11,25 +13,69 @@ use actix_web::{
     App, HttpResponse, HttpServer, Responder,
 };
 use api::{
-    job::{delete_job, insert_job},
-    user::{signin, signup},
+    error,
+    event::{stream_events, stream_events_ws},
+    job::{
+        apply_jobs, clone_job, delete_job, delete_job_by_id, download_artifact, insert_job,
+        list_artifacts, list_children, list_jobs, list_revisions, list_runs, list_steps,
+        list_trash, pause_job, purge_job, restore_job, resume_job, rollback_job,
+    },
+    module::{delete_module, list_modules, upload_module},
+    notification::{
+        delete_channel, delete_rule, list_channels, list_deliveries, list_rules, upsert_channel,
+        upsert_rule,
+    },
+    openapi::{docs, docs_asset, openapi_json},
+    priority::{delete_priority_class, list_priority_classes, upsert_priority_class},
+    project::{insert_project, list_projects, update_project},
+    routes::Routes,
+    run::run_logs,
+    secret::{delete_secret, list_secrets, upsert_secret},
+    template::{insert_template, instantiate_template, list_templates, update_template},
+    user::{create_key, delete_key, list_keys, signin, signup},
+    webhook::{
+        delete_subscription, list_subscriptions, list_webhook_deliveries, redeliver_events,
+        rotate_secret, upsert_subscription,
+    },
 };
 use certs::load_rustls_config;
+use config::Config;
+use event::hub::Hub;
 use iam::schema::AuthorizedUser;
-use schedin_common::db::create_pool;
+use ratelimit::{Limit, RateLimiter};
+use schedin_common::{db::create_pool, secret::MASTER_KEY_VAR};
 use sqlx::{migrate::Migrator, Postgres};
-use std::env;
+use std::{env, sync::Arc};
 
 mod api;
 mod certs;
+mod config;
 mod db;
+mod event;
+mod grpc;
 mod iam;
 mod job;
+mod module;
+mod notification;
+mod priority;
+mod project;
+mod ratelimit;
+mod run;
+mod secret;
+mod template;
+mod webhook;
 
 static MIGRATOR: Migrator = sqlx::migrate!();
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    let config = match Config::from_env() {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("{}: {}", MASTER_KEY_VAR, error.reason());
+            std::process::exit(1);
+        }
+    };
     let tls_config = load_rustls_config();
 
     let pool = create_pool::<Postgres>(&env::var("DATABASE_URL").unwrap())
@@ -38,31 +84,234 @@ async fn main() -> std::io::Result<()> {
 
     MIGRATOR.run(&pool).await.unwrap();
 
+    let cipher = Data::new(config.cipher.clone());
+
+    let hub = Data::new(Hub::listen(&pool).await.unwrap());
+
+    let listener = tokio::net::TcpListener::bind(&config.grpc_addr).await?;
+    let grpc = grpc::serve(
+        listener,
+        load_rustls_config(),
+        Arc::new(pool.clone()),
+        hub.clone().into_inner(),
+    );
+    actix_web::rt::spawn(async move {
+        if let Err(error) = grpc.await {
+            eprintln!("grpc: {}", error);
+        }
+    });
+
+    let limiter = Data::new(
+        RateLimiter::new(Arc::new(pool.clone()))
+            .route("/api/user/signin", Limit::new(10, 60))
+            .route("/api/user/signup", Limit::new(10, 60))
+            .route("/api/job/new", Limit::new(30, 60))
+            .route("/api/project/{project}/job/new", Limit::new(30, 60)),
+    );
+
     HttpServer::new(move || {
         App::new()
-            .service(
-                web::scope("/api")
-                    .route("/test", web::get().to(test_endpoint))
-                    .service(
-                        web::scope("/user")
-                            .route("/signup", web::post().to(signup))
-                            .route("/signin", web::post().to(signin)),
-                    )
-                    .service(
-                        web::scope("/job")
-                            .route("/new", web::post().to(insert_job))
-                            .route("/delete", web::post().to(delete_job)),
-                    ),
-            )
+            .service(api().into_scope())
+            .wrap(middleware::from_fn(ratelimit::limit))
+            .wrap(middleware::from_fn(error::identify))
             .wrap(middleware::NormalizePath::default())
+            .app_data(web::JsonConfig::default().error_handler(error::rejected))
+            .app_data(web::PathConfig::default().error_handler(error::rejected))
+            .app_data(web::QueryConfig::default().error_handler(error::rejected))
             .app_data(Data::new(pool.clone()))
+            .app_data(cipher.clone())
+            .app_data(hub.clone())
+            .app_data(limiter.clone())
     })
-    .bind_rustls("localhost:8080", tls_config)?
+    .bind_rustls(&config.http_addr, tls_config)?
     .run()
     .await
 }
 
+/// Endpoints of the API
+fn api() -> Routes {
+    Routes::new("/api")
+        .get("/test", test_endpoint)
+        .get("/openapi.json", openapi_json)
+        .get("/docs", docs)
+        .get("/docs/{file}", docs_asset)
+        .get("/events", stream_events)
+        .get("/events/ws", stream_events_ws)
+        .service(
+            Routes::new("/user")
+                .post("/signup", signup)
+                .post("/signin", signin)
+                .post("/key/new", create_key)
+                .get("/key/list", list_keys)
+                .post("/key/delete", delete_key),
+        )
+        .service(job_scope("/job"))
+        .service(Routes::new("/run").get("/{id}/logs", run_logs))
+        .service(
+            Routes::new("/project")
+                .post("/new", insert_project)
+                .get("/list", list_projects)
+                .post("/update", update_project)
+                .service(job_scope("/{project}/job")),
+        )
+        .service(
+            Routes::new("/module")
+                .app_data(web::PayloadConfig::new(module::MAX_MODULE))
+                .post("/upload", upload_module)
+                .get("/list", list_modules)
+                .post("/delete", delete_module),
+        )
+        .service(
+            Routes::new("/notification")
+                .post("/channel/new", upsert_channel)
+                .get("/channel/list", list_channels)
+                .post("/channel/delete", delete_channel)
+                .post("/rule/new", upsert_rule)
+                .get("/rule/list", list_rules)
+                .post("/rule/delete", delete_rule)
+                .get("/deliveries", list_deliveries),
+        )
+        .service(
+            Routes::new("/priority")
+                .post("/new", upsert_priority_class)
+                .get("/list", list_priority_classes)
+                .post("/delete", delete_priority_class),
+        )
+        .service(
+            Routes::new("/secret")
+                .post("/new", upsert_secret)
+                .get("/list", list_secrets)
+                .post("/delete", delete_secret),
+        )
+        .service(
+            Routes::new("/template")
+                .post("/new", insert_template)
+                .get("/list", list_templates)
+                .post("/update", update_template)
+                .post("/instantiate", instantiate_template),
+        )
+        .service(
+            Routes::new("/webhook")
+                .post("/new", upsert_subscription)
+                .get("/list", list_subscriptions)
+                .post("/delete", delete_subscription)
+                .post("/rotate", rotate_secret)
+                .post("/redeliver", redeliver_events)
+                .get("/deliveries", list_webhook_deliveries),
+        )
+}
+
+/// Job endpoints, mounted for the default project and for each project
+fn job_scope(path: &str) -> Routes {
+    Routes::new(path)
+        .post("/new", insert_job)
+        .post("/delete", delete_job)
+        .get("/list", list_jobs)
+        .post("/apply", apply_jobs)
+        .get("/trash", list_trash)
+        .post("/{id}/delete", delete_job_by_id)
+        .post("/{id}/restore", restore_job)
+        .post("/{id}/purge", purge_job)
+        .post("/{id}/clone", clone_job)
+        .post("/{id}/pause", pause_job)
+        .post("/{id}/resume", resume_job)
+        .get("/{id}/revisions", list_revisions)
+        .post("/{id}/rollback", rollback_job)
+        .get("/{id}/runs", list_runs)
+        .get("/{id}/runs/{run}/children", list_children)
+        .get("/{id}/runs/{run}/steps", list_steps)
+        .get("/{id}/runs/{run}/artifacts", list_artifacts)
+        .get("/{id}/runs/{run}/artifacts/{path:.*}", download_artifact)
+}
+
+/// # Test
+/// This function checks the bearer token of the request.
+#[utoipa::path(
+    get,
+    path = "/api/test",
+    tag = "user",
+    responses((status = 200, description = "Valid token", body = String)),
+    security(("bearer" = [])),
+)]
 async fn test_endpoint(user: AuthorizedUser) -> impl Responder {
     println!("user.id = {}", user.id);
     HttpResponse::Ok().json("ok!")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::api;
+    use crate::api::openapi::ApiDoc;
+    use actix_web::{
+        http::{Method, StatusCode},
+        test::{call_service, init_service, TestRequest},
+        web, App, HttpResponse,
+    };
+    use utoipa::OpenApi;
+
+    #[test]
+    fn every_route_is_described() {
+        let openapi = ApiDoc::openapi();
+
+        for (method, path) in api().routes() {
+            let item = openapi.paths.paths.get(&path);
+            let operation = item.and_then(|item| match *method {
+                Method::GET => item.get.as_ref(),
+                Method::POST => item.post.as_ref(),
+                _ => None,
+            });
+
+            assert!(
+                operation.is_some(),
+                "{} {} is not described in the OpenAPI document",
+                method,
+                path
+            );
+        }
+    }
+
+    /// Every operation of the document, the project-scoped ones included,
+    /// against the routing table of the app: the default service only
+    /// answers requests no route matches, and a matched path with another
+    /// method is not allowed.
+    #[actix_web::test]
+    async fn every_operation_is_routed() {
+        let app = init_service(
+            App::new()
+                .service(api().into_scope())
+                .default_service(web::to(HttpResponse::ImATeapot)),
+        )
+        .await;
+
+        for (path, item) in ApiDoc::openapi().paths.paths {
+            // any value matches the path parameters, ids must be uuids
+            let uri = path
+                .split('/')
+                .map(|segment| match segment.starts_with('{') {
+                    true => "00000000-0000-0000-0000-000000000000",
+                    false => segment,
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+
+            for (method, operation) in [(Method::GET, &item.get), (Method::POST, &item.post)] {
+                if operation.is_none() {
+                    continue;
+                }
+
+                let req = TestRequest::default()
+                    .method(method.clone())
+                    .uri(&uri)
+                    .to_request();
+                let status = call_service(&app, req).await.status();
+
+                assert!(
+                    status != StatusCode::IM_A_TEAPOT && status != StatusCode::METHOD_NOT_ALLOWED,
+                    "{} {} is described but not routed",
+                    method,
+                    path
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,21 @@
+//! WebAssembly Modules
+
+extern crate hex;
+extern crate sha2;
+
+pub mod schema;
+
+use sha2::{Digest, Sha256};
+
+/// Largest module accepted, in bytes
+pub const MAX_MODULE: usize = 16 * 1024 * 1024;
+
+/// Hex-encoded SHA-256 digest of a module, which addresses it
+pub fn digest(module: &[u8]) -> String {
+    hex::encode(Sha256::digest(module))
+}
+
+/// Whether `module` is a binary WebAssembly module of an accepted size
+pub fn is_wasm(module: &[u8]) -> bool {
+    module.len() <= MAX_MODULE && module.starts_with(b"\0asm")
+}
@@ -0,0 +1,26 @@
+//! WebAssembly Module Schema
+
+extern crate serde;
+extern crate time;
+extern crate utoipa;
+
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+
+/// Module addressed by its digest, as referenced by `wasm` jobs
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ModuleDigest {
+    pub digest: String,
+}
+
+/// # Module Listing
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ModuleRow {
+    pub digest: String,
+    /// Size in bytes
+    pub size: i32,
+    pub jobs: i64,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub created_at: Option<OffsetDateTime>,
+}
@@ -0,0 +1,192 @@
+//! Notification Schema
+//! Unified Schema for API and Database
+
+extern crate serde;
+extern crate time;
+extern crate utoipa;
+extern crate uuid;
+extern crate validator;
+
+use crate::api::validation::{
+    validate_channel, validate_channel_name, validate_http_url, validate_message_template,
+    validate_recipients, validate_rule,
+};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+/// # Channel
+/// Destination of notifications, owned by a user, or by one of their
+/// projects if `project` is set.
+#[derive(Debug, Default, Deserialize, Validate, ToSchema)]
+#[validate(schema(function = "validate_channel"))]
+pub struct Channel {
+    #[validate(custom(
+        function = "validate_channel_name",
+        message = "Channel name must be 1-63 characters of [a-z0-9-]"
+    ))]
+    pub name: String,
+    pub project: Option<String>,
+    #[serde(default)]
+    pub kind: ChannelKind,
+    /// URL of `webhook` and `slack` channels
+    #[validate(custom(
+        function = "validate_http_url",
+        message = "Must be an http:// or https:// URL"
+    ))]
+    pub url: Option<String>,
+    /// Addresses of `email` channels
+    #[validate(custom(
+        function = "validate_recipients",
+        message = "Recipients must be 1-16 email addresses"
+    ))]
+    pub recipients: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize, sqlx::types::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "channel_kind", rename_all = "lowercase")]
+pub enum ChannelKind {
+    /// JSON payload POSTed to `url`
+    #[default]
+    Webhook,
+
+    /// Slack-compatible `{ "text": ... }` payload POSTed to `url`
+    Slack,
+
+    /// Email sent to `recipients` through the SMTP relay of the orchestrator
+    Email,
+}
+
+/// # Channel Listing
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChannelRow {
+    pub name: String,
+    pub project: Option<String>,
+    pub kind: ChannelKind,
+    pub url: Option<String>,
+    pub recipients: Option<Vec<String>>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub created_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub updated_at: Option<OffsetDateTime>,
+}
+
+/// # Rule
+/// Notify a channel about an event of the runs of the jobs of a user,
+/// optionally narrowed down to a project and a job name.
+#[derive(Debug, Default, Deserialize, Validate, ToSchema)]
+#[validate(schema(function = "validate_rule"))]
+pub struct Rule {
+    #[validate(custom(
+        function = "validate_channel_name",
+        message = "Rule name must be 1-63 characters of [a-z0-9-]"
+    ))]
+    pub name: String,
+    #[serde(default)]
+    pub channel: String,
+    pub project: Option<String>,
+    pub job: Option<String>,
+    #[serde(default)]
+    pub event: Event,
+    /// Consecutive failures `consecutive_failures` rules fire at
+    #[validate(range(min = 2, max = 100))]
+    pub threshold: Option<i32>,
+    /// Message template, the default message of the event if unset
+    #[validate(custom(
+        function = "validate_message_template",
+        message = "Template must be at most 4096 characters, with known {{ placeholders }}"
+    ))]
+    pub template: Option<String>,
+}
+
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize, sqlx::types::Type, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "notification_event", rename_all = "snake_case")]
+#[schema(as = NotificationEvent)]
+pub enum Event {
+    /// Run failed
+    #[default]
+    Failure,
+
+    /// Run failed, `threshold` times in a row
+    ConsecutiveFailures,
+
+    /// Run succeeded after one or more failed runs
+    Recovery,
+
+    /// Run exceeded its timeout
+    Timeout,
+}
+
+/// # Rule Listing
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RuleRow {
+    pub name: String,
+    pub channel: String,
+    pub project: Option<String>,
+    pub job: Option<String>,
+    pub event: Event,
+    pub threshold: Option<i32>,
+    pub template: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub created_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub updated_at: Option<OffsetDateTime>,
+}
+
+/// # Delivery
+/// Entry of the delivery log, pending deliveries are retried with backoff.
+#[derive(Debug, Serialize, ToSchema)]
+#[schema(as = NotificationDelivery)]
+pub struct Delivery {
+    pub id: Uuid,
+    pub rule: String,
+    pub channel: String,
+    pub job: String,
+    pub run_id: Option<Uuid>,
+    pub event: Event,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub message: String,
+    /// Response status of `webhook` and `slack` channels
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub next_attempt_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub delivered_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::types::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "delivery_status", rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// Delivery is yet to succeed, see `next_attempt_at`
+    Pending,
+
+    /// Channel accepted the notification
+    Delivered,
+
+    /// Every attempt failed
+    Failed,
+}
+
+/// Delivery log query
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Deliveries {
+    /// Maximum number of deliveries, most recent first
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+}
+
+fn default_limit() -> i64 {
+    50
+}
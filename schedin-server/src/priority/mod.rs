@@ -0,0 +1,3 @@
+//! Priority Classes
+
+pub mod schema;
@@ -0,0 +1,45 @@
+//! Priority Class Schema
+//! Unified Schema for API and Database
+
+extern crate serde;
+extern crate time;
+extern crate utoipa;
+extern crate validator;
+
+use crate::api::validation::validate_priority_class_name;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use validator::Validate;
+
+/// # Priority Class
+/// Named priority defined by an administrator, referenced by jobs.
+/// Runs of higher values are placed first.
+#[derive(Debug, Default, Deserialize, Validate, ToSchema)]
+pub struct PriorityClass {
+    #[validate(custom(
+        function = "validate_priority_class_name",
+        message = "Priority class name must be 1-63 characters of [a-z0-9-]"
+    ))]
+    pub name: String,
+    #[validate(required)]
+    pub value: Option<i32>,
+    pub description: Option<String>,
+    /// Whether runs of the class may preempt running runs of lower priority
+    #[serde(default)]
+    pub preempt: bool,
+}
+
+/// # Priority Class Listing
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PriorityClassRow {
+    pub name: String,
+    pub value: i32,
+    pub description: Option<String>,
+    pub preempt: bool,
+    pub jobs: i64,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub created_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub updated_at: Option<OffsetDateTime>,
+}
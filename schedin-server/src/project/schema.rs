@@ -0,0 +1,89 @@
+//! Project Schema
+//! Unified Schema for API and Database
+
+extern crate serde;
+extern crate time;
+extern crate utoipa;
+extern crate uuid;
+extern crate validator;
+
+use crate::api::validation::validate_project_name;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+
+/// Project of the unscoped job endpoints, created on first use
+pub const DEFAULT_PROJECT: &str = "default";
+
+/// Days deleted jobs stay in the trash of projects without a retention
+pub const DEFAULT_TRASH_RETENTION_DAYS: i32 = 30;
+
+/// Days artifacts are kept in projects without a retention
+pub const DEFAULT_ARTIFACT_RETENTION_DAYS: i32 = 14;
+
+/// # Project
+/// Namespace owning jobs, job names are unique within a project.
+#[derive(Debug, Default, Deserialize, Validate, ToSchema)]
+pub struct Project {
+    #[validate(custom(
+        function = "validate_project_name",
+        message = "Project name must be 1-63 characters of [a-z0-9-]"
+    ))]
+    pub name: String,
+    #[serde(default)]
+    #[validate]
+    pub quotas: Quotas,
+}
+
+/// # Project Quotas
+/// Limits enforced on the jobs of a project, unset quotas are unlimited.
+#[derive(Debug, Default, Clone, Deserialize, Serialize, Validate, ToSchema)]
+pub struct Quotas {
+    /// Maximum number of jobs
+    #[validate(range(min = 1))]
+    pub max_jobs: Option<i32>,
+
+    /// Minimum interval (in seconds) of recurring jobs
+    #[validate(range(min = 1))]
+    pub min_interval: Option<i32>,
+
+    /// Maximum number of queued or running runs
+    #[validate(range(min = 1))]
+    pub max_concurrent_runs: Option<i32>,
+
+    /// Maximum run timeout (in seconds)
+    #[validate(range(min = 1))]
+    pub max_timeout: Option<i32>,
+
+    /// Days deleted jobs stay in the trash before being purged,
+    /// `DEFAULT_TRASH_RETENTION_DAYS` if unset
+    #[validate(range(min = 1))]
+    pub trash_retention_days: Option<i32>,
+
+    /// Days the artifacts of runs are kept before being purged,
+    /// `DEFAULT_ARTIFACT_RETENTION_DAYS` if unset
+    #[validate(range(min = 1))]
+    pub artifact_retention_days: Option<i32>,
+}
+
+/// # Project Scope
+/// Project resolved from the request path, see `FromRequest` impl.
+#[derive(Debug)]
+pub struct ProjectScope {
+    pub id: Uuid,
+    pub name: String,
+}
+
+/// # Project Listing
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProjectRow {
+    pub name: String,
+    pub quotas: Quotas,
+    pub jobs: i64,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub created_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub updated_at: Option<OffsetDateTime>,
+}
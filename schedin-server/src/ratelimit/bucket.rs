@@ -0,0 +1,123 @@
+//! In-Memory Buckets
+
+extern crate std;
+
+use crate::db::ratelimit::Take;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// # Buckets
+/// Token buckets of a single server instance, see `RateLimits` for the
+/// buckets shared by a cluster
+#[derive(Default)]
+pub struct Buckets {
+    buckets: HashMap<String, Bucket>,
+}
+
+impl Buckets {
+    /// # Take
+    /// Take a token from the bucket `key`, holding up to `burst` tokens and
+    /// refilled at `rate` tokens per second.
+    pub fn take(&mut self, key: &str, burst: f64, rate: f64) -> Take {
+        self.take_at(key, burst, rate, Instant::now())
+    }
+
+    /// Take a token from the bucket `key` at `now`
+    fn take_at(&mut self, key: &str, burst: f64, rate: f64, now: Instant) -> Take {
+        let bucket = self.buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: burst,
+            updated_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        let refilled = burst.min(bucket.tokens + elapsed * rate);
+        let allowed = refilled >= 1.0;
+
+        bucket.tokens = match allowed {
+            true => refilled - 1.0,
+            false => refilled,
+        };
+        bucket.updated_at = now;
+
+        Take {
+            remaining: bucket.tokens,
+            allowed,
+        }
+    }
+
+    /// Drop the buckets untouched for `idle`, full again by then
+    pub fn purge(&mut self, idle: Duration) {
+        self.buckets
+            .retain(|_, bucket| bucket.updated_at.elapsed() < idle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Buckets;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn takes_until_empty() {
+        let mut buckets = Buckets::default();
+        let now = Instant::now();
+
+        for remaining in (0..3).rev() {
+            let take = buckets.take_at("client", 3.0, 1.0, now);
+            assert!(take.allowed);
+            assert_eq!(take.remaining, remaining as f64);
+        }
+
+        let take = buckets.take_at("client", 3.0, 1.0, now);
+        assert!(!take.allowed);
+        assert_eq!(take.remaining, 0.0);
+    }
+
+    #[test]
+    fn refills_over_elapsed_time() {
+        let mut buckets = Buckets::default();
+        let now = Instant::now();
+
+        buckets.take_at("client", 2.0, 0.5, now);
+        buckets.take_at("client", 2.0, 0.5, now);
+        assert!(!buckets.take_at("client", 2.0, 0.5, now).allowed);
+
+        // half a token per second, a token after 2 seconds
+        let take = buckets.take_at("client", 2.0, 0.5, now + Duration::from_secs(1));
+        assert!(!take.allowed);
+        assert_eq!(take.remaining, 0.5);
+
+        let take = buckets.take_at("client", 2.0, 0.5, now + Duration::from_secs(2));
+        assert!(take.allowed);
+        assert_eq!(take.remaining, 0.0);
+    }
+
+    #[test]
+    fn refills_up_to_burst() {
+        let mut buckets = Buckets::default();
+        let now = Instant::now();
+
+        buckets.take_at("client", 5.0, 1.0, now);
+
+        let take = buckets.take_at("client", 5.0, 1.0, now + Duration::from_secs(60));
+        assert!(take.allowed);
+        assert_eq!(take.remaining, 4.0);
+    }
+
+    #[test]
+    fn keeps_keys_apart() {
+        let mut buckets = Buckets::default();
+        let now = Instant::now();
+
+        assert!(buckets.take_at("a", 1.0, 1.0, now).allowed);
+        assert!(!buckets.take_at("a", 1.0, 1.0, now).allowed);
+        assert!(buckets.take_at("b", 1.0, 1.0, now).allowed);
+    }
+}
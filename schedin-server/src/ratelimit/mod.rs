@@ -0,0 +1,316 @@
+//! Rate Limiting
+//!
+//! Token buckets per client of the API: the authenticated user, the API
+//! key, or the client IP of anonymous requests. Routes with a limit of their own get
+//! buckets of their own. When the server runs clustered (`CLUSTERED=true`)
+//! the buckets live in Postgres and are shared by all instances, otherwise
+//! they are kept in memory.
+
+extern crate actix_web;
+extern crate serde_json;
+extern crate sqlx;
+extern crate std;
+
+pub mod bucket;
+
+use crate::{
+    api::error::ApiError,
+    db::{key::ApiKeys, ratelimit::RateLimits},
+    iam::{key, token::Claims},
+};
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, RETRY_AFTER},
+    middleware::Next,
+    web::Data,
+    Error,
+};
+use bucket::Buckets;
+use serde_json::json;
+use sqlx::PgPool;
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Environment variable set to `true` when several server instances share
+/// the database
+pub const CLUSTERED_VAR: &str = "CLUSTERED";
+
+/// Limit of the routes without a limit of their own
+pub const DEFAULT_LIMIT: Limit = Limit::new(120, 60);
+
+/// Time (in seconds) a bucket is kept after its last request
+const IDLE: u64 = 60 * 60;
+
+/// Time (in seconds) between sweeps of the idle buckets
+const SWEEP: u64 = 60;
+
+const RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+const RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+const RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+/// # Limit
+/// `burst` requests per `window` seconds, the bucket refills steadily over
+/// the window
+#[derive(Clone, Copy, Debug)]
+pub struct Limit {
+    pub burst: u32,
+    pub window: u32,
+}
+
+impl Limit {
+    /// New limit
+    pub const fn new(burst: u32, window: u32) -> Self {
+        Self { burst, window }
+    }
+
+    /// Tokens refilled per second
+    fn rate(&self) -> f64 {
+        self.burst as f64 / self.window as f64
+    }
+}
+
+enum Store {
+    Memory(Mutex<Buckets>),
+    Postgres(RateLimits),
+}
+
+/// # Rate Limiter
+/// Buckets of the clients of the API, and the limits of the routes
+pub struct RateLimiter {
+    store: Store,
+    routes: HashMap<String, Limit>,
+    swept: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// New rate limiter, backed by Postgres in clustered mode
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        let store = match env::var(CLUSTERED_VAR).as_deref() {
+            Ok("true") | Ok("1") => Store::Postgres(RateLimits::new(pool)),
+            _ => Store::Memory(Mutex::new(Buckets::default())),
+        };
+
+        Self {
+            store,
+            routes: HashMap::new(),
+            swept: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Limit the route `pattern` (e.g. `/api/job/{id}/runs`) to `limit`,
+    /// counted apart from the other routes
+    pub fn route(mut self, pattern: &str, limit: Limit) -> Self {
+        self.routes.insert(pattern.to_string(), limit);
+        self
+    }
+
+    /// Take a token from the bucket of `client` for the route `pattern`,
+    /// none if the store is unavailable
+    async fn take(&self, client: &str, pattern: Option<&str>) -> Option<Quota> {
+        let (key, limit) = match pattern.and_then(|p| self.routes.get(p).map(|l| (p, l))) {
+            Some((pattern, limit)) => (format!("{} {}", client, pattern), *limit),
+            None => (client.to_string(), DEFAULT_LIMIT),
+        };
+
+        let sweep = {
+            let mut swept = self.swept.lock().unwrap();
+            let sweep = swept.elapsed() >= Duration::from_secs(SWEEP);
+            if sweep {
+                *swept = Instant::now();
+            }
+            sweep
+        };
+
+        let take = match &self.store {
+            Store::Memory(buckets) => {
+                let mut buckets = buckets.lock().unwrap();
+                if sweep {
+                    buckets.purge(Duration::from_secs(IDLE));
+                }
+                buckets.take(&key, limit.burst as f64, limit.rate())
+            }
+            Store::Postgres(buckets) => {
+                if sweep {
+                    let _ = buckets.purge(IDLE as f64).await;
+                }
+                buckets
+                    .take(&key, limit.burst as f64, limit.rate())
+                    .await
+                    .ok()?
+            }
+        };
+
+        Some(Quota {
+            limit,
+            remaining: take.remaining,
+            allowed: take.allowed,
+        })
+    }
+}
+
+/// Bucket of a request once a token was taken from it
+struct Quota {
+    limit: Limit,
+    remaining: f64,
+    allowed: bool,
+}
+
+impl Quota {
+    /// Seconds until `tokens` are in the bucket
+    fn wait(&self, tokens: f64) -> u64 {
+        ((tokens - self.remaining).max(0.0) / self.limit.rate()).ceil() as u64
+    }
+
+    /// `RateLimit-*` headers, and `Retry-After` once the bucket is empty
+    fn headers(&self, headers: &mut HeaderMap) {
+        headers.insert(RATELIMIT_LIMIT, HeaderValue::from(self.limit.burst));
+        headers.insert(
+            RATELIMIT_REMAINING,
+            HeaderValue::from(self.remaining.floor() as u64),
+        );
+        headers.insert(
+            RATELIMIT_RESET,
+            HeaderValue::from(self.wait(self.limit.burst as f64)),
+        );
+
+        if !self.allowed {
+            headers.insert(RETRY_AFTER, HeaderValue::from(self.wait(1.0)));
+        }
+    }
+}
+
+/// Client of a request: the user of a valid bearer token, the API key if
+/// it is known, the client IP otherwise
+async fn client(req: &ServiceRequest) -> String {
+    let token = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match token {
+        // looked up, made-up keys must not get fresh buckets
+        Some(token) if key::is_key(token) => {
+            if let Some(pool) = req.app_data::<Data<PgPool>>() {
+                if let Ok(owner) = ApiKeys::new(pool.clone().into_inner()).owner(token).await {
+                    return format!("key:{}", owner.key_id);
+                }
+            }
+        }
+        Some(token) => {
+            if let Ok(claims) = Claims::decode(token) {
+                return format!("user:{}", claims.sub);
+            }
+        }
+        None => {}
+    }
+
+    format!(
+        "ip:{}",
+        req.peer_addr()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_default()
+    )
+}
+
+/// # Rate Limit
+/// Middleware taking a token from the bucket of every request, answering
+/// `429 Too Many Requests` with a `rate_limited` error once it is empty. Responses carry the
+/// `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset` headers.
+///
+/// Requests go through when the buckets are unavailable.
+pub async fn limit<B>(
+    limiter: Data<RateLimiter>,
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error>
+where
+    B: MessageBody,
+{
+    let quota = limiter
+        .take(&client(&req).await, req.match_pattern().as_deref())
+        .await;
+
+    let Some(quota) = quota else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+
+    let mut res = match quota.allowed {
+        true => next.call(req).await?.map_into_left_body(),
+        false => {
+            let retry_after = quota.wait(1.0);
+            let error = ApiError::too_many_requests(format!(
+                "Too Many Requests! Retry in {}s.",
+                retry_after
+            ))
+            .details(json!({ "retry_after": retry_after }));
+
+            req.error_response(error).map_into_right_body()
+        }
+    };
+
+    quota.headers(res.headers_mut());
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{client, Limit, Quota};
+    use crate::db::{fixture::user, key::ApiKeys};
+    use actix_web::{http::header::AUTHORIZATION, test::TestRequest, web::Data};
+    use sqlx::PgPool;
+    use std::sync::Arc;
+
+    fn quota(remaining: f64) -> Quota {
+        Quota {
+            limit: Limit::new(10, 60),
+            remaining,
+            allowed: remaining >= 1.0,
+        }
+    }
+
+    #[test]
+    fn retry_after_waits_for_a_token() {
+        // 10 tokens per minute, a token every 6 seconds
+        assert_eq!(quota(0.0).wait(1.0), 6);
+        assert_eq!(quota(0.5).wait(1.0), 3);
+        assert_eq!(quota(0.9).wait(1.0), 1);
+        assert_eq!(quota(3.0).wait(1.0), 0);
+    }
+
+    #[test]
+    fn reset_waits_for_a_full_bucket() {
+        assert_eq!(quota(0.0).wait(10.0), 60);
+        assert_eq!(quota(9.0).wait(10.0), 6);
+        assert_eq!(quota(10.0).wait(10.0), 0);
+    }
+
+    #[sqlx::test]
+    async fn keeps_api_keys_apart(pool: PgPool) {
+        let user_id = user(&pool).await;
+        let keys = ApiKeys::new(Arc::new(pool.clone()));
+        let key = keys.create(&user_id, "ci").await.unwrap();
+        let owner = keys.owner(key.expose()).await.unwrap();
+
+        let request = |token: &str| {
+            TestRequest::default()
+                .insert_header((AUTHORIZATION, format!("Bearer {}", token)))
+                .peer_addr("10.0.0.1:443".parse().unwrap())
+                .app_data(Data::new(pool.clone()))
+                .to_srv_request()
+        };
+
+        assert_eq!(
+            client(&request(key.expose())).await,
+            format!("key:{}", owner.key_id)
+        );
+        // made-up keys count against the client IP
+        assert_eq!(client(&request("sk_unknown")).await, "ip:10.0.0.1");
+    }
+}
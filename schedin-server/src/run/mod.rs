@@ -0,0 +1,4 @@
+//! Job Runs
+
+pub mod schema;
+pub mod tail;
@@ -0,0 +1,256 @@
+//! Run Schema
+//! Unified Schema for API and Database
+
+extern crate serde;
+extern crate serde_json;
+extern crate sqlx;
+extern crate time;
+extern crate utoipa;
+extern crate uuid;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// # Run
+/// Single execution of a job, along with the revision it executed.
+/// A matrix run is running until its children complete, and only
+/// succeeds if they all succeed.
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct Run {
+    pub id: Uuid,
+    pub job_id: Uuid,
+    pub revision: i32,
+    pub status: RunStatus,
+    #[serde(with = "time::serde::rfc3339")]
+    pub scheduled_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub started_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub finished_at: Option<OffsetDateTime>,
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+    /// Effective timeout (in seconds), capped by the project quota
+    pub timeout: Option<i32>,
+    /// Name of the worker the run is placed on
+    pub worker: Option<String>,
+    /// Why the run is unschedulable, still waiting for a worker, or was requeued
+    pub reason: Option<String>,
+    /// Value of the priority class of the job at dispatch
+    pub priority: i32,
+    /// Number of times the run was preempted and queued again
+    pub preemptions: i32,
+    /// Response status of http runs
+    pub response_status: Option<i32>,
+    /// Response time (in milliseconds) of http runs
+    pub latency_ms: Option<i32>,
+    /// Response body of http runs, truncated
+    pub response_body: Option<String>,
+    /// Rows affected by the statements of sql runs
+    pub rows_affected: Option<i64>,
+    /// Columns and first rows returned by each statement of sql runs
+    pub result_preview: Option<Value>,
+    /// Standard output of code, bin and wasm runs, truncated
+    pub stdout: Option<String>,
+    /// Standard error of code, bin and wasm runs, truncated
+    pub stderr: Option<String>,
+    /// Matrix run the run is a child of
+    pub parent_id: Option<Uuid>,
+    /// Values of the matrix combination of a child run, by axis
+    pub combination: Option<Value>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::types::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "run_status", rename_all = "lowercase")]
+pub enum RunStatus {
+    /// Run is waiting for a worker
+    Queued,
+
+    /// Run is executing
+    Running,
+
+    /// Run completed successfully
+    Succeeded,
+
+    /// Run failed
+    Failed,
+
+    /// No worker can take the run, see `reason`
+    Unschedulable,
+
+    /// Run cancelled before it started, see `reason`
+    Cancelled,
+}
+
+impl RunStatus {
+    /// Whether the run completed, and will not run again
+    pub fn finished(&self) -> bool {
+        matches!(self, Self::Succeeded | Self::Failed | Self::Cancelled)
+    }
+}
+
+/// # Run Step
+/// Step of a pipeline run, in execution order.
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct RunStep {
+    pub position: i32,
+    pub name: String,
+    pub status: StepStatus,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub started_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub finished_at: Option<OffsetDateTime>,
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+    /// Response status of http steps
+    pub response_status: Option<i32>,
+    /// Standard output, or response body of http steps, truncated
+    pub stdout: Option<String>,
+    /// Standard error, truncated
+    pub stderr: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::types::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "step_status", rename_all = "lowercase")]
+pub enum StepStatus {
+    /// Step is executing
+    Running,
+
+    /// Step completed successfully
+    Succeeded,
+
+    /// Step failed
+    Failed,
+
+    /// Step did not run, as a previous step failed
+    Skipped,
+}
+
+/// # Artifact
+/// File collected from the work directory of a run, stored in the blob
+/// store by digest. Downloadable until it expires.
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct Artifact {
+    /// Path relative to the work directory
+    pub path: String,
+    /// Hex-encoded SHA-256 digest of the contents
+    pub digest: String,
+    /// Size in bytes
+    pub size: i64,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+}
+
+/// # Log Chunk
+/// Output read at once from a stream of a run, at byte `position` of the
+/// output of the run, both streams interleaved.
+#[derive(Debug, sqlx::FromRow)]
+pub struct LogChunk {
+    pub position: i64,
+    pub stream: LogStream,
+    pub data: Vec<u8>,
+    pub logged_at: OffsetDateTime,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, sqlx::types::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "log_stream", rename_all = "lowercase")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+/// # Log Entry
+/// Chunk of a run log, as streamed to followers
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LogEntry {
+    /// Byte offset of the chunk, resume after `offset` plus its length in bytes
+    pub offset: i64,
+    pub stream: LogStream,
+    #[serde(with = "time::serde::rfc3339")]
+    pub time: OffsetDateTime,
+    /// Text of the chunk, invalid UTF-8 replaced
+    pub data: String,
+}
+
+impl LogChunk {
+    /// End of the chunk, the offset of the next one
+    pub fn end(&self) -> i64 {
+        self.position + self.data.len() as i64
+    }
+
+    /// Remainder of the chunk from `offset` on
+    pub fn skip_to(mut self, offset: i64) -> Self {
+        if offset > self.position {
+            let skipped = ((offset - self.position) as usize).min(self.data.len());
+            self.data.drain(..skipped);
+            self.position += skipped as i64;
+        }
+        self
+    }
+
+    /// Entry of the chunk, as streamed to followers
+    pub fn entry(&self) -> LogEntry {
+        LogEntry {
+            offset: self.position,
+            stream: self.stream,
+            time: self.logged_at,
+            data: String::from_utf8_lossy(&self.data).into_owned(),
+        }
+    }
+}
+
+/// Run logs query
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Logs {
+    /// Byte offset to start from
+    #[serde(default)]
+    pub offset: i64,
+    /// Whether to stream the log until the run finished
+    #[serde(default)]
+    pub follow: bool,
+}
+
+/// Path of run endpoints addressing a run by id alone
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct RunIdPath {
+    pub id: Uuid,
+}
+
+/// Path of run endpoints addressing a single run of a job
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct RunPath {
+    pub id: Uuid,
+    pub run: Uuid,
+}
+
+/// Path of the artifact endpoint addressing a single artifact of a run
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct ArtifactPath {
+    pub id: Uuid,
+    pub run: Uuid,
+    pub path: String,
+}
+
+/// Run history query
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct History {
+    /// Maximum number of runs, most recent first
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+}
+
+fn default_limit() -> i64 {
+    50
+}
@@ -0,0 +1,106 @@
+//! Log Tail
+//!
+//! Followers read the log of a run from the database, and wait for the hub
+//! to announce new chunks of it in between. The worker writes the whole
+//! log before it records the outcome of the run, so a follower is done once
+//! the run finished and no chunk is left.
+
+extern crate schedin_common;
+extern crate sqlx;
+extern crate std;
+extern crate tokio;
+extern crate uuid;
+
+use crate::{
+    db::run::Runs,
+    event::hub::{Hub, Signal},
+    run::schema::LogChunk,
+};
+use schedin_common::error::CrudError;
+use sqlx::PgPool;
+use std::{collections::VecDeque, future, sync::Arc, time::Duration};
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    time,
+};
+use uuid::Uuid;
+
+/// Chunks read from the log at once
+const BATCH: i64 = 100;
+
+/// Time (in seconds) between reads of a log without announcements, so that
+/// a missed one only delays chunks
+const POLL: u64 = 5;
+
+/// # Tail
+/// Chunks of the log of a run from an offset on, as they are written
+pub struct Tail {
+    pool: Arc<PgPool>,
+    user_id: String,
+    run_id: Uuid,
+    offset: i64,
+    buffer: VecDeque<LogChunk>,
+    receiver: broadcast::Receiver<Signal>,
+}
+
+impl Tail {
+    /// Follow the log of the run `run_id` of a job of `user_id` from byte `offset`
+    pub fn new(hub: &Hub, pool: Arc<PgPool>, user_id: &str, run_id: Uuid, offset: i64) -> Self {
+        Self {
+            pool,
+            user_id: user_id.to_string(),
+            run_id,
+            offset: offset.max(0),
+            buffer: VecDeque::new(),
+            // subscribed first, so that no chunk slips in between
+            receiver: hub.subscribe(),
+        }
+    }
+
+    /// # Next
+    /// Next chunk of the log, `None` once the run finished and its log was read
+    pub async fn next(&mut self) -> Result<Option<LogChunk>, CrudError> {
+        loop {
+            if let Some(chunk) = self.buffer.pop_front() {
+                self.offset = chunk.end();
+                return Ok(Some(chunk));
+            }
+
+            let runs = Runs::new(self.pool.clone());
+
+            // read before the log, which is complete once the run finished
+            let finished = runs.status(&self.user_id, &self.run_id).await?.finished();
+
+            let chunks = runs.log(&self.run_id, self.offset, None, BATCH).await?;
+
+            if !chunks.is_empty() {
+                let offset = self.offset;
+                self.buffer
+                    .extend(chunks.into_iter().map(|chunk| chunk.skip_to(offset)));
+                continue;
+            }
+
+            if finished {
+                return Ok(None);
+            }
+
+            let _ = time::timeout(Duration::from_secs(POLL), self.wake()).await;
+        }
+    }
+
+    /// Wait for an announcement of a chunk of the log
+    async fn wake(&mut self) {
+        loop {
+            match self.receiver.recv().await {
+                Ok(Signal::Log(run_id)) if run_id == self.run_id => return,
+                // events of the user include the run finishing
+                Ok(Signal::Event(user_id)) if user_id.to_string() == self.user_id => return,
+                Ok(_) => continue,
+                // announcements were dropped, read the log
+                Err(RecvError::Lagged(_)) => return,
+                // the listener is gone, the log is read every `POLL` seconds
+                Err(RecvError::Closed) => future::pending().await,
+            }
+        }
+    }
+}
@@ -0,0 +1,3 @@
+//! Secrets Management
+
+pub mod schema;
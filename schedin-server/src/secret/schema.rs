@@ -0,0 +1,47 @@
+//! Secret Schema
+//! Unified Schema for API and Database
+
+extern crate serde;
+extern crate std;
+extern crate time;
+extern crate utoipa;
+extern crate validator;
+
+use crate::api::validation::validate_secret_name;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use validator::Validate;
+
+/// # Secret
+/// Write-only secret, the value is accepted over the API but never returned.
+#[derive(Default, Deserialize, Validate, ToSchema)]
+pub struct Secret {
+    #[validate(custom(
+        function = "validate_secret_name",
+        message = "Secret name must be 1-255 characters of [A-Za-z0-9_.-]"
+    ))]
+    pub name: String,
+    pub value: Option<String>,
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Secret")
+            .field("name", &self.name)
+            .field("value", &self.value.as_ref().map(|_| "[REDACTED]"))
+            .finish()
+    }
+}
+
+/// # Secret Metadata
+/// Listing entry for a secret, without its value.
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct SecretRow {
+    pub name: String,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub created_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub updated_at: Option<OffsetDateTime>,
+}
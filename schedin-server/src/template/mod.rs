@@ -0,0 +1,4 @@
+//! Job Templates
+
+pub mod render;
+pub mod schema;
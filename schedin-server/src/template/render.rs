@@ -0,0 +1,310 @@
+//! Template Rendering
+
+extern crate serde_json;
+extern crate std;
+extern crate validator;
+
+use super::schema::{Parameter, ParameterKind, Template};
+use crate::job::schema::Job;
+use serde_json::Value;
+use std::{borrow::Cow, collections::HashMap};
+use validator::ValidationError;
+
+impl Template {
+    /// # Placeholders
+    /// Names of every `{{ parameter }}` placeholder in the job definition.
+    pub fn placeholders(&self) -> Result<Vec<String>, ValidationError> {
+        let mut names = Vec::new();
+        visit(&self.job, &mut |input| {
+            let mut rest = input;
+            while let Some((name, after)) = next_placeholder(rest)? {
+                names.push(name.to_string());
+                rest = after;
+            }
+            Ok(())
+        })?;
+        Ok(names)
+    }
+
+    /// # Resolve
+    /// Check `values` against the parameters, falling back to their defaults.
+    ///
+    /// ## Returns
+    ///
+    /// The checked value of every parameter, keyed by name.
+    pub fn resolve(
+        &self,
+        values: &HashMap<String, Value>,
+    ) -> Result<HashMap<String, Value>, ValidationError> {
+        if let Some(name) = values
+            .keys()
+            .find(|name| !self.parameters.iter().any(|p| &p.name == *name))
+        {
+            return Err(error("unknown_parameter", name));
+        }
+
+        self.parameters
+            .iter()
+            .map(|parameter| {
+                let value = values
+                    .get(&parameter.name)
+                    .or(parameter.default.as_ref())
+                    .ok_or_else(|| error("missing_parameter", &parameter.name))?;
+                Ok((parameter.name.clone(), parameter.render(value)?))
+            })
+            .collect()
+    }
+
+    /// # Render
+    /// Substitute the parameter values into the job definition. A string
+    /// made of a single placeholder takes the value as-is, e.g. a number for
+    /// `int` parameters, placeholders within text are replaced by its text.
+    ///
+    /// ## Arguments
+    ///
+    /// - `name`: Name of the resulting job.
+    /// - `values`: Parameter values of the instance.
+    pub fn render(
+        &self,
+        name: &str,
+        values: &HashMap<String, Value>,
+    ) -> Result<Job, ValidationError> {
+        let values = self.resolve(values)?;
+
+        let mut definition = self.job.clone();
+        substitute(&mut definition, &values)?;
+
+        let Value::Object(map) = &mut definition else {
+            return Err(ValidationError::new("job_not_an_object"));
+        };
+        map.insert("name".to_string(), Value::String(name.to_string()));
+
+        serde_json::from_value(definition).map_err(|e| {
+            let mut err = ValidationError::new("invalid_job");
+            err.message = Some(Cow::Owned(e.to_string()));
+            err
+        })
+    }
+}
+
+impl Parameter {
+    /// Check `value` against the parameter type, returns the checked value
+    pub fn render(&self, value: &Value) -> Result<Value, ValidationError> {
+        match (&self.kind, value) {
+            (ParameterKind::String, Value::String(_)) | (ParameterKind::Bool, Value::Bool(_)) => {
+                Ok(value.clone())
+            }
+            (ParameterKind::Int { min, max }, Value::Number(n)) => {
+                let int = n
+                    .as_i64()
+                    .ok_or_else(|| error("invalid_parameter_type", &self.name))?;
+
+                if min.is_some_and(|min| int < min) || max.is_some_and(|max| int > max) {
+                    return Err(error("parameter_out_of_range", &self.name));
+                }
+
+                Ok(Value::from(int))
+            }
+            (ParameterKind::Enum { values }, Value::String(s)) => {
+                if !values.contains(s) {
+                    return Err(error("invalid_enum_value", &self.name));
+                }
+                Ok(value.clone())
+            }
+            _ => Err(error("invalid_parameter_type", &self.name)),
+        }
+    }
+}
+
+/// Validation error referencing the offending parameter
+fn error(code: &'static str, name: &str) -> ValidationError {
+    let mut err = ValidationError::new(code);
+    err.add_param(Cow::Borrowed("parameter"), &name);
+    err
+}
+
+/// Find the next `{{ name }}` placeholder, returns its name and the remaining input
+fn next_placeholder(input: &str) -> Result<Option<(&str, &str)>, ValidationError> {
+    let Some(start) = input.find("{{") else {
+        return Ok(None);
+    };
+
+    let Some(len) = input[start + 2..].find("}}") else {
+        return Err(ValidationError::new("unterminated_placeholder"));
+    };
+
+    let name = input[start + 2..start + 2 + len].trim();
+    Ok(Some((name, &input[start + 4 + len..])))
+}
+
+/// Call `f` on every string in `value`
+fn visit<F>(value: &Value, f: &mut F) -> Result<(), ValidationError>
+where
+    F: FnMut(&str) -> Result<(), ValidationError>,
+{
+    match value {
+        Value::String(s) => f(s),
+        Value::Array(items) => items.iter().try_for_each(|item| visit(item, f)),
+        Value::Object(map) => map.values().try_for_each(|item| visit(item, f)),
+        _ => Ok(()),
+    }
+}
+
+/// Text of a parameter value within a string
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Replace the placeholders of every string in `value`
+fn substitute(value: &mut Value, values: &HashMap<String, Value>) -> Result<(), ValidationError> {
+    match value {
+        Value::String(s) => {
+            if let Some((name, "")) = next_placeholder(s)?.filter(|_| s.starts_with("{{")) {
+                *value = values
+                    .get(name)
+                    .ok_or_else(|| error("unknown_parameter", name))?
+                    .clone();
+                return Ok(());
+            }
+
+            let mut output = String::with_capacity(s.len());
+            let mut rest = s.as_str();
+
+            while let Some((name, after)) = next_placeholder(rest)? {
+                let rendered = values
+                    .get(name)
+                    .ok_or_else(|| error("unknown_parameter", name))?;
+                output.push_str(&rest[..rest.find("{{").unwrap_or_default()]);
+                output.push_str(&text(rendered));
+                rest = after;
+            }
+
+            output.push_str(rest);
+            *s = output;
+            Ok(())
+        }
+        Value::Array(items) => items
+            .iter_mut()
+            .try_for_each(|item| substitute(item, values)),
+        Value::Object(map) => map
+            .values_mut()
+            .try_for_each(|item| substitute(item, values)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::template::schema::Template;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    fn template() -> Template {
+        serde_json::from_value(json!({
+            "name": "report",
+            "parameters": [
+                { "name": "region", "type": "enum", "values": ["eu", "us"] },
+                { "name": "every", "type": "int", "min": 1, "max": 60, "default": 10 },
+                { "name": "memory", "type": "int", "default": 512 },
+                { "name": "verbose", "type": "bool", "default": false },
+                { "name": "owner", "type": "string", "default": "data" }
+            ],
+            "job": {
+                "schedule": "@every {{ every }} min",
+                "task": { "name": "report-{{ region }}-{{ verbose }}" },
+                "timeout": "{{ every }}",
+                "resources": { "requests": { "memory_mb": "{{memory}}" } },
+                "labels": { "team": "{{ owner }}" }
+            }
+        }))
+        .unwrap()
+    }
+
+    fn values(values: Value) -> HashMap<String, Value> {
+        serde_json::from_value(values).unwrap()
+    }
+
+    fn code(result: Result<impl std::fmt::Debug, validator::ValidationError>) -> String {
+        result.unwrap_err().code.to_string()
+    }
+
+    #[test]
+    fn resolves_defaults() {
+        let resolved = template()
+            .resolve(&values(json!({ "region": "eu", "every": 5 })))
+            .unwrap();
+
+        assert_eq!(resolved["region"], json!("eu"));
+        assert_eq!(resolved["every"], json!(5));
+        assert_eq!(resolved["memory"], json!(512));
+        assert_eq!(resolved["verbose"], json!(false));
+        assert_eq!(resolved["owner"], json!("data"));
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let template = template();
+        let resolve = |values: Value| code(template.resolve(&self::values(values)));
+
+        assert_eq!(resolve(json!({})), "missing_parameter");
+        assert_eq!(
+            resolve(json!({ "region": "eu", "shard": 1 })),
+            "unknown_parameter"
+        );
+        assert_eq!(resolve(json!({ "region": "ap" })), "invalid_enum_value");
+        assert_eq!(
+            resolve(json!({ "region": "eu", "every": 0 })),
+            "parameter_out_of_range"
+        );
+        assert_eq!(
+            resolve(json!({ "region": "eu", "every": 61 })),
+            "parameter_out_of_range"
+        );
+        assert_eq!(
+            resolve(json!({ "region": "eu", "every": "5" })),
+            "invalid_parameter_type"
+        );
+        assert_eq!(
+            resolve(json!({ "region": "eu", "every": 1.5 })),
+            "invalid_parameter_type"
+        );
+        assert_eq!(
+            resolve(json!({ "region": "eu", "verbose": "yes" })),
+            "invalid_parameter_type"
+        );
+    }
+
+    #[test]
+    fn renders_typed_values() {
+        let job = template()
+            .render(
+                "report-eu",
+                &values(json!({ "region": "eu", "every": 5, "verbose": true })),
+            )
+            .unwrap();
+
+        assert_eq!(job.name, "report-eu");
+        assert_eq!(job.schedule.as_deref(), Some("@every 5 min"));
+        assert_eq!(job.task.unwrap().name, "report-eu-true");
+        assert_eq!(job.timeout, Some(5));
+        assert_eq!(job.resources.unwrap().requests.memory_mb, Some(512));
+        assert_eq!(job.labels.unwrap()["team"], "data");
+    }
+
+    #[test]
+    fn rejects_undeclared_placeholders() {
+        let mut template = template();
+        template.job["description"] = json!("{{ shard }}");
+
+        assert_eq!(
+            code(template.render("report-eu", &values(json!({ "region": "eu" })))),
+            "unknown_parameter"
+        );
+
+        template.job["description"] = json!("{{ region");
+        assert_eq!(code(template.placeholders()), "unterminated_placeholder");
+    }
+}
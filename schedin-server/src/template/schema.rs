@@ -0,0 +1,117 @@
+//! Template Schema
+//! Unified Schema for API and Database
+
+extern crate serde;
+extern crate serde_json;
+extern crate sqlx;
+extern crate std;
+extern crate time;
+extern crate utoipa;
+extern crate uuid;
+extern crate validator;
+
+use crate::api::validation::validate_template;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::types::Json;
+use std::collections::HashMap;
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+/// # Job Template
+/// Stored job definition with typed parameters.
+///
+/// String fields of `job` may contain `{{ parameter }}` placeholders,
+/// which are substituted with the instance values. A field made of a single
+/// placeholder takes the typed value, so `"{{ n }}"` fills numeric fields.
+#[derive(Debug, Default, Deserialize, Serialize, Validate, ToSchema)]
+#[validate(schema(function = "validate_template", skip_on_field_errors = false))]
+pub struct Template {
+    #[validate(length(min = 1, max = 255))]
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub parameters: Vec<Parameter>,
+    pub job: Value,
+}
+
+/// # Template Parameter
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct Parameter {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: ParameterKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value>,
+}
+
+/// # Parameter Type
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ParameterKind {
+    String,
+    Int {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min: Option<i64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max: Option<i64>,
+    },
+    Enum {
+        values: Vec<String>,
+    },
+    Bool,
+}
+
+/// # Template Instance
+/// Job created from a template by supplying parameter values.
+#[derive(Debug, Default, Deserialize, Validate, ToSchema)]
+pub struct Instance {
+    pub template: String,
+    #[validate(length(min = 1, max = 255))]
+    pub name: String,
+    #[serde(default)]
+    pub values: HashMap<String, Value>,
+    /// Project of the instance, `default` if omitted
+    pub project: Option<String>,
+}
+
+/// Template update options
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Rollout {
+    /// Re-render every instance with the updated template
+    #[serde(default)]
+    pub rollout: bool,
+}
+
+/// Stored template
+pub struct TemplateRecord {
+    pub template_id: Uuid,
+    pub template: Template,
+}
+
+/// Instance of a stored template
+#[derive(sqlx::FromRow)]
+pub struct InstanceRow {
+    pub job_id: Uuid,
+    pub job_name: String,
+    pub template_values: Option<Json<HashMap<String, Value>>>,
+}
+
+/// # Template Listing
+#[derive(Debug, Serialize, sqlx::FromRow, ToSchema)]
+pub struct TemplateRow {
+    pub name: String,
+    pub description: Option<String>,
+    #[schema(value_type = Vec<Parameter>)]
+    pub parameters: Json<Value>,
+    #[schema(value_type = Object)]
+    pub job: Json<Value>,
+    pub instances: Option<i64>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub created_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub updated_at: Option<OffsetDateTime>,
+}
@@ -0,0 +1,156 @@
+//! Webhook Schema
+//! Unified Schema for API and Database
+
+extern crate serde;
+extern crate time;
+extern crate utoipa;
+extern crate uuid;
+extern crate validator;
+
+use crate::{
+    api::validation::{validate_channel_name, validate_http_url},
+    notification::schema::DeliveryStatus,
+};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+/// # Subscription
+/// Subscriber URL receiving the events of the user, signed with a secret
+/// generated on creation.
+#[derive(Debug, Default, Deserialize, Validate, ToSchema)]
+pub struct Subscription {
+    #[validate(custom(
+        function = "validate_channel_name",
+        message = "Subscription name must be 1-63 characters of [a-z0-9-]"
+    ))]
+    pub name: String,
+    #[serde(default)]
+    #[validate(custom(
+        function = "validate_http_url",
+        message = "Must be an http:// or https:// URL"
+    ))]
+    pub url: String,
+    /// Events delivered to the subscriber, all of them if unset
+    #[validate(length(min = 1, max = 5))]
+    pub events: Option<Vec<EventType>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, ToSchema)]
+pub enum EventType {
+    /// Run was enqueued
+    #[serde(rename = "run.scheduled")]
+    RunScheduled,
+
+    /// Run was picked up by a worker
+    #[serde(rename = "run.started")]
+    RunStarted,
+
+    /// Run succeeded
+    #[serde(rename = "run.succeeded")]
+    RunSucceeded,
+
+    /// Run failed
+    #[serde(rename = "run.failed")]
+    RunFailed,
+
+    /// Job definition changed, with the diff of the new revision
+    #[serde(rename = "job.updated")]
+    JobUpdated,
+}
+
+impl EventType {
+    pub const ALL: [EventType; 5] = [
+        EventType::RunScheduled,
+        EventType::RunStarted,
+        EventType::RunSucceeded,
+        EventType::RunFailed,
+        EventType::JobUpdated,
+    ];
+
+    /// Name of the event, as written to the outbox
+    pub fn name(self) -> &'static str {
+        match self {
+            EventType::RunScheduled => "run.scheduled",
+            EventType::RunStarted => "run.started",
+            EventType::RunSucceeded => "run.succeeded",
+            EventType::RunFailed => "run.failed",
+            EventType::JobUpdated => "job.updated",
+        }
+    }
+}
+
+/// # Subscription Listing
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SubscriptionRow {
+    pub name: String,
+    pub url: String,
+    pub events: Vec<String>,
+    /// Time until which the previous secret still signs deliveries
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub rotating_until: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub updated_at: Option<OffsetDateTime>,
+}
+
+/// # Rotate Secret
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct Rotate {
+    pub name: String,
+    /// Time (in seconds) the previous secret keeps signing deliveries
+    #[serde(default = "default_grace")]
+    #[validate(range(min = 0, max = 604800))]
+    pub grace: i32,
+}
+
+fn default_grace() -> i32 {
+    86400
+}
+
+/// # Redeliver
+/// Deliver again the events of a subscription, one of them if `event` is
+/// set, those since `since` otherwise.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct Redeliver {
+    pub name: String,
+    pub event: Option<Uuid>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub since: Option<OffsetDateTime>,
+}
+
+/// Delivery log query
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Deliveries {
+    pub name: String,
+    /// Maximum number of deliveries, most recent first
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+}
+
+fn default_limit() -> i64 {
+    50
+}
+
+/// # Delivery
+#[derive(Debug, Serialize, ToSchema)]
+#[schema(as = WebhookDelivery)]
+pub struct Delivery {
+    pub id: Uuid,
+    pub event_id: Uuid,
+    pub event: String,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub next_attempt_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub delivered_at: Option<OffsetDateTime>,
+}
@@ -0,0 +1,151 @@
+//! Run Artifacts
+
+extern crate glob;
+extern crate schedin_common;
+extern crate std;
+extern crate tokio;
+
+use crate::{
+    db::DB,
+    run::{self, Assignment},
+};
+use glob::{MatchOptions, Pattern};
+use schedin_common::blob::{self, BlobStore};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+use tokio::task;
+
+/// Files collected per run
+const MAX_ARTIFACTS: usize = 100;
+
+/// Bytes collected per run
+const MAX_ARTIFACTS_SIZE: u64 = 512 * 1024 * 1024;
+
+/// Blob store shared by the runs of the worker
+static STORE: OnceLock<Box<dyn BlobStore>> = OnceLock::new();
+
+/// # Collect
+/// Upload the files of the work directory of a run matching `patterns`
+/// to the blob store, and record them as artifacts of the run. Returns the
+/// number of artifacts.
+///
+/// `*` never matches across directories, `**` matches any number of them.
+/// Only regular files inside the work directory are collected, symlinks
+/// are skipped.
+///
+/// ## Errors
+///
+/// Invalid patterns, too many or too large files, and blob store or
+/// database failures.
+pub async fn collect(db: &DB, run: &Assignment, patterns: &[String]) -> Result<usize, String> {
+    let workdir = run::workdir(&run.run_id);
+    let files = task::block_in_place(|| matches(&workdir, patterns))?;
+
+    if files.is_empty() {
+        return Ok(0);
+    }
+
+    let store = store()?;
+
+    for (path, (file, size)) in &files {
+        let digest = task::block_in_place(|| blob::digest(file))
+            .map_err(|error| format!("Unable to read artifact '{}': {}", path, error))?;
+
+        db.lease(&digest, *size as i64)
+            .await
+            .map_err(|error| error.reason().to_string())?;
+
+        task::block_in_place(|| store.put(&digest, file))
+            .map_err(|error| format!("Unable to store artifact '{}': {}", path, error.reason()))?;
+
+        db.artifact(&run.run_id, path, &digest, *size as i64)
+            .await
+            .map_err(|error| error.reason().to_string())?;
+    }
+
+    Ok(files.len())
+}
+
+/// Regular files of `workdir` matching any of `patterns`, along with
+/// their size, by path relative to `workdir`
+fn matches(
+    workdir: &Path,
+    patterns: &[String],
+) -> Result<BTreeMap<String, (PathBuf, u64)>, String> {
+    let root = match workdir.canonicalize() {
+        Ok(root) => root,
+        // nothing ran, e.g. the payload failed to decode
+        Err(_) => return Ok(BTreeMap::new()),
+    };
+
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::new()
+    };
+
+    let mut files = BTreeMap::new();
+    let mut total = 0;
+
+    for pattern in patterns {
+        let absolute = format!("{}/{}", Pattern::escape(&root.to_string_lossy()), pattern);
+        let paths = glob::glob_with(&absolute, options)
+            .map_err(|_| format!("Invalid artifact pattern '{}'", pattern))?;
+
+        for file in paths.flatten() {
+            let Ok(metadata) = fs::symlink_metadata(&file) else {
+                continue;
+            };
+
+            // symlinked directories may lead out of the work directory
+            let inside = file
+                .canonicalize()
+                .is_ok_and(|resolved| resolved.starts_with(&root));
+
+            if !metadata.is_file() || !inside {
+                continue;
+            }
+
+            let Some(path) = file
+                .strip_prefix(&root)
+                .ok()
+                .and_then(Path::to_str)
+                .map(str::to_string)
+            else {
+                continue;
+            };
+
+            if files.contains_key(&path) {
+                continue;
+            }
+
+            total += metadata.len();
+            files.insert(path, (file, metadata.len()));
+
+            if files.len() > MAX_ARTIFACTS {
+                return Err(format!("More than {} artifacts", MAX_ARTIFACTS));
+            }
+            if total > MAX_ARTIFACTS_SIZE {
+                return Err(format!(
+                    "Artifacts exceed {} MiB",
+                    MAX_ARTIFACTS_SIZE / 1024 / 1024
+                ));
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Blob store of the `BLOB_STORE` environment variable, resolved on first use
+fn store() -> Result<&'static dyn BlobStore, String> {
+    if let Some(store) = STORE.get() {
+        return Ok(store.as_ref());
+    }
+
+    let store = blob::from_env().map_err(|error| error.reason().to_string())?;
+    Ok(STORE.get_or_init(|| store).as_ref())
+}
@@ -0,0 +1,623 @@
+//! Database Operations
+
+extern crate schedin_common;
+extern crate sqlx;
+extern crate std;
+
+use crate::{
+    job::{JobType, Payload, Step},
+    log::Chunk,
+    run::{Assignment, Outcome},
+    worker::Capacity,
+};
+use schedin_common::{
+    error::CrudError,
+    secret::{self, Cipher, Redacted},
+};
+use sqlx::{
+    types::{Json, Uuid},
+    Pool, Postgres,
+};
+use std::collections::HashMap;
+
+#[derive(Clone)]
+pub struct DB {
+    pub pool: Pool<Postgres>,
+}
+
+impl DB {
+    /// Create new instance
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+
+    /// # Register
+    /// Advertise the capacity and labels of the worker, returns its id.
+    /// Registering again under the same name updates them.
+    pub async fn register(&self, capacity: &Capacity) -> Result<Uuid, CrudError> {
+        match sqlx::query_scalar!(
+            r#"
+            INSERT INTO workers (worker_name, cpu, memory_mb, disk_mb, labels) 
+            VALUES ($1, $2, $3, $4, $5) 
+            ON CONFLICT (worker_name) DO UPDATE SET cpu = EXCLUDED.cpu, 
+            memory_mb = EXCLUDED.memory_mb, disk_mb = EXCLUDED.disk_mb, 
+            labels = EXCLUDED.labels, heartbeat_at = NOW() 
+            RETURNING worker_id
+            "#,
+            capacity.name,
+            capacity.cpu,
+            capacity.memory_mb,
+            capacity.disk_mb,
+            Json(&capacity.labels) as _
+        )
+        .fetch_one(&self.pool)
+        .await
+        {
+            Ok(worker_id) => Ok(worker_id),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// Mark the worker as alive
+    pub async fn heartbeat(&self, worker_id: &Uuid) -> Result<(), CrudError> {
+        match sqlx::query!(
+            "UPDATE workers SET heartbeat_at = NOW() WHERE worker_id = $1",
+            worker_id
+        )
+        .execute(&self.pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Claim
+    /// Start the queued runs placed on the worker.
+    pub async fn claim(&self, worker_id: &Uuid) -> Result<Vec<Assignment>, CrudError> {
+        match sqlx::query_as!(
+            Assignment,
+            r#"
+            UPDATE runs SET run_status = 'running', started_at = NOW() 
+            WHERE run_id IN (
+                SELECT run_id FROM runs 
+                WHERE worker_id = $1 AND run_status = 'queued' 
+                ORDER BY priority DESC, scheduled_at, position 
+                FOR UPDATE SKIP LOCKED
+            ) 
+            RETURNING run_id, job_id AS "job_id!", timeout, 
+            combination AS "combination: Json<HashMap<String, String>>"
+            "#,
+            worker_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        {
+            Ok(runs) => Ok(runs),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// Running runs of the worker that were preempted by the orchestrator
+    pub async fn preempted(&self, worker_id: &Uuid) -> Result<Vec<Uuid>, CrudError> {
+        match sqlx::query_scalar!(
+            r#"
+            SELECT run_id FROM runs 
+            WHERE worker_id = $1 AND run_status = 'running' AND preempted_at IS NOT NULL
+            "#,
+            worker_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        {
+            Ok(runs) => Ok(runs),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// Executable payload and resource limits of a job
+    pub async fn payload(&self, job_id: &Uuid) -> Result<Payload, CrudError> {
+        match sqlx::query_as!(
+            Payload,
+            r#"
+            SELECT j.job_id, j.job_type AS "job_type: JobType", 
+            c.src AS "src?", c.cmd AS "code_cmd?", 
+            b.path AS "path?", b.cmd AS "bin_cmd?", 
+            h.method AS "method?", h.url AS "url?", h.body AS "http_body?", 
+            h.timeout AS "http_timeout?", h.expected_status AS "expected_status?", 
+            h.assertion AS "assertion?", 
+            q.statements AS "statements?", q.statement_timeout AS "statement_timeout?", 
+            m.module AS "module?", w.args AS "args?", w.fuel AS "fuel?", 
+            p.steps AS "steps?: Json<Vec<Step>>", j.artifacts, 
+            j.limit_cpu, j.limit_memory_mb, j.limit_disk_mb 
+            FROM jobs j 
+            LEFT JOIN codes c ON c.job_id = j.job_id 
+            LEFT JOIN bins b ON b.job_id = j.job_id 
+            LEFT JOIN http_requests h ON h.job_id = j.job_id 
+            LEFT JOIN sql_queries q ON q.job_id = j.job_id 
+            LEFT JOIN wasm_jobs w ON w.job_id = j.job_id 
+            LEFT JOIN wasm_modules m ON m.module_id = w.module_id 
+            LEFT JOIN pipelines p ON p.job_id = j.job_id 
+            WHERE j.job_id = $1
+            "#,
+            job_id
+        )
+        .fetch_one(&self.pool)
+        .await
+        {
+            Ok(payload) => Ok(payload),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// # Finish
+    /// Record the outcome of a run, which releases its reservation.
+    /// Preempted runs are queued again, to be placed on any worker.
+    pub async fn finish(&self, run_id: &Uuid, outcome: &Outcome) -> Result<(), CrudError> {
+        if outcome.preempted {
+            return self.requeue(run_id).await;
+        }
+
+        let status = match outcome.succeeded {
+            true => "succeeded",
+            false => "failed",
+        };
+
+        match sqlx::query!(
+            r#"
+            UPDATE runs SET run_status = $2::text::run_status, finished_at = NOW(), 
+            exit_code = $3, error = $4, 
+            response_status = $5, latency_ms = $6, response_body = $7, 
+            rows_affected = $8, result_preview = $9, stdout = $10, stderr = $11, timed_out = $12 
+            WHERE run_id = $1
+            "#,
+            run_id,
+            status,
+            outcome.exit_code,
+            outcome.error,
+            outcome.response.as_ref().map(|response| response.status),
+            outcome
+                .response
+                .as_ref()
+                .map(|response| response.latency_ms),
+            outcome
+                .response
+                .as_ref()
+                .map(|response| response.body.as_str()),
+            outcome.result.as_ref().map(|result| result.rows_affected),
+            outcome.result.as_ref().map(|result| Json(&result.preview)) as _,
+            outcome.output.as_ref().map(|output| output.stdout.as_str()),
+            outcome.output.as_ref().map(|output| output.stderr.as_str()),
+            outcome.timed_out
+        )
+        .execute(&self.pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    async fn requeue(&self, run_id: &Uuid) -> Result<(), CrudError> {
+        match sqlx::query!(
+            r#"
+            UPDATE runs SET run_status = 'queued', worker_id = NULL, started_at = NULL, 
+            preempted_at = NULL, preemptions = preemptions + 1, 
+            reason = 'Preempted by a run of higher priority' 
+            WHERE run_id = $1
+            "#,
+            run_id
+        )
+        .execute(&self.pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Lease
+    /// Record the blob `digest` as in use, before uploading it. Blobs are
+    /// only collected once unreferenced and their lease expired, so that a
+    /// blob is never collected while uploaded.
+    pub async fn lease(&self, digest: &str, size: i64) -> Result<(), CrudError> {
+        match sqlx::query!(
+            r#"
+            INSERT INTO blobs (digest, size) VALUES ($1, $2) 
+            ON CONFLICT (digest) DO UPDATE SET leased_at = NOW()
+            "#,
+            digest,
+            size
+        )
+        .execute(&self.pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// Record the artifact `path` of a run, stored as the blob `digest`
+    pub async fn artifact(
+        &self,
+        run_id: &Uuid,
+        path: &str,
+        digest: &str,
+        size: i64,
+    ) -> Result<(), CrudError> {
+        match sqlx::query!(
+            r#"
+            INSERT INTO run_artifacts (run_id, path, digest, size) VALUES ($1, $2, $3, $4) 
+            ON CONFLICT (run_id, path) DO UPDATE 
+            SET digest = EXCLUDED.digest, size = EXCLUDED.size, created_at = NOW()
+            "#,
+            run_id,
+            path,
+            digest,
+            size
+        )
+        .execute(&self.pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// Forget the log written by a previous attempt of a run
+    pub async fn clear_log(&self, run_id: &Uuid) -> Result<(), CrudError> {
+        match sqlx::query!("DELETE FROM run_logs WHERE run_id = $1", run_id)
+            .execute(&self.pool)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// Append `chunks` to the log of a run, the first one at byte `position`
+    pub async fn append_log(
+        &self,
+        run_id: &Uuid,
+        position: i64,
+        chunks: &[Chunk],
+    ) -> Result<(), CrudError> {
+        let mut positions = Vec::with_capacity(chunks.len());
+        let mut next = position;
+        for chunk in chunks {
+            positions.push(next);
+            next += chunk.data.len() as i64;
+        }
+
+        match sqlx::query!(
+            r#"
+            INSERT INTO run_logs (run_id, position, stream, data, logged_at) 
+            SELECT $1, c.position, c.stream::log_stream, c.data, c.logged_at 
+            FROM UNNEST($2::BIGINT[], $3::TEXT[], $4::BYTEA[], $5::TIMESTAMPTZ[]) 
+            AS c(position, stream, data, logged_at)
+            "#,
+            run_id,
+            &positions,
+            &chunks
+                .iter()
+                .map(|chunk| chunk.stream.name().to_string())
+                .collect::<Vec<_>>(),
+            &chunks
+                .iter()
+                .map(|chunk| chunk.data.clone())
+                .collect::<Vec<_>>(),
+            &chunks
+                .iter()
+                .map(|chunk| chunk.logged_at)
+                .collect::<Vec<_>>()
+        )
+        .execute(&self.pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// Forget the steps recorded by a previous attempt of a run
+    pub async fn clear_steps(&self, run_id: &Uuid) -> Result<(), CrudError> {
+        match sqlx::query!("DELETE FROM run_steps WHERE run_id = $1", run_id)
+            .execute(&self.pool)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// Record the step at `position` of a run, as running or skipped
+    pub async fn start_step(
+        &self,
+        run_id: &Uuid,
+        position: usize,
+        name: &str,
+        skipped: bool,
+    ) -> Result<(), CrudError> {
+        let status = match skipped {
+            true => "skipped",
+            false => "running",
+        };
+
+        match sqlx::query!(
+            r#"
+            INSERT INTO run_steps (run_id, position, step_name, step_status, started_at) 
+            VALUES ($1, $2, $3, $4::text::step_status, CASE WHEN $5 THEN NULL ELSE NOW() END)
+            "#,
+            run_id,
+            position as i32,
+            name,
+            status,
+            skipped
+        )
+        .execute(&self.pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// Record the outcome of the step at `position` of a run. The response
+    /// body of http steps is stored as their output.
+    pub async fn finish_step(
+        &self,
+        run_id: &Uuid,
+        position: usize,
+        outcome: &Outcome,
+    ) -> Result<(), CrudError> {
+        let status = match outcome.succeeded {
+            true => "succeeded",
+            false => "failed",
+        };
+
+        let stdout = match (&outcome.output, &outcome.response) {
+            (Some(output), _) => Some(output.stdout.as_str()),
+            (None, Some(response)) => Some(response.body.as_str()),
+            (None, None) => None,
+        };
+
+        match sqlx::query!(
+            r#"
+            UPDATE run_steps SET step_status = $3::text::step_status, finished_at = NOW(), 
+            exit_code = $4, error = $5, response_status = $6, stdout = $7, stderr = $8 
+            WHERE run_id = $1 AND position = $2
+            "#,
+            run_id,
+            position as i32,
+            status,
+            outcome.exit_code,
+            outcome.error,
+            outcome.response.as_ref().map(|response| response.status),
+            stdout,
+            outcome.output.as_ref().map(|output| output.stderr.as_str())
+        )
+        .execute(&self.pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Insertion)
+            }
+        }
+    }
+
+    /// # Environment
+    /// Resolve the environment of a job about to run.
+    ///
+    /// Plain values are returned as-is, secret references are decrypted
+    /// with the master key. Every value is wrapped in `Redacted` so it
+    /// never ends up in the logs.
+    ///
+    /// ## Errors
+    ///
+    /// `Err(CrudError::Read)` if the environment cannot be read or a
+    /// secret fails to decrypt.
+    pub async fn env(
+        &self,
+        job_id: &Uuid,
+        cipher: &Cipher,
+    ) -> Result<HashMap<String, Redacted<String>>, CrudError> {
+        match sqlx::query_as!(
+            Value,
+            r#"
+            SELECT e.env_name AS name, e.env_value AS value, s.user_id AS "owner?", 
+            s.secret_name AS "secret_name?", s.nonce AS "nonce?", s.ciphertext AS "ciphertext?" 
+            FROM envs e LEFT JOIN secrets s ON s.secret_id = e.secret_id 
+            WHERE e.job_id = $1
+            "#,
+            job_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        {
+            Ok(rows) => reveal(rows, cipher, "env"),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// # Headers
+    /// Resolve the request headers of an http job about to run,
+    /// the same way as the environment.
+    pub async fn headers(
+        &self,
+        job_id: &Uuid,
+        cipher: &Cipher,
+    ) -> Result<HashMap<String, Redacted<String>>, CrudError> {
+        match sqlx::query_as!(
+            Value,
+            r#"
+            SELECT h.header_name AS name, h.header_value AS value, s.user_id AS "owner?", 
+            s.secret_name AS "secret_name?", s.nonce AS "nonce?", s.ciphertext AS "ciphertext?" 
+            FROM http_headers h LEFT JOIN secrets s ON s.secret_id = h.secret_id 
+            WHERE h.job_id = $1
+            "#,
+            job_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        {
+            Ok(rows) => reveal(rows, cipher, "header"),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// # Step Secrets
+    /// Resolve the secrets referenced by the headers of the http steps of a
+    /// pipeline job about to run, by secret name.
+    pub async fn step_secrets(
+        &self,
+        job_id: &Uuid,
+        cipher: &Cipher,
+    ) -> Result<HashMap<String, Redacted<String>>, CrudError> {
+        match sqlx::query_as!(
+            Value,
+            r#"
+            SELECT s.secret_name AS name, NULL::text AS value, s.user_id AS "owner?", 
+            s.secret_name AS "secret_name?", s.nonce AS "nonce?", s.ciphertext AS "ciphertext?" 
+            FROM pipeline_secrets p JOIN secrets s ON s.secret_id = p.secret_id 
+            WHERE p.job_id = $1
+            "#,
+            job_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        {
+            Ok(rows) => reveal(rows, cipher, "step secret"),
+            Err(error) => {
+                eprintln!("{}", error);
+                Err(CrudError::Read)
+            }
+        }
+    }
+
+    /// # Connection
+    /// Resolve the DSN of a sql job about to run, decrypted from its secret.
+    pub async fn connection(
+        &self,
+        job_id: &Uuid,
+        cipher: &Cipher,
+    ) -> Result<Redacted<String>, CrudError> {
+        let rows = match sqlx::query_as!(
+            Value,
+            r#"
+            SELECT 'connection' AS "name!", NULL::text AS value, s.user_id AS "owner?", 
+            s.secret_name AS "secret_name?", s.nonce AS "nonce?", s.ciphertext AS "ciphertext?" 
+            FROM sql_queries q LEFT JOIN secrets s ON s.secret_id = q.secret_id 
+            WHERE q.job_id = $1
+            "#,
+            job_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        {
+            Ok(rows) => rows,
+            Err(error) => {
+                eprintln!("{}", error);
+                return Err(CrudError::Read);
+            }
+        };
+
+        reveal(rows, cipher, "sql")?
+            .remove("connection")
+            .ok_or(CrudError::Read)
+    }
+}
+
+/// Plain value or sealed secret of an environment variable or header
+struct Value {
+    name: String,
+    value: Option<String>,
+    owner: Option<Uuid>,
+    secret_name: Option<String>,
+    nonce: Option<Vec<u8>>,
+    ciphertext: Option<Vec<u8>>,
+}
+
+/// Decrypt the secret values, `kind` names the values in the logs
+fn reveal(
+    rows: Vec<Value>,
+    cipher: &Cipher,
+    kind: &str,
+) -> Result<HashMap<String, Redacted<String>>, CrudError> {
+    let mut values = HashMap::with_capacity(rows.len());
+
+    for row in rows {
+        let value = match (
+            row.value,
+            row.owner,
+            row.secret_name,
+            row.nonce,
+            row.ciphertext,
+        ) {
+            (Some(value), ..) => Redacted::new(value),
+            (None, Some(owner), Some(name), Some(nonce), Some(ciphertext)) => {
+                match cipher.decrypt(&nonce, &ciphertext, &secret::aad(&owner.to_string(), &name)) {
+                    Ok(value) => value,
+                    Err(error) => {
+                        eprintln!("{} '{}': {}", kind, row.name, error.reason());
+                        return Err(CrudError::Read);
+                    }
+                }
+            }
+            _ => {
+                eprintln!("{} '{}': dangling secret reference", kind, row.name);
+                return Err(CrudError::Read);
+            }
+        };
+
+        values.insert(row.name, value);
+    }
+
+    Ok(values)
+}
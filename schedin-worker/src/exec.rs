@@ -0,0 +1,288 @@
+//! Run Execution
+
+extern crate base64;
+extern crate schedin_common;
+extern crate std;
+extern crate tokio;
+
+use crate::{
+    job::{JobType, Payload},
+    log::{self, Log, Stream},
+    run::{self, truncate, Assignment, Outcome, Output, STORED_OUTPUT},
+};
+use base64::Engine;
+use schedin_common::secret::Redacted;
+use std::{
+    collections::HashMap, fs, future, os::unix::process::CommandExt, path::Path, process::Stdio,
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    process::{Child, Command},
+    sync::oneshot,
+    task::JoinHandle,
+    time,
+};
+
+/// Time (in seconds) a preempted run is given to exit after `SIGTERM`
+const GRACE_PERIOD: u64 = 10;
+
+/// Time (in seconds) the output is still read for once the run exited,
+/// background processes may keep it open
+const OUTPUT_GRACE_PERIOD: u64 = 1;
+
+/// # Execute
+/// Run the payload of a job in its work directory, with its environment.
+///
+/// - `Code`: The decoded source is written to the file named by the last
+///   argument of `cmd`, e.g. `main.py` for `python main.py`, then `cmd` runs.
+/// - `Bin`: `cmd` runs if set, `path` otherwise.
+/// - `Task`: Has no executable payload and fails.
+///
+/// Stdout and stderr are written to the log of the run as they are read,
+/// and stored truncated with the run.
+///
+/// ## Limits
+///
+/// The memory limit caps the address space and the disk limit caps the size of
+/// every written file, through `ulimit`. The CPU limit is advisory and exported
+/// as `SCHEDIN_CPU_LIMIT` (in millicores). Runs exceeding their timeout are terminated.
+///
+/// ## Preemption
+///
+/// Once `preempt` fires, the process group of the run receives `SIGTERM` and is
+/// killed if still running after the grace period. The run is then queued again.
+/// Runs exceeding their timeout are stopped the same way.
+pub async fn execute(
+    run: &Assignment,
+    payload: &Payload,
+    env: HashMap<String, Redacted<String>>,
+    log: &Log,
+    preempt: oneshot::Receiver<()>,
+) -> Outcome {
+    let workdir = run::workdir(&run.run_id);
+
+    if let Err(error) = fs::create_dir_all(&workdir) {
+        return Outcome::failed(format!("Unable to create work directory: {}", error));
+    }
+
+    step(run, payload, &workdir, env, log, preempt).await
+}
+
+/// # Step
+/// Run a payload in `workdir`, which outlives the run, as pipeline
+/// steps share their work directory.
+pub async fn step(
+    run: &Assignment,
+    payload: &Payload,
+    workdir: &Path,
+    env: HashMap<String, Redacted<String>>,
+    log: &Log,
+    preempt: oneshot::Receiver<()>,
+) -> Outcome {
+    match command(payload, workdir) {
+        Ok(command) => spawn(run, payload, workdir, &command, env, log, preempt).await,
+        Err(error) => Outcome::failed(error),
+    }
+}
+
+/// Shell command of the payload, writing the source of `Code` jobs into `workdir`
+fn command(payload: &Payload, workdir: &Path) -> Result<String, String> {
+    match payload.job_type {
+        JobType::Code => {
+            let (src, cmd) = match (&payload.src, &payload.code_cmd) {
+                (Some(src), Some(cmd)) => (src, cmd),
+                _ => return Err("Code job is missing its source or command".to_string()),
+            };
+
+            let source = base64::engine::general_purpose::STANDARD
+                .decode(src)
+                .map_err(|_| "Invalid Base64-encoded source code".to_string())?;
+
+            let file = cmd
+                .split_whitespace()
+                .last()
+                .filter(|file| !file.contains('/'))
+                .ok_or_else(|| "Code command must end with a file name".to_string())?;
+
+            fs::write(workdir.join(file), source)
+                .map_err(|error| format!("Unable to write source: {}", error))?;
+
+            Ok(cmd.clone())
+        }
+        JobType::Bin => match (&payload.bin_cmd, &payload.path) {
+            (Some(cmd), _) => Ok(cmd.clone()),
+            (None, Some(path)) => Ok(path.clone()),
+            (None, None) => Err("Bin job is missing its path".to_string()),
+        },
+        JobType::Task => Err("Task jobs have no executable payload".to_string()),
+        JobType::Http => Err("Http jobs are sent as requests".to_string()),
+        JobType::Sql => Err("Sql jobs run against their database".to_string()),
+        JobType::Wasm => Err("Wasm jobs run in the embedded runtime".to_string()),
+        JobType::Pipeline => Err("Pipeline jobs run step by step".to_string()),
+        JobType::Invalid => Err("Invalid job".to_string()),
+    }
+}
+
+async fn spawn(
+    run: &Assignment,
+    payload: &Payload,
+    workdir: &Path,
+    command: &str,
+    env: HashMap<String, Redacted<String>>,
+    log: &Log,
+    preempt: oneshot::Receiver<()>,
+) -> Outcome {
+    let mut script = String::new();
+    if let Some(memory_mb) = payload.limit_memory_mb {
+        script.push_str(&format!("ulimit -v {}; ", memory_mb as i64 * 1024));
+    }
+    if let Some(disk_mb) = payload.limit_disk_mb {
+        // 512-byte blocks
+        script.push_str(&format!("ulimit -f {}; ", disk_mb as i64 * 2048));
+    }
+    script.push_str(command);
+
+    // own process group, so that preemption signals reach the whole command
+    let mut process = std::process::Command::new("sh");
+    process.process_group(0);
+
+    let mut process = Command::from(process);
+    process
+        .arg("-c")
+        .arg(script)
+        .current_dir(workdir)
+        .envs(env.iter().map(|(name, value)| (name, value.expose())))
+        .env("SCHEDIN_RUN_ID", run.run_id.to_string())
+        .env("SCHEDIN_JOB_ID", payload.job_id.to_string())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    if let Some(cpu) = payload.limit_cpu {
+        process.env("SCHEDIN_CPU_LIMIT", cpu.to_string());
+    }
+
+    let mut child = match process.spawn() {
+        Ok(child) => child,
+        Err(error) => return Outcome::failed(format!("Unable to spawn: {}", error)),
+    };
+
+    let stdout = tokio::spawn(capture(child.stdout.take(), log.clone(), Stream::Stdout));
+    let stderr = tokio::spawn(capture(child.stderr.take(), log.clone(), Stream::Stderr));
+
+    let timeout = async {
+        match run.timeout {
+            Some(timeout) => time::sleep(Duration::from_secs(timeout as u64)).await,
+            None => future::pending().await,
+        }
+    };
+
+    let outcome = tokio::select! {
+        status = child.wait() => match status {
+            Ok(status) => Outcome::exited(status.code()),
+            Err(error) => Outcome::failed(error),
+        },
+        _ = timeout => {
+            terminate(run, &mut child).await;
+            Outcome::timed_out(run.timeout.unwrap_or_default())
+        }
+        Ok(_) = preempt => {
+            terminate(run, &mut child).await;
+            Outcome::preempted()
+        }
+    };
+
+    outcome.with_output(Output {
+        stdout: output(stdout).await,
+        stderr: output(stderr).await,
+    })
+}
+
+/// Read `pipe` to the end, writing it to `log` and keeping its first bytes
+async fn capture<R: AsyncRead + Unpin>(pipe: Option<R>, log: Log, stream: Stream) -> Vec<u8> {
+    let mut kept = Vec::new();
+    let Some(mut pipe) = pipe else {
+        return kept;
+    };
+
+    let mut buffer = [0; 8192];
+    let mut pending = Vec::new();
+    while let Ok(read) = pipe.read(&mut buffer).await {
+        if read == 0 {
+            break;
+        }
+        let room = STORED_OUTPUT.saturating_sub(kept.len());
+        kept.extend_from_slice(&buffer[..read.min(room)]);
+
+        pending.extend_from_slice(&buffer[..read]);
+        let complete = log::boundary(&pending);
+        log.append(stream, &pending[..complete]);
+        pending.drain(..complete);
+    }
+    log.append(stream, &pending);
+
+    kept
+}
+
+/// Captured output, abandoned if the pipe is still open after the grace period
+async fn output(mut capture: JoinHandle<Vec<u8>>) -> String {
+    match time::timeout(Duration::from_secs(OUTPUT_GRACE_PERIOD), &mut capture).await {
+        Ok(Ok(output)) => truncate(&output, STORED_OUTPUT),
+        _ => {
+            // stops writing to the log, which is then closed
+            capture.abort();
+            String::new()
+        }
+    }
+}
+
+/// Send `SIGTERM` to the process group of `child`, and `SIGKILL` to what
+/// is left of it after the grace period. Processes of the group may outlive
+/// `sh`, and hold its output open.
+async fn terminate(run: &Assignment, child: &mut Child) {
+    let Some(pid) = child.id() else {
+        return;
+    };
+
+    signal(run, pid, "TERM").await;
+
+    let exited = time::timeout(Duration::from_secs(GRACE_PERIOD), async {
+        while !matches!(child.try_wait(), Ok(Some(_))) || group_alive(pid).await {
+            time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .is_ok();
+
+    if !exited {
+        signal(run, pid, "KILL").await;
+
+        if let Err(error) = child.wait().await {
+            eprintln!("run {}: {}", run.run_id, error);
+        }
+    }
+}
+
+/// Send `signal` to the process group `pgid`
+async fn signal(run: &Assignment, pgid: u32, signal: &str) {
+    let status = Command::new("kill")
+        .args([&format!("-{}", signal), "--", &format!("-{}", pgid)])
+        .stderr(Stdio::null())
+        .status()
+        .await;
+
+    if let Err(error) = status {
+        eprintln!("run {}: {}", run.run_id, error);
+    }
+}
+
+/// Whether any process of the group `pgid` is still running
+async fn group_alive(pgid: u32) -> bool {
+    Command::new("kill")
+        .args(["-0", "--", &format!("-{}", pgid)])
+        .stderr(Stdio::null())
+        .status()
+        .await
+        .is_ok_and(|status| status.success())
+}
@@ -0,0 +1,140 @@
+//! HTTP Request Execution
+
+extern crate reqwest;
+extern crate schedin_common;
+extern crate serde_json;
+extern crate serde_json_path;
+extern crate std;
+extern crate tokio;
+
+use crate::{
+    job::Payload,
+    run::{truncate, Assignment, Outcome, Response},
+};
+use reqwest::{Client, Method};
+use schedin_common::secret::Redacted;
+use serde_json::Value;
+use serde_json_path::JsonPath;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tokio::sync::oneshot;
+
+/// Bytes of the response body stored with the run
+const STORED_BODY: usize = 4 * 1024;
+
+/// Bytes of the response body read for the assertion
+const MAX_BODY: usize = 1024 * 1024;
+
+/// # Request
+/// Send the request of an http job with its resolved headers.
+///
+/// The run succeeds if the response status is expected (any 2xx by default)
+/// and, with an assertion, the JSON body has at least one node matching it.
+/// The request is bounded by the shorter of the request and run timeouts,
+/// and abandoned once `preempt` fires.
+pub async fn request(
+    run: &Assignment,
+    payload: &Payload,
+    headers: HashMap<String, Redacted<String>>,
+    preempt: oneshot::Receiver<()>,
+) -> Outcome {
+    let (Some(method), Some(url)) = (&payload.method, &payload.url) else {
+        return Outcome::failed("Http job is missing its method or URL");
+    };
+
+    let method = match Method::from_bytes(method.as_bytes()) {
+        Ok(method) => method,
+        Err(_) => return Outcome::failed(format!("Invalid method '{}'", method)),
+    };
+
+    let timeout = [payload.http_timeout, run.timeout]
+        .into_iter()
+        .flatten()
+        .min();
+
+    let mut client = Client::builder();
+    if let Some(timeout) = timeout {
+        client = client.timeout(Duration::from_secs(timeout as u64));
+    }
+
+    let client = match client.build() {
+        Ok(client) => client,
+        Err(error) => return Outcome::failed(error),
+    };
+
+    let mut request = client.request(method, url);
+    for (name, value) in &headers {
+        request = request.header(name, value.expose());
+    }
+    if let Some(body) = &payload.http_body {
+        request = request.body(body.clone());
+    }
+
+    let started = Instant::now();
+
+    let (status, body) = tokio::select! {
+        result = send(request) => match result {
+            Ok(response) => response,
+            Err(error) if error.is_timeout() => {
+                return Outcome::timed_out(timeout.unwrap_or_default())
+            }
+            Err(error) => return Outcome::failed(error),
+        },
+        Ok(_) = preempt => return Outcome::preempted(),
+    };
+
+    let response = Response {
+        status: status as i32,
+        latency_ms: started.elapsed().as_millis().min(i32::MAX as u128) as i32,
+        body: truncate(&body, STORED_BODY),
+    };
+
+    let expected = match &payload.expected_status {
+        Some(codes) if !codes.is_empty() => codes.contains(&(status as i32)),
+        _ => (200..300).contains(&status),
+    };
+
+    if !expected {
+        return Outcome::responded(response, Some(format!("Unexpected status {}", status)));
+    }
+
+    let error = payload
+        .assertion
+        .as_ref()
+        .and_then(|assertion| assert(assertion, &body).err());
+
+    Outcome::responded(response, error)
+}
+
+/// Send the request, returns the status and the body, up to `MAX_BODY` bytes
+async fn send(request: reqwest::RequestBuilder) -> Result<(u16, Vec<u8>), reqwest::Error> {
+    let mut response = request.send().await?;
+    let status = response.status().as_u16();
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        let room = MAX_BODY - body.len();
+        body.extend_from_slice(&chunk[..chunk.len().min(room)]);
+        if chunk.len() >= room {
+            break;
+        }
+    }
+
+    Ok((status, body))
+}
+
+/// Whether the JSON `body` has a node matching the JSONPath `assertion`
+fn assert(assertion: &str, body: &[u8]) -> Result<(), String> {
+    let path =
+        JsonPath::parse(assertion).map_err(|_| format!("Invalid assertion '{}'", assertion))?;
+
+    let value: Value = serde_json::from_slice(body)
+        .map_err(|_| "Response body is not JSON, or too large".to_string())?;
+
+    match path.query(&value).is_empty() {
+        true => Err(format!("Assertion '{}' matched nothing", assertion)),
+        false => Ok(()),
+    }
+}
@@ -0,0 +1,143 @@
+//! Job
+
+extern crate serde;
+extern crate sqlx;
+extern crate std;
+
+use serde::Deserialize;
+use sqlx::types::{Json, Uuid};
+use std::collections::HashMap;
+
+/// Executable payload of a job, along with its resource limits
+#[derive(Debug)]
+pub struct Payload {
+    pub job_id: Uuid,
+    pub job_type: JobType,
+    pub src: Option<String>,
+    pub code_cmd: Option<String>,
+    pub path: Option<String>,
+    pub bin_cmd: Option<String>,
+    pub method: Option<String>,
+    pub url: Option<String>,
+    pub http_body: Option<String>,
+    /// Request timeout (in seconds)
+    pub http_timeout: Option<i32>,
+    pub expected_status: Option<Vec<i32>>,
+    pub assertion: Option<String>,
+    pub statements: Option<Vec<String>>,
+    /// Statement timeout (in seconds)
+    pub statement_timeout: Option<i32>,
+    /// Binary WASI module
+    pub module: Option<Vec<u8>>,
+    pub args: Option<Vec<String>>,
+    /// Instructions budget of the module
+    pub fuel: Option<i64>,
+    /// Steps of a pipeline
+    pub steps: Option<Json<Vec<Step>>>,
+    /// Globs of the files collected from the work directory
+    pub artifacts: Option<Vec<String>>,
+    /// CPU limit in millicores
+    pub limit_cpu: Option<i32>,
+    pub limit_memory_mb: Option<i32>,
+    pub limit_disk_mb: Option<i32>,
+}
+
+// Job Type
+#[derive(Debug, sqlx::types::Type)]
+#[sqlx(type_name = "job_types", rename_all = "lowercase")]
+pub enum JobType {
+    Bin,
+    Code,
+    Http,
+    Invalid,
+    Pipeline,
+    Sql,
+    Task,
+    Wasm,
+}
+
+impl Payload {
+    /// Payload of a pipeline step, with the resource limits of the pipeline
+    pub fn step(&self, step: &Step) -> Self {
+        let job_type = match (&step.code, &step.bin, &step.http) {
+            (Some(_), ..) => JobType::Code,
+            (_, Some(_), _) => JobType::Bin,
+            (.., Some(_)) => JobType::Http,
+            _ => JobType::Task,
+        };
+
+        Self {
+            job_id: self.job_id,
+            job_type,
+            src: step.code.as_ref().map(|code| code.src.clone()),
+            code_cmd: step.code.as_ref().map(|code| code.cmd.clone()),
+            path: step.bin.as_ref().map(|bin| bin.path.clone()),
+            bin_cmd: step.bin.as_ref().and_then(|bin| bin.cmd.clone()),
+            method: step.http.as_ref().map(|http| http.method.clone()),
+            url: step.http.as_ref().map(|http| http.url.clone()),
+            http_body: step.http.as_ref().and_then(|http| http.body.clone()),
+            http_timeout: step.http.as_ref().and_then(|http| http.timeout),
+            expected_status: step
+                .http
+                .as_ref()
+                .and_then(|http| http.expected_status.clone()),
+            assertion: step.http.as_ref().and_then(|http| http.assertion.clone()),
+            statements: None,
+            statement_timeout: None,
+            module: None,
+            args: None,
+            fuel: None,
+            steps: None,
+            artifacts: None,
+            limit_cpu: self.limit_cpu,
+            limit_memory_mb: self.limit_memory_mb,
+            limit_disk_mb: self.limit_disk_mb,
+        }
+    }
+}
+
+/// Step of a pipeline, as stored by the server. Steps without
+/// `code`, `bin` or `http` are tasks.
+#[derive(Debug, Deserialize)]
+pub struct Step {
+    pub name: String,
+    pub code: Option<StepCode>,
+    pub bin: Option<StepBin>,
+    pub http: Option<StepHttp>,
+    /// Step timeout (in seconds)
+    pub timeout: Option<i32>,
+    #[serde(default)]
+    pub continue_on_error: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StepCode {
+    pub src: String,
+    pub cmd: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StepBin {
+    pub path: String,
+    pub cmd: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StepHttp {
+    pub method: String,
+    pub url: String,
+    pub headers: Option<HashMap<String, Header>>,
+    pub body: Option<String>,
+    /// Request timeout (in seconds)
+    pub timeout: Option<i32>,
+    pub expected_status: Option<Vec<i32>>,
+    pub assertion: Option<String>,
+}
+
+/// Header value of an http step, either plain or the name of a secret
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Header {
+    Value(String),
+    Secret { secret: String },
+}
@@ -0,0 +1,121 @@
+//! Run Logs
+//!
+//! Stdout and stderr are written to the log of the run as they are read,
+//! in chunks tagged with their stream and the time they were read at.
+//! Chunks are numbered by their byte offset in the output of the run, so
+//! that followers can resume from any offset.
+
+extern crate sqlx;
+extern crate std;
+extern crate tokio;
+
+use crate::db::DB;
+use sqlx::types::{time::OffsetDateTime, Uuid};
+use tokio::{sync::mpsc, task::JoinHandle};
+
+/// Bytes of output kept in the log of a run, later output is dropped
+pub const MAX_LOG: i64 = 16 * 1024 * 1024;
+
+/// Chunks written at once
+const BATCH: usize = 64;
+
+/// Stream a chunk was read from
+#[derive(Debug, Clone, Copy)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Stream {
+    /// Name of the stream, as the `log_stream` type
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+        }
+    }
+}
+
+/// Output read at once from a stream
+#[derive(Debug)]
+pub struct Chunk {
+    pub stream: Stream,
+    pub data: Vec<u8>,
+    pub logged_at: OffsetDateTime,
+}
+
+/// # Log
+/// Handle to the log of a run, shared by everything that produces its output
+#[derive(Debug, Clone)]
+pub struct Log {
+    sender: mpsc::UnboundedSender<Chunk>,
+}
+
+impl Log {
+    /// # Open
+    /// Open the log of `run_id`, discarding the output of a previous attempt.
+    /// Chunks are written in the background until every handle is dropped,
+    /// the returned task completes once all of them are written.
+    pub fn open(db: DB, run_id: Uuid) -> (Self, JoinHandle<()>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let writer = tokio::spawn(write(db, run_id, receiver));
+
+        (Self { sender }, writer)
+    }
+
+    /// Append `data` read from `stream`
+    pub fn append(&self, stream: Stream, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+
+        // the writer only stops once every handle is dropped
+        let _ = self.sender.send(Chunk {
+            stream,
+            data: data.to_vec(),
+            logged_at: OffsetDateTime::now_utc(),
+        });
+    }
+}
+
+/// Write the chunks of `receiver` to the log of `run_id`, in batches
+async fn write(db: DB, run_id: Uuid, mut receiver: mpsc::UnboundedReceiver<Chunk>) {
+    if let Err(error) = db.clear_log(&run_id).await {
+        eprintln!("run {}: {}", run_id, error.reason());
+    }
+
+    let mut position = 0;
+    let mut batch = Vec::with_capacity(BATCH);
+
+    while receiver.recv_many(&mut batch, BATCH).await > 0 {
+        let room = (MAX_LOG - position).max(0) as usize;
+        let mut kept = 0;
+
+        for chunk in batch.iter_mut() {
+            chunk.data.truncate(room - kept);
+            kept += chunk.data.len();
+        }
+        batch.retain(|chunk| !chunk.data.is_empty());
+
+        if !batch.is_empty() {
+            if let Err(error) = db.append_log(&run_id, position, &batch).await {
+                eprintln!("run {}: {}", run_id, error.reason());
+            }
+        }
+
+        position += kept as i64;
+        batch.clear();
+    }
+}
+
+/// Length of the longest prefix of `data` that does not end within a
+/// UTF-8 character, so that chunks of text are not cut in the middle of one
+pub fn boundary(data: &[u8]) -> usize {
+    match std::str::from_utf8(data) {
+        Ok(_) => data.len(),
+        // incomplete character at the end, kept for the next chunk
+        Err(error) if error.error_len().is_none() => error.valid_up_to(),
+        // invalid byte, not text
+        Err(_) => data.len(),
+    }
+}
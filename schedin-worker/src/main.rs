@@ -1,3 +1,195 @@
-fn main() {
-    println!("Hello! Schedin Worker.");
+//! Worker
+
+extern crate schedin_common;
+extern crate sqlx;
+extern crate std;
+extern crate tokio;
+
+mod artifact;
+mod db;
+mod exec;
+mod http;
+mod job;
+mod log;
+mod pipeline;
+mod run;
+mod sql;
+mod wasm;
+mod worker;
+
+use db::DB;
+use job::{JobType, Payload};
+use log::Log;
+use run::{Assignment, Outcome};
+use schedin_common::{
+    db::create_pool,
+    secret::{self, Cipher, Redacted},
+};
+use sqlx::{types::Uuid, Postgres};
+use std::{
+    collections::HashMap,
+    env, fs,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::oneshot;
+use worker::Capacity;
+
+/// Preemption signals of the runs executing on the worker
+type Running = Arc<Mutex<HashMap<Uuid, oneshot::Sender<()>>>>;
+
+#[tokio::main]
+async fn main() {
+    let cipher = match Cipher::from_env() {
+        Ok(cipher) => Arc::new(cipher),
+        Err(error) => {
+            eprintln!("{}: {}", secret::MASTER_KEY_VAR, error.reason());
+            std::process::exit(1);
+        }
+    };
+
+    let pool = create_pool::<Postgres>(&env::var("DATABASE_URL").unwrap())
+        .await
+        .unwrap();
+
+    let db = DB::new(pool);
+
+    let capacity = Capacity::from_env();
+    let worker_id = db.register(&capacity).await.unwrap();
+    println!("worker {} registered: {:?}", worker_id, capacity);
+
+    let running = Running::default();
+
+    loop {
+        if let Err(error) = db.heartbeat(&worker_id).await {
+            eprintln!("heartbeat: {}", error.reason());
+        }
+
+        if let Ok(runs) = db.claim(&worker_id).await {
+            for run in runs {
+                let (preempt, preempted) = oneshot::channel();
+                running.lock().unwrap().insert(run.run_id, preempt);
+                tokio::spawn(execute(
+                    db.clone(),
+                    cipher.clone(),
+                    running.clone(),
+                    run,
+                    preempted,
+                ));
+            }
+        }
+
+        if let Ok(runs) = db.preempted(&worker_id).await {
+            for run_id in runs {
+                // signalled once, the entry is gone until the run is claimed again
+                if let Some(preempt) = running.lock().unwrap().remove(&run_id) {
+                    println!("run {} preempted", run_id);
+                    let _ = preempt.send(());
+                }
+            }
+        }
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Execute a claimed run and record its outcome
+async fn execute(
+    db: DB,
+    cipher: Arc<Cipher>,
+    running: Running,
+    run: Assignment,
+    preempt: oneshot::Receiver<()>,
+) {
+    println!("run {} job {} started", run.run_id, run.job_id);
+
+    let (log, writer) = Log::open(db.clone(), run.run_id);
+
+    let outcome = match db.payload(&run.job_id).await {
+        Ok(payload) => {
+            let mut outcome = dispatch(&db, &cipher, &run, &payload, &log, preempt).await;
+
+            // preempted runs start over, their files are not collected
+            if let (false, Some(patterns)) = (outcome.preempted, &payload.artifacts) {
+                if let Err(error) = artifact::collect(&db, &run, patterns).await {
+                    outcome.succeeded = false;
+                    outcome.error.get_or_insert(error);
+                }
+            }
+
+            let workdir = run::workdir(&run.run_id);
+            if workdir.exists() {
+                if let Err(error) = fs::remove_dir_all(&workdir) {
+                    eprintln!("run {}: {}", run.run_id, error);
+                }
+            }
+
+            outcome
+        }
+        Err(error) => Outcome::failed(error.reason()),
+    };
+
+    // the log is complete before the run is, followers stop once it finished
+    drop(log);
+    if let Err(error) = writer.await {
+        eprintln!("run {}: {}", run.run_id, error);
+    }
+
+    println!("run {} finished: {:?}", run.run_id, outcome);
+    running.lock().unwrap().remove(&run.run_id);
+
+    if let Err(error) = db.finish(&run.run_id, &outcome).await {
+        eprintln!("run {}: {}", run.run_id, error.reason());
+    }
+}
+
+/// Execute the payload of a run according to its job type
+async fn dispatch(
+    db: &DB,
+    cipher: &Cipher,
+    run: &Assignment,
+    payload: &Payload,
+    log: &Log,
+    preempt: oneshot::Receiver<()>,
+) -> Outcome {
+    // secrets are decrypted only on the worker, `Redacted` keeps them out of the logs
+    match payload.job_type {
+        JobType::Http => match db.headers(&run.job_id, cipher).await {
+            Ok(headers) => http::request(run, payload, headers, preempt).await,
+            Err(error) => Outcome::failed(error.reason()),
+        },
+        JobType::Sql => match db.connection(&run.job_id, cipher).await {
+            Ok(dsn) => sql::query(run, payload, dsn, preempt).await,
+            Err(error) => Outcome::failed(error.reason()),
+        },
+        JobType::Wasm => match db.env(&run.job_id, cipher).await {
+            Ok(env) => wasm::run(run, payload, with_matrix(run, env), log, preempt).await,
+            Err(error) => Outcome::failed(error.reason()),
+        },
+        JobType::Pipeline => {
+            match (
+                db.env(&run.job_id, cipher).await,
+                db.step_secrets(&run.job_id, cipher).await,
+            ) {
+                (Ok(env), Ok(secrets)) => {
+                    let env = with_matrix(run, env);
+                    pipeline::run(db, run, payload, env, secrets, log, preempt).await
+                }
+                (Err(error), _) | (_, Err(error)) => Outcome::failed(error.reason()),
+            }
+        }
+        _ => match db.env(&run.job_id, cipher).await {
+            Ok(env) => exec::execute(run, payload, with_matrix(run, env), log, preempt).await,
+            Err(error) => Outcome::failed(error.reason()),
+        },
+    }
+}
+
+/// Environment of a run, along with its matrix values
+fn with_matrix(
+    run: &Assignment,
+    mut env: HashMap<String, Redacted<String>>,
+) -> HashMap<String, Redacted<String>> {
+    env.extend(run.matrix_env());
+    env
 }
@@ -0,0 +1,168 @@
+//! Pipeline Execution
+
+extern crate futures;
+extern crate schedin_common;
+extern crate std;
+extern crate tokio;
+
+use crate::{
+    db::DB,
+    exec, http,
+    job::{Header, JobType, Payload, Step},
+    log::Log,
+    run::{self, Assignment, Outcome},
+};
+use futures::FutureExt;
+use schedin_common::secret::Redacted;
+use std::{
+    collections::HashMap,
+    fs,
+    time::{Duration, Instant},
+};
+use tokio::sync::oneshot;
+
+/// # Run
+/// Run the steps of a pipeline job in order, in the work directory they share,
+/// with the environment of the job.
+///
+/// Every step is recorded under the run as it starts and finishes, and
+/// writes its output to the log of the run. A failed
+/// step fails the run and the next steps are skipped, unless it continues on
+/// error. Steps are bounded by their own timeout and by what is left of the
+/// run timeout.
+///
+/// ## Preemption
+///
+/// Once `preempt` fires, the current step is preempted and the run queued
+/// again. It restarts from its first step.
+pub async fn run(
+    db: &DB,
+    run: &Assignment,
+    payload: &Payload,
+    env: HashMap<String, Redacted<String>>,
+    secrets: HashMap<String, Redacted<String>>,
+    log: &Log,
+    preempt: oneshot::Receiver<()>,
+) -> Outcome {
+    let Some(steps) = &payload.steps else {
+        return Outcome::failed("Pipeline job is missing its steps");
+    };
+
+    if let Err(error) = db.clear_steps(&run.run_id).await {
+        return Outcome::failed(error.reason());
+    }
+
+    let workdir = run::workdir(&run.run_id);
+
+    if let Err(error) = fs::create_dir_all(&workdir) {
+        return Outcome::failed(format!("Unable to create work directory: {}", error));
+    }
+
+    let deadline = run
+        .timeout
+        .map(|timeout| Instant::now() + Duration::from_secs(timeout as u64));
+
+    let mut preempt = preempt.fuse();
+    let mut failure = None;
+
+    for (position, step) in steps.iter().enumerate() {
+        if failure.is_some() {
+            if let Err(error) = db.start_step(&run.run_id, position, &step.name, true).await {
+                eprintln!("run {}: {}", run.run_id, error.reason());
+            }
+            continue;
+        }
+
+        if let Err(error) = db
+            .start_step(&run.run_id, position, &step.name, false)
+            .await
+        {
+            eprintln!("run {}: {}", run.run_id, error.reason());
+        }
+
+        let remaining = deadline.map(|deadline| {
+            // a step started past the deadline still gets a second, and times out
+            deadline
+                .saturating_duration_since(Instant::now())
+                .as_secs()
+                .max(1) as i32
+        });
+
+        let assignment = Assignment {
+            run_id: run.run_id,
+            job_id: run.job_id,
+            timeout: [step.timeout, remaining].into_iter().flatten().min(),
+            combination: None,
+        };
+
+        let (signal, signalled) = oneshot::channel();
+        let execution = execute(&assignment, payload, step, &env, &secrets, log, signalled);
+        tokio::pin!(execution);
+
+        let outcome = tokio::select! {
+            outcome = &mut execution => outcome,
+            Ok(_) = &mut preempt => {
+                let _ = signal.send(());
+                execution.await
+            }
+        };
+
+        if outcome.preempted {
+            return outcome;
+        }
+
+        if let Err(error) = db.finish_step(&run.run_id, position, &outcome).await {
+            eprintln!("run {}: {}", run.run_id, error.reason());
+        }
+
+        if !outcome.succeeded && !step.continue_on_error {
+            failure = Some(Outcome {
+                timed_out: outcome.timed_out,
+                ..Outcome::failed(format!(
+                    "Step '{}' failed: {}",
+                    step.name,
+                    outcome.error.unwrap_or_default()
+                ))
+            });
+        }
+    }
+
+    failure.unwrap_or_else(|| Outcome::exited(Some(0)))
+}
+
+/// Execute a step in the work directory of the run, http steps send their
+/// request with the resolved headers
+async fn execute(
+    run: &Assignment,
+    payload: &Payload,
+    step: &Step,
+    env: &HashMap<String, Redacted<String>>,
+    secrets: &HashMap<String, Redacted<String>>,
+    log: &Log,
+    preempt: oneshot::Receiver<()>,
+) -> Outcome {
+    let payload = payload.step(step);
+
+    if !matches!(payload.job_type, JobType::Http) {
+        let workdir = run::workdir(&run.run_id);
+        return exec::step(run, &payload, &workdir, env.clone(), log, preempt).await;
+    }
+
+    let mut headers = HashMap::new();
+    for (name, value) in step
+        .http
+        .iter()
+        .flat_map(|http| http.headers.iter().flatten())
+    {
+        let value = match value {
+            Header::Value(value) => Redacted::new(value.clone()),
+            Header::Secret { secret } => match secrets.get(secret) {
+                Some(value) => value.clone(),
+                None => return Outcome::failed(format!("Unknown secret '{}'", secret)),
+            },
+        };
+        headers.insert(name.clone(), value);
+    }
+
+    http::request(run, &payload, headers, preempt).await
+}
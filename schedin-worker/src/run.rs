@@ -0,0 +1,186 @@
+//! Run
+
+extern crate schedin_common;
+extern crate serde_json;
+extern crate sqlx;
+extern crate std;
+
+use schedin_common::secret::Redacted;
+use serde_json::Value;
+use sqlx::types::{Json, Uuid};
+use std::{collections::HashMap, env, path::PathBuf};
+
+/// Bytes of stdout and stderr stored with the run
+pub const STORED_OUTPUT: usize = 4 * 1024;
+
+/// Work directory of the run `run_id`, removed once the run completed
+pub fn workdir(run_id: &Uuid) -> PathBuf {
+    env::temp_dir().join("schedin").join(run_id.to_string())
+}
+
+/// Run claimed by this worker
+#[derive(Debug, sqlx::FromRow)]
+pub struct Assignment {
+    pub run_id: Uuid,
+    pub job_id: Uuid,
+    /// Timeout (in seconds), capped by the project quota
+    pub timeout: Option<i32>,
+    /// Values of the matrix combination of a child run, by axis
+    pub combination: Option<Json<HashMap<String, String>>>,
+}
+
+impl Assignment {
+    /// Matrix values of the run, as `SCHEDIN_MATRIX_<AXIS>` env vars
+    pub fn matrix_env(&self) -> impl Iterator<Item = (String, Redacted<String>)> + '_ {
+        self.combination.iter().flat_map(|combination| {
+            combination.iter().map(|(axis, value)| {
+                (
+                    format!("SCHEDIN_MATRIX_{}", axis.to_ascii_uppercase()),
+                    Redacted::new(value.clone()),
+                )
+            })
+        })
+    }
+}
+
+/// Result of a run
+#[derive(Debug)]
+pub struct Outcome {
+    pub succeeded: bool,
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+    /// Terminated to make room for a run of higher priority
+    pub preempted: bool,
+    /// Exceeded its timeout
+    pub timed_out: bool,
+    /// Response of http runs
+    pub response: Option<Response>,
+    /// Result of sql runs
+    pub result: Option<QueryResult>,
+    /// Captured output of code, bin and wasm runs
+    pub output: Option<Output>,
+}
+
+/// Captured output of a code, bin or wasm run
+#[derive(Debug)]
+pub struct Output {
+    /// Standard output, truncated
+    pub stdout: String,
+    /// Standard error, truncated
+    pub stderr: String,
+}
+
+/// Result of a sql run
+#[derive(Debug)]
+pub struct QueryResult {
+    /// Rows affected, or returned by queries, over all statements
+    pub rows_affected: i64,
+    /// Columns and first rows returned by each statement
+    pub preview: Value,
+}
+
+/// Response of an http run
+#[derive(Debug)]
+pub struct Response {
+    pub status: i32,
+    pub latency_ms: i32,
+    /// Body, truncated
+    pub body: String,
+}
+
+impl Outcome {
+    /// Outcome of an exited process
+    pub fn exited(exit_code: Option<i32>) -> Self {
+        Self {
+            succeeded: exit_code == Some(0),
+            exit_code,
+            error: match exit_code {
+                Some(0) => None,
+                Some(code) => Some(format!("Exited with code {}", code)),
+                None => Some("Terminated by a signal".to_string()),
+            },
+            preempted: false,
+            timed_out: false,
+            response: None,
+            result: None,
+            output: None,
+        }
+    }
+
+    /// Outcome of a run that failed before or while executing
+    pub fn failed<S: ToString>(error: S) -> Self {
+        Self {
+            succeeded: false,
+            exit_code: None,
+            error: Some(error.to_string()),
+            preempted: false,
+            timed_out: false,
+            response: None,
+            result: None,
+            output: None,
+        }
+    }
+
+    /// Outcome of a run that exceeded its timeout of `seconds`
+    pub fn timed_out(seconds: i32) -> Self {
+        Self {
+            timed_out: true,
+            ..Self::failed(format!("Timed out after {}s", seconds))
+        }
+    }
+
+    /// Outcome of a preempted run, which is queued again
+    pub fn preempted() -> Self {
+        Self {
+            succeeded: false,
+            exit_code: None,
+            error: None,
+            preempted: true,
+            timed_out: false,
+            response: None,
+            result: None,
+            output: None,
+        }
+    }
+
+    /// Outcome of an http run, failed with `error` if the response is not the expected one
+    pub fn responded(response: Response, error: Option<String>) -> Self {
+        Self {
+            succeeded: error.is_none(),
+            exit_code: None,
+            error,
+            preempted: false,
+            timed_out: false,
+            response: Some(response),
+            result: None,
+            output: None,
+        }
+    }
+
+    /// Outcome of a sql run whose transaction committed
+    pub fn queried(result: QueryResult) -> Self {
+        Self {
+            succeeded: true,
+            exit_code: None,
+            error: None,
+            preempted: false,
+            timed_out: false,
+            response: None,
+            result: Some(result),
+            output: None,
+        }
+    }
+
+    /// Outcome of a run, along with its captured output
+    pub fn with_output(mut self, output: Output) -> Self {
+        self.output = Some(output);
+        self
+    }
+}
+
+/// First `max` bytes of `output` as text, cut at a character boundary
+pub fn truncate(output: &[u8], max: usize) -> String {
+    let text = String::from_utf8_lossy(&output[..output.len().min(max)]);
+    text.trim_end_matches(char::REPLACEMENT_CHARACTER)
+        .to_string()
+}
@@ -0,0 +1,160 @@
+//! SQL Statement Execution
+
+extern crate futures;
+extern crate schedin_common;
+extern crate serde_json;
+extern crate sqlx;
+extern crate std;
+extern crate tokio;
+
+use crate::{
+    job::Payload,
+    run::{Assignment, Outcome, QueryResult},
+};
+use futures::TryStreamExt;
+use schedin_common::secret::Redacted;
+use serde_json::{json, Value};
+use sqlx::{postgres::PgRow, Column, Connection, Either, Executor, PgConnection, Row, ValueRef};
+use std::{future, time::Duration};
+use tokio::{sync::oneshot, time};
+
+/// Rows previewed per statement
+const PREVIEW_ROWS: usize = 20;
+
+/// Characters previewed per value
+const PREVIEW_VALUE: usize = 256;
+
+/// # Query
+/// Run the statements of a sql job in a single transaction, against the
+/// PostgreSQL database at `dsn`.
+///
+/// Statements are sent over the simple query protocol, so values are previewed
+/// as text whatever their type. The statement timeout applies to every
+/// statement, the run timeout to the whole transaction. Any error, timeout or
+/// preemption rolls the transaction back.
+pub async fn query(
+    run: &Assignment,
+    payload: &Payload,
+    dsn: Redacted<String>,
+    preempt: oneshot::Receiver<()>,
+) -> Outcome {
+    let Some(statements) = &payload.statements else {
+        return Outcome::failed("Sql job is missing its statements");
+    };
+
+    let timeout = async {
+        match run.timeout {
+            Some(timeout) => time::sleep(Duration::from_secs(timeout as u64)).await,
+            None => future::pending().await,
+        }
+    };
+
+    tokio::select! {
+        result = transaction(dsn.expose(), statements, payload.statement_timeout) => match result {
+            Ok(result) => Outcome::queried(result),
+            Err(error) => Outcome::failed(error),
+        },
+        _ = timeout => Outcome::timed_out(run.timeout.unwrap_or_default()),
+        Ok(_) = preempt => Outcome::preempted(),
+    }
+}
+
+async fn transaction(
+    dsn: &str,
+    statements: &[String],
+    statement_timeout: Option<i32>,
+) -> Result<QueryResult, String> {
+    // the error may echo the DSN, which holds the credentials
+    let mut connection = PgConnection::connect(dsn)
+        .await
+        .map_err(|_| "Unable to connect to the database".to_string())?;
+
+    let mut tx = connection
+        .begin()
+        .await
+        .map_err(|error| error.to_string())?;
+
+    if let Some(timeout) = statement_timeout {
+        tx.execute(format!("SET LOCAL statement_timeout = {}", timeout as i64 * 1000).as_str())
+            .await
+            .map_err(|error| error.to_string())?;
+    }
+
+    let mut rows_affected = 0;
+    let mut previews = Vec::with_capacity(statements.len());
+
+    for (index, statement) in statements.iter().enumerate() {
+        let mut preview = Preview::default();
+        let mut results = tx.fetch_many(statement.as_str());
+
+        while let Some(result) = results
+            .try_next()
+            .await
+            .map_err(|error| format!("Statement {}: {}", index + 1, error))?
+        {
+            match result {
+                Either::Left(done) => preview.rows_affected += done.rows_affected(),
+                Either::Right(row) => preview.push(&row),
+            }
+        }
+
+        rows_affected += preview.rows_affected;
+        previews.push(preview.into_json(index + 1));
+    }
+
+    tx.commit().await.map_err(|error| error.to_string())?;
+
+    Ok(QueryResult {
+        rows_affected: rows_affected as i64,
+        preview: Value::Array(previews),
+    })
+}
+
+/// Columns and first rows returned by a statement
+#[derive(Default)]
+struct Preview {
+    rows_affected: u64,
+    columns: Vec<String>,
+    rows: Vec<Vec<Value>>,
+    truncated: bool,
+}
+
+impl Preview {
+    fn push(&mut self, row: &PgRow) {
+        if self.columns.is_empty() {
+            self.columns = row
+                .columns()
+                .iter()
+                .map(|column| column.name().to_string())
+                .collect();
+        }
+
+        if self.rows.len() == PREVIEW_ROWS {
+            self.truncated = true;
+            return;
+        }
+
+        self.rows.push(
+            (0..row.len())
+                .map(|index| match row.try_get_raw(index) {
+                    Ok(value) if value.is_null() => Value::Null,
+                    Ok(value) => value
+                        .as_str()
+                        .map(|text| Value::String(text.chars().take(PREVIEW_VALUE).collect()))
+                        .unwrap_or(Value::Null),
+                    Err(_) => Value::Null,
+                })
+                .collect(),
+        );
+    }
+
+    fn into_json(self, statement: usize) -> Value {
+        json!({
+            "statement": statement,
+            "rows_affected": self.rows_affected,
+            "columns": self.columns,
+            "rows": self.rows,
+            "truncated": self.truncated,
+        })
+    }
+}
@@ -0,0 +1,205 @@
+//! WebAssembly Module Execution
+
+extern crate schedin_common;
+extern crate std;
+extern crate tokio;
+extern crate wasmtime;
+extern crate wasmtime_wasi;
+
+use crate::{
+    job::Payload,
+    log::{Log, Stream},
+    run::{self, truncate, Assignment, Outcome, Output, STORED_OUTPUT},
+};
+use schedin_common::secret::Redacted;
+use std::{collections::HashMap, fs, future, path::Path, sync::OnceLock, thread, time::Duration};
+use tokio::{sync::oneshot, task, time};
+use wasmtime::{Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, Trap};
+use wasmtime_wasi::{
+    pipe::MemoryOutputPipe,
+    preview1::{self, WasiP1Ctx},
+    DirPerms, FilePerms, I32Exit, WasiCtxBuilder,
+};
+
+/// Bytes of stdout and stderr captured, the module traps past it
+const MAX_OUTPUT: usize = 1024 * 1024;
+
+/// Memory (in MiB) of modules without a memory limit
+const DEFAULT_MEMORY_MB: i32 = 256;
+
+/// Interval between epoch ticks, at which modules yield to the worker
+const EPOCH_TICK: Duration = Duration::from_millis(100);
+
+/// Engine shared by the runs of the worker
+static ENGINE: OnceLock<Engine> = OnceLock::new();
+
+/// Store state of a running module
+struct State {
+    wasi: WasiP1Ctx,
+    limits: StoreLimits,
+}
+
+/// # Run
+/// Run the WASI module of a wasm job in an embedded runtime, with its
+/// arguments and environment.
+///
+/// ## Sandbox
+///
+/// The module only sees a scratch directory, preopened as `.`, and has no
+/// network access. Its stdout and stderr are captured, written to the log
+/// of the run once it exits, and stored truncated with the run.
+///
+/// ## Limits
+///
+/// The memory limit caps the linear memories of the module, 256 MiB by
+/// default. Executed instructions consume the job fuel, the module traps
+/// once it runs out. The module yields at every epoch tick, so that runs
+/// exceeding their timeout or preempted are interrupted in between.
+pub async fn run(
+    run: &Assignment,
+    payload: &Payload,
+    env: HashMap<String, Redacted<String>>,
+    log: &Log,
+    preempt: oneshot::Receiver<()>,
+) -> Outcome {
+    let Some(module) = &payload.module else {
+        return Outcome::failed("Wasm job is missing its module");
+    };
+
+    let workdir = run::workdir(&run.run_id);
+
+    if let Err(error) = fs::create_dir_all(&workdir) {
+        return Outcome::failed(format!("Unable to create work directory: {}", error));
+    }
+
+    let stdout = MemoryOutputPipe::new(MAX_OUTPUT);
+    let stderr = MemoryOutputPipe::new(MAX_OUTPUT);
+
+    let mut wasi = WasiCtxBuilder::new();
+    wasi.stdout(stdout.clone())
+        .stderr(stderr.clone())
+        .arg("main.wasm")
+        .args(payload.args.as_deref().unwrap_or_default())
+        .envs(
+            &env.iter()
+                .map(|(name, value)| (name.as_str(), value.expose().as_str()))
+                .collect::<Vec<_>>(),
+        )
+        .env("SCHEDIN_RUN_ID", run.run_id.to_string())
+        .env("SCHEDIN_JOB_ID", payload.job_id.to_string());
+
+    let outcome = match sandbox(&mut wasi, &workdir) {
+        Ok(_) => instantiate(run, payload, module, wasi.build_p1(), preempt).await,
+        Err(error) => Outcome::failed(error),
+    };
+
+    let (stdout, stderr) = (stdout.contents(), stderr.contents());
+    log.append(Stream::Stdout, &stdout);
+    log.append(Stream::Stderr, &stderr);
+
+    outcome.with_output(Output {
+        stdout: truncate(&stdout, STORED_OUTPUT),
+        stderr: truncate(&stderr, STORED_OUTPUT),
+    })
+}
+
+/// Engine shared by the runs of the worker. Its epoch ticks on a dedicated
+/// thread, which a module spinning on the runtime threads cannot starve.
+fn engine() -> wasmtime::Result<&'static Engine> {
+    if let Some(engine) = ENGINE.get() {
+        return Ok(engine);
+    }
+
+    let mut config = Config::new();
+    config
+        .async_support(true)
+        .consume_fuel(true)
+        .epoch_interruption(true);
+    let engine = Engine::new(&config)?;
+
+    Ok(ENGINE.get_or_init(|| {
+        let ticking = engine.clone();
+        thread::spawn(move || loop {
+            thread::sleep(EPOCH_TICK);
+            ticking.increment_epoch();
+        });
+        engine
+    }))
+}
+
+/// Preopen `workdir`, the only directory the module has access to
+fn sandbox(wasi: &mut WasiCtxBuilder, workdir: &Path) -> Result<(), String> {
+    wasi.preopened_dir(workdir, ".", DirPerms::all(), FilePerms::all())
+        .map(|_| ())
+        .map_err(|error| format!("Unable to preopen work directory: {}", error))
+}
+
+async fn instantiate(
+    run: &Assignment,
+    payload: &Payload,
+    module: &[u8],
+    wasi: WasiP1Ctx,
+    preempt: oneshot::Receiver<()>,
+) -> Outcome {
+    let engine = match engine() {
+        Ok(engine) => engine,
+        Err(error) => return Outcome::failed(format!("{:#}", error)),
+    };
+
+    // compilation is CPU-bound, keep the other runs of the worker going
+    let module = match task::block_in_place(|| Module::new(engine, module)) {
+        Ok(module) => module,
+        Err(error) => return Outcome::failed(format!("Invalid module: {:#}", error)),
+    };
+
+    let mut linker: Linker<State> = Linker::new(engine);
+    if let Err(error) = preview1::add_to_linker_async(&mut linker, |state| &mut state.wasi) {
+        return Outcome::failed(format!("{:#}", error));
+    }
+
+    let memory_mb = payload.limit_memory_mb.unwrap_or(DEFAULT_MEMORY_MB);
+    let limits = StoreLimitsBuilder::new()
+        .memory_size(memory_mb as usize * 1024 * 1024)
+        .build();
+
+    let mut store = Store::new(engine, State { wasi, limits });
+    store.limiter(|state| &mut state.limits);
+    store.epoch_deadline_async_yield_and_update(1);
+
+    let fuel = payload.fuel.map_or(u64::MAX, |fuel| fuel as u64);
+    if let Err(error) = store.set_fuel(fuel) {
+        return Outcome::failed(format!("{:#}", error));
+    }
+
+    let start = match linker.instantiate_async(&mut store, &module).await {
+        Ok(instance) => instance.get_typed_func::<(), ()>(&mut store, "_start"),
+        Err(error) => return Outcome::failed(format!("Unable to instantiate: {:#}", error)),
+    };
+
+    let start = match start {
+        Ok(start) => start,
+        Err(_) => return Outcome::failed("Module must export a WASI '_start' function"),
+    };
+
+    let timeout = async {
+        match run.timeout {
+            Some(timeout) => time::sleep(Duration::from_secs(timeout as u64)).await,
+            None => future::pending().await,
+        }
+    };
+
+    // dropping the call between two ticks interrupts the module
+    tokio::select! {
+        result = start.call_async(&mut store, ()) => match result {
+            Ok(_) => Outcome::exited(Some(0)),
+            Err(error) => match (error.downcast_ref::<I32Exit>(), error.downcast_ref::<Trap>()) {
+                (Some(exit), _) => Outcome::exited(Some(exit.0)),
+                (_, Some(Trap::OutOfFuel)) => Outcome::failed("Ran out of fuel"),
+                (_, Some(trap)) => Outcome::failed(trap),
+                _ => Outcome::failed(format!("{:#}", error)),
+            },
+        },
+        _ = timeout => Outcome::timed_out(run.timeout.unwrap_or_default()),
+        Ok(_) = preempt => Outcome::preempted(),
+    }
+}
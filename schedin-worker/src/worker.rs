@@ -0,0 +1,79 @@
+//! Worker
+
+extern crate std;
+
+use std::{collections::HashMap, env, fs, thread};
+
+/// # Capacity
+/// Resources advertised by the worker, read from the environment.
+///
+/// - `WORKER_NAME`: Unique worker name (default: `HOSTNAME`).
+/// - `WORKER_CPU`: CPU cores (default: available parallelism).
+/// - `WORKER_MEMORY_MB`: Memory in MB (default: total memory).
+/// - `WORKER_DISK_MB`: Disk in MB (default: 10240).
+/// - `WORKER_LABELS`: Labels matched by job node selectors, e.g. `runtime=python,zone=eu`.
+#[derive(Debug)]
+pub struct Capacity {
+    pub name: String,
+    /// CPU in millicores
+    pub cpu: i32,
+    pub memory_mb: i32,
+    pub disk_mb: i32,
+    pub labels: HashMap<String, String>,
+}
+
+impl Capacity {
+    pub fn from_env() -> Self {
+        let name = env::var("WORKER_NAME")
+            .or_else(|_| env::var("HOSTNAME"))
+            .unwrap_or_else(|_| "worker".to_string());
+
+        let cpu = match env::var("WORKER_CPU") {
+            Ok(cpu) => cpu.parse::<f64>().expect("WORKER_CPU must be a number"),
+            Err(_) => thread::available_parallelism().map_or(1, |n| n.get()) as f64,
+        };
+
+        let memory_mb = match env::var("WORKER_MEMORY_MB") {
+            Ok(memory) => memory.parse().expect("WORKER_MEMORY_MB must be an integer"),
+            Err(_) => total_memory_mb().unwrap_or(1024),
+        };
+
+        let disk_mb = match env::var("WORKER_DISK_MB") {
+            Ok(disk) => disk.parse().expect("WORKER_DISK_MB must be an integer"),
+            Err(_) => 10240,
+        };
+
+        let labels = env::var("WORKER_LABELS")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|label| !label.trim().is_empty())
+            .map(|label| match label.split_once('=') {
+                Some((key, value)) => (key.trim().to_string(), value.trim().to_string()),
+                None => panic!("WORKER_LABELS must be comma-separated key=value pairs"),
+            })
+            .collect();
+
+        Self {
+            name,
+            cpu: (cpu * 1000.0).round() as i32,
+            memory_mb,
+            disk_mb,
+            labels,
+        }
+    }
+}
+
+/// Total memory from `/proc/meminfo`
+fn total_memory_mb() -> Option<i32> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let kb = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemTotal:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<i64>()
+        .ok()?;
+
+    Some((kb / 1024) as i32)
+}